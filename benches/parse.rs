@@ -0,0 +1,77 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flax::{borrowed, BindingReader};
+
+/// A document of `n` servers, each a block of scalars, lists of values and a nested map.
+fn servers(n: usize) -> String {
+    let mut input = String::new();
+    for i in 0..n {
+        input.push_str(&format!(
+            "# Server {i}\nserver=\"srv-{i}\"{{\n  host=host-{i}.example.com\n  port={port}\n  \
+             weight=0.{i}\n  tags=primary, \"zone a\", rack_{i}\n  limits={{cpu=4 memory=8_192}}\n}}\n",
+            i = i,
+            port = 8000 + i,
+        ));
+    }
+    input
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_document");
+    for n in [10, 1_000].iter() {
+        let input = servers(*n);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("fast", n), &input, |b, input| {
+            b.iter(|| borrowed::parse_document_complete(black_box(input)).unwrap())
+        });
+        // The nom grammar, which the fast path falls back to and is checked against.
+        group.bench_with_input(BenchmarkId::new("nom", n), &input, |b, input| {
+            b.iter(|| borrowed::parse_document(black_box(input)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("owned", n), &input, |b, input| {
+            b.iter(|| flax::parse_document_complete(black_box(input)).unwrap())
+        });
+        // Run with `--features arena`. The arena is reset rather than dropped between iterations,
+        // as a long-lived service parsing many documents would.
+        #[cfg(feature = "arena")]
+        group.bench_with_input(BenchmarkId::new("arena", n), &input, |b, input| {
+            let mut bump = bumpalo::Bump::new();
+            b.iter(|| {
+                bump.reset();
+                flax::arena::parse_document_in(&bump, black_box(input))
+                    .unwrap()
+                    .bindings()
+                    .len()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// A single binding of `n` children, which a streaming reader must buffer whole.
+fn large_binding(n: usize) -> String {
+    let mut input = "root={".to_string();
+    for i in 0..n {
+        input.push_str(&format!("k{}=v ", i));
+    }
+    input.push('}');
+    input
+}
+
+fn stream(c: &mut Criterion) {
+    let mut group = c.benchmark_group("binding_reader");
+    group.sample_size(10);
+    for n in [1_000, 100_000].iter() {
+        let input = large_binding(*n);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("large_binding", n), &input, |b, input| {
+            b.iter(|| {
+                let reader = BindingReader::new(black_box(input.as_bytes()));
+                reader.collect::<Result<Vec<_>, _>>().unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, stream);
+criterion_main!(benches);
@@ -0,0 +1,293 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, FieldsNamed, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Single,
+    Optional,
+    Repeated,
+}
+
+fn kind(ty: &Type) -> Kind {
+    if let Type::Path(p) = ty {
+        if let Some(segment) = p.path.segments.last() {
+            if segment.ident == "Vec" {
+                return Kind::Repeated;
+            }
+            if segment.ident == "Option" {
+                return Kind::Optional;
+            }
+        }
+    }
+    Kind::Single
+}
+
+/// Returns the element type of an `Option` or `Vec` field, or the type of any other field.
+fn element(ty: &Type) -> &Type {
+    if let Type::Path(p) = ty {
+        if let Some(segment) = p.path.segments.last() {
+            if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                if let Some(GenericArgument::Type(element)) = arguments.args.first() {
+                    return element;
+                }
+            }
+        }
+    }
+    ty
+}
+
+/// Generates the schema of a struct with the given fields.
+fn schema_fields(fields: &FieldsNamed) -> TokenStream2 {
+    let fields = fields.named.iter().map(|f| {
+        let name = f.ident.as_ref().unwrap().to_string();
+        let (repeated, optional, ty) = match kind(&f.ty) {
+            Kind::Single => (false, false, &f.ty),
+            Kind::Optional => (false, true, element(&f.ty)),
+            Kind::Repeated => (true, false, element(&f.ty)),
+        };
+        quote! {
+            ::flax::__private::field(
+                #name,
+                #repeated,
+                #optional,
+                <#ty as ::flax::ToSchema>::schema(),
+            )
+        }
+    });
+    quote! {
+        ::flax::Schema::Struct {
+            fields: ::flax::__private::vec![#(#fields),*],
+        }
+    }
+}
+
+/// Generates the statements that push one binding per field into `__fuji_children`, named so as
+/// not to collide with the fields bound by enum variant patterns.
+fn serialize_fields(fields: &FieldsNamed, access: impl Fn(&Ident) -> TokenStream2) -> TokenStream2 {
+    let pushes = fields.named.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let field = access(ident);
+        let values = match kind(&f.ty) {
+            Kind::Single => {
+                quote! { ::flax::__private::vec![::flax::FujiSerialize::to_value(#field)] }
+            }
+            Kind::Optional | Kind::Repeated => quote! {
+                (#field).iter().map(::flax::FujiSerialize::to_value).collect()
+            },
+        };
+        quote! { ::flax::__private::push(&mut __fuji_children, #name, #values); }
+    });
+    quote! {
+        let mut __fuji_children = ::flax::__private::Vec::new();
+        #(#pushes)*
+    }
+}
+
+/// Generates the field initializers of a struct literal, reading from `value`.
+fn deserialize_fields(fields: &FieldsNamed) -> TokenStream2 {
+    let inits = fields.named.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let values = quote! { &::flax::__private::values(value, #name) };
+        let init = match kind(&f.ty) {
+            Kind::Single => quote! { ::flax::__private::required(#name, #values)? },
+            Kind::Optional => quote! { ::flax::__private::optional(#name, #values)? },
+            Kind::Repeated => quote! { ::flax::__private::repeated(#values)? },
+        };
+        quote! { #ident: #init, }
+    });
+    quote! { #(#inits)* }
+}
+
+fn unsupported(input: &DeriveInput) -> TokenStream {
+    syn::Error::new_spanned(
+        &input.ident,
+        "only structs with named fields and enums with unit or named-field variants are supported",
+    )
+    .to_compile_error()
+    .into()
+}
+
+#[proc_macro_derive(FujiSerialize)]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => {
+                let tag = ident.to_string();
+                let children = serialize_fields(fields, |f| quote! { &self.#f });
+                quote! {
+                    #children
+                    ::flax::__private::value(#tag, __fuji_children)
+                }
+            }
+            _ => return unsupported(&input),
+        },
+        Data::Enum(e) => {
+            let mut arms = vec![];
+            for v in &e.variants {
+                let variant = &v.ident;
+                let tag = variant.to_string();
+                arms.push(match &v.fields {
+                    Fields::Unit => quote! {
+                        #ident::#variant => ::flax::__private::value(#tag, ::flax::__private::Vec::new()),
+                    },
+                    Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|f| &f.ident);
+                        let children = serialize_fields(fields, |f| quote! { #f });
+                        quote! {
+                            #ident::#variant { #(#names),* } => {
+                                #children
+                                ::flax::__private::value(#tag, __fuji_children)
+                            }
+                        }
+                    }
+                    Fields::Unnamed(_) => return unsupported(&input),
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => return unsupported(&input),
+    };
+    let expanded = quote! {
+        impl #impl_generics ::flax::FujiSerialize for #ident #ty_generics #where_clause {
+            fn to_value(&self) -> ::flax::Value {
+                #body
+            }
+        }
+    };
+    expanded.into()
+}
+
+#[proc_macro_derive(FujiDeserialize)]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => {
+                let inits = deserialize_fields(fields);
+                quote! { Ok(#ident { #inits }) }
+            }
+            _ => return unsupported(&input),
+        },
+        Data::Enum(e) => {
+            let mut arms = vec![];
+            for v in &e.variants {
+                let variant = &v.ident;
+                let tag = variant.to_string();
+                arms.push(match &v.fields {
+                    Fields::Unit => quote! { #tag => Ok(#ident::#variant), },
+                    Fields::Named(fields) => {
+                        let inits = deserialize_fields(fields);
+                        quote! { #tag => Ok(#ident::#variant { #inits }), }
+                    }
+                    Fields::Unnamed(_) => return unsupported(&input),
+                });
+            }
+            quote! {
+                match ::flax::__private::tag(value) {
+                    #(#arms)*
+                    tag => Err(::flax::DeserializeError::UnknownVariant(tag.into())),
+                }
+            }
+        }
+        Data::Union(_) => return unsupported(&input),
+    };
+    let expanded = quote! {
+        impl #impl_generics ::flax::FujiDeserialize for #ident #ty_generics #where_clause {
+            fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+                #body
+            }
+        }
+    };
+    expanded.into()
+}
+
+#[proc_macro_derive(ToSchema)]
+pub fn derive_to_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => schema_fields(fields),
+            _ => return unsupported(&input),
+        },
+        Data::Enum(e) => {
+            let mut variants = vec![];
+            for v in &e.variants {
+                let tag = v.ident.to_string();
+                let schema = match &v.fields {
+                    Fields::Unit => quote! {
+                        ::flax::Schema::Struct { fields: ::flax::__private::Vec::new() }
+                    },
+                    Fields::Named(fields) => schema_fields(fields),
+                    Fields::Unnamed(_) => return unsupported(&input),
+                };
+                variants.push(quote! { ::flax::__private::variant(#tag, #schema) });
+            }
+            quote! {
+                ::flax::Schema::Enum {
+                    variants: ::flax::__private::vec![#(#variants),*],
+                }
+            }
+        }
+        Data::Union(_) => return unsupported(&input),
+    };
+    let expanded = quote! {
+        impl #impl_generics ::flax::ToSchema for #ident #ty_generics #where_clause {
+            fn schema() -> ::flax::Schema {
+                #body
+            }
+        }
+    };
+    expanded.into()
+}
+
+fn binding_tokens(binding: &flax::Binding) -> TokenStream2 {
+    let name = binding.name();
+    let values = binding.values().iter().map(value_tokens);
+    quote! { ::flax::Binding::new(#name)#(.value(#values))* }
+}
+
+fn value_tokens(value: &flax::Value) -> TokenStream2 {
+    if let Some(items) = value.items() {
+        let items = items.iter().map(value_tokens);
+        return quote! { ::flax::Value::list(::flax::__private::vec![#(#items),*]) };
+    }
+    let children = value.children().iter().map(binding_tokens);
+    if value.is_map() {
+        return quote! { ::flax::Value::map(::flax::__private::vec![#(#children),*]) };
+    }
+    let tag = value.value();
+    quote! { ::flax::Value::new(#tag)#(.child(#children))* }
+}
+
+/// Parses a binding at compile time, e.g. `fuji!("foo=bar{zoo=qat}")`, expanding to an expression
+/// that builds the equivalent `flax::Binding`. Syntax errors are reported as compile errors.
+#[proc_macro]
+pub fn fuji(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    match flax::parse_binding_complete(&literal.value()) {
+        Ok(binding) => binding_tokens(&binding).into(),
+        Err(e) => syn::Error::new(literal.span(), format!("invalid fuji binding: {}", e))
+            .to_compile_error()
+            .into(),
+    }
+}
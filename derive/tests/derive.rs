@@ -0,0 +1,183 @@
+use flax::{
+    decode_document_with_schema, decode_with_schema, parse_binding, parse_document_complete,
+    print_binding, print_document, FujiDeserialize, FujiSerialize, Schema, ToSchema,
+};
+use flax_derive::{FujiDeserialize, FujiSerialize, ToSchema};
+
+#[derive(Debug, PartialEq, FujiSerialize, FujiDeserialize, ToSchema)]
+struct Server {
+    host: String,
+    ports: Vec<u16>,
+    tls: Option<bool>,
+    mode: Mode,
+}
+
+#[derive(Debug, PartialEq, FujiSerialize, FujiDeserialize, ToSchema)]
+enum Mode {
+    Passive,
+    Active { retries: u32 },
+}
+
+#[test]
+fn test_round_trip() {
+    struct Test {
+        string: String,
+        value: Server,
+    }
+
+    let tests = [
+        Test {
+            string: "server=Server{host=localhost ports=80,443 mode=Passive}".to_string(),
+            value: Server {
+                host: "localhost".to_string(),
+                ports: vec![80, 443],
+                tls: None,
+                mode: Mode::Passive,
+            },
+        },
+        Test {
+            string: "server=Server{host=example tls=true mode=Active{retries=3}}".to_string(),
+            value: Server {
+                host: "example".to_string(),
+                ports: vec![],
+                tls: Some(true),
+                mode: Mode::Active { retries: 3 },
+            },
+        },
+    ];
+
+    for t in tests.iter() {
+        assert_eq!(t.string, print_binding(&t.value.to_binding("server")));
+        let (_, binding) = parse_binding(&t.string).unwrap();
+        assert_eq!(Ok(&t.value), Server::from_binding(&binding).as_ref());
+    }
+}
+
+/// Has a field with the same name as the locals of the generated code.
+#[derive(Debug, PartialEq, FujiSerialize, FujiDeserialize)]
+enum Node {
+    Branch { children: Vec<String> },
+    Leaf,
+}
+
+#[test]
+fn test_field_names() {
+    let node = Node::Branch {
+        children: vec!["a".to_string(), "b".to_string()],
+    };
+    assert_eq!(
+        "node=Branch{children=a,b}",
+        print_binding(&node.to_binding("node"))
+    );
+    let (_, binding) = parse_binding("node=Branch{children=a,b}").unwrap();
+    assert_eq!(Ok(node), Node::from_binding(&binding));
+    assert_eq!("node=Leaf", print_binding(&Node::Leaf.to_binding("node")));
+}
+
+#[test]
+fn test_errors() {
+    let (_, binding) = parse_binding("server=Server{ports=80 mode=Passive}").unwrap();
+    assert_eq!(
+        Err(flax::DeserializeError::MissingField("host".to_string())),
+        Server::from_binding(&binding)
+    );
+
+    let (_, binding) = parse_binding("server=Server{host=a mode=Unknown}").unwrap();
+    assert_eq!(
+        Err(flax::DeserializeError::UnknownVariant(
+            "Unknown".to_string()
+        )),
+        Server::from_binding(&binding)
+    );
+}
+
+#[test]
+fn test_schema() {
+    let schema = Server::schema();
+    let expected = "field=host{type=string}\n\
+                    field=ports{type=string repeated=true}\n\
+                    field=tls{type=bool optional=true}\n\
+                    field=mode{type=enum{variant=Passive variant=Active{type=struct{field=retries{type=string}}}}}";
+    assert_eq!(expected, print_document(&schema.to_document()));
+    assert_eq!(
+        Ok(schema.clone()),
+        Schema::from_document(&parse_document_complete(expected).unwrap())
+    );
+
+    let server = Server {
+        host: "localhost".to_string(),
+        ports: vec![80],
+        tls: None,
+        mode: Mode::Active { retries: 3 },
+    };
+    let document = server
+        .to_value()
+        .children()
+        .iter()
+        .cloned()
+        .fold(flax::Document::new(), flax::Document::binding);
+    assert_eq!(
+        Vec::<flax::ValidationError>::new(),
+        schema.validate(&document)
+    );
+}
+
+#[test]
+fn test_decode_with_schema() {
+    let schema = Schema::from_document(
+        &parse_document_complete(
+            "field=host{type=string default=localhost}\n\
+             field=ports{type=string repeated=true}\n\
+             field=tls{type=bool optional=true}\n\
+             field=mode{type=enum{variant=Passive variant=Active{type=struct{field=retries{type=string}}}}}",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let (_, binding) = parse_binding("server=Server{ports=80 tls=Yes mode=Passive}").unwrap();
+    assert_eq!(
+        Ok(Server {
+            host: "localhost".to_string(),
+            ports: vec![80],
+            tls: Some(true),
+            mode: Mode::Passive,
+        }),
+        decode_with_schema::<Server>(&binding, &schema)
+    );
+
+    let errors = |input: &str| {
+        let (_, binding) = parse_binding(input).unwrap();
+        decode_with_schema::<Server>(&binding, &schema)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![
+            "server.tls: invalid value `maybe`, expected bool",
+            "server.mode.retries: missing required field",
+        ],
+        errors("server=Server{ports=80 tls=maybe mode=Active}")
+    );
+    assert_eq!(
+        vec!["server.ports[1]: invalid value `70000`, expected u16"],
+        errors("server=Server{ports=80,70000 mode=Passive}")
+    );
+    assert_eq!(
+        vec!["server: expected a single value, found 2"],
+        errors("server=Server{ports=80 mode=Passive},Server{ports=81 mode=Passive}")
+    );
+
+    let document = parse_document_complete("ports=443 tls=off mode=Active{retries=3}").unwrap();
+    assert_eq!(
+        Ok(Server {
+            host: "localhost".to_string(),
+            ports: vec![443],
+            tls: Some(false),
+            mode: Mode::Active { retries: 3 },
+        }),
+        decode_document_with_schema::<Server>(&document, &schema)
+    );
+}
@@ -0,0 +1,23 @@
+use flax::{parse_binding_complete, Binding};
+use flax_derive::fuji;
+
+#[test]
+fn test_fuji() {
+    let tests: [(Binding, &str); 8] = [
+        (fuji!("foo=bar"), "foo=bar"),
+        (
+            fuji!("foo=bar{zoo=qat x=y},baz"),
+            "foo=bar{zoo=qat x=y},baz",
+        ),
+        (fuji!(" # defaults\n a=b{c=d{e=f}} "), "a=b{c=d{e=f}}"),
+        (fuji!("a=[[x, z], y]"), "a=[[x, z], y]"),
+        (fuji!("a=[x, [[y{b=c}], []]]"), "a=[x, [[y{b=c}], []]]"),
+        (fuji!("a={c=d}"), "a={c=d}"),
+        (fuji!("a={},b{c={d=e}}"), "a={},b{c={d=e}}"),
+        (fuji!("a=\"\"{c=d}"), "a=\"\"{c=d}"),
+    ];
+
+    for (binding, expected) in tests.iter() {
+        assert_eq!(&parse_binding_complete(expected).unwrap(), binding);
+    }
+}
@@ -0,0 +1,37 @@
+/* C bindings for the fuji format, built with the `capi` feature. */
+
+#ifndef FUJI_H
+#define FUJI_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FujiDocument FujiDocument;
+
+/* An error reported through an out-parameter. `line` and `column` are 1-based, or 0 if the error
+ * has no location in the input. Released with fuji_error_free. */
+typedef struct FujiError {
+  char *message;
+  size_t line;
+  size_t column;
+} FujiError;
+
+/* Functions taking a FujiError ** return NULL on failure and, if the out-parameter is not NULL,
+ * store a newly allocated error in it. */
+
+FujiDocument *fuji_parse(const char *input, FujiError **error);
+char *fuji_print(const FujiDocument *document);
+char *fuji_get(const FujiDocument *document, const char *path, FujiError **error);
+
+void fuji_free(FujiDocument *document);
+void fuji_string_free(char *s);
+void fuji_error_free(FujiError *error);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FUJI_H */
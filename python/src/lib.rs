@@ -0,0 +1,146 @@
+//! Python bindings for the fuji format, built as the `fuji` extension module with `maturin`.
+//!
+//! Documents are exchanged as the lossless JSON mapping of `flax::convert::json`, with references
+//! expanded, built from Python lists, dicts and strings: `loads("a=b{c=d},e")` returns `[{"a": [{"b": [{"c": "d"}]},
+//! "e"]}]`. Errors are raised as `ValueError`.
+
+// Triggered by the code `#[pyfunction]` generates for functions returning `PyResult`.
+#![allow(clippy::useless_conversion)]
+
+use flax::convert::json::{document_from_json, document_to_json};
+use flax::{cst, line_column, parse_document_complete, print_document, Schema};
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple},
+};
+use serde_json::{Map, Number, Value as Json};
+
+fn to_py(py: Python<'_>, json: &Json) -> PyResult<PyObject> {
+    Ok(match json {
+        Json::Null => py.None(),
+        Json::Bool(b) => b.into_py(py),
+        Json::Number(n) => n.to_string().into_py(py),
+        Json::String(s) => s.into_py(py),
+        Json::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| to_py(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new_bound(py, items).into_py(py)
+        }
+        Json::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in map {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+fn from_py(object: &Bound<'_, PyAny>) -> PyResult<Json> {
+    if let Ok(s) = object.downcast::<PyString>() {
+        Ok(Json::String(s.to_str()?.to_string()))
+    } else if let Ok(b) = object.downcast::<PyBool>() {
+        Ok(Json::Bool(b.is_true()))
+    } else if object.is_instance_of::<PyLong>() {
+        Ok(Json::Number(object.extract::<i64>()?.into()))
+    } else if object.is_instance_of::<PyFloat>() {
+        Number::from_f64(object.extract()?)
+            .map(Json::Number)
+            .ok_or_else(|| PyValueError::new_err("non-finite floats are not supported"))
+    } else if object.is_instance_of::<PyList>() || object.is_instance_of::<PyTuple>() {
+        object
+            .iter()?
+            .map(|item| from_py(&item?))
+            .collect::<PyResult<_>>()
+            .map(Json::Array)
+    } else if let Ok(dict) = object.downcast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            map.insert(key.extract::<String>()?, from_py(&value)?);
+        }
+        Ok(Json::Object(map))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "cannot convert {} to fuji",
+            object.get_type().name()?
+        )))
+    }
+}
+
+/// Parses a document into a list of single-key dicts, one per binding.
+#[pyfunction]
+fn loads(py: Python<'_>, s: &str) -> PyResult<PyObject> {
+    let error = |e: &dyn std::fmt::Display| PyValueError::new_err(e.to_string());
+    let mut document = parse_document_complete(s).map_err(|e| error(&e))?;
+    document.expand_references().map_err(|e| error(&e))?;
+    to_py(py, &document_to_json(&document).map_err(|e| error(&e))?)
+}
+
+/// Prints a document given as returned by `loads`.
+#[pyfunction]
+fn dumps(document: &Bound<'_, PyAny>) -> PyResult<String> {
+    let document = document_from_json(&from_py(document)?)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(print_document(&document))
+}
+
+/// Validates a document against a schema, returning a list of violations, each a dict with the
+/// `path`, `message`, `severity` (`error` or `warning`), `line` and `column` of the offending value.
+#[pyfunction]
+fn validate(py: Python<'_>, schema: &str, s: &str) -> PyResult<PyObject> {
+    let error = |e: &dyn std::fmt::Display| PyValueError::new_err(e.to_string());
+    let schema = parse_document_complete(schema).map_err(|e| error(&e))?;
+    let schema = Schema::from_document(&schema).map_err(|e| error(&e))?;
+    let document = cst::parse_document(s).map_err(|e| error(&e))?;
+    let violations = PyList::empty_bound(py);
+    for violation in schema.validate(&document.to_ast()) {
+        let (line, column) = line_column(s, document.offset(&violation.address).unwrap_or(0));
+        let dict = PyDict::new_bound(py);
+        dict.set_item("path", &violation.path)?;
+        dict.set_item("message", &violation.message)?;
+        dict.set_item("severity", violation.severity.to_string())?;
+        dict.set_item("line", line)?;
+        dict.set_item("column", column)?;
+        violations.append(dict)?;
+    }
+    Ok(violations.into_py(py))
+}
+
+#[pymodule]
+fn fuji(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(loads, m)?)?;
+    m.add_function(wrap_pyfunction!(dumps, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python() {
+        Python::with_gil(|py| {
+            let document = loads(py, "a=b{c=d},e").unwrap();
+            assert_eq!(
+                "[{'a': [{'b': [{'c': 'd'}]}, 'e']}]",
+                document.bind(py).repr().unwrap().to_str().unwrap()
+            );
+            assert_eq!("a=b{c=d},e", dumps(document.bind(py)).unwrap());
+
+            let document = PyList::new_bound(py, [PyDict::new_bound(py)]);
+            assert!(dumps(document.as_any()).is_err());
+
+            let violations = validate(py, "field=a{type=bool}", "a=maybe").unwrap();
+            assert_eq!(
+                "[{'path': 'a', 'message': 'invalid value `maybe`, expected bool', 'severity': \
+                 'error', 'line': 1, 'column': 3}]",
+                violations.bind(py).repr().unwrap().to_str().unwrap()
+            );
+            assert!(loads(py, "a=b{").is_err());
+        });
+    }
+}
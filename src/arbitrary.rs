@@ -0,0 +1,173 @@
+//! `proptest` strategies generating valid documents and schemas.
+
+use crate::{Binding, Document, Field, Schema, Value, Variant};
+use proptest::{
+    arbitrary::Arbitrary,
+    collection::vec,
+    prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+
+/// Maximum nesting depth of generated values and schemas.
+const DEPTH: u32 = 3;
+
+/// Generates identifiers, which are valid both as binding names and as values. They do not start
+/// with a digit, which would make them numbers when used as values, and include some non-ASCII
+/// letters.
+pub fn identifier() -> impl Strategy<Value = String> {
+    "[a-zA-Zà-öø-ÿα-ω][a-zA-Z0-9à-öø-ÿα-ω]{0,7}"
+}
+
+/// Generates scalars valid as values: identifiers and numbers.
+pub fn scalar() -> impl Strategy<Value = String> {
+    prop_oneof![
+        identifier(),
+        "[+-]?[0-9]{1,3}(_[0-9]{1,3})?",
+        "[+-]?[0-9]{1,3}\\.[0-9]{1,3}([eE][+-]?[0-9])?",
+        "[+-]?0x[0-9a-fA-F]{1,4}",
+    ]
+}
+
+/// Generates arbitrary strings, including ones that must be quoted or escaped when printed, or
+/// that resemble numbers, anchors, references and placeholders.
+pub fn text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        scalar(),
+        "(?s).{0,8}",
+        "[ \"\\\\{},=#\n\t]{1,4}",
+        "[&*$][a-z{]{0,2}( (?s).{0,4})?",
+    ]
+}
+
+/// Generates values, including lists, maps, references and anchored scalars. Anchors and
+/// references are identifiers, and references need not name an anchor.
+fn values(name: BoxedStrategy<String>, scalar: BoxedStrategy<String>) -> BoxedStrategy<Value> {
+    prop_oneof![
+        3 => scalar.clone().prop_map(Value::new),
+        1 => identifier().prop_map(Value::reference),
+    ]
+    .prop_recursive(DEPTH, 32, 4, move |inner| {
+        let binding = (name.clone(), vec(inner.clone(), 0..3)).prop_map(|(name, values)| Binding {
+            name,
+            values: values.into(),
+        });
+        prop_oneof![
+            3 => (scalar.clone(), vec(binding.clone(), 0..4)).prop_map(|(value, children)| {
+                children.into_iter().fold(Value::new(value), Value::child)
+            }),
+            1 => (identifier(), scalar.clone(), vec(binding.clone(), 0..4)).prop_map(
+                |(anchor, value, children)| {
+                    children.into_iter().fold(Value::new(value).anchor(anchor), Value::child)
+                }
+            ),
+            1 => vec(inner, 0..3).prop_map(Value::list),
+            1 => vec(binding, 0..4).prop_map(Value::map),
+        ]
+    })
+    .boxed()
+}
+
+fn value() -> BoxedStrategy<Value> {
+    values(identifier().boxed(), scalar().boxed())
+}
+
+/// Generates documents whose names and scalars are arbitrary strings, as may be built
+/// programmatically. They survive printing and parsing again, but may not be printed bare.
+pub fn document_with_text() -> BoxedStrategy<Document> {
+    let binding =
+        (text(), vec(values(text().boxed(), text().boxed()), 0..4)).prop_map(|(name, values)| {
+            Binding {
+                name,
+                values: values.into(),
+            }
+        });
+    vec(binding, 0..4).prop_map(Document::from_bindings).boxed()
+}
+
+fn fields(schema: BoxedStrategy<Schema>) -> impl Strategy<Value = Vec<Field>> {
+    vec(
+        (
+            identifier(),
+            proptest::bool::ANY,
+            proptest::bool::ANY,
+            schema,
+        )
+            .prop_map(|(name, repeated, optional, schema)| Field {
+                name,
+                repeated,
+                optional,
+                secret: false,
+                min: None,
+                max: None,
+                schema,
+                default: None,
+                deprecated: None,
+                doc: None,
+            }),
+        0..4,
+    )
+}
+
+fn schema() -> BoxedStrategy<Schema> {
+    prop_oneof![Just(Schema::String), Just(Schema::Bool)]
+        .prop_recursive(DEPTH, 32, 4, |inner| {
+            let variant =
+                (identifier(), fields(inner.clone())).prop_map(|(name, fields)| Variant {
+                    name,
+                    schema: Schema::Struct { fields },
+                    deprecated: None,
+                    doc: None,
+                });
+            prop_oneof![
+                fields(inner).prop_map(|fields| Schema::Struct { fields }),
+                vec(variant, 0..4).prop_map(|variants| Schema::Enum { variants }),
+            ]
+        })
+        .boxed()
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        value()
+    }
+}
+
+impl Arbitrary for Binding {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Binding>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (identifier(), vec(value(), 0..4))
+            .prop_map(|(name, values)| Binding {
+                name,
+                values: values.into(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Document {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Document>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        vec(Binding::arbitrary(), 0..4)
+            .prop_map(Document::from_bindings)
+            .boxed()
+    }
+}
+
+/// Generates struct schemas, as read by [`Schema::from_document`].
+impl Arbitrary for Schema {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Schema>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        fields(schema())
+            .prop_map(|fields| Schema::Struct { fields })
+            .boxed()
+    }
+}
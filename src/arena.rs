@@ -0,0 +1,239 @@
+//! A variant of the AST allocated in a `bumpalo` arena, enabled by the `arena` feature.
+//!
+//! Every node lives in the arena and refers to its children by slices, so the tree owns no heap
+//! memory of its own: it is `Copy`, and dropping the arena frees all of it at once. Identifiers
+//! borrow from the input where possible, as in the [`borrowed`](crate::borrowed) AST; escaped
+//! identifiers are copied into the arena.
+//!
+//! Input within the subset of the fast parser, which covers most documents, is parsed straight
+//! into the arena without any heap allocation. Other input is parsed by the usual grammar and its
+//! nodes then moved into the arena; the nodes of the abandoned fast attempt stay in the arena
+//! until it is dropped.
+
+use crate::{borrowed, fast, ParseError};
+use alloc::borrow::Cow;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Binding<'a> {
+    name: &'a str,
+    values: &'a [Value<'a>],
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Value<'a> {
+    value: &'a str,
+    kind: ValueKind<'a>,
+    children: &'a [Binding<'a>],
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ValueKind<'a> {
+    Scalar,
+    List(&'a [Value<'a>]),
+    Map,
+    Reference,
+    Anchored(&'a str),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Document<'a> {
+    bindings: &'a [Binding<'a>],
+}
+
+impl<'a> Binding<'a> {
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn values(&self) -> &'a [Value<'a>] {
+        self.values
+    }
+
+    pub fn into_owned(self) -> crate::Binding {
+        crate::Binding {
+            name: self.name.into(),
+            values: self.values.iter().map(|v| v.into_owned()).collect(),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    pub fn value(&self) -> &'a str {
+        self.value
+    }
+
+    pub fn kind(&self) -> &ValueKind<'a> {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &'a [Binding<'a>] {
+        self.children
+    }
+
+    pub fn into_owned(self) -> crate::Value {
+        crate::Value {
+            value: self.value.into(),
+            kind: match self.kind {
+                ValueKind::Scalar => crate::ValueKind::Scalar,
+                ValueKind::List(items) => {
+                    crate::ValueKind::List(items.iter().map(|v| v.into_owned()).collect())
+                }
+                ValueKind::Map => crate::ValueKind::Map,
+                ValueKind::Reference => crate::ValueKind::Reference,
+                ValueKind::Anchored(anchor) => crate::ValueKind::Anchored(anchor.into()),
+            },
+            children: self.children.iter().map(|b| b.into_owned()).collect(),
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    pub fn bindings(&self) -> &'a [Binding<'a>] {
+        self.bindings
+    }
+
+    pub fn into_owned(self) -> crate::Document {
+        crate::Document::from_bindings(self.bindings.iter().map(|b| b.into_owned()).collect())
+    }
+}
+
+impl<'a> fast::Builder<'a> for &'a Bump {
+    type Binding = Binding<'a>;
+    type Value = Value<'a>;
+    type Bindings = BumpVec<'a, Binding<'a>>;
+    type Values = BumpVec<'a, Value<'a>>;
+
+    fn bindings(&self) -> Self::Bindings {
+        BumpVec::new_in(self)
+    }
+
+    fn push_binding(bindings: &mut Self::Bindings, binding: Self::Binding) {
+        bindings.push(binding);
+    }
+
+    fn values(&self) -> Self::Values {
+        BumpVec::new_in(self)
+    }
+
+    fn push_value(values: &mut Self::Values, value: Self::Value) {
+        values.push(value);
+    }
+
+    fn binding(&self, name: &'a str, values: Self::Values) -> Self::Binding {
+        Binding {
+            name,
+            values: values.into_bump_slice(),
+        }
+    }
+
+    fn value(&self, scalar: Option<&'a str>, children: Self::Bindings) -> Self::Value {
+        Value {
+            value: scalar.unwrap_or(""),
+            kind: match scalar {
+                Some(_) => ValueKind::Scalar,
+                None => ValueKind::Map,
+            },
+            children: children.into_bump_slice(),
+        }
+    }
+}
+
+fn str_in<'a>(bump: &'a Bump, s: Cow<'a, str>) -> &'a str {
+    match s {
+        Cow::Borrowed(s) => s,
+        Cow::Owned(s) => bump.alloc_str(&s),
+    }
+}
+
+fn binding_in<'a>(bump: &'a Bump, binding: borrowed::Binding<'a>) -> Binding<'a> {
+    Binding {
+        name: str_in(bump, binding.name),
+        values: bump.alloc_slice_fill_iter(binding.values.into_iter().map(|v| value_in(bump, v))),
+    }
+}
+
+fn value_in<'a>(bump: &'a Bump, value: borrowed::Value<'a>) -> Value<'a> {
+    Value {
+        value: str_in(bump, value.value),
+        kind: match value.kind {
+            borrowed::ValueKind::Scalar => ValueKind::Scalar,
+            borrowed::ValueKind::List(items) => ValueKind::List(
+                bump.alloc_slice_fill_iter(items.into_iter().map(|v| value_in(bump, v))),
+            ),
+            borrowed::ValueKind::Map => ValueKind::Map,
+            borrowed::ValueKind::Reference => ValueKind::Reference,
+            borrowed::ValueKind::Anchored(anchor) => ValueKind::Anchored(anchor),
+        },
+        children: bump
+            .alloc_slice_fill_iter(value.children.into_iter().map(|b| binding_in(bump, b))),
+    }
+}
+
+/// Parses a binding that must span the entire input, allocating its nodes in `bump`.
+pub fn parse_binding_in<'a>(bump: &'a Bump, input: &'a str) -> Result<Binding<'a>, ParseError> {
+    if let Some(binding) = fast::binding_with(bump, input) {
+        return Ok(binding);
+    }
+    borrowed::parse_binding_complete(input).map(|b| binding_in(bump, b))
+}
+
+/// Parses a document that must span the entire input, allocating its nodes in `bump`.
+pub fn parse_document_in<'a>(bump: &'a Bump, input: &'a str) -> Result<Document<'a>, ParseError> {
+    if let Some(bindings) = fast::bindings_with(bump, input) {
+        return Ok(Document {
+            bindings: bindings.into_bump_slice(),
+        });
+    }
+    let document = borrowed::parse_document_complete(input)?;
+    Ok(Document {
+        bindings: bump
+            .alloc_slice_fill_iter(document.bindings.into_iter().map(|b| binding_in(bump, b))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena() {
+        let bump = Bump::new();
+        let input = r#"foo=[bar{"z\too"=qat}, x, [y]]"#;
+        let binding = parse_binding_in(&bump, input).unwrap();
+        assert_eq!("foo", binding.name());
+        assert_eq!("bar", binding.values()[0].value());
+        assert_eq!("z\too", binding.values()[0].children()[0].name());
+        assert!(matches!(binding.values()[2].kind(), ValueKind::List(items) if items.len() == 1));
+        assert_eq!(
+            crate::parse_binding_complete(input),
+            Ok(binding.into_owned())
+        );
+
+        let input = "a=b c={d=e}";
+        let document = parse_document_in(&bump, input).unwrap();
+        assert_eq!(
+            crate::parse_document_complete(input),
+            Ok(document.into_owned())
+        );
+
+        // Within the subset of the fast parser, nodes are built directly in the arena.
+        for input in ["server=main{port=80 host=h}", " a=b,c d={e=f} g= "].iter() {
+            assert_eq!(
+                crate::parse_document_complete(input),
+                Ok(parse_document_in(&bump, input).unwrap().into_owned())
+            );
+        }
+        let binding = parse_binding_in(&bump, "a=b{c=d},{}").unwrap();
+        assert!(matches!(binding.values()[1].kind(), ValueKind::Map));
+        assert_eq!(
+            crate::parse_binding_complete("a=b{c=d},{}"),
+            Ok(binding.into_owned())
+        );
+        assert_eq!(
+            Err(ParseError::new("a=b{", 4, "'}'")),
+            parse_document_in(&bump, "a=b{")
+        );
+    }
+}
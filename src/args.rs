@@ -0,0 +1,270 @@
+//! Conversion of command-line flags and environment variables into documents, so that overrides
+//! given on the command line or in the environment can be merged over configuration files, e.g.
+//! with [`Document::merge`].
+//!
+//! Each argument is a flag binding the dotted path after `--` to values written as in a document:
+//! `--server.port=8080`, `--features=a,b`, or `--db{host=x}` for a map. A flag without values,
+//! such as `--verbose`, binds its path to `true`.
+//!
+//! Each environment variable with a given prefix binds the path spelled by the rest of its name,
+//! with `__` separating segments, to its value verbatim: `APP_SERVER__PORT=8080` binds
+//! `server.port` to `8080`.
+
+use crate::error::{complete, Expected};
+use crate::flatten::insert_segments;
+use crate::parser::binding_values;
+use crate::prelude::*;
+use crate::{borrowed, Binding, Document, ParseError, Path, PathError, Segment, Value};
+use crate::{ValueKind, Values};
+use core::fmt;
+
+/// An argument that is not a valid flag.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ArgError {
+    /// The argument at the index does not start with `--`.
+    NotAFlag { index: usize },
+    /// The path of the flag at the index is invalid, or has an index, as in `--a[1]=b`.
+    Path { index: usize, error: PathError },
+    /// The values of the flag at the index are invalid. The error is located in the argument.
+    Parse { index: usize, error: ParseError },
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::NotAFlag { index } => write!(f, "argument {}: expected a flag", index),
+            ArgError::Path { index, error } => write!(f, "argument {}: {}", index, error),
+            ArgError::Parse { index, error } => write!(f, "argument {}: {}", index, error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ArgError {}
+
+/// Parses the flag `arg`, returning the binding of the first segment of its path.
+fn parse_flag(arg: &str, index: usize) -> Result<Binding, ArgError> {
+    let flag = arg.strip_prefix("--").ok_or(ArgError::NotAFlag { index })?;
+    parse_override(arg, arg.len() - flag.len(), index)
+}
+
+/// Parses the path and values of the flag `arg` starting at `offset`, e.g. `a.b=c` after the `--`
+/// of `--a.b=c`, returning the binding of the first segment of the path.
+pub(crate) fn parse_override(arg: &str, offset: usize, index: usize) -> Result<Binding, ArgError> {
+    let flag = &arg[offset..];
+    let end = flag.find(['=', '{']).unwrap_or(flag.len());
+    let path: Path = flag[..end]
+        .parse()
+        .map_err(|error| ArgError::Path { index, error })?;
+    if path.segments.iter().any(|s| s.index.is_some()) {
+        let error = PathError::Invalid(path.to_string());
+        return Err(ArgError::Path { index, error });
+    }
+
+    let start = offset + end;
+    let values = match flag[end..].strip_prefix('=') {
+        _ if end == flag.len() => smallvec![Value::from("true")],
+        Some(_) => parse_values(arg, start + 1, index)?,
+        None => parse_values(arg, start, index)?,
+    };
+    Ok(nest(path.segments.into_iter().map(|s| s.name), values))
+}
+
+/// Returns the binding of the first of `names` to a map binding the next one, and so on, the last
+/// one being bound to `values`.
+fn nest<I>(names: I, values: Values) -> Binding
+where
+    I: DoubleEndedIterator<Item = String>,
+{
+    let mut names = names.rev();
+    let last = names.next().unwrap_or_default();
+    let mut binding = Binding { name: last, values };
+    for name in names {
+        binding = Binding {
+            name,
+            values: smallvec![Value {
+                value: String::new(),
+                kind: ValueKind::Map,
+                children: vec![binding],
+            }],
+        };
+    }
+    binding
+}
+
+/// Parses the values of the flag `arg` starting at `start`.
+fn parse_values(arg: &str, start: usize, index: usize) -> Result<Values, ArgError> {
+    complete(arg, |input| binding_values::<Expected>(&input[start..]))
+        .map(|values| {
+            values
+                .into_iter()
+                .map(borrowed::Value::into_owned)
+                .collect()
+        })
+        .map_err(|error| ArgError::Parse { index, error })
+}
+
+/// Builds a document from command-line flags, such as `--server.port=8080 --features=a,b
+/// --db{host=x}`, typically the arguments of the program without its name.
+///
+/// Flags are combined as [`Document::deep_merge`] does: flags sharing a path prefix build a
+/// single tree, as in `--server.host=h --server.port=80`, and a flag repeated with the same path
+/// overrides the earlier ones.
+///
+/// ```
+/// let args = ["--server.port=8080", "--features=a,b", "--db{host=x}", "--server.tls"];
+/// let document = flax::from_args(&args).unwrap();
+/// assert_eq!(
+///     "server={port=8080 tls=true}\nfeatures=a,b\ndb={host=x}",
+///     document.to_string()
+/// );
+/// ```
+pub fn from_args<S: AsRef<str>>(args: &[S]) -> Result<Document, ArgError> {
+    let bindings = args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| parse_flag(arg.as_ref(), index))
+        .collect::<Result<_, _>>()?;
+    let mut document = Document::from_bindings(bindings);
+    document.deep_merge();
+    Ok(document)
+}
+
+/// Builds a document from the variables among `vars` whose names start with `prefix` followed by
+/// `_`, such as `APP_SERVER__PORT=8080` for the prefix `APP`.
+///
+/// The rest of each name is lowercased and split on `__` into the segments of a path, which is
+/// bound to the value as a scalar; variables with an empty segment, such as `APP_A____B`, are
+/// ignored. Variables are applied in the order of their names, so `APP_SERVER=main` and
+/// `APP_SERVER__PORT=8080` build `server=main{port=8080}`.
+///
+/// ```
+/// let vars = [("APP_SERVER__PORT", "8080"), ("APP_LOG_LEVEL", "debug"), ("HOME", "/root")];
+/// let document = flax::from_vars("APP", vars.iter().copied());
+/// assert_eq!("log_level=debug\nserver={port=8080}", document.to_string());
+/// ```
+pub fn from_vars<I, K, V>(prefix: &str, vars: I) -> Document
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut vars = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.as_ref().strip_prefix(prefix)?.strip_prefix('_')?;
+            let segments = name
+                .split("__")
+                .map(|name| Segment {
+                    name: name.to_lowercase(),
+                    index: None,
+                })
+                .collect::<Vec<_>>();
+            if segments.iter().any(|s| s.name.is_empty()) {
+                return None;
+            }
+            Some((segments, value))
+        })
+        .collect::<Vec<_>>();
+    vars.sort_by(|(a, _), (b, _)| a.iter().map(|s| &s.name).cmp(b.iter().map(|s| &s.name)));
+    let mut bindings = vec![];
+    for (segments, value) in vars {
+        // Without indices, every segment adds to the first value of its binding.
+        insert_segments(&mut bindings, &segments, value.as_ref());
+    }
+    Document::from_bindings(bindings)
+}
+
+/// Builds a document from the environment variables whose names start with `prefix` followed by
+/// `_`, as [`from_vars`] does. Variables whose names or values are not valid Unicode are ignored.
+#[cfg(feature = "std")]
+pub fn from_env(prefix: &str) -> Document {
+    let vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    from_vars(prefix, vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_args() {
+        let tests = [
+            (vec!["--a=b"], "a=b"),
+            (
+                vec!["--a.b.c=1,2", "--a.d=[x,[y]]"],
+                "a={b={c=1,2} d=[x,[y]]}",
+            ),
+            (vec!["--a=", "--b={}"], "a=\nb={}"),
+            (vec!["--a=1", "--a=2"], "a=2"),
+            (vec!["--a{b=1 c=2}", "--a.c=3"], "a={b=1 c=3}"),
+            (vec!["--\"a b\".c=\"x y\""], "\"a b\"={c=\"x y\"}"),
+            (vec![], ""),
+        ];
+
+        for (args, expected) in tests.iter() {
+            let document = from_args(args).unwrap();
+            assert_eq!(*expected, document.to_string(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let vars = [
+            ("FUJI_SERVER__PORT", "8080"),
+            ("FUJI_SERVER__TLS__CERT", "/etc/cert.pem"),
+            ("FUJI_SERVER", "main"),
+            ("FUJI_FEATURES", "a,b"),
+            ("FUJI_A____B", "x"),
+            ("FUJI_", "x"),
+            ("FUJIX", "x"),
+            ("OTHER_SERVER__PORT", "80"),
+        ];
+        assert_eq!(
+            "features=\"a,b\"\nserver=main{port=8080 tls={cert=\"/etc/cert.pem\"}}",
+            from_vars("FUJI", vars.iter().copied()).to_string()
+        );
+        assert!(from_vars("NONE", vars.iter().copied()).bindings.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("FLAX_TEST_FROM_ENV_DB__HOST", "localhost");
+        assert_eq!(
+            "db={host=localhost}",
+            from_env("FLAX_TEST_FROM_ENV").to_string()
+        );
+    }
+
+    #[test]
+    fn test_from_args_errors() {
+        assert_eq!(
+            Err(ArgError::NotAFlag { index: 1 }),
+            from_args(&["--a=b", "c"])
+        );
+        assert_eq!(
+            Err(ArgError::Path {
+                index: 0,
+                error: PathError::Invalid("a[1].b".to_string())
+            }),
+            from_args(&["--a[1].b=c"])
+        );
+        assert_eq!(
+            Err(ArgError::Path {
+                index: 0,
+                error: PathError::Invalid("a..b".to_string())
+            }),
+            from_args(&["--a..b=c"])
+        );
+        assert_eq!(
+            "argument 0: 1:8: expected '}', found end of input",
+            from_args(&["--a{b=c"]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "argument 0: 1:6: expected end of input, found ` c`",
+            from_args(&["--a=b c"]).unwrap_err().to_string()
+        );
+    }
+}
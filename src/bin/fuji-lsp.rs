@@ -0,0 +1,364 @@
+//! A language server for fuji documents, speaking LSP over stdin and stdout.
+//!
+//! It reports syntax errors and, given a schema, validation errors; goes from references to the
+//! anchors they refer to; completes field names from the schema; and formats documents as
+//! `fuji fmt` does. The schema is read from the file named by the `schema` initialization option.
+
+use flax::span::{self, Span, Spanned, ValueKind};
+use flax::{cst, parse_document_complete, parse_document_lossy, Field, Schema, Severity};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use tower_lsp::jsonrpc::{self, Result};
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService, Server};
+
+/// Converts a byte offset in `input` to a position, whose character counts UTF-16 code units.
+fn position(input: &str, offset: usize) -> Position {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn range(input: &str, span: Span) -> Range {
+    Range::new(position(input, span.start), position(input, span.end))
+}
+
+/// Converts a position to a byte offset in `input`, clamping it to the end of its line.
+fn offset(input: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match input[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return input.len(),
+        }
+    }
+    let line = &input[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+fn diagnostic(range: Range, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some("fuji".to_string()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Reports every syntax error of `input` or, if there are none, its violations of `schema`.
+fn diagnostics(input: &str, schema: Option<&Schema>) -> Vec<Diagnostic> {
+    let (_, errors) = parse_document_lossy(input);
+    if !errors.is_empty() {
+        return errors
+            .iter()
+            .map(|e| {
+                let at = position(input, e.offset);
+                let message = format!("expected {}", e.expected);
+                diagnostic(Range::new(at, at), DiagnosticSeverity::ERROR, message)
+            })
+            .collect();
+    }
+    let (schema, document) = match (schema, span::parse_document(input)) {
+        (Some(schema), Ok(document)) => (schema, document),
+        _ => return vec![],
+    };
+    schema
+        .validate(&document.to_ast())
+        .into_iter()
+        .map(|e| {
+            // Fields missing from the document are reported at its start.
+            let span = document
+                .span(&e.address)
+                .unwrap_or(Span { start: 0, end: 0 });
+            let severity = match e.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            };
+            diagnostic(range(input, span), severity, e.to_string())
+        })
+        .collect()
+}
+
+/// Collects the values bound in `bindings` and their descendants, in document order.
+fn values<'a>(bindings: &'a [Spanned<span::Binding>], out: &mut Vec<&'a Spanned<span::Value>>) {
+    for binding in bindings {
+        for value in binding.node.values.iter() {
+            push_value(value, out);
+        }
+    }
+}
+
+fn push_value<'a>(value: &'a Spanned<span::Value>, out: &mut Vec<&'a Spanned<span::Value>>) {
+    out.push(value);
+    if let ValueKind::List(items) = &value.node.kind {
+        for item in items {
+            push_value(item, out);
+        }
+    }
+    values(&value.node.children, out);
+}
+
+/// Returns the span of the anchored value referred to by the reference at `offset`, i.e. the
+/// last value anchored with its name before it.
+fn definition(input: &str, offset: usize) -> Option<Span> {
+    let document = span::parse_document(input).ok()?;
+    let mut all = vec![];
+    values(&document.bindings, &mut all);
+    let reference = all.iter().find(|v| {
+        v.span.start <= offset && offset <= v.span.end && v.node.kind == ValueKind::Reference
+    })?;
+    let name = &reference.node.value;
+    let anchors = |value: &&&Spanned<span::Value>| matches!(&value.node.kind, ValueKind::Anchored(anchor) if anchor == name);
+    all.iter()
+        .filter(|v| v.span.end <= reference.span.start)
+        .rfind(anchors)
+        .map(|v| v.span)
+}
+
+/// Returns the names and values of the blocks enclosing the end of `prefix`, outermost first, e.g.
+/// `[("server", "s")]` within `server=s{`. Tolerates the syntax errors of a document being edited.
+fn enclosing(prefix: &str) -> Vec<(String, String)> {
+    let mut stack = vec![];
+    let (mut name, mut value, mut token) = (String::new(), String::new(), String::new());
+    let mut chars = prefix.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' | '/' if c == '#' || chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '"' => {
+                token.clear();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => token.extend(chars.next()),
+                        '"' => break,
+                        c => token.push(c),
+                    }
+                }
+            }
+            '=' => {
+                name = std::mem::take(&mut token);
+                value.clear();
+            }
+            ',' => token.clear(),
+            '{' => {
+                if !token.is_empty() {
+                    value = std::mem::take(&mut token);
+                }
+                stack.push((std::mem::take(&mut name), std::mem::take(&mut value)));
+            }
+            '}' => {
+                stack.pop();
+                token.clear();
+            }
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    value = std::mem::take(&mut token);
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    stack
+}
+
+/// Returns the fields of the struct enclosing the end of `prefix` in a document of `schema`.
+fn fields<'a>(schema: &'a Schema, prefix: &str) -> Option<&'a [Field]> {
+    // The recursive types entered so far, which references within them resolve to.
+    let mut recursive = vec![];
+    let mut resolve = |mut schema: &'a Schema| loop {
+        match schema {
+            Schema::Recursive { name, schema: body } => {
+                recursive.push((name, &**body));
+                schema = body;
+            }
+            Schema::Ref(name) => schema = recursive.iter().rev().find(|(n, _)| *n == name)?.1,
+            schema => return Some(schema),
+        }
+    };
+    let mut schema = resolve(schema)?;
+    for (name, value) in enclosing(prefix) {
+        let fields = match schema {
+            Schema::Struct { fields } => fields,
+            _ => return None,
+        };
+        schema = resolve(fields.iter().find(|f| f.name() == name)?.schema())?;
+        if let Schema::Enum { variants } = schema {
+            schema = resolve(variants.iter().find(|v| v.name() == value)?.schema())?;
+        }
+    }
+    match schema {
+        Schema::Struct { fields } => Some(fields),
+        _ => None,
+    }
+}
+
+fn completion(field: &Field) -> CompletionItem {
+    let detail = match (field.is_repeated(), field.is_optional()) {
+        (true, _) => "repeated",
+        (false, true) => "optional",
+        (false, false) => "required",
+    };
+    CompletionItem {
+        label: field.name().to_string(),
+        kind: Some(CompletionItemKind::FIELD),
+        detail: Some(detail.to_string()),
+        insert_text: Some(format!("{}=", field.name())),
+        ..CompletionItem::default()
+    }
+}
+
+/// Returns the edit formatting `input`, if it is valid.
+fn format(input: &str) -> Option<Vec<TextEdit>> {
+    let mut document = cst::parse_document(input).ok()?;
+    document.normalize();
+    let formatted = document.print();
+    if formatted == input {
+        return Some(vec![]);
+    }
+    let whole = Range::new(Position::new(0, 0), position(input, input.len()));
+    Some(vec![TextEdit::new(whole, formatted)])
+}
+
+fn load_schema(path: &str) -> std::result::Result<Schema, String> {
+    let input = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let document = parse_document_complete(&input).map_err(|e| format!("{}:{}", path, e))?;
+    Schema::from_document(&document).map_err(|e| format!("{}: {}", path, e))
+}
+
+struct Backend {
+    client: Client,
+    documents: Mutex<HashMap<Url, String>>,
+    schema: Mutex<Option<Schema>>,
+}
+
+impl Backend {
+    fn text(&self, uri: &Url) -> Option<String> {
+        self.documents.lock().unwrap().get(uri).cloned()
+    }
+
+    async fn update(&self, uri: Url, text: String, version: i32) {
+        let diagnostics = diagnostics(&text, self.schema.lock().unwrap().as_ref());
+        self.documents.lock().unwrap().insert(uri.clone(), text);
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let path = params
+            .initialization_options
+            .as_ref()
+            .and_then(|options| options.get("schema"))
+            .and_then(|path| path.as_str());
+        if let Some(path) = path {
+            let schema = load_schema(path).map_err(jsonrpc::Error::invalid_params)?;
+            *self.schema.lock().unwrap() = Some(schema);
+        }
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                definition_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions::default()),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "fuji-lsp".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.update(document.uri, document.text, document.version)
+            .await;
+    }
+
+    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        // With full synchronization, the last change holds the whole text.
+        if let Some(change) = params.content_changes.pop() {
+            let document = params.text_document;
+            self.update(document.uri, change.text, document.version)
+                .await;
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.lock().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, vec![], None).await;
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let params = params.text_document_position_params;
+        let uri = params.text_document.uri;
+        let text = match self.text(&uri) {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let span = definition(&text, offset(&text, params.position));
+        Ok(span.map(|span| GotoDefinitionResponse::Scalar(Location::new(uri, range(&text, span)))))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let params = params.text_document_position;
+        let text = match self.text(&params.text_document.uri) {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let prefix = &text[..offset(&text, params.position)];
+        let schema = self.schema.lock().unwrap();
+        let items = schema
+            .as_ref()
+            .and_then(|schema| fields(schema, prefix))
+            .map(|fields| fields.iter().map(completion).collect());
+        Ok(items.map(CompletionResponse::Array))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        Ok(self
+            .text(&params.text_document.uri)
+            .and_then(|text| format(&text)))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let (service, socket) = LspService::new(|client| Backend {
+        client,
+        documents: Mutex::default(),
+        schema: Mutex::default(),
+    });
+    Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+        .serve(service)
+        .await;
+}
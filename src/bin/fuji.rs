@@ -0,0 +1,488 @@
+use flax::lint::Linter;
+use flax::{
+    convert, cst, docgen, line_column, Address, Document, Schema, Severity, ValidationOptions,
+};
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process,
+};
+
+const USAGE: &str = "usage: fuji <command> [args]
+
+commands:
+  fmt [-w] [OPTION...] [FILE...]      format documents, printing to stdout or rewriting them
+                                      with -w; --trailing-commas ends every list of several
+                                      values with a comma, --no-trailing-commas strips them
+  validate --schema SCHEMA [FILE...]  check documents against a schema, reporting every
+                                      violation; --strict makes unknown fields errors rather
+                                      than warnings, --ignore-case matches fields ignoring case
+  lint [--strict] [-A RULE] [FILE...] report likely mistakes: duplicate keys, empty blocks,
+                                      keys differing only in case and unquoted numbers and
+                                      bools; --strict fails on warnings too, -A (--allow)
+                                      disables a rule and may be repeated
+  doc [--html] [--title T] SCHEMA     print reference documentation for the fields of a
+                                      schema, from their doc attributes and ## comments, as
+                                      markdown or html
+  convert [--from F] [--to F] [FILE]  convert between fuji, json, yaml, toml, xml and
+                                      textproto, or export to csv or tsv rows of paths and
+                                      values; formats default to the file extension and fuji
+  get [--raw|--json] PATH [FILE]      print the values matching a dotted path, one per line;
+                                      --raw prints only the value itself, without children
+  query [--paths] QUERY [FILE]        print the values matching a query such as
+                                      `.users[] | select(role=admin) | .name`, one per line;
+                                      --paths prefixes each with its path and a tab";
+
+/// Returns the usage error for an option that `command` does not recognise, so that a mistyped
+/// flag is not mistaken for a file name.
+fn unknown_option(command: &str, option: &str) -> String {
+    format!("{}: unknown option `{}`\n\n{}", command, option, USAGE)
+}
+
+/// Reads the named file, or stdin for `-`.
+fn read(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("<stdin>: {}", e))?;
+        Ok(input)
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn fmt(args: &[String]) -> Result<(), String> {
+    let mut write = false;
+    let mut trailing_commas = None;
+    let mut files = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "-w" | "--write" => write = true,
+            "--trailing-commas" => trailing_commas = Some(true),
+            "--no-trailing-commas" => trailing_commas = Some(false),
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("fmt", option))
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    if files.is_empty() {
+        if write {
+            return Err("fmt: -w requires at least one file".to_string());
+        }
+        files.push("-");
+    }
+
+    let mut failed = false;
+    for file in files {
+        let result = read(file).and_then(|input| {
+            let mut document =
+                cst::parse_document(&input).map_err(|e| format!("{}:{}", file, e))?;
+            document.normalize();
+            if let Some(trailing) = trailing_commas {
+                document.set_trailing_commas(trailing);
+            }
+            let output = document.print();
+            if !write {
+                print!("{}", output);
+            } else if output != input {
+                fs::write(file, output).map_err(|e| format!("{}: {}", file, e))?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        Err("fmt: some files could not be formatted".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Formats the location of the node at `address` as `file:line:column`.
+fn location(file: &str, input: &str, document: &cst::Document, address: &Address) -> String {
+    let (line, column) = line_column(input, document.offset(address).unwrap_or(0));
+    format!("{}:{}:{}", file, line, column)
+}
+
+fn parse(file: &str) -> Result<(String, cst::Document), String> {
+    let input = read(file)?;
+    let document = cst::parse_document(&input).map_err(|e| format!("{}:{}", file, e))?;
+    Ok((input, document))
+}
+
+fn validate(args: &[String]) -> Result<(), String> {
+    let mut schema_file = None;
+    let mut options = ValidationOptions::new();
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" | "--schema" => schema_file = args.next(),
+            "--strict" => options = options.unknown_fields(Severity::Error),
+            "--ignore-case" => options = options.ignore_case(true),
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("validate", option))
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    let schema_file = schema_file.ok_or("validate: --schema requires a file")?;
+    if files.is_empty() {
+        files.push("-");
+    }
+
+    let (input, document) = parse(schema_file)?;
+    let schema = Schema::from_document(&document.to_ast()).map_err(|e| {
+        format!(
+            "{}: {}",
+            location(schema_file, &input, &document, &e.address),
+            e
+        )
+    })?;
+
+    let mut failed = false;
+    for file in files {
+        let (input, document) = match parse(file) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+                continue;
+            }
+        };
+        for error in schema.validate_with(&document.to_ast(), &options) {
+            let location = location(file, &input, &document, &error.address);
+            match error.severity {
+                Severity::Error => {
+                    eprintln!("{}: {}", location, error);
+                    failed = true;
+                }
+                Severity::Warning => eprintln!("{}: warning: {}", location, error),
+            }
+        }
+    }
+    if failed {
+        Err("validate: some files are not valid".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn lint(args: &[String]) -> Result<(), String> {
+    let mut strict = false;
+    let mut linter = Linter::new();
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            "-A" | "--allow" => {
+                let rule = args.next().ok_or("lint: --allow requires a rule")?;
+                if !linter.rules().contains(&rule.as_str()) {
+                    return Err(format!("lint: unknown rule `{}`", rule));
+                }
+                linter = linter.allow(rule);
+            }
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("lint", option))
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    if files.is_empty() {
+        files.push("-");
+    }
+
+    let mut failed = false;
+    for file in files {
+        let (input, document) = match parse(file) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+                continue;
+            }
+        };
+        for lint in linter.lint(&document) {
+            let location = location(file, &input, &document, &lint.address);
+            match lint.severity {
+                Severity::Error => eprintln!("{}: {}", location, lint),
+                Severity::Warning => eprintln!("{}: warning: {}", location, lint),
+            }
+            failed |= strict || lint.severity == Severity::Error;
+        }
+    }
+    if failed {
+        Err("lint: some files have issues".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn doc(args: &[String]) -> Result<(), String> {
+    let mut html = false;
+    let mut title = "Configuration";
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--html" => html = true,
+            "-t" | "--title" => title = args.next().ok_or("doc: --title requires a title")?,
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("doc", option))
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    let file = match files.as_slice() {
+        [file] => *file,
+        _ => return Err("doc: expected a single schema".to_string()),
+    };
+
+    let (input, document) = parse(file)?;
+    let schema = Schema::from_cst(&document)
+        .map_err(|e| format!("{}: {}", location(file, &input, &document, &e.address), e))?;
+    let out = if html {
+        docgen::html(&schema, title)
+    } else {
+        docgen::markdown(&schema, title)
+    };
+    io::stdout()
+        .write_all(out.as_bytes())
+        .map_err(|e| format!("<stdout>: {}", e))
+}
+
+/// Returns the format implied by the extension of `file`, if any.
+fn extension(file: &str) -> Option<&str> {
+    match file.rsplit_once('.')?.1 {
+        "fuji" => Some("fuji"),
+        "json" => Some("json"),
+        "yaml" | "yml" => Some("yaml"),
+        "toml" => Some("toml"),
+        "xml" => Some("xml"),
+        "csv" => Some("csv"),
+        "tsv" => Some("tsv"),
+        "textproto" | "txtpb" | "pbtxt" => Some("textproto"),
+        _ => None,
+    }
+}
+
+fn decode(format: &str, input: &str) -> Result<Document, String> {
+    match format {
+        "fuji" => flax::parse_document_complete(input).map_err(|e| e.to_string()),
+        #[cfg(feature = "json")]
+        "json" => {
+            let json = serde_json::from_str(input).map_err(|e| e.to_string())?;
+            convert::json::document_from_json(&json).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "yaml")]
+        "yaml" => {
+            let yaml = serde_yaml::from_str(input).map_err(|e| e.to_string())?;
+            convert::yaml::from_yaml(&yaml).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "toml")]
+        "toml" => {
+            let table = input.parse().map_err(|e: toml::de::Error| e.to_string())?;
+            convert::toml::from_toml(&table).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "xml")]
+        "xml" => convert::xml::from_xml(input).map_err(|e| e.to_string()),
+        "textproto" => convert::textproto::from_textproto(input).map_err(|e| e.to_string()),
+        "csv" | "tsv" => Err(format!("{} can only be converted to", format)),
+        _ => Err(unsupported(format)),
+    }
+}
+
+fn encode(format: &str, document: &Document) -> Result<String, String> {
+    match format {
+        "fuji" => Ok(format!("{}\n", flax::print_document(document))),
+        #[cfg(feature = "json")]
+        "json" => {
+            let json = convert::json::document_to_json(document).map_err(|e| e.to_string())?;
+            serde_json::to_string_pretty(&json)
+                .map(|json| format!("{}\n", json))
+                .map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "yaml")]
+        "yaml" => {
+            let yaml = convert::yaml::to_yaml(document).map_err(|e| e.to_string())?;
+            serde_yaml::to_string(&yaml).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "toml")]
+        "toml" => {
+            let table = convert::toml::to_toml(document).map_err(|e| e.to_string())?;
+            toml::to_string(&table).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "xml")]
+        "xml" => convert::xml::to_xml(document, &convert::xml::XmlOptions::new())
+            .map(|xml| format!("{}\n", xml))
+            .map_err(|e| e.to_string()),
+        "textproto" => convert::textproto::to_textproto(document).map_err(|e| e.to_string()),
+        "csv" => Ok(convert::csv::to_csv(
+            document,
+            &convert::csv::CsvOptions::new(),
+        )),
+        "tsv" => Ok(convert::csv::to_csv(
+            document,
+            &convert::csv::CsvOptions::new().tabs(),
+        )),
+        _ => Err(unsupported(format)),
+    }
+}
+
+fn unsupported(format: &str) -> String {
+    match format {
+        "json" | "yaml" | "toml" | "xml" => format!(
+            "{} support is not enabled; rebuild with `--features {}`",
+            format, format
+        ),
+        _ => format!("unknown format `{}`", format),
+    }
+}
+
+fn convert(args: &[String]) -> Result<(), String> {
+    let mut from = None;
+    let mut to = None;
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--from" => from = args.next().map(String::as_str),
+            "-t" | "--to" => to = args.next().map(String::as_str),
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("convert", option))
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    let file = match files.as_slice() {
+        [] => "-",
+        [file] => file,
+        _ => return Err("convert: expected at most one file".to_string()),
+    };
+    let from = from.or_else(|| extension(file)).unwrap_or("fuji");
+
+    let to = to.unwrap_or("fuji");
+
+    let input = read(file)?;
+    let mut document = decode(from, &input).map_err(|e| format!("{}: {}", file, e))?;
+    // Other formats have no anchors, so references are materialized before converting.
+    if to != "fuji" {
+        document
+            .expand_references()
+            .map_err(|e| format!("{}: {}", file, e))?;
+    }
+    let output = encode(to, &document).map_err(|e| format!("convert: {}", e))?;
+    io::stdout()
+        .write_all(output.as_bytes())
+        .map_err(|e| format!("<stdout>: {}", e))
+}
+
+fn get(args: &[String]) -> Result<(), String> {
+    let mut mode = "fuji";
+    let mut positional = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "-r" | "--raw" => mode = "raw",
+            "-j" | "--json" => mode = "json",
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("get", option))
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (path, file) = match positional.as_slice() {
+        [path] => (*path, "-"),
+        [path, file] => (*path, *file),
+        _ => return Err("get: expected a path and at most one file".to_string()),
+    };
+
+    let input = read(file)?;
+    let mut document =
+        flax::parse_document_complete(&input).map_err(|e| format!("{}:{}", file, e))?;
+    if mode == "json" {
+        document
+            .expand_references()
+            .map_err(|e| format!("{}: {}", file, e))?;
+    }
+    let values = document.query(path).map_err(|e| format!("get: {}", e))?;
+    if values.is_empty() {
+        return Err(format!("get: no values match `{}`", path));
+    }
+    let mut out = String::new();
+    for value in values {
+        match mode {
+            "raw" => out.push_str(value.value()),
+            #[cfg(feature = "json")]
+            "json" => {
+                let json =
+                    convert::json::value_to_json(value).map_err(|e| format!("get: {}", e))?;
+                out.push_str(&json.to_string());
+            }
+            #[cfg(not(feature = "json"))]
+            "json" => return Err(unsupported("json")),
+            _ => out.push_str(&flax::print_value(value)),
+        }
+        out.push('\n');
+    }
+    io::stdout()
+        .write_all(out.as_bytes())
+        .map_err(|e| format!("<stdout>: {}", e))
+}
+
+fn query(args: &[String]) -> Result<(), String> {
+    let mut paths = false;
+    let mut positional = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "-p" | "--paths" => paths = true,
+            option if option.starts_with('-') && option != "-" => {
+                return Err(unknown_option("query", option))
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (query, file) = match positional.as_slice() {
+        [query] => (*query, "-"),
+        [query, file] => (*query, *file),
+        _ => return Err("query: expected a query and at most one file".to_string()),
+    };
+
+    let query: flax::query::Query = query.parse().map_err(|e| format!("query:{}", e))?;
+    let input = read(file)?;
+    let document = flax::parse_document_complete(&input).map_err(|e| format!("{}:{}", file, e))?;
+    let mut out = String::new();
+    for m in query.eval(&document) {
+        if paths {
+            out.push_str(&format!("{}\t", m.path));
+        }
+        out.push_str(&flax::print_value(m.value));
+        out.push('\n');
+    }
+    io::stdout()
+        .write_all(out.as_bytes())
+        .map_err(|e| format!("<stdout>: {}", e))
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "fmt" => fmt(args),
+        Some((command, args)) if command == "validate" => validate(args),
+        Some((command, args)) if command == "lint" => lint(args),
+        Some((command, args)) if command == "doc" => doc(args),
+        Some((command, args)) if command == "convert" => convert(args),
+        Some((command, args)) if command == "get" => get(args),
+        Some((command, args)) if command == "query" => query(args),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,393 @@
+//! A compact binary encoding of documents, e.g. to cache parsed documents or send them over the
+//! wire without printing and parsing them again.
+//!
+//! An encoding starts with a version byte, currently `1`, followed by the encoded node:
+//!
+//! - a length or count is an unsigned LEB128 varint;
+//! - a string is its length in bytes followed by its UTF-8 bytes;
+//! - a binding is its name followed by the count of its values and the values;
+//! - a value is a kind byte (`0` for a scalar, `1` for a nested list, `2` for a map, `3` for a
+//!   reference, `4` for an anchored scalar), for an anchored scalar its anchor, its scalar, for a
+//!   nested list the count of its items and the items, and lastly the count of its child bindings
+//!   and the bindings;
+//! - a document is the count of its bindings followed by the bindings.
+
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+use core::fmt;
+
+/// The version of the encoding written by `to_bytes`.
+const VERSION: u8 = 1;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DecodeError {
+    /// The encoding starts with a version this crate cannot decode.
+    UnsupportedVersion(u8),
+    /// The input ends in the middle of a node.
+    UnexpectedEnd,
+    /// A varint overflows a `usize`.
+    InvalidLength,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// A value has an unknown kind byte.
+    InvalidKind(u8),
+    /// The input continues after the encoded node, at the given offset.
+    TrailingBytes(usize),
+    /// Values are nested more deeply than the parsers accept in text.
+    TooDeep,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported encoding version {}", version)
+            }
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::InvalidLength => write!(f, "length out of range"),
+            DecodeError::InvalidUtf8 => write!(f, "invalid UTF-8 in string"),
+            DecodeError::InvalidKind(kind) => write!(f, "invalid value kind {}", kind),
+            DecodeError::TrailingBytes(offset) => write!(f, "trailing bytes at offset {}", offset),
+            DecodeError::TooDeep => write!(f, "more than {} levels of nesting", MAX_DEPTH),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// A part of a tree that remains to be encoded.
+enum Write<'a> {
+    Binding(&'a Binding),
+    Value(&'a Value),
+    /// The number of child bindings of a value, which follows its list items.
+    Len(usize),
+}
+
+/// Encodes `item` with an explicit stack rather than recursion, so that trees of any depth built
+/// programmatically can be encoded without overflowing the stack.
+fn write(out: &mut Vec<u8>, item: Write) {
+    let mut stack = vec![item];
+    while let Some(item) = stack.pop() {
+        match item {
+            Write::Len(len) => write_len(out, len),
+            Write::Binding(binding) => {
+                write_str(out, &binding.name);
+                write_len(out, binding.values.len());
+                stack.extend(binding.values.iter().rev().map(Write::Value));
+            }
+            Write::Value(value) => {
+                match &value.kind {
+                    ValueKind::Scalar => out.push(0),
+                    ValueKind::List(_) => out.push(1),
+                    ValueKind::Map => out.push(2),
+                    ValueKind::Reference => out.push(3),
+                    ValueKind::Anchored(anchor) => {
+                        out.push(4);
+                        write_str(out, anchor);
+                    }
+                }
+                write_str(out, &value.value);
+                stack.extend(value.children.iter().rev().map(Write::Binding));
+                stack.push(Write::Len(value.children.len()));
+                if let ValueKind::List(items) = &value.kind {
+                    write_len(out, items.len());
+                    stack.extend(items.iter().rev().map(Write::Value));
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// The number of values enclosing the current position.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the version byte of `input`.
+    fn new(input: &'a [u8]) -> Result<Self, DecodeError> {
+        match input.first() {
+            Some(&VERSION) => Ok(Reader {
+                input,
+                pos: 1,
+                depth: 0,
+            }),
+            Some(&version) => Err(DecodeError::UnsupportedVersion(version)),
+            None => Err(DecodeError::UnexpectedEnd),
+        }
+    }
+
+    /// Fails if any input is left.
+    fn finish<T>(self, node: T) -> Result<T, DecodeError> {
+        if self.pos < self.input.len() {
+            return Err(DecodeError::TrailingBytes(self.pos));
+        }
+        Ok(node)
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.input.get(self.pos).ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let mut len = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as usize;
+            if shift >= usize::BITS || bits.checked_shl(shift).map(|b| b >> shift) != Some(bits) {
+                return Err(DecodeError::InvalidLength);
+            }
+            len |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads a count of nodes, each at least one byte long, so that a corrupt count fails before
+    /// allocating for it.
+    fn count(&mut self) -> Result<usize, DecodeError> {
+        let count = self.len()?;
+        if count > self.input.len() - self.pos {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.count()?;
+        let bytes = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+        core::str::from_utf8(bytes)
+            .map(|s| s.to_string())
+            .map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    fn binding(&mut self) -> Result<Binding, DecodeError> {
+        let name = self.string()?;
+        let count = self.count()?;
+        let values = (0..count).map(|_| self.value()).collect::<Result<_, _>>()?;
+        Ok(Binding { name, values })
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        let kind = self.byte()?;
+        let anchor = if kind == 4 {
+            Some(self.string()?)
+        } else {
+            None
+        };
+        let value = self.string()?;
+        let kind = match (kind, anchor) {
+            (0, _) => ValueKind::Scalar,
+            (1, _) => {
+                let count = self.count()?;
+                ValueKind::List(self.nested(count, Self::value)?)
+            }
+            (2, _) => ValueKind::Map,
+            (3, _) => ValueKind::Reference,
+            (4, Some(anchor)) => ValueKind::Anchored(anchor),
+            (kind, _) => return Err(DecodeError::InvalidKind(kind)),
+        };
+        let count = self.count()?;
+        let children = self.nested(count, Self::binding)?;
+        Ok(Value {
+            value,
+            kind,
+            children,
+        })
+    }
+
+    /// Reads the `count` items or child bindings of a value with `read`, failing if they are
+    /// nested in more than `MAX_DEPTH` values, so that adversarial input cannot overflow the stack.
+    fn nested<T, F>(&mut self, count: usize, mut read: F) -> Result<Vec<T>, DecodeError>
+    where
+        F: FnMut(&mut Self) -> Result<T, DecodeError>,
+    {
+        if count > 0 && self.depth >= MAX_DEPTH {
+            return Err(DecodeError::TooDeep);
+        }
+        self.depth += 1;
+        let nodes = (0..count).map(|_| read(self)).collect();
+        self.depth -= 1;
+        nodes
+    }
+
+    fn bindings(&mut self) -> Result<Vec<Binding>, DecodeError> {
+        let count = self.count()?;
+        (0..count).map(|_| self.binding()).collect()
+    }
+}
+
+impl Binding {
+    /// Encodes the binding in the binary encoding described in the [`binary`](crate::binary)
+    /// module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        write(&mut out, Write::Binding(self));
+        out
+    }
+
+    /// Decodes a binding encoded by [`Binding::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Binding, DecodeError> {
+        let mut reader = Reader::new(bytes)?;
+        let binding = reader.binding()?;
+        reader.finish(binding)
+    }
+}
+
+impl Value {
+    /// Encodes the value in the binary encoding described in the [`binary`](crate::binary)
+    /// module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        write(&mut out, Write::Value(self));
+        out
+    }
+
+    /// Decodes a value encoded by [`Value::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Value, DecodeError> {
+        let mut reader = Reader::new(bytes)?;
+        let value = reader.value()?;
+        reader.finish(value)
+    }
+}
+
+impl Document {
+    /// Encodes the document in the binary encoding described in the [`binary`](crate::binary)
+    /// module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        write_len(&mut out, self.bindings.len());
+        for binding in self.bindings.iter() {
+            write(&mut out, Write::Binding(binding));
+        }
+        out
+    }
+
+    /// Decodes a document encoded by [`Document::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Document, DecodeError> {
+        let mut reader = Reader::new(bytes)?;
+        let bindings = reader.bindings()?;
+        reader.finish(Document::from_bindings(bindings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "arbitrary")]
+    use crate::arbitrary::document_with_text;
+    use crate::parse_binding_complete;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prop_assert_eq, proptest};
+
+    #[test]
+    fn test_bytes() {
+        let binding = parse_binding_complete("a=[b{c=d}, [e], {f=g}]").unwrap();
+        let bytes = binding.to_bytes();
+        assert_eq!(
+            vec![
+                1, 1, b'a', 3, // binding `a` with three values
+                0, 1, b'b', 1, // scalar `b` with one child
+                1, b'c', 1, 0, 1, b'd', 0, // binding `c=d`
+                1, 0, 1, 0, 1, b'e', 0, 0, // nested list `[e]`
+                2, 0, 1, 1, b'f', 1, 0, 1, b'g', 0, // map `{f=g}`
+            ],
+            bytes
+        );
+        assert_eq!(Ok(binding), Binding::from_bytes(&bytes));
+
+        let long = Binding::new("x".repeat(300)).value("y");
+        assert_eq!(&[1, 0xac, 0x02], &long.to_bytes()[..3]);
+        assert_eq!(Ok(long.clone()), Binding::from_bytes(&long.to_bytes()));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let tests: [(&[u8], DecodeError); 7] = [
+            (&[], DecodeError::UnexpectedEnd),
+            (&[2, 0], DecodeError::UnsupportedVersion(2)),
+            (&[1, 1, b'a', 1], DecodeError::UnexpectedEnd),
+            (&[1, 1, b'a', 1, 5, 0, 0], DecodeError::InvalidKind(5)),
+            (&[1, 1, 0xff, 0], DecodeError::InvalidUtf8),
+            (&[1, 1, b'a', 0, 0], DecodeError::TrailingBytes(4)),
+            (
+                &[
+                    1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1,
+                ],
+                DecodeError::InvalidLength,
+            ),
+        ];
+        for (bytes, error) in tests.iter() {
+            assert_eq!(
+                Err(error.clone()),
+                Binding::from_bytes(bytes),
+                "{:?}",
+                bytes
+            );
+        }
+        assert_eq!(
+            Err(DecodeError::UnexpectedEnd),
+            Document::from_bytes(&[1, 0x80, 0x80, 0x01])
+        );
+
+        // Each level is a map, `2, 0`, with a single child binding, `1`, named `a`, `1, b'a'`, bound
+        // to a single value, `1`.
+        let nested = |depth: usize| {
+            let mut bytes = vec![1, 1, b'a', 1];
+            for _ in 0..depth {
+                bytes.extend_from_slice(&[2, 0, 1, 1, b'a', 1]);
+            }
+            bytes.extend_from_slice(&[0, 0, 0]);
+            bytes
+        };
+        assert!(Binding::from_bytes(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            Err(DecodeError::TooDeep),
+            Binding::from_bytes(&nested(MAX_DEPTH + 1))
+        );
+        assert_eq!(
+            Err(DecodeError::TooDeep),
+            Binding::from_bytes(&nested(100_000))
+        );
+
+        // Encoding does not recurse, so trees built programmatically can be any depth.
+        let mut value = Value::from("");
+        for _ in 0..200_000 {
+            value = Value::map(vec![Binding::new("a").value(value)]);
+        }
+        let binding = Binding::new("a").value(value);
+        assert_eq!(nested(200_000), binding.to_bytes());
+        crate::limits::drop_deep(binding);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_bytes_round_trip(document in document_with_text()) {
+            prop_assert_eq!(Ok(document.clone()), Document::from_bytes(&document.to_bytes()));
+        }
+    }
+}
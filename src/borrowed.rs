@@ -0,0 +1,158 @@
+//! A zero-copy variant of the AST that borrows identifiers from the input.
+//!
+//! Useful for read-only workloads over large documents; call `into_owned` to detach a node from
+//! the input.
+
+use crate::error::complete;
+use crate::prelude::*;
+use crate::{fast, parser, ParseError};
+use alloc::borrow::Cow;
+use nom::IResult;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Binding<'a> {
+    pub(crate) name: Cow<'a, str>,
+    pub(crate) values: Vec<Value<'a>>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Value<'a> {
+    pub(crate) value: Cow<'a, str>,
+    pub(crate) kind: ValueKind<'a>,
+    pub(crate) children: Vec<Binding<'a>>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ValueKind<'a> {
+    Scalar,
+    List(Vec<Value<'a>>),
+    Map,
+    Reference,
+    Anchored(&'a str),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Document<'a> {
+    pub(crate) bindings: Vec<Binding<'a>>,
+}
+
+impl<'a> Binding<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn values(&self) -> &[Value<'a>] {
+        &self.values
+    }
+
+    pub fn into_owned(self) -> crate::Binding {
+        crate::Binding {
+            name: self.name.into_owned(),
+            values: self.values.into_iter().map(Value::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn kind(&self) -> &ValueKind<'a> {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &[Binding<'a>] {
+        &self.children
+    }
+
+    /// Returns the value itself, or the values nested in it if it is a list, recursively.
+    pub(crate) fn scalars(&self) -> Vec<&Value<'a>> {
+        match &self.kind {
+            ValueKind::List(items) => items.iter().flat_map(Value::scalars).collect(),
+            _ => vec![self],
+        }
+    }
+
+    pub(crate) fn scalars_mut(&mut self) -> Vec<&mut Value<'a>> {
+        if !matches!(self.kind, ValueKind::List(_)) {
+            return vec![self];
+        }
+        match &mut self.kind {
+            ValueKind::List(items) => items.iter_mut().flat_map(Value::scalars_mut).collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn into_owned(self) -> crate::Value {
+        crate::Value {
+            value: self.value.into_owned(),
+            kind: match self.kind {
+                ValueKind::Scalar => crate::ValueKind::Scalar,
+                ValueKind::List(items) => {
+                    crate::ValueKind::List(items.into_iter().map(Value::into_owned).collect())
+                }
+                ValueKind::Map => crate::ValueKind::Map,
+                ValueKind::Reference => crate::ValueKind::Reference,
+                ValueKind::Anchored(anchor) => crate::ValueKind::Anchored(anchor.to_string()),
+            },
+            children: self.children.into_iter().map(Binding::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    pub fn bindings(&self) -> &[Binding<'a>] {
+        &self.bindings
+    }
+
+    pub fn into_owned(self) -> crate::Document {
+        crate::Document::from_bindings(self.bindings.into_iter().map(Binding::into_owned).collect())
+    }
+}
+
+pub fn parse_binding(input: &str) -> IResult<&str, Binding<'_>> {
+    parser::binding(input)
+}
+
+pub fn parse_binding_complete(input: &str) -> Result<Binding<'_>, ParseError> {
+    match fast::binding(input) {
+        Some(binding) => Ok(binding),
+        None => complete(input, parser::complete_binding),
+    }
+}
+
+pub fn parse_value(input: &str) -> IResult<&str, Value<'_>> {
+    parser::value(input)
+}
+
+pub fn parse_document(input: &str) -> IResult<&str, Document<'_>> {
+    parser::document(input)
+}
+
+pub fn parse_document_complete(input: &str) -> Result<Document<'_>, ParseError> {
+    match fast::document(input) {
+        Some(document) => Ok(document),
+        None => complete(input, parser::document),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrowed() {
+        let input = "foo=bar{zoo=qat},xxx";
+        let (_, binding) = parse_binding(input).unwrap();
+        assert!(matches!(binding.name, Cow::Borrowed(_)));
+        assert_eq!("foo", binding.name());
+        assert_eq!("bar", binding.values()[0].value());
+        assert_eq!("zoo", binding.values()[0].children()[0].name());
+        assert_eq!("xxx", binding.values()[1].value());
+        assert_eq!(
+            crate::parse_binding_complete(input),
+            Ok(binding.into_owned())
+        );
+    }
+}
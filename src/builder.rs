@@ -0,0 +1,130 @@
+//! Fluent construction of documents, e.g.
+//! `Binding::new("foo").value("bar").child(Binding::new("zoo").value("qat"))`.
+
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+
+impl Binding {
+    /// Creates a binding with no values; add them with [`Binding::value`].
+    pub fn new(name: impl Into<String>) -> Self {
+        Binding {
+            name: name.into(),
+            values: smallvec![],
+        }
+    }
+
+    /// Appends a value.
+    pub fn value(mut self, value: impl Into<Value>) -> Self {
+        self.values.push(value.into());
+        self
+    }
+
+    /// Appends a child binding to the last value.
+    ///
+    /// # Panics
+    ///
+    /// If the binding has no values yet.
+    pub fn child(mut self, child: Binding) -> Self {
+        self.values
+            .last_mut()
+            .expect("Binding::child called before Binding::value")
+            .children
+            .push(child);
+        self
+    }
+}
+
+impl Value {
+    /// Creates a value with no children.
+    pub fn new(value: impl Into<String>) -> Self {
+        Value {
+            value: value.into(),
+            kind: ValueKind::Scalar,
+            children: vec![],
+        }
+    }
+
+    /// Creates a list, which is printed in brackets.
+    pub fn list(items: impl IntoIterator<Item = Value>) -> Self {
+        Value {
+            value: String::new(),
+            kind: ValueKind::List(items.into_iter().collect()),
+            children: vec![],
+        }
+    }
+
+    /// Creates a map, i.e. a block of children without a leading scalar, printed as `{..}`.
+    pub fn map(children: impl IntoIterator<Item = Binding>) -> Self {
+        Value {
+            value: String::new(),
+            kind: ValueKind::Map,
+            children: children.into_iter().collect(),
+        }
+    }
+
+    /// Creates a reference to the value anchored as `name`, printed as `*name`.
+    pub fn reference(name: impl Into<String>) -> Self {
+        Value {
+            value: name.into(),
+            kind: ValueKind::Reference,
+            children: vec![],
+        }
+    }
+
+    /// Names a scalar with an anchor, printed as `&name scalar`.
+    pub fn anchor(mut self, name: impl Into<String>) -> Self {
+        self.kind = ValueKind::Anchored(name.into());
+        self
+    }
+
+    /// Appends a child binding.
+    pub fn child(mut self, child: Binding) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+impl Document {
+    /// Creates an empty document.
+    pub fn new() -> Self {
+        Document::from_bindings(vec![])
+    }
+
+    /// Appends a top-level binding.
+    pub fn binding(mut self, binding: Binding) -> Self {
+        self.bindings_mut().push(binding);
+        self
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    #[test]
+    fn test_builder() {
+        assert_eq!(
+            parse_binding_complete("foo=bar{zoo=qat x=y},baz").unwrap(),
+            Binding::new("foo")
+                .value("bar")
+                .child(Binding::new("zoo").value("qat"))
+                .child(Binding::new("x").value("y"))
+                .value("baz")
+        );
+        assert_eq!(
+            parse_document_complete("a=b{c=d} e=f").unwrap(),
+            Document::new()
+                .binding(
+                    Binding::new("a").value(Value::new("b").child(Binding::new("c").value("d")))
+                )
+                .binding(Binding::new("e").value("f"))
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! A canonical form of documents, for content addressing and diff-friendly storage.
+//!
+//! The canonical bytes and hash of a binding or document encode its canonical form directly,
+//! rather than as printed, so that they are stable across versions of this crate; see
+//! [`Binding::canonical_hash`].
+
+use crate::prelude::*;
+use crate::sha256::sha256;
+use crate::{Binding, Document, Value, ValueKind};
+
+/// Sorts `bindings` by name, recursively. The sort is stable: repeated bindings with the same
+/// name keep their relative order, since the order of their values is significant.
+fn canonicalize(bindings: &mut [Binding]) {
+    bindings.sort_by(|a, b| a.name.cmp(&b.name));
+    for binding in bindings.iter_mut() {
+        binding.canonicalize();
+    }
+}
+
+fn encode_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn encode_str(out: &mut Vec<u8>, s: &str) {
+    encode_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode_binding(out: &mut Vec<u8>, binding: &Binding) {
+    encode_str(out, &binding.name);
+    encode_len(out, binding.values.len());
+    for value in binding.values.iter() {
+        encode_value(out, value);
+    }
+}
+
+fn encode_value(out: &mut Vec<u8>, value: &Value) {
+    out.push(match value.kind {
+        ValueKind::Scalar => 0,
+        ValueKind::Map => 1,
+        ValueKind::List(_) => 2,
+        ValueKind::Reference => 3,
+        ValueKind::Anchored(_) => 4,
+    });
+    if let ValueKind::Anchored(anchor) = &value.kind {
+        encode_str(out, anchor);
+    }
+    encode_str(out, &value.value);
+    if let ValueKind::List(items) = &value.kind {
+        encode_len(out, items.len());
+        for item in items {
+            encode_value(out, item);
+        }
+    }
+    encode_len(out, value.children.len());
+    for child in value.children.iter() {
+        encode_binding(out, child);
+    }
+}
+
+impl Binding {
+    /// Returns the canonical bytes of this binding, described in the documentation of
+    /// [`Binding::canonical_hash`].
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut binding = self.clone();
+        binding.canonicalize();
+        let mut out = b"fuji\x01B".to_vec();
+        encode_binding(&mut out, &binding);
+        out
+    }
+
+    /// Returns the SHA-256 digest of the canonical bytes of this binding, which only depend on
+    /// its canonical form: bindings that differ only in the order of differently named siblings,
+    /// in whitespace or in comments have the same hash.
+    ///
+    /// The hash is stable across versions of this crate, so it can key caches or detect drift
+    /// between stored and deployed configurations. The canonical bytes encode the tree directly
+    /// rather than as printed:
+    ///
+    /// - integers are 64-bit little-endian, and each string is its length in bytes followed by its
+    ///   UTF-8 bytes;
+    /// - a binding is its name, then the number of its values, then each value;
+    /// - a value is a tag byte, `0` for a scalar, `1` for a map, `2` for a nested list, `3` for a
+    ///   reference and `4` for an anchored scalar, then, for an anchored scalar, the name of its
+    ///   anchor, then its scalar, which for a reference is the name of the anchor it refers to,
+    ///   then, for a list, the number of its items and each item, then the number of its children
+    ///   and each child binding;
+    /// - the canonical bytes of a binding are `fuji\x01B` followed by the binding, and those of a
+    ///   document `fuji\x01D` followed by the number of its bindings, then each binding.
+    ///
+    /// ```
+    /// use flax::Binding;
+    ///
+    /// let a: Binding = "server=main{port=80 host=h}".parse().unwrap();
+    /// let b: Binding = "server=main{ host=h # first\n port=80 }".parse().unwrap();
+    /// assert_eq!(a.canonical_hash(), b.canonical_hash());
+    /// ```
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        sha256(&self.canonical_bytes())
+    }
+
+    /// Sorts the child bindings of every value by name, recursively, comparing names byte by
+    /// byte. Bindings that share a name keep their relative order, and the order of values within
+    /// a binding is preserved.
+    ///
+    /// Printing a canonicalized binding yields the same bytes for any two bindings that differ
+    /// only in the order of differently named siblings; the printer already normalizes
+    /// whitespace and drops comments.
+    pub fn canonicalize(&mut self) {
+        for value in self.values.iter_mut().flat_map(Value::scalars_mut) {
+            canonicalize(&mut value.children);
+        }
+    }
+}
+
+impl Document {
+    /// Sorts the top-level bindings and their descendants as `Binding::canonicalize` does.
+    pub fn canonicalize(&mut self) {
+        canonicalize(self.bindings_mut());
+    }
+
+    /// Returns the canonical bytes of the document, as [`Binding::canonical_hash`] describes.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut document = self.clone();
+        document.canonicalize();
+        let mut out = b"fuji\x01D".to_vec();
+        encode_len(&mut out, document.bindings.len());
+        for binding in document.bindings.iter() {
+            encode_binding(&mut out, binding);
+        }
+        out
+    }
+
+    /// Returns the SHA-256 digest of the canonical bytes of the document, which is stable across
+    /// versions of this crate, as [`Binding::canonical_hash`] is.
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        sha256(&self.canonical_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    fn hex(digest: [u8; 32]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let tests = [
+            ("", ""),
+            ("b=x a=y", "a=y\nb=x"),
+            (
+                "z=1{c=d b=e{y=z x=w},f{B=g A=h}} a=2 z=0",
+                "a=2\nz=1{b=e{x=w y=z},f{A=h B=g} c=d}\nz=0",
+            ),
+            ("a=3 b=x a=1,2 a=0", "a=3\na=1,2\na=0\nb=x"),
+        ];
+
+        for (input, canonical) in tests.iter() {
+            let mut document = parse_document_complete(input).unwrap();
+            document.canonicalize();
+            assert_eq!(*canonical, document.to_string(), "{}", input);
+        }
+
+        let mut a = parse_document_complete("# x\nk=v{ q=1  p=2 }  j=u").unwrap();
+        let mut b = parse_document_complete("j=u k=v{p=2 q=1}").unwrap();
+        assert_ne!(a, b);
+        a.canonicalize();
+        b.canonicalize();
+        assert_eq!(a.to_string(), b.to_string());
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        let binding = parse_binding_complete("a=b").unwrap();
+        let mut expected = b"fuji\x01B".to_vec();
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, b'a']);
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, b'b']);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(expected, binding.canonical_bytes());
+
+        let document = parse_document_complete("a=b").unwrap();
+        assert_eq!(
+            b"fuji\x01D\x01\x00\x00\x00\x00\x00\x00\x00"[..],
+            document.canonical_bytes()[..14]
+        );
+        assert_eq!(expected[6..], document.canonical_bytes()[14..]);
+    }
+
+    /// The hashes must never change: they are part of the stability guarantee.
+    #[test]
+    fn test_canonical_hash() {
+        let tests = [
+            (
+                "a=b",
+                "56400309b5a693dce3b48d61e2f9ecc8b88b5e7e41ee62c0a6f9a2847b55d0ad",
+            ),
+            (
+                "server=main{port=80 host=h} tags=[x,[y,z]] empty= map={}",
+                "29d9e1bf006348a7664070c47dce17eb77674172ffd311f44f2afc4870627faa",
+            ),
+            (
+                "a=&x v{c=d} b=*x",
+                "7172d5340df750081d0b842c20371d49a74dc7731fb316fec356eb3e0867b469",
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            assert_eq!(*expected, hex(document.canonical_hash()), "{}", input);
+        }
+
+        let binding = parse_binding_complete("a=b").unwrap();
+        assert_eq!(
+            "d012934c89974f6db5b40acdef42fd0314b0b1fae96d90f897a93ca2b7d82355",
+            hex(binding.canonical_hash())
+        );
+
+        let hash = |input| parse_document_complete(input).unwrap().canonical_hash();
+        assert_eq!(hash("b=x{d=1 c=2} a=y"), hash("# c\na=y b=x{c=2\n d=1}"));
+        assert_eq!(hash("a=b"), hash("a=\"b\"{}"));
+        let distinct = [
+            "a=b",
+            "a={}",
+            "a=",
+            "a=b,c",
+            "a=b a=c",
+            "a=[b,[c]]",
+            "a=b{c=d}",
+            "a=\"b{c=d}\"",
+            "a=c a=b",
+            "a=*b",
+            "a=&b b",
+            "a=&c b",
+        ];
+        for (i, a) in distinct.iter().enumerate() {
+            for b in distinct[i + 1..].iter() {
+                assert_ne!(hash(a), hash(b), "{} {}", a, b);
+            }
+        }
+    }
+}
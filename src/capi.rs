@@ -0,0 +1,201 @@
+//! C bindings, declared in `include/fuji.h`.
+//!
+//! Build a shared library with `cargo rustc --lib --release --features capi --crate-type cdylib`.
+//!
+//! Functions that can fail take a `FujiError **` out-parameter, which may be null. On failure they
+//! return null and, if the out-parameter is not null, store a newly allocated error in it, to be
+//! released with `fuji_error_free`. Strings returned by the library are released with
+//! `fuji_string_free`.
+
+use crate::{parse_document_complete, print_document, print_value, Document};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    ptr,
+};
+
+/// An error reported through an out-parameter. `line` and `column` are 1-based, or 0 if the
+/// error has no location in the input.
+#[repr(C)]
+pub struct FujiError {
+    pub message: *mut c_char,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Converts `s` to a C string, replacing any interior NUL bytes, which cannot be represented.
+fn c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "\u{fffd}"))
+        .unwrap()
+        .into_raw()
+}
+
+unsafe fn set_error(error: *mut *mut FujiError, message: &str, line: usize, column: usize) {
+    if !error.is_null() {
+        *error = Box::into_raw(Box::new(FujiError {
+            message: c_string(message),
+            line,
+            column,
+        }));
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, error: *mut *mut FujiError) -> Option<&'a str> {
+    if s.is_null() {
+        set_error(error, "null input", 0, 0);
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(e) => {
+            set_error(error, &format!("invalid UTF-8: {}", e), 0, 0);
+            None
+        }
+    }
+}
+
+/// Parses a NUL-terminated document. The result is released with `fuji_free`.
+///
+/// # Safety
+///
+/// `input` must be null or a valid NUL-terminated string, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_parse(
+    input: *const c_char,
+    error: *mut *mut FujiError,
+) -> *mut Document {
+    let text = match str_arg(input, error) {
+        Some(text) => text,
+        None => return ptr::null_mut(),
+    };
+    match parse_document_complete(text) {
+        Ok(document) => Box::into_raw(Box::new(document)),
+        Err(e) => {
+            set_error(error, &e.to_string(), e.line, e.column);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Prints a document, or returns null if `document` is null.
+///
+/// # Safety
+///
+/// `document` must be null or returned by `fuji_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_print(document: *const Document) -> *mut c_char {
+    match document.as_ref() {
+        Some(document) => c_string(&print_document(document)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Prints the value at a dotted path, e.g. `server.ports[1]`.
+///
+/// # Safety
+///
+/// `document` must be returned by `fuji_parse` and not yet freed, `path` must be null or a valid
+/// NUL-terminated string, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_get(
+    document: *const Document,
+    path: *const c_char,
+    error: *mut *mut FujiError,
+) -> *mut c_char {
+    let document = match document.as_ref() {
+        Some(document) => document,
+        None => {
+            set_error(error, "null document", 0, 0);
+            return ptr::null_mut();
+        }
+    };
+    let path = match str_arg(path, error) {
+        Some(path) => path,
+        None => return ptr::null_mut(),
+    };
+    match document.get(path) {
+        Some(value) => c_string(&print_value(value)),
+        None => {
+            set_error(error, &format!("path `{}` not found", path), 0, 0);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases a document returned by `fuji_parse`. Null is ignored.
+///
+/// # Safety
+///
+/// `document` must be null or returned by `fuji_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_free(document: *mut Document) {
+    if !document.is_null() {
+        drop(Box::from_raw(document));
+    }
+}
+
+/// Releases a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or returned by the library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Releases an error. Null is ignored.
+///
+/// # Safety
+///
+/// `error` must be null or stored by the library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fuji_error_free(error: *mut FujiError) {
+    if !error.is_null() {
+        let error = Box::from_raw(error);
+        fuji_string_free(error.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take(s: *mut c_char) -> String {
+        let string = CStr::from_ptr(s).to_str().unwrap().to_string();
+        fuji_string_free(s);
+        string
+    }
+
+    #[test]
+    fn test_capi() {
+        unsafe {
+            let mut error = ptr::null_mut();
+            let document = fuji_parse(b"a=b{c=d}  e=f\0".as_ptr() as *const c_char, &mut error);
+            assert!(!document.is_null() && error.is_null());
+            assert_eq!("a=b{c=d}\ne=f", take(fuji_print(document)));
+            assert_eq!(
+                "d",
+                take(fuji_get(
+                    document,
+                    b"a.c\0".as_ptr() as *const c_char,
+                    &mut error
+                ))
+            );
+            assert!(fuji_get(document, b"x\0".as_ptr() as *const c_char, &mut error).is_null());
+            assert_eq!("path `x` not found", take((*error).message));
+            drop(Box::from_raw(error));
+            fuji_free(document);
+
+            let mut error = ptr::null_mut();
+            let document = fuji_parse(b"a=b\nc\0".as_ptr() as *const c_char, &mut error);
+            assert!(document.is_null());
+            assert_eq!((2, 2), ((*error).line, (*error).column));
+            fuji_error_free(error);
+
+            assert!(fuji_parse(ptr::null(), ptr::null_mut()).is_null());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Integration with [clap](https://docs.rs/clap), layering command-line overrides over a
+//! configuration file.
+//!
+//! [`OverrideParser`] parses the values of a flag such as `--set server.port=8080` into bindings,
+//! written as the flags of [`from_args`](crate::from_args) without their `--`. [`load`] and
+//! [`load_as`] then apply them over a file:
+//!
+//! ```no_run
+//! use clap::{Arg, ArgAction, Command};
+//! use flax::clap::{load, OverrideParser};
+//! use flax::Binding;
+//!
+//! let matches = Command::new("server")
+//!     .arg(Arg::new("config").long("config").default_value("server.fuji"))
+//!     .arg(
+//!         Arg::new("set")
+//!             .long("set")
+//!             .action(ArgAction::Append)
+//!             .value_parser(OverrideParser),
+//!     )
+//!     .get_matches();
+//! let overrides = matches
+//!     .get_many::<Binding>("set")
+//!     .unwrap_or_default()
+//!     .cloned()
+//!     .collect::<Vec<_>>();
+//! let config = load(matches.get_one::<String>("config").unwrap(), &overrides).unwrap();
+//! ```
+
+use crate::args::parse_override;
+use crate::include::{self, FileResolver, LoadError};
+use crate::prelude::*;
+use crate::{ArgError, Binding, DeserializeError, Document, FujiDeserialize, Value, ValueKind};
+use ::clap::builder::TypedValueParser;
+use ::clap::{Arg, Command};
+use core::fmt;
+use std::ffi::OsStr;
+
+/// A clap value parser for overrides such as `server.port=8080`, `features=a,b` or
+/// `db{host=x}`, producing a [`Binding`] of the first segment of the path.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OverrideParser;
+
+impl TypedValueParser for OverrideParser {
+    type Value = Binding;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Binding, ::clap::Error> {
+        let parse = |value: &str| {
+            parse_override(value, 0, 0).map_err(|error| match error {
+                ArgError::NotAFlag { .. } => unreachable!(),
+                ArgError::Path { error, .. } => error.to_string(),
+                ArgError::Parse { error, .. } => error.to_string(),
+            })
+        };
+        parse.parse_ref(cmd, arg, value)
+    }
+}
+
+/// A configuration that could not be loaded or deserialized.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ConfigError {
+    Load(LoadError),
+    Deserialize(DeserializeError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Load(e) => write!(f, "{}", e),
+            ConfigError::Deserialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Applies `overlay` to `bindings`. A map, such as those built along the path of an override,
+/// adds its children to the first value bound to its name, keeping that value's scalar and other
+/// children; any other values replace the existing ones.
+fn apply(bindings: &mut Vec<Binding>, overlay: &Binding) {
+    let binding = match bindings.iter_mut().find(|b| b.name == overlay.name) {
+        Some(binding) => binding,
+        None => return bindings.push(overlay.clone()),
+    };
+    match (binding.values.as_mut_slice(), overlay.values.as_slice()) {
+        ([value], [map]) if map.is_map() && value.items().is_none() => {
+            for child in map.children.iter() {
+                apply(&mut value.children, child);
+            }
+        }
+        _ => binding.values = overlay.values.clone(),
+    }
+}
+
+/// Loads the document at `path`, expanding include directives, and applies `overrides` over it,
+/// in order. An override replaces the values at its path, e.g. `server.port=8080` only replaces
+/// the port of the first `server`, whatever its other children or its scalar.
+pub fn load(path: &str, overrides: &[Binding]) -> Result<Document, LoadError> {
+    let mut document = include::load(path, &FileResolver)?;
+    for overlay in overrides {
+        apply(document.bindings_mut(), overlay);
+    }
+    Ok(document)
+}
+
+/// Loads the document at `path` with `overrides`, as [`load`] does, and converts its top-level
+/// bindings to the fields of a `T`.
+pub fn load_as<T: FujiDeserialize>(path: &str, overrides: &[Binding]) -> Result<T, ConfigError> {
+    let document = load(path, overrides).map_err(ConfigError::Load)?;
+    let value = Value {
+        value: String::new(),
+        kind: ValueKind::Map,
+        children: document.bindings,
+    };
+    T::from_value(&value).map_err(ConfigError::Deserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::__private::{required, values};
+    use ::clap::ArgAction;
+
+    fn overrides(args: &[&str]) -> Result<Vec<Binding>, String> {
+        let command = Command::new("app").arg(
+            Arg::new("set")
+                .long("set")
+                .action(ArgAction::Append)
+                .value_parser(OverrideParser),
+        );
+        let matches = command
+            .try_get_matches_from(["app"].iter().chain(args))
+            .map_err(|e| e.to_string())?;
+        Ok(matches
+            .get_many::<Binding>("set")
+            .unwrap_or_default()
+            .cloned()
+            .collect())
+    }
+
+    #[test]
+    fn test_override_parser() {
+        let bindings = overrides(&["--set", "a.b=1,2", "--set=c{d=e}", "--set", "f"]).unwrap();
+        assert_eq!(
+            vec!["a={b=1,2}", "c={d=e}", "f=true"],
+            bindings.iter().map(|b| b.to_string()).collect::<Vec<_>>()
+        );
+
+        let error = overrides(&["--set", "a..b=c"]).unwrap_err();
+        assert!(
+            error.starts_with(
+                "error: invalid value 'a..b=c' for '--set <set>': invalid path `a..b`"
+            ),
+            "{}",
+            error
+        );
+        let error = overrides(&["--set", "a={b=c"]).unwrap_err();
+        assert!(
+            error.contains("1:7: expected '}', found end of input"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("flax-clap-{}.fuji", std::process::id()));
+        std::fs::write(&path, "server=main{host=h port=80} mode=tcp").unwrap();
+        let path = path.to_str().unwrap();
+
+        let bindings = overrides(&[
+            "--set",
+            "server.port=8080",
+            "--set",
+            "server.tls{cert=c}",
+            "--set",
+            "mode=udp",
+            "--set",
+            "debug",
+        ])
+        .unwrap();
+        assert_eq!(
+            "server=main{host=h port=8080 tls={cert=c}}\nmode=udp\ndebug=true",
+            load(path, &bindings).unwrap().to_string()
+        );
+
+        #[derive(Debug, PartialEq)]
+        struct Config {
+            mode: String,
+            debug: bool,
+        }
+        impl FujiDeserialize for Config {
+            fn from_value(value: &Value) -> Result<Self, DeserializeError> {
+                Ok(Config {
+                    mode: required("mode", &values(value, "mode"))?,
+                    debug: required("debug", &values(value, "debug"))?,
+                })
+            }
+        }
+        assert_eq!(
+            Ok(Config {
+                mode: "udp".to_string(),
+                debug: true,
+            }),
+            load_as::<Config>(path, &bindings)
+        );
+        let bindings = overrides(&["--set", "debug=maybe"]).unwrap();
+        assert!(matches!(
+            load_as::<Config>(path, &bindings),
+            Err(ConfigError::Deserialize(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+
+        assert!(matches!(
+            load_as::<Config>("/nonexistent/flax.fuji", &[]),
+            Err(ConfigError::Load(_))
+        ));
+    }
+}
@@ -0,0 +1,429 @@
+//! Generation of Rust types from schemas, e.g. from a build script:
+//!
+//! ```text
+//! let schema = Schema::from_document(&parse_document_complete(&input)?)?;
+//! fs::write(out_dir.join("config.rs"), codegen::generate(&schema, "Config"))?;
+//! ```
+//!
+//! Each struct schema becomes a struct and each enum schema an enum, with `FujiSerialize` and
+//! `FujiDeserialize` impls and therefore `to_binding` and `from_binding`. The root struct also gets
+//! `from_document` and `to_document`, reading and writing its fields as top-level bindings.
+//!
+//! Nested types are named after their field, in upper camel case, prefixed with the name of the
+//! enclosing type if that name is taken. Recursive types are named after themselves instead, and
+//! their singular fields referring back to them are boxed. Repeated fields become `Vec`s and optional fields
+//! `Option`s; fields with a default are required, so defaults should be applied with
+//! `Schema::apply_defaults_document` before deserializing. Structs are serialized as maps.
+
+use crate::prelude::*;
+use crate::{Field, Schema, Variant};
+use core::fmt::Write;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+/// Returns `name` as a field identifier, replacing characters not allowed in identifiers by `_`
+/// and escaping keywords.
+fn field_ident(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_numeric()) {
+        ident.insert(0, '_');
+    }
+    match ident.as_str() {
+        "self" | "super" | "crate" | "Self" | "_" => ident + "_",
+        keyword if KEYWORDS.contains(&keyword) => format!("r#{}", ident),
+        _ => ident,
+    }
+}
+
+/// Returns `name` in upper camel case, e.g. `log_level` as `LogLevel`.
+fn type_ident(name: &str) -> String {
+    let mut ident = String::new();
+    for part in name.split(|c: char| !c.is_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            ident.extend(first.to_uppercase());
+            ident.extend(chars);
+        }
+    }
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_numeric()) {
+        ident.insert(0, 'T');
+    }
+    if ident == "Self" {
+        ident.push('_');
+    }
+    ident
+}
+
+struct Generator {
+    /// The names and definitions of the types, in the order their names were reserved, so that
+    /// each type precedes the types of its fields.
+    types: Vec<(String, String)>,
+    /// The names of the recursive types in the schema and of the Rust types generated for them.
+    recursive: Vec<(String, String)>,
+}
+
+impl Generator {
+    /// Reserves a name for the type of the field `name` of `parent`.
+    fn name(&mut self, parent: &str, name: &str) -> String {
+        let taken = |ident: &String| self.types.iter().any(|(name, _)| name == ident);
+        let mut ident = type_ident(name);
+        if taken(&ident) {
+            ident = format!("{}{}", parent, ident);
+        }
+        while taken(&ident) {
+            ident.push('_');
+        }
+        self.types.push((ident.clone(), String::new()));
+        ident
+    }
+
+    fn define(&mut self, name: &str, definition: String) {
+        if let Some((_, slot)) = self.types.iter_mut().find(|(n, _)| n == name) {
+            *slot = definition;
+        }
+    }
+
+    /// Returns the Rust type of the values of `field` of the type `parent`, generating it if
+    /// needed.
+    fn value_type(&mut self, parent: &str, field: &Field) -> String {
+        if let Schema::Recursive { name, .. } | Schema::Ref(name) = &field.schema {
+            if let Some((_, ident)) = self.recursive.iter().find(|(n, _)| n == name) {
+                return ident.clone();
+            }
+        }
+        match &field.schema {
+            Schema::String => "String".to_string(),
+            Schema::Bool => "bool".to_string(),
+            schema @ Schema::Recursive { name, .. } => {
+                let name = self.name(parent, name);
+                self.generate(schema, &name);
+                name
+            }
+            schema => {
+                let name = self.name(parent, &field.name);
+                self.generate(schema, &name);
+                name
+            }
+        }
+    }
+
+    /// Returns the fields of a struct or enum variant named `parent`, as pairs of identifiers and
+    /// types, generating their types.
+    fn fields(&mut self, parent: &str, fields: &[Field]) -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|field| {
+                let mut ty = self.value_type(parent, field);
+                if let (Schema::Ref(_), false) = (&field.schema, field.repeated) {
+                    ty = format!("Box<{}>", ty);
+                }
+                let ty = if field.repeated {
+                    format!("Vec<{}>", ty)
+                } else if field.optional {
+                    format!("Option<{}>", ty)
+                } else {
+                    ty
+                };
+                (field_ident(&field.name), ty)
+            })
+            .collect()
+    }
+
+    fn generate(&mut self, schema: &Schema, name: &str) {
+        match schema {
+            Schema::Struct { fields } => self.generate_struct(name, fields),
+            Schema::Enum { variants } => self.generate_enum(name, variants),
+            Schema::Recursive {
+                name: type_name,
+                schema,
+            } => {
+                self.recursive.push((type_name.clone(), name.to_string()));
+                self.generate(schema, name);
+            }
+            Schema::String | Schema::Bool | Schema::Ref(_) => {}
+        }
+    }
+
+    fn generate_struct(&mut self, name: &str, fields: &[Field]) {
+        let idents = self.fields(name, fields);
+        let mut out = String::new();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (ident, ty) in idents.iter() {
+            writeln!(out, "    pub {}: {},", ident, ty).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "impl ::flax::FujiSerialize for {} {{", name).unwrap();
+        writeln!(out, "    fn to_value(&self) -> ::flax::Value {{").unwrap();
+        serialize_fields(&mut out, fields, true, 2);
+        writeln!(out, "        ::flax::Value::map(children)").unwrap();
+        writeln!(out, "    }}\n}}\n").unwrap();
+
+        writeln!(out, "impl ::flax::FujiDeserialize for {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {{"
+        )
+        .unwrap();
+        writeln!(out, "        Ok({} {{", name).unwrap();
+        deserialize_fields(&mut out, fields, 3);
+        writeln!(out, "        }})\n    }}\n}}\n").unwrap();
+        self.define(name, out);
+    }
+
+    fn generate_enum(&mut self, name: &str, variants: &[Variant]) {
+        let mut out = String::new();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+        writeln!(out, "pub enum {} {{", name).unwrap();
+        let mut arms = vec![];
+        for variant in variants {
+            let ident = type_ident(&variant.name);
+            let fields = match &variant.schema {
+                Schema::Struct { fields } => fields.as_slice(),
+                _ => &[],
+            };
+            if fields.is_empty() {
+                writeln!(out, "    {},", ident).unwrap();
+            } else {
+                writeln!(out, "    {} {{", ident).unwrap();
+                for (field, ty) in self.fields(name, fields) {
+                    writeln!(out, "        {}: {},", field, ty).unwrap();
+                }
+                writeln!(out, "    }},").unwrap();
+            }
+            arms.push((variant, ident, fields));
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "impl ::flax::FujiSerialize for {} {{", name).unwrap();
+        writeln!(out, "    fn to_value(&self) -> ::flax::Value {{").unwrap();
+        writeln!(out, "        match self {{").unwrap();
+        for (variant, ident, fields) in arms.iter() {
+            if fields.is_empty() {
+                writeln!(
+                    out,
+                    "            {}::{} => ::flax::__private::value({:?}, ::flax::__private::Vec::new()),",
+                    name, ident, variant.name
+                )
+                .unwrap();
+                continue;
+            }
+            let idents = fields
+                .iter()
+                .map(|f| field_ident(&f.name))
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "            {}::{} {{ {} }} => {{",
+                name,
+                ident,
+                idents.join(", ")
+            )
+            .unwrap();
+            serialize_fields(&mut out, fields, false, 4);
+            writeln!(
+                out,
+                "                ::flax::__private::value({:?}, children)\n            }}",
+                variant.name
+            )
+            .unwrap();
+        }
+        writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+        writeln!(out, "impl ::flax::FujiDeserialize for {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {{"
+        )
+        .unwrap();
+        writeln!(out, "        match ::flax::__private::tag(value) {{").unwrap();
+        for (variant, ident, fields) in arms.iter() {
+            if fields.is_empty() {
+                writeln!(
+                    out,
+                    "            {:?} => Ok({}::{}),",
+                    variant.name, name, ident
+                )
+                .unwrap();
+                continue;
+            }
+            writeln!(
+                out,
+                "            {:?} => Ok({}::{} {{",
+                variant.name, name, ident
+            )
+            .unwrap();
+            deserialize_fields(&mut out, fields, 4);
+            writeln!(out, "            }}),").unwrap();
+        }
+        writeln!(
+            out,
+            "            tag => Err(::flax::DeserializeError::UnknownVariant(tag.into())),"
+        )
+        .unwrap();
+        writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+        self.define(name, out);
+    }
+}
+
+/// Writes statements collecting the bindings of `fields` into `children`, reading each field from
+/// `self` if `owned`, or else from a reference bound by a pattern.
+fn serialize_fields(out: &mut String, fields: &[Field], owned: bool, indent: usize) {
+    let prefix = "    ".repeat(indent);
+    // An empty struct has no children to push.
+    let binding = if fields.is_empty() { "let" } else { "let mut" };
+    writeln!(
+        out,
+        "{}{} children = ::flax::__private::Vec::new();",
+        prefix, binding
+    )
+    .unwrap();
+    for field in fields {
+        let ident = field_ident(&field.name);
+        let (place, reference) = if owned {
+            (format!("self.{}", ident), format!("&self.{}", ident))
+        } else {
+            (ident.clone(), ident)
+        };
+        let values = if field.repeated || field.optional {
+            format!(
+                "{}.iter().map(::flax::FujiSerialize::to_value).collect()",
+                place
+            )
+        } else {
+            format!(
+                "::flax::__private::vec![::flax::FujiSerialize::to_value({})]",
+                reference
+            )
+        };
+        writeln!(
+            out,
+            "{}::flax::__private::push(&mut children, {:?}, {});",
+            prefix, field.name, values
+        )
+        .unwrap();
+    }
+}
+
+/// Writes the initializers of `fields`, reading from `value`.
+fn deserialize_fields(out: &mut String, fields: &[Field], indent: usize) {
+    let prefix = "    ".repeat(indent);
+    for field in fields {
+        let values = format!("&::flax::__private::values(value, {:?})", field.name);
+        let init = if field.repeated {
+            format!("::flax::__private::repeated({})?", values)
+        } else if field.optional {
+            format!("::flax::__private::optional({:?}, {})?", field.name, values)
+        } else {
+            format!("::flax::__private::required({:?}, {})?", field.name, values)
+        };
+        writeln!(out, "{}{}: {},", prefix, field_ident(&field.name), init).unwrap();
+    }
+}
+
+/// Generates Rust source defining the type `name` for `schema`, usually a struct, and the types of
+/// its fields.
+pub fn generate(schema: &Schema, name: &str) -> String {
+    let mut generator = Generator {
+        types: vec![(name.to_string(), String::new())],
+        recursive: vec![],
+    };
+    generator.generate(schema, name);
+    let mut out = String::from("// @generated by flax::codegen. Do not edit.\n\n");
+    for (_, definition) in generator.types {
+        out.push_str(&definition);
+    }
+    if let Schema::Struct { .. } = &*schema.unfold() {
+        writeln!(out, "impl {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    /// Reads the fields from the top-level bindings of `document`."
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    pub fn from_document(document: &::flax::Document) -> Result<Self, ::flax::DeserializeError> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        ::flax::FujiDeserialize::from_value(&::flax::Value::map(document.bindings().to_vec()))"
+        )
+        .unwrap();
+        writeln!(out, "    }}\n").unwrap();
+        writeln!(out, "    /// Writes the fields as top-level bindings.").unwrap();
+        writeln!(out, "    pub fn to_document(&self) -> ::flax::Document {{").unwrap();
+        writeln!(
+            out,
+            "        let value = ::flax::FujiSerialize::to_value(self);"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        value.children().iter().cloned().fold(::flax::Document::new(), ::flax::Document::binding)"
+        )
+        .unwrap();
+        writeln!(out, "    }}\n}}").unwrap();
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idents() {
+        assert_eq!("log_level", field_ident("log-level"));
+        assert_eq!("r#type", field_ident("type"));
+        assert_eq!("self_", field_ident("self"));
+        assert_eq!("_0", field_ident("0"));
+        assert_eq!("LogLevel", type_ident("log-level"));
+        assert_eq!("Tcp", type_ident("tcp"));
+        assert_eq!("T0", type_ident("0"));
+    }
+
+    #[test]
+    fn test_names() {
+        let mut generator = Generator {
+            types: vec![("Config".to_string(), String::new())],
+            recursive: vec![],
+        };
+        assert_eq!("Server", generator.name("Config", "server"));
+        assert_eq!("BackendServer", generator.name("Backend", "server"));
+        assert_eq!("BackendServer_", generator.name("Backend", "server"));
+        assert_eq!("ConfigConfig", generator.name("Config", "config"));
+    }
+
+    #[test]
+    fn test_recursive() {
+        let schema = crate::parse_document_complete(
+            "
+            struct=Tree{field=children{type=Tree repeated=true} field=parent{type=Tree optional=true}}
+            field=root{type=Tree}
+            field=other{type=Tree}
+            ",
+        )
+        .unwrap();
+        let code = generate(&Schema::from_document(&schema).unwrap(), "Config");
+        assert!(
+            code.contains("pub struct Config {\n    pub root: Tree,\n    pub other: Tree,\n}\n")
+        );
+        assert!(code.contains(
+            "pub struct Tree {\n    pub children: Vec<Tree>,\n    pub parent: Option<Box<Tree>>,\n}\n"
+        ));
+        assert_eq!(1, code.matches("pub struct Tree ").count());
+    }
+}
@@ -0,0 +1,274 @@
+//! Detection of breaking changes between two versions of a schema.
+//!
+//! A change is breaking if a document valid against the old schema may be invalid against the new
+//! one, e.g. because a field it uses was removed.
+
+use crate::prelude::*;
+use crate::{Field, Path, Schema, Segment};
+use core::fmt;
+
+/// A breaking change between two versions of a schema.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct CompatIssue {
+    /// The path of the affected field, e.g. `server.port`, with variant names as segments of the
+    /// fields within them.
+    pub path: String,
+    pub kind: CompatIssueKind,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum CompatIssueKind {
+    RemovedField,
+    /// A field that documents must now have, without a default.
+    AddedRequiredField,
+    /// A field that documents could omit, which they must now have.
+    BecameRequired,
+    ChangedType {
+        old: String,
+        new: String,
+    },
+    RemovedVariant(String),
+    RepeatedToSingular,
+    /// A raised `min` or lowered `max`.
+    NarrowedBounds,
+}
+
+impl fmt::Display for CompatIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            CompatIssueKind::RemovedField => write!(f, "field removed"),
+            CompatIssueKind::AddedRequiredField => write!(f, "required field added"),
+            CompatIssueKind::BecameRequired => write!(f, "field changed from optional to required"),
+            CompatIssueKind::ChangedType { old, new } => {
+                write!(f, "type changed from `{}` to `{}`", old, new)
+            }
+            CompatIssueKind::RemovedVariant(name) => write!(f, "variant `{}` removed", name),
+            CompatIssueKind::RepeatedToSingular => {
+                write!(f, "field changed from repeated to singular")
+            }
+            CompatIssueKind::NarrowedBounds => write!(f, "bounds on the number of values narrowed"),
+        }
+    }
+}
+
+fn type_name(schema: &Schema) -> &'static str {
+    match schema {
+        Schema::Struct { .. } => "struct",
+        Schema::Enum { .. } => "enum",
+        Schema::String => "string",
+        Schema::Bool => "bool",
+        Schema::Recursive { schema, .. } => type_name(schema),
+        // References only occur within the recursive types they refer to, which are unfolded.
+        Schema::Ref(_) => "struct",
+    }
+}
+
+fn required(field: &Field) -> bool {
+    !field.optional && field.default.is_none()
+}
+
+fn child(path: &Path, name: &str) -> Path {
+    let mut path = path.clone();
+    path.segments.push(Segment {
+        name: name.to_string(),
+        index: None,
+    });
+    path
+}
+
+fn issue(path: &Path, kind: CompatIssueKind) -> CompatIssue {
+    CompatIssue {
+        path: path.to_string(),
+        kind,
+    }
+}
+
+/// The state of a check: the issues found so far, and the pairs of old and new recursive types
+/// already compared, so that comparing recursive types terminates.
+struct Checker {
+    issues: Vec<CompatIssue>,
+    compared: Vec<(String, String)>,
+}
+
+fn check(old: &Schema, new: &Schema, path: &Path, checker: &mut Checker) {
+    if let (Schema::Recursive { name: old, .. }, Schema::Recursive { name: new, .. }) = (old, new) {
+        let pair = (old.clone(), new.clone());
+        if checker.compared.contains(&pair) {
+            return;
+        }
+        checker.compared.push(pair);
+    }
+    let (old, new) = (old.unfold(), new.unfold());
+    match (&*old, &*new) {
+        (Schema::Struct { fields: old }, Schema::Struct { fields: new }) => {
+            for field in old {
+                let path = child(path, &field.name);
+                match new.iter().find(|f| f.name == field.name) {
+                    Some(new) => check_field(field, new, &path, checker),
+                    None => checker
+                        .issues
+                        .push(issue(&path, CompatIssueKind::RemovedField)),
+                }
+            }
+            for field in new {
+                if required(field) && old.iter().all(|f| f.name != field.name) {
+                    let path = child(path, &field.name);
+                    checker
+                        .issues
+                        .push(issue(&path, CompatIssueKind::AddedRequiredField));
+                }
+            }
+        }
+        (Schema::Enum { variants: old }, Schema::Enum { variants: new }) => {
+            for variant in old {
+                match new.iter().find(|v| v.name == variant.name) {
+                    Some(new) => check(
+                        &variant.schema,
+                        &new.schema,
+                        &child(path, &variant.name),
+                        checker,
+                    ),
+                    None => checker.issues.push(issue(
+                        path,
+                        CompatIssueKind::RemovedVariant(variant.name.clone()),
+                    )),
+                }
+            }
+        }
+        (old, new) if type_name(old) != type_name(new) => checker.issues.push(issue(
+            path,
+            CompatIssueKind::ChangedType {
+                old: type_name(old).to_string(),
+                new: type_name(new).to_string(),
+            },
+        )),
+        _ => {}
+    }
+}
+
+fn check_field(old: &Field, new: &Field, path: &Path, checker: &mut Checker) {
+    let issues = &mut checker.issues;
+    if old.repeated && !new.repeated {
+        issues.push(issue(path, CompatIssueKind::RepeatedToSingular));
+    }
+    if !required(old) && required(new) {
+        issues.push(issue(path, CompatIssueKind::BecameRequired));
+    }
+    let min = |field: &Field| field.min.unwrap_or(0);
+    let max = |field: &Field| field.max.unwrap_or(usize::MAX);
+    if new.repeated && (min(new) > min(old) || max(new) < max(old)) {
+        issues.push(issue(path, CompatIssueKind::NarrowedBounds));
+    }
+    check(&old.schema, &new.schema, path, checker);
+}
+
+impl Schema {
+    /// Returns the breaking changes from `old` to `new`, in the order of the fields of `old` and
+    /// then of the fields added in `new`. Additions of optional fields and variants, and changes
+    /// from singular to repeated, are compatible.
+    pub fn check_compatibility(old: &Schema, new: &Schema) -> Vec<CompatIssue> {
+        let mut checker = Checker {
+            issues: vec![],
+            compared: vec![],
+        };
+        check(old, new, &Path { segments: vec![] }, &mut checker);
+        checker.issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    fn schema(input: &str) -> Schema {
+        Schema::from_document(&parse_document_complete(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        let old = schema(
+            "
+            field=name{type=string}
+            field=tags{type=string repeated=true optional=true}
+            field=hosts{type=string repeated=true max=5}
+            field=server{type=struct{field=host{type=string} field=tls{type=bool}}}
+            field=mode{type=enum{variant=tcp{type=struct{field=port{type=string}}} variant=udp}}
+            field=debug{type=bool default=false}
+            ",
+        );
+        assert_eq!(
+            Vec::<CompatIssue>::new(),
+            Schema::check_compatibility(&old, &old)
+        );
+
+        let new = schema(
+            "
+            field=name{type=string repeated=true}
+            field=tags{type=string}
+            field=hosts{type=string repeated=true max=3}
+            field=server{type=struct{field=host{type=bool} field=port{type=string}}}
+            field=mode{type=enum{variant=tcp{type=struct{field=port{type=bool}}} variant=quic}}
+            field=debug{type=bool}
+            field=extra{type=string optional=true}
+            field=required{type=string}
+            ",
+        );
+        let issues = Schema::check_compatibility(&old, &new)
+            .iter()
+            .map(CompatIssue::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "tags: field changed from repeated to singular",
+                "tags: field changed from optional to required",
+                "hosts: bounds on the number of values narrowed",
+                "server.host: type changed from `string` to `bool`",
+                "server.tls: field removed",
+                "server.port: required field added",
+                "mode.tcp.port: type changed from `string` to `bool`",
+                "mode: variant `udp` removed",
+                "debug: field changed from optional to required",
+                "required: required field added",
+            ],
+            issues
+        );
+    }
+
+    #[test]
+    fn test_recursive() {
+        let old = schema(
+            "
+            struct=Tree{field=name{type=string} field=children{type=Tree repeated=true}}
+            field=root{type=Tree}
+            ",
+        );
+        assert_eq!(
+            Vec::<CompatIssue>::new(),
+            Schema::check_compatibility(&old, &old)
+        );
+        let new = schema(
+            "
+            struct=Node{field=name{type=bool} field=children{type=Node repeated=true}}
+            field=root{type=Node}
+            ",
+        );
+        let issues = Schema::check_compatibility(&old, &new)
+            .iter()
+            .map(CompatIssue::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["root.name: type changed from `string` to `bool`"],
+            issues
+        );
+        let new = schema("field=root{type=struct{field=name{type=string}}}");
+        assert_eq!(
+            vec!["root.children: field removed"],
+            Schema::check_compatibility(&old, &new)
+                .iter()
+                .map(CompatIssue::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,530 @@
+//! Layered configuration, e.g. built-in defaults overridden by a configuration file, then by the
+//! environment, then by command-line flags.
+
+use crate::merge::merges_children;
+use crate::prelude::*;
+use crate::{span, Address, Binding, DeserializeError, Document, FujiDeserialize};
+use crate::{MergeStrategy, ParseError, Path, Value};
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+/// The kind of a configuration source. Sources of a later kind take precedence over sources of
+/// an earlier one, regardless of the order in which they are added to a [`ConfigStack`].
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+pub enum Layer {
+    /// Values built into the application.
+    Defaults,
+    /// Values read from configuration files.
+    File,
+    /// Values derived from environment variables.
+    Env,
+    /// Values given as command-line overrides.
+    Cli,
+}
+
+/// Where a value of a document merged by a [`ConfigStack`] came from.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Origin {
+    pub layer: Layer,
+    /// The name of the source.
+    pub source: String,
+    /// The address of the value in the source document.
+    pub address: Address,
+    /// The 1-based line and column of the value, for sources added with
+    /// [`ConfigStack::push_str`].
+    pub position: Option<(usize, usize)>,
+}
+
+/// The origin of a value, along with the origins of the values of its children, aligned with
+/// them.
+#[derive(Debug, Clone)]
+struct ValueOrigin {
+    /// The `id` of the source.
+    source: usize,
+    address: Address,
+    position: Option<(usize, usize)>,
+    children: Vec<Vec<ValueOrigin>>,
+}
+
+/// The origins of the values of a document merged by a [`ConfigStack`], aligned with its
+/// bindings. They take no part in comparing or hashing the document.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Origins {
+    /// The layer and name of each source, indexed by its `id`.
+    sources: Vec<(Layer, String)>,
+    values: Vec<Vec<ValueOrigin>>,
+}
+
+impl Origins {
+    /// Drops the origins of a document about to be modified, which they would no longer match.
+    pub(crate) fn clear(&mut self) {
+        *self = Origins::default();
+    }
+}
+
+impl PartialEq for Origins {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Origins {}
+
+impl PartialOrd for Origins {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Origins {
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Origins {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    /// Identifies the source, unlike its index, which changes as sources are added below it.
+    id: usize,
+    layer: Layer,
+    name: String,
+    document: Document,
+    origins: Vec<Vec<ValueOrigin>>,
+}
+
+/// Returns the origins of the values of `bindings`, at `address` in the source `id`.
+fn origins(
+    bindings: &[Binding],
+    address: &Address,
+    id: usize,
+    position: &dyn Fn(&Address) -> Option<(usize, usize)>,
+) -> Vec<Vec<ValueOrigin>> {
+    bindings
+        .iter()
+        .enumerate()
+        .map(|(i, binding)| {
+            let address = address.child(i);
+            binding
+                .values
+                .iter()
+                .enumerate()
+                .map(|(j, value)| {
+                    let address = address.child(j);
+                    ValueOrigin {
+                        source: id,
+                        position: position(&address),
+                        children: origins(&value.children, &address, id, position),
+                        address,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Merges `overlay` into `base` as [`Document::merge`] does, merging the origins of their values
+/// alongside.
+fn merge_bindings(
+    base: &mut Vec<Binding>,
+    base_origins: &mut Vec<Vec<ValueOrigin>>,
+    overlay: &[Binding],
+    overlay_origins: &[Vec<ValueOrigin>],
+    strategy: MergeStrategy,
+) {
+    for (binding, origins) in overlay.iter().zip(overlay_origins) {
+        match base.iter().position(|b| b.name == binding.name) {
+            Some(i) => merge_binding(
+                &mut base[i],
+                &mut base_origins[i],
+                binding,
+                origins,
+                strategy,
+            ),
+            None => {
+                base.push(binding.clone());
+                base_origins.push(origins.clone());
+            }
+        }
+    }
+}
+
+fn merge_binding(
+    base: &mut Binding,
+    base_origins: &mut Vec<ValueOrigin>,
+    overlay: &Binding,
+    overlay_origins: &[ValueOrigin],
+    strategy: MergeStrategy,
+) {
+    match (
+        base.values.as_mut_slice(),
+        overlay.values.as_slice(),
+        strategy,
+    ) {
+        ([value], [overlay_value], _) if merges_children(value, overlay_value) => {
+            let (origin, overlay_origin) = (&mut base_origins[0], &overlay_origins[0]);
+            merge_bindings(
+                &mut value.children,
+                &mut origin.children,
+                &overlay_value.children,
+                &overlay_origin.children,
+                strategy,
+            );
+            origin.source = overlay_origin.source;
+            origin.address = overlay_origin.address.clone();
+            origin.position = overlay_origin.position;
+        }
+        ([_], [_], _) | (_, _, MergeStrategy::Replace) => {
+            base.values = overlay.values.clone();
+            *base_origins = overlay_origins.to_vec();
+        }
+        (_, _, MergeStrategy::Append) => {
+            base.values.extend(overlay.values.iter().cloned());
+            base_origins.extend(overlay_origins.iter().cloned());
+        }
+    }
+}
+
+/// A stack of configuration sources, merged into a single document.
+///
+/// Sources are merged with [`Document::merge`], from the lowest [`Layer`] to the highest and, within
+/// a layer, in the order they were added. A later source therefore overrides the scalar values
+/// of an earlier one, merges into its blocks by name, and combines repeated bindings according to
+/// the stack's [`MergeStrategy`], `Replace` by default. The merged document records which source
+/// each of its values came from, see [`Document::origin`].
+///
+/// ```
+/// use flax::{parse_document_complete, ConfigStack, Layer};
+///
+/// let defaults = parse_document_complete("server={host=localhost port=80}").unwrap();
+/// let overrides = parse_document_complete("server={port=9090}").unwrap();
+/// let config = ConfigStack::new()
+///     .push(Layer::Cli, "--set", overrides)
+///     .push(Layer::Defaults, "defaults", defaults);
+/// assert_eq!(Ok("localhost".to_string()), config.get("server.host"));
+/// assert_eq!(Ok(9090), config.get::<u16>("server.port"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigStack {
+    sources: Vec<Source>,
+    strategy: MergeStrategy,
+    merged: Document,
+}
+
+impl Default for ConfigStack {
+    fn default() -> Self {
+        ConfigStack {
+            sources: vec![],
+            strategy: MergeStrategy::Replace,
+            merged: Document::default(),
+        }
+    }
+}
+
+impl ConfigStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how repeated bindings of different sources are combined.
+    pub fn strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.strategy = strategy;
+        self.remerge();
+        self
+    }
+
+    /// Adds `document` as a source of kind `layer`, above the sources of the same kind added
+    /// before it. `name` identifies the source, e.g. a file name.
+    pub fn push(self, layer: Layer, name: impl Into<String>, document: Document) -> Self {
+        self.insert(layer, name.into(), document, &|_| None)
+    }
+
+    /// Parses `input` and adds it as a source, as [`ConfigStack::push`] does. The origins of its
+    /// values include their positions in `input`.
+    pub fn push_str(
+        self,
+        layer: Layer,
+        name: impl Into<String>,
+        input: &str,
+    ) -> Result<Self, ParseError> {
+        let spanned = span::parse_document(input)?;
+        let position = |address: &Address| Some(spanned.span(address)?.line_column(input));
+        Ok(self.insert(layer, name.into(), spanned.to_ast(), &position))
+    }
+
+    fn insert(
+        mut self,
+        layer: Layer,
+        name: String,
+        document: Document,
+        position: &dyn Fn(&Address) -> Option<(usize, usize)>,
+    ) -> Self {
+        let id = self.sources.len();
+        let index = self.sources.partition_point(|s| s.layer <= layer);
+        self.sources.insert(
+            index,
+            Source {
+                id,
+                layer,
+                name,
+                origins: origins(&document.bindings, &Address::default(), id, position),
+                document,
+            },
+        );
+        self.remerge();
+        self
+    }
+
+    fn remerge(&mut self) {
+        let (mut bindings, mut origins) = (vec![], vec![]);
+        for source in self.sources.iter() {
+            merge_bindings(
+                &mut bindings,
+                &mut origins,
+                &source.document.bindings,
+                &source.origins,
+                self.strategy,
+            );
+        }
+        let mut sources = vec![(Layer::Defaults, String::new()); self.sources.len()];
+        for source in self.sources.iter() {
+            sources[source.id] = (source.layer, source.name.clone());
+        }
+        self.merged = Document {
+            bindings,
+            origins: Origins {
+                sources,
+                values: origins,
+            },
+        };
+    }
+
+    /// Returns the names and layers of the sources, from the lowest precedence to the highest.
+    pub fn sources(&self) -> impl Iterator<Item = (Layer, &str)> {
+        self.sources.iter().map(|s| (s.layer, s.name.as_str()))
+    }
+
+    /// Returns the merged document, which records the origins of its values.
+    pub fn document(&self) -> &Document {
+        &self.merged
+    }
+
+    /// Converts the merged value at the given dotted path, failing with
+    /// `DeserializeError::MissingField` if there is none.
+    pub fn get<T: FujiDeserialize>(&self, path: &str) -> Result<T, DeserializeError> {
+        match self.merged.get(path) {
+            Some(value) => T::from_value(value),
+            None => Err(DeserializeError::MissingField(path.to_string())),
+        }
+    }
+
+    /// Converts the merged value at the given dotted path, if any.
+    pub fn get_optional<T: FujiDeserialize>(
+        &self,
+        path: &str,
+    ) -> Result<Option<T>, DeserializeError> {
+        self.merged.get(path).map(T::from_value).transpose()
+    }
+
+    /// Converts all merged values matching the given dotted path, as [`Document::query`] finds
+    /// them. An invalid path matches no values.
+    pub fn get_all<T: FujiDeserialize>(&self, path: &str) -> Result<Vec<T>, DeserializeError> {
+        let values: Vec<&Value> = self.merged.query(path).unwrap_or_default();
+        values.into_iter().map(T::from_value).collect()
+    }
+
+    /// Returns the merged binding at the given dotted path, if any.
+    pub fn get_binding(&self, path: &str) -> Option<&Binding> {
+        self.merged.get_binding(path)
+    }
+
+    /// Returns where the merged value at the given dotted path came from, as
+    /// [`Document::origin`] does.
+    pub fn origin(&self, path: &str) -> Option<Origin> {
+        self.merged.origin(path)
+    }
+}
+
+impl Document {
+    /// Returns where the value at the given dotted path came from, if the document was merged by
+    /// a [`ConfigStack`] and there is such a value.
+    ///
+    /// A value whose children were merged from several sources comes from the highest of them
+    /// that binds it; each of its children has its own origin. Methods that modify the document
+    /// drop its origins, as they may no longer hold.
+    pub fn origin(&self, path: &str) -> Option<Origin> {
+        let path: Path = path.parse().ok()?;
+        let mut bindings = self.bindings.as_slice();
+        let mut origins = self.origins.values.as_slice();
+        let mut origin = None;
+        for segment in &path.segments {
+            let i = bindings.iter().position(|b| b.name == segment.name)?;
+            let index = segment.index.unwrap_or(0);
+            let value = bindings[i].values.get(index)?;
+            let value_origin = origins.get(i)?.get(index)?;
+            bindings = &value.children;
+            origins = &value_origin.children;
+            origin = Some(value_origin);
+        }
+        let origin = origin?;
+        let (layer, source) = self.origins.sources.get(origin.source)?;
+        Some(Origin {
+            layer: *layer,
+            source: source.clone(),
+            address: origin.address.clone(),
+            position: origin.position,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::print_document;
+
+    #[test]
+    fn test_config_stack() {
+        let config = ConfigStack::new()
+            .push_str(Layer::Cli, "--set", "server={port=9090} debug=true")
+            .unwrap()
+            .push_str(Layer::Env, "env", "server={port=8080 tags=env}")
+            .unwrap()
+            .push_str(Layer::File, "b.fuji", "server={host=b.example.com tags=b}")
+            .unwrap()
+            .push_str(Layer::File, "a.fuji", "server={host=a.example.com}")
+            .unwrap()
+            .push_str(
+                Layer::Defaults,
+                "defaults",
+                "server={host=localhost port=80 tags=x,y} debug=false",
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                (Layer::Defaults, "defaults"),
+                (Layer::File, "b.fuji"),
+                (Layer::File, "a.fuji"),
+                (Layer::Env, "env"),
+                (Layer::Cli, "--set"),
+            ],
+            config.sources().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "server={host=a.example.com port=9090 tags=env}\ndebug=true",
+            print_document(config.document())
+        );
+        assert_eq!(Ok(9090), config.get::<u16>("server.port"));
+        assert_eq!(Ok(true), config.get::<bool>("debug"));
+        assert_eq!(
+            Err(DeserializeError::MissingField("server.user".to_string())),
+            config.get::<String>("server.user")
+        );
+        assert_eq!(Ok(None), config.get_optional::<String>("server.user"));
+        assert!(config.get::<u16>("server.host").is_err());
+
+        let merged = config
+            .sources
+            .iter()
+            .fold(Document::default(), |merged, s| {
+                merged.merge(&s.document, MergeStrategy::Replace)
+            });
+        assert_eq!(&merged, config.document());
+
+        let config = config.strategy(MergeStrategy::Append);
+        assert_eq!(
+            Ok(vec![
+                "x".to_string(),
+                "y".to_string(),
+                "b".to_string(),
+                "env".to_string()
+            ]),
+            config.get_all::<String>("server.tags")
+        );
+    }
+
+    #[test]
+    fn test_origin() {
+        let config = ConfigStack::new()
+            .push_str(
+                Layer::Defaults,
+                "defaults",
+                "server={\n  host=localhost\n  port=80\n}\ntags=a,b",
+            )
+            .unwrap()
+            .push_str(Layer::File, "app.fuji", "tags=c,d\nserver={port=8080}")
+            .unwrap()
+            .push(
+                Layer::Cli,
+                "--set",
+                crate::parse_document_complete("debug=true").unwrap(),
+            )
+            .strategy(MergeStrategy::Append);
+
+        let tests = [
+            (
+                "server.host",
+                Layer::Defaults,
+                "defaults",
+                vec![0, 0, 0, 0],
+                Some((2, 8)),
+            ),
+            (
+                "server.port",
+                Layer::File,
+                "app.fuji",
+                vec![1, 0, 0, 0],
+                Some((2, 14)),
+            ),
+            ("server", Layer::File, "app.fuji", vec![1, 0], Some((2, 8))),
+            (
+                "tags[1]",
+                Layer::Defaults,
+                "defaults",
+                vec![1, 1],
+                Some((5, 8)),
+            ),
+            ("tags[2]", Layer::File, "app.fuji", vec![0, 0], Some((1, 6))),
+            ("debug", Layer::Cli, "--set", vec![0, 0], None),
+        ];
+        for (path, layer, source, address, position) in tests.iter() {
+            let expected = Origin {
+                layer: *layer,
+                source: source.to_string(),
+                address: Address(address.clone()),
+                position: *position,
+            };
+            assert_eq!(Some(&expected), config.origin(path).as_ref(), "{}", path);
+            assert_eq!(Some(expected), config.document().origin(path), "{}", path);
+        }
+        assert_eq!(None, config.origin("tags[4]"));
+        assert_eq!(None, config.origin("server.user"));
+
+        // The origins do not survive modifying the document, nor take part in comparing it.
+        let mut document = config.document().clone();
+        assert!(document.origin("server.port").is_some());
+        document.set("server.port", "1").unwrap();
+        assert_eq!(None, document.origin("server.port"));
+        let parsed = crate::parse_document_complete(&print_document(config.document())).unwrap();
+        assert_eq!(None, parsed.origin("server.port"));
+        assert_eq!(&parsed, config.document());
+
+        // Nor applying a patch, which would leave them pointing at other bindings.
+        let config = ConfigStack::new()
+            .push_str(Layer::Defaults, "defaults", "a=1 b=2 c=3")
+            .unwrap()
+            .push_str(Layer::File, "app.fuji", "c=30")
+            .unwrap();
+        let mut document = config.document().clone();
+        assert_eq!(Layer::File, document.origin("c").unwrap().layer);
+        let patch = crate::diff_documents(
+            &document,
+            &crate::parse_document_complete("b=2 c=30").unwrap(),
+        );
+        crate::apply_patch_document(&mut document, &patch).unwrap();
+        assert_eq!(None, document.origin("c"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Lossless conversion between bindings and CBOR values.
+//!
+//! The mapping is:
+//!
+//! - a binding becomes a map with a single entry, from the binding name to the single value of
+//!   the binding, or to an array if the binding has zero or several values, or a single list;
+//! - a value without children becomes a text string, and a nested list an array;
+//! - a value with children becomes a map with a single entry, from the value itself to a map of
+//!   its child bindings; a map uses null as its key.
+//!
+//! A map of child bindings, like a document, has an entry per binding in order, from its name to
+//! its values as above. CBOR maps are ordered sequences of entries, so keys may repeat and bindings
+//! sharing a name keep their relative order. So `foo=bar{zoo=qat},xxx` becomes
+//! `{"foo": [{"bar": {"zoo": "qat"}}, "xxx"]}`.
+//!
+//! References and anchors have no CBOR representation, so converting a document that contains
+//! them is an error; expand them first with `Document::expand_references`.
+//!
+//! When converting from CBOR, integers, floats and booleans are accepted as scalar values; null,
+//! byte strings and tagged values are rejected.
+
+use super::{check_expanded, children_path, ConvertError};
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+use ciborium::value::Value as Cbor;
+
+pub fn to_cbor(binding: &Binding) -> Result<Cbor, ConvertError> {
+    Ok(Cbor::Map(vec![entry(binding, "")?]))
+}
+
+pub fn document_to_cbor(document: &Document) -> Result<Cbor, ConvertError> {
+    bindings_to_cbor(&document.bindings, "")
+}
+
+fn bindings_to_cbor(bindings: &[Binding], parent: &str) -> Result<Cbor, ConvertError> {
+    bindings
+        .iter()
+        .map(|b| entry(b, parent))
+        .collect::<Result<_, _>>()
+        .map(Cbor::Map)
+}
+
+fn entry(binding: &Binding, parent: &str) -> Result<(Cbor, Cbor), ConvertError> {
+    let path = if parent.is_empty() {
+        binding.name.clone()
+    } else {
+        format!("{}.{}", parent, binding.name)
+    };
+    let values = match binding.values.as_slice() {
+        [value] if value.items().is_none() => value_at(value, &path)?,
+        values => Cbor::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    Ok((Cbor::Text(binding.name.clone()), values))
+}
+
+/// Converts a value; references and anchors must be expanded first.
+pub fn value_to_cbor(value: &Value) -> Result<Cbor, ConvertError> {
+    value_at(value, "")
+}
+
+fn value_at(value: &Value, path: &str) -> Result<Cbor, ConvertError> {
+    check_expanded(value, path)?;
+    Ok(if let Some(items) = value.items() {
+        Cbor::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        )
+    } else if value.children.is_empty() && !value.is_map() {
+        Cbor::Text(value.value.clone())
+    } else if value.is_map() {
+        Cbor::Map(vec![(Cbor::Null, bindings_to_cbor(&value.children, path)?)])
+    } else {
+        let path = children_path(path, &value.value);
+        Cbor::Map(vec![(
+            Cbor::Text(value.value.clone()),
+            bindings_to_cbor(&value.children, &path)?,
+        )])
+    })
+}
+
+fn text<'a>(cbor: &'a Cbor, path: &str) -> Result<&'a str, ConvertError> {
+    match cbor {
+        Cbor::Text(text) => Ok(text),
+        _ => Err(ConvertError::new(path, "expected a text string key")),
+    }
+}
+
+fn single_entry<'a>(cbor: &'a Cbor, path: &str) -> Result<(&'a str, &'a Cbor), ConvertError> {
+    match cbor {
+        Cbor::Map(entries) if entries.len() == 1 => {
+            let (key, value) = &entries[0];
+            Ok((text(key, path)?, value))
+        }
+        _ => Err(ConvertError::new(
+            path,
+            "expected a map with a single entry",
+        )),
+    }
+}
+
+pub fn from_cbor(cbor: &Cbor) -> Result<Binding, ConvertError> {
+    let (name, values) = single_entry(cbor, ".")?;
+    binding_from_cbor(name, values, "")
+}
+
+pub fn document_from_cbor(cbor: &Cbor) -> Result<Document, ConvertError> {
+    Ok(Document::from_bindings(bindings_from_cbor(cbor, "")?))
+}
+
+fn bindings_from_cbor(cbor: &Cbor, parent: &str) -> Result<Vec<Binding>, ConvertError> {
+    match cbor {
+        Cbor::Map(entries) => entries
+            .iter()
+            .map(|(name, values)| binding_from_cbor(text(name, parent)?, values, parent))
+            .collect(),
+        _ => Err(ConvertError::new(
+            if parent.is_empty() { "." } else { parent },
+            "expected a map of bindings",
+        )),
+    }
+}
+
+fn binding_from_cbor(name: &str, values: &Cbor, parent: &str) -> Result<Binding, ConvertError> {
+    let path = if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", parent, name)
+    };
+    let values = match values {
+        Cbor::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_cbor(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<_, _>>()?,
+        value => smallvec![value_from_cbor(value, &path)?],
+    };
+    Ok(Binding {
+        name: name.to_string(),
+        values,
+    })
+}
+
+fn value_from_cbor(cbor: &Cbor, path: &str) -> Result<Value, ConvertError> {
+    let scalar = |value: String| Value {
+        value,
+        kind: ValueKind::Scalar,
+        children: vec![],
+    };
+    match cbor {
+        Cbor::Text(s) => Ok(scalar(s.clone())),
+        Cbor::Integer(n) => Ok(scalar(i128::from(*n).to_string())),
+        Cbor::Float(f) => Ok(scalar(f.to_string())),
+        Cbor::Bool(b) => Ok(scalar(b.to_string())),
+        Cbor::Null => Err(ConvertError::new(path, "null has no fuji representation")),
+        Cbor::Bytes(_) => Err(ConvertError::new(
+            path,
+            "byte strings have no fuji representation",
+        )),
+        Cbor::Tag(..) => Err(ConvertError::new(
+            path,
+            "tagged values have no fuji representation",
+        )),
+        Cbor::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_cbor(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::list),
+        Cbor::Map(entries) if entries.len() == 1 && entries[0].0 == Cbor::Null => {
+            let children = bindings_from_cbor(&entries[0].1, path)?;
+            Ok(Value::map(children))
+        }
+        Cbor::Map(_) => {
+            let (value, children) = single_entry(cbor, path)?;
+            let children = bindings_from_cbor(children, &children_path(path, value))?;
+            Ok(Value {
+                value: value.to_string(),
+                kind: ValueKind::Scalar,
+                children,
+            })
+        }
+        _ => Err(ConvertError::new(path, "unsupported CBOR value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    fn text(s: &str) -> Cbor {
+        Cbor::Text(s.to_string())
+    }
+
+    fn map(entries: Vec<(&str, Cbor)>) -> Cbor {
+        Cbor::Map(entries.into_iter().map(|(k, v)| (text(k), v)).collect())
+    }
+
+    #[test]
+    fn test_cbor() {
+        struct Test {
+            fuji: String,
+            cbor: Cbor,
+        }
+
+        let tests = [
+            Test {
+                fuji: "foo=bar".to_string(),
+                cbor: map(vec![("foo", text("bar"))]),
+            },
+            Test {
+                fuji: "foo=a,b".to_string(),
+                cbor: map(vec![("foo", Cbor::Array(vec![text("a"), text("b")]))]),
+            },
+            Test {
+                fuji: "foo=bar{zoo=qat},xxx".to_string(),
+                cbor: map(vec![(
+                    "foo",
+                    Cbor::Array(vec![
+                        map(vec![("bar", map(vec![("zoo", text("qat"))]))]),
+                        text("xxx"),
+                    ]),
+                )]),
+            },
+            Test {
+                fuji: "a=b{c=d c=e}".to_string(),
+                cbor: map(vec![(
+                    "a",
+                    map(vec![("b", map(vec![("c", text("d")), ("c", text("e"))]))]),
+                )]),
+            },
+            Test {
+                fuji: "foo=[[a],{}]".to_string(),
+                cbor: map(vec![(
+                    "foo",
+                    Cbor::Array(vec![
+                        Cbor::Array(vec![text("a")]),
+                        Cbor::Map(vec![(Cbor::Null, map(vec![]))]),
+                    ]),
+                )]),
+            },
+            Test {
+                fuji: "foo={a=b},\"\"{a=b}".to_string(),
+                cbor: map(vec![(
+                    "foo",
+                    Cbor::Array(vec![
+                        Cbor::Map(vec![(Cbor::Null, map(vec![("a", text("b"))]))]),
+                        map(vec![("", map(vec![("a", text("b"))]))]),
+                    ]),
+                )]),
+            },
+        ];
+
+        for t in tests.iter() {
+            let binding = parse_binding_complete(&t.fuji).unwrap();
+            assert_eq!(Ok(t.cbor.clone()), to_cbor(&binding), "{}", t.fuji);
+            assert_eq!(Ok(binding), from_cbor(&t.cbor), "{}", t.fuji);
+        }
+    }
+
+    #[test]
+    fn test_document_cbor() {
+        let document = parse_document_complete("a=b\nc=d{e=f}\na=g").unwrap();
+        let cbor = map(vec![
+            ("a", text("b")),
+            ("c", map(vec![("d", map(vec![("e", text("f"))]))])),
+            ("a", text("g")),
+        ]);
+        assert_eq!(Ok(cbor.clone()), document_to_cbor(&document));
+        assert_eq!(Ok(document), document_from_cbor(&cbor));
+
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&cbor, &mut bytes).unwrap();
+        let decoded: Cbor = ciborium::de::from_reader(&bytes[..]).unwrap();
+        assert_eq!(cbor, decoded);
+    }
+
+    #[test]
+    fn test_cbor_references() {
+        let mut document = parse_document_complete("a=&x v b={c=*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "b.c",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            document_to_cbor(&Document::from_bindings(document.bindings[1..].to_vec()))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "unexpanded anchor `&x`; expand references first"
+            )),
+            document_to_cbor(&document)
+        );
+
+        document.expand_references().unwrap();
+        let cbor = map(vec![
+            ("a", text("v")),
+            (
+                "b",
+                Cbor::Map(vec![(Cbor::Null, map(vec![("c", text("v"))]))]),
+            ),
+        ]);
+        assert_eq!(Ok(cbor.clone()), document_to_cbor(&document));
+        assert_eq!(Ok(document), document_from_cbor(&cbor));
+    }
+
+    #[test]
+    fn test_from_cbor_errors() {
+        let number = map(vec![(
+            "a",
+            Cbor::Array(vec![Cbor::Integer(1.into()), Cbor::Float(1.5)]),
+        )]);
+        assert_eq!(
+            Ok(parse_binding_complete("a=1,1.5").unwrap()),
+            from_cbor(&number)
+        );
+        assert_eq!(
+            Err(ConvertError::new("a[1]", "null has no fuji representation")),
+            from_cbor(&map(vec![("a", Cbor::Array(vec![text("b"), Cbor::Null]))]))
+        );
+        // Maps and empty scalars add no segment to the paths of their children.
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.b.c",
+                "null has no fuji representation"
+            )),
+            from_cbor(&map(vec![(
+                "a",
+                Cbor::Map(vec![(
+                    Cbor::Null,
+                    map(vec![("b", map(vec![("", map(vec![("c", Cbor::Null)]))]))])
+                )])
+            )]))
+        );
+        assert_eq!(
+            Err(ConvertError::new("a.b", "expected a map of bindings")),
+            from_cbor(&map(vec![("a", map(vec![("b", text("c"))]))]))
+        );
+        assert_eq!(
+            Err(ConvertError::new(".", "expected a map with a single entry")),
+            from_cbor(&map(vec![("a", text("b")), ("c", text("d"))]))
+        );
+        assert_eq!(
+            Err(ConvertError::new("a", "expected a text string key")),
+            from_cbor(&map(vec![(
+                "a",
+                Cbor::Map(vec![(Cbor::Integer(1.into()), map(vec![]))])
+            )]))
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Export of documents as CSV or TSV rows of dotted paths and values, e.g. to audit a large
+//! configuration in a spreadsheet.
+//!
+//! There is a row for each value in document order, as [`Document::iter`] yields them, except
+//! for maps, whose rows are those of their children. A value with children has a row for its
+//! scalar before the rows of its children. Nested lists, references and anchored scalars are printed
+//! as fuji, e.g. `*base`. The first row is the header `path,value`.
+//!
+//! CSV fields are quoted if they contain the delimiter, a quote or a line break, as in RFC 4180.
+//! TSV fields cannot be quoted, so tabs, line breaks and backslashes in them are escaped as `\t`,
+//! `\n`, `\r` and `\\`.
+
+use crate::prelude::*;
+use crate::{print_value, quote, Document, ValueKind};
+
+/// Options for [`to_csv`].
+#[derive(Debug, Default, Clone)]
+pub struct CsvOptions {
+    tabs: bool,
+    indices: bool,
+}
+
+impl CsvOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes tab-separated values rather than comma-separated ones.
+    pub fn tabs(mut self) -> Self {
+        self.tabs = true;
+        self
+    }
+
+    /// Includes the indices of repeated values in paths, e.g. `ports[1]`, so that each path
+    /// identifies a single row. Without them, the rows of repeated values share a path.
+    pub fn indices(mut self) -> Self {
+        self.indices = true;
+        self
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn tsv_field(field: &str) -> String {
+    let mut out = String::new();
+    for c in field.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns the rows of `document`, each ending with a line break.
+pub fn to_csv(document: &Document, options: &CsvOptions) -> String {
+    let (delimiter, field): (char, fn(&str) -> String) = if options.tabs {
+        ('\t', tsv_field)
+    } else {
+        (',', csv_field)
+    };
+    let mut out = format!("path{}value\n", delimiter);
+    for (mut path, value) in document.iter() {
+        if value.is_map() {
+            continue;
+        }
+        if !options.indices {
+            for segment in path.segments.iter_mut() {
+                segment.index = None;
+            }
+        }
+        let value = match value.kind() {
+            ValueKind::List(_) => print_value(value),
+            ValueKind::Reference | ValueKind::Anchored(_) => quote::value(value).into_owned(),
+            _ => value.value.clone(),
+        };
+        out.push_str(&field(&path.to_string()));
+        out.push(delimiter);
+        out.push_str(&field(&value));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_csv() {
+        let document = parse_document_complete(
+            "server=main{host=h ports=80,443 tls={cert=\"a,b\"}}\n\
+             motd=\"say \\\"hi\\\"\\n\\tbye\"\n\
+             list=[x,[y,z]]\n\
+             a=&x v b=*x",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "path,value\n\
+             server,main\n\
+             server.host,h\n\
+             server.ports,80\n\
+             server.ports,443\n\
+             server.tls.cert,\"a,b\"\n\
+             motd,\"say \"\"hi\"\"\n\tbye\"\n\
+             list,x\n\
+             list,\"[y,z]\"\n\
+             a,&x v\n\
+             b,*x\n",
+            to_csv(&document, &CsvOptions::new())
+        );
+        assert_eq!(
+            "path\tvalue\n\
+             server\tmain\n\
+             server.host\th\n\
+             server.ports[0]\t80\n\
+             server.ports[1]\t443\n\
+             server.tls.cert\ta,b\n\
+             motd\tsay \"hi\"\\n\\tbye\n\
+             list[0]\tx\n\
+             list[1]\t[y,z]\n\
+             a\t&x v\n\
+             b\t*x\n",
+            to_csv(&document, &CsvOptions::new().tabs().indices())
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Lossless conversion between bindings and JSON.
+//!
+//! The mapping is:
+//!
+//! - a binding becomes an object with a single key, the binding name, whose value is the single
+//!   value of the binding, or an array if the binding has zero or several values, or a single list;
+//! - a value without children becomes a string, and a nested list an array;
+//! - a value with children becomes an object with a single key, the value itself, mapped to an
+//!   array of its child bindings; a map uses the empty string as its key;
+//! - an empty value with children, which would read as a map, becomes an object with the keys
+//!   `value` and `children` instead, e.g. `{"value": "", "children": [{"a": "b"}]}`.
+//!
+//! So `foo=bar{zoo=qat},xxx` becomes `{"foo": [{"bar": [{"zoo": "qat"}]}, "xxx"]}`. Arrays of
+//! bindings preserve both the order and any duplicate names.
+//!
+//! A document becomes an array of its top-level binding objects.
+//!
+//! References and anchors have no JSON representation, so converting a document that contains
+//! them is an error; expand them first with `Document::expand_references`.
+//!
+//! When converting from JSON, numbers and booleans are accepted as scalar values; `null` is
+//! rejected.
+
+use super::{check_expanded, children_path, ConvertError};
+use crate::{Binding, Document, Value, ValueKind};
+use serde_json::{Map, Value as Json};
+
+pub fn to_json(binding: &Binding) -> Result<Json, ConvertError> {
+    binding_to_json(binding, "")
+}
+
+pub fn document_to_json(document: &Document) -> Result<Json, ConvertError> {
+    document
+        .bindings
+        .iter()
+        .map(|b| binding_to_json(b, ""))
+        .collect::<Result<_, _>>()
+        .map(Json::Array)
+}
+
+fn binding_to_json(binding: &Binding, parent: &str) -> Result<Json, ConvertError> {
+    let path = if parent.is_empty() {
+        binding.name.clone()
+    } else {
+        format!("{}.{}", parent, binding.name)
+    };
+    let values = match binding.values.as_slice() {
+        [value] if value.items().is_none() => value_at(value, &path)?,
+        values => Json::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    let mut map = Map::new();
+    map.insert(binding.name.clone(), values);
+    Ok(Json::Object(map))
+}
+
+/// Converts a value; references and anchors must be expanded first.
+pub fn value_to_json(value: &Value) -> Result<Json, ConvertError> {
+    value_at(value, "")
+}
+
+fn value_at(value: &Value, path: &str) -> Result<Json, ConvertError> {
+    check_expanded(value, path)?;
+    let children = |path: &str| {
+        value
+            .children
+            .iter()
+            .map(|c| binding_to_json(c, path))
+            .collect::<Result<_, _>>()
+            .map(Json::Array)
+    };
+    Ok(if let Some(items) = value.items() {
+        Json::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        )
+    } else if value.children.is_empty() && !value.is_map() {
+        Json::String(value.value.clone())
+    } else if value.value.is_empty() && !value.is_map() {
+        let mut map = Map::new();
+        map.insert("value".to_string(), Json::String(String::new()));
+        map.insert("children".to_string(), children(path)?);
+        Json::Object(map)
+    } else {
+        let mut map = Map::new();
+        map.insert(
+            value.value.clone(),
+            children(&children_path(path, &value.value))?,
+        );
+        Json::Object(map)
+    })
+}
+
+fn single_entry<'a>(json: &'a Json, path: &str) -> Result<(&'a String, &'a Json), ConvertError> {
+    match json {
+        Json::Object(map) if map.len() == 1 => Ok(map.iter().next().unwrap()),
+        _ => Err(ConvertError::new(
+            path,
+            "expected an object with a single key",
+        )),
+    }
+}
+
+pub fn from_json(json: &Json) -> Result<Binding, ConvertError> {
+    binding_from_json(json, "")
+}
+
+pub fn document_from_json(json: &Json) -> Result<Document, ConvertError> {
+    match json {
+        Json::Array(bindings) => Ok(Document::from_bindings(
+            bindings
+                .iter()
+                .map(|b| binding_from_json(b, ""))
+                .collect::<Result<_, _>>()?,
+        )),
+        _ => Err(ConvertError::new(".", "expected an array of bindings")),
+    }
+}
+
+fn binding_from_json(json: &Json, parent: &str) -> Result<Binding, ConvertError> {
+    let (name, values) = single_entry(json, if parent.is_empty() { "." } else { parent })?;
+    let path = if parent.is_empty() {
+        name.clone()
+    } else {
+        format!("{}.{}", parent, name)
+    };
+    let values = match values {
+        Json::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_json(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<_, _>>()?,
+        value => vec![value_from_json(value, &path)?],
+    };
+    Ok(Binding {
+        name: name.clone(),
+        values: values.into(),
+    })
+}
+
+fn value_from_json(json: &Json, path: &str) -> Result<Value, ConvertError> {
+    let scalar = |value: String| Value {
+        value,
+        kind: ValueKind::Scalar,
+        children: vec![],
+    };
+    match json {
+        Json::String(s) => Ok(scalar(s.clone())),
+        Json::Number(n) => Ok(scalar(n.to_string())),
+        Json::Bool(b) => Ok(scalar(b.to_string())),
+        Json::Null => Err(ConvertError::new(path, "null has no fuji representation")),
+        Json::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_json(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::list),
+        Json::Object(map) if map.len() == 2 => {
+            let (value, children) = match (map.get("value"), map.get("children")) {
+                (Some(Json::String(value)), Some(children)) => (value, children),
+                _ => {
+                    return Err(ConvertError::new(
+                        path,
+                        "expected an object with a single key, or `value` and `children`",
+                    ))
+                }
+            };
+            Ok(Value {
+                value: value.clone(),
+                kind: ValueKind::Scalar,
+                children: children_from_json(children, &children_path(path, value))?,
+            })
+        }
+        Json::Object(_) => {
+            let (value, children) = single_entry(json, path)?;
+            let children = children_from_json(children, &children_path(path, value))?;
+            Ok(Value {
+                value: value.clone(),
+                kind: if value.is_empty() {
+                    ValueKind::Map
+                } else {
+                    ValueKind::Scalar
+                },
+                children,
+            })
+        }
+    }
+}
+
+fn children_from_json(json: &Json, path: &str) -> Result<Vec<Binding>, ConvertError> {
+    match json {
+        Json::Array(children) => children
+            .iter()
+            .map(|c| binding_from_json(c, path))
+            .collect(),
+        _ => Err(ConvertError::new(
+            path,
+            "expected an array of child bindings",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding, parse_document_complete};
+    use serde_json::json;
+
+    #[test]
+    fn test_json() {
+        struct Test {
+            fuji: String,
+            json: Json,
+        }
+
+        let tests = [
+            Test {
+                fuji: "foo=bar".to_string(),
+                json: json!({"foo": "bar"}),
+            },
+            Test {
+                fuji: "foo=a,b".to_string(),
+                json: json!({"foo": ["a", "b"]}),
+            },
+            Test {
+                fuji: "foo=bar{zoo=qat},xxx".to_string(),
+                json: json!({"foo": [{"bar": [{"zoo": "qat"}]}, "xxx"]}),
+            },
+            Test {
+                fuji: "a=b{c=d c=e{f=g}}".to_string(),
+                json: json!({"a": {"b": [{"c": "d"}, {"c": {"e": [{"f": "g"}]}}]}}),
+            },
+            Test {
+                fuji: "foo=[[a,b]]".to_string(),
+                json: json!({"foo": [["a", "b"]]}),
+            },
+            Test {
+                fuji: "foo=[a,[b{c=d},[]]]".to_string(),
+                json: json!({"foo": ["a", [{"b": [{"c": "d"}]}, []]]}),
+            },
+            Test {
+                fuji: "foo={a=b},{}".to_string(),
+                json: json!({"foo": [{"": [{"a": "b"}]}, {"": []}]}),
+            },
+            Test {
+                fuji: "foo=\"\"{a=b},\"\"".to_string(),
+                json: json!({"foo": [{"value": "", "children": [{"a": "b"}]}, ""]}),
+            },
+        ];
+
+        for t in tests.iter() {
+            let (_, binding) = parse_binding(&t.fuji).unwrap();
+            assert_eq!(Ok(t.json.clone()), to_json(&binding));
+            assert_eq!(Ok(binding), from_json(&t.json));
+        }
+    }
+
+    #[test]
+    fn test_document_json() {
+        let document = parse_document_complete("a=b\nc=d{e=f}").unwrap();
+        let json = json!([{"a": "b"}, {"c": {"d": [{"e": "f"}]}}]);
+        assert_eq!(Ok(json.clone()), document_to_json(&document));
+        assert_eq!(Ok(document), document_from_json(&json));
+        assert_eq!(
+            Err(ConvertError::new(".", "expected an array of bindings")),
+            document_from_json(&json!({"a": "b"}))
+        );
+    }
+
+    #[test]
+    fn test_json_references() {
+        let mut document = parse_document_complete("a=&x v b=y{c=d,*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "unexpanded anchor `&x`; expand references first"
+            )),
+            document_to_json(&document)
+        );
+        let (_, binding) = parse_binding("b=y{c=d,*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "b.y.c[1]",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            to_json(&binding)
+        );
+
+        document.expand_references().unwrap();
+        let json = json!([{"a": "v"}, {"b": {"y": [{"c": ["d", "v"]}]}}]);
+        assert_eq!(Ok(json.clone()), document_to_json(&document));
+        assert_eq!(Ok(document), document_from_json(&json));
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        assert_eq!(
+            Err(ConvertError::new("a[1]", "null has no fuji representation")),
+            from_json(&json!({"a": [1, null]}))
+        );
+        // Maps and empty scalars add no segment to the paths of their children.
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.b.c",
+                "null has no fuji representation"
+            )),
+            from_json(&json!({"a": {"": [{"b": {"value": "", "children": [{"c": null}]}}]}}))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.b",
+                "expected an array of child bindings"
+            )),
+            from_json(&json!({"a": {"b": {"c": "d"}}}))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                ".",
+                "expected an object with a single key"
+            )),
+            from_json(&json!({"a": "b", "c": "d"}))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "expected an object with a single key, or `value` and `children`"
+            )),
+            from_json(&json!({"a": {"b": [], "c": []}}))
+        );
+    }
+}
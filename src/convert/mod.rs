@@ -0,0 +1,80 @@
+//! Conversions between fuji bindings and other data formats.
+
+use crate::prelude::*;
+use crate::{Value, ValueKind};
+use core::fmt;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod csv;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod textproto;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "xml")]
+pub mod xml;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+/// A construct in the source format that has no fuji representation.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ConvertError {
+    /// Location of the offending construct in the source document, e.g. `foo[1].bar`.
+    pub path: String,
+    pub message: String,
+}
+
+impl ConvertError {
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        ConvertError {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConvertError {}
+
+/// Returns the path of the children of a value at `path` whose scalar is `value`. The scalar names
+/// a segment of the path unless it is empty, as for a map, e.g. `a.b.c` for `a=b{c=d}` but `a.c`
+/// for `a={c=d}`.
+#[cfg(any(feature = "json", feature = "cbor", feature = "msgpack"))]
+pub(crate) fn children_path(path: &str, value: &str) -> String {
+    if value.is_empty() {
+        path.to_string()
+    } else if path.is_empty() {
+        value.to_string()
+    } else {
+        format!("{}.{}", path, value)
+    }
+}
+
+/// Rejects a reference or an anchored value at `path`, which other formats cannot represent
+/// without changing its meaning; `Document::expand_references` removes them.
+pub(crate) fn check_expanded(value: &Value, path: &str) -> Result<(), ConvertError> {
+    let path = if path.is_empty() { "." } else { path };
+    match &value.kind {
+        ValueKind::Reference => Err(ConvertError::new(
+            path,
+            format!(
+                "unexpanded reference `*{}`; expand references first",
+                value.value
+            ),
+        )),
+        ValueKind::Anchored(anchor) => Err(ConvertError::new(
+            path,
+            format!("unexpanded anchor `&{}`; expand references first", anchor),
+        )),
+        _ => Ok(()),
+    }
+}
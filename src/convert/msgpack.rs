@@ -0,0 +1,373 @@
+//! Lossless conversion between bindings and MessagePack values.
+//!
+//! The mapping is:
+//!
+//! - a binding becomes a map with a single entry, from the binding name to the single value of
+//!   the binding, or to an array if the binding has zero or several values, or a single list;
+//! - a value without children becomes a string, and a nested list an array;
+//! - a value with children becomes a map with a single entry, from the value itself to a map of
+//!   its child bindings; a map uses nil as its key.
+//!
+//! A map of child bindings, like a document, has an entry per binding in order, from its name to
+//! its values as above. MessagePack maps are ordered sequences of entries, so keys may repeat and
+//! bindings sharing a name keep their relative order. So `foo=bar{zoo=qat},xxx` becomes
+//! `{"foo": [{"bar": {"zoo": "qat"}}, "xxx"]}`.
+//!
+//! References and anchors have no MessagePack representation, so converting a document that contains
+//! them is an error; expand them first with `Document::expand_references`.
+//!
+//! When converting from MessagePack, integers, floats and booleans are accepted as scalar values;
+//! nil, binary data, extension types and strings that are not valid UTF-8 are rejected.
+
+use super::{check_expanded, children_path, ConvertError};
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+use rmpv::Value as MsgPack;
+
+pub fn to_msgpack(binding: &Binding) -> Result<MsgPack, ConvertError> {
+    Ok(MsgPack::Map(vec![entry(binding, "")?]))
+}
+
+pub fn document_to_msgpack(document: &Document) -> Result<MsgPack, ConvertError> {
+    bindings_to_msgpack(&document.bindings, "")
+}
+
+fn bindings_to_msgpack(bindings: &[Binding], parent: &str) -> Result<MsgPack, ConvertError> {
+    bindings
+        .iter()
+        .map(|b| entry(b, parent))
+        .collect::<Result<_, _>>()
+        .map(MsgPack::Map)
+}
+
+fn entry(binding: &Binding, parent: &str) -> Result<(MsgPack, MsgPack), ConvertError> {
+    let path = if parent.is_empty() {
+        binding.name.clone()
+    } else {
+        format!("{}.{}", parent, binding.name)
+    };
+    let values = match binding.values.as_slice() {
+        [value] if value.items().is_none() => value_at(value, &path)?,
+        values => MsgPack::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    Ok((MsgPack::from(binding.name.clone()), values))
+}
+
+/// Converts a value; references and anchors must be expanded first.
+pub fn value_to_msgpack(value: &Value) -> Result<MsgPack, ConvertError> {
+    value_at(value, "")
+}
+
+fn value_at(value: &Value, path: &str) -> Result<MsgPack, ConvertError> {
+    check_expanded(value, path)?;
+    Ok(if let Some(items) = value.items() {
+        MsgPack::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_at(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        )
+    } else if value.children.is_empty() && !value.is_map() {
+        MsgPack::from(value.value.clone())
+    } else if value.is_map() {
+        MsgPack::Map(vec![(
+            MsgPack::Nil,
+            bindings_to_msgpack(&value.children, path)?,
+        )])
+    } else {
+        let path = children_path(path, &value.value);
+        MsgPack::Map(vec![(
+            MsgPack::from(value.value.clone()),
+            bindings_to_msgpack(&value.children, &path)?,
+        )])
+    })
+}
+
+fn text<'a>(msgpack: &'a MsgPack, path: &str) -> Result<&'a str, ConvertError> {
+    msgpack
+        .as_str()
+        .ok_or_else(|| ConvertError::new(path, "expected a string key"))
+}
+
+fn single_entry<'a>(
+    msgpack: &'a MsgPack,
+    path: &str,
+) -> Result<(&'a str, &'a MsgPack), ConvertError> {
+    match msgpack {
+        MsgPack::Map(entries) if entries.len() == 1 => {
+            let (key, value) = &entries[0];
+            Ok((text(key, path)?, value))
+        }
+        _ => Err(ConvertError::new(
+            path,
+            "expected a map with a single entry",
+        )),
+    }
+}
+
+pub fn from_msgpack(msgpack: &MsgPack) -> Result<Binding, ConvertError> {
+    let (name, values) = single_entry(msgpack, ".")?;
+    binding_from_msgpack(name, values, "")
+}
+
+pub fn document_from_msgpack(msgpack: &MsgPack) -> Result<Document, ConvertError> {
+    Ok(Document::from_bindings(bindings_from_msgpack(msgpack, "")?))
+}
+
+fn bindings_from_msgpack(msgpack: &MsgPack, parent: &str) -> Result<Vec<Binding>, ConvertError> {
+    match msgpack {
+        MsgPack::Map(entries) => entries
+            .iter()
+            .map(|(name, values)| binding_from_msgpack(text(name, parent)?, values, parent))
+            .collect(),
+        _ => Err(ConvertError::new(
+            if parent.is_empty() { "." } else { parent },
+            "expected a map of bindings",
+        )),
+    }
+}
+
+fn binding_from_msgpack(
+    name: &str,
+    values: &MsgPack,
+    parent: &str,
+) -> Result<Binding, ConvertError> {
+    let path = if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", parent, name)
+    };
+    let values = match values {
+        MsgPack::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_msgpack(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<_, _>>()?,
+        value => smallvec![value_from_msgpack(value, &path)?],
+    };
+    Ok(Binding {
+        name: name.to_string(),
+        values,
+    })
+}
+
+fn value_from_msgpack(msgpack: &MsgPack, path: &str) -> Result<Value, ConvertError> {
+    let scalar = |value: String| Value {
+        value,
+        kind: ValueKind::Scalar,
+        children: vec![],
+    };
+    match msgpack {
+        MsgPack::String(s) => match s.as_str() {
+            Some(s) => Ok(scalar(s.to_string())),
+            None => Err(ConvertError::new(path, "invalid UTF-8 in string")),
+        },
+        MsgPack::Integer(n) => Ok(scalar(n.to_string())),
+        MsgPack::F32(f) => Ok(scalar(f.to_string())),
+        MsgPack::F64(f) => Ok(scalar(f.to_string())),
+        MsgPack::Boolean(b) => Ok(scalar(b.to_string())),
+        MsgPack::Nil => Err(ConvertError::new(path, "nil has no fuji representation")),
+        MsgPack::Binary(_) => Err(ConvertError::new(
+            path,
+            "binary data has no fuji representation",
+        )),
+        MsgPack::Ext(..) => Err(ConvertError::new(
+            path,
+            "extension types have no fuji representation",
+        )),
+        MsgPack::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_msgpack(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::list),
+        MsgPack::Map(entries) if entries.len() == 1 && entries[0].0 == MsgPack::Nil => {
+            let children = bindings_from_msgpack(&entries[0].1, path)?;
+            Ok(Value::map(children))
+        }
+        MsgPack::Map(_) => {
+            let (value, children) = single_entry(msgpack, path)?;
+            let children = bindings_from_msgpack(children, &children_path(path, value))?;
+            Ok(Value {
+                value: value.to_string(),
+                kind: ValueKind::Scalar,
+                children,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    fn text(s: &str) -> MsgPack {
+        MsgPack::from(s)
+    }
+
+    fn map(entries: Vec<(&str, MsgPack)>) -> MsgPack {
+        MsgPack::Map(entries.into_iter().map(|(k, v)| (text(k), v)).collect())
+    }
+
+    #[test]
+    fn test_msgpack() {
+        struct Test {
+            fuji: String,
+            msgpack: MsgPack,
+        }
+
+        let tests = [
+            Test {
+                fuji: "foo=bar".to_string(),
+                msgpack: map(vec![("foo", text("bar"))]),
+            },
+            Test {
+                fuji: "foo=a,b".to_string(),
+                msgpack: map(vec![("foo", MsgPack::Array(vec![text("a"), text("b")]))]),
+            },
+            Test {
+                fuji: "foo=bar{zoo=qat},xxx".to_string(),
+                msgpack: map(vec![(
+                    "foo",
+                    MsgPack::Array(vec![
+                        map(vec![("bar", map(vec![("zoo", text("qat"))]))]),
+                        text("xxx"),
+                    ]),
+                )]),
+            },
+            Test {
+                fuji: "a=b{c=d c=e}".to_string(),
+                msgpack: map(vec![(
+                    "a",
+                    map(vec![("b", map(vec![("c", text("d")), ("c", text("e"))]))]),
+                )]),
+            },
+            Test {
+                fuji: "foo=[[a],{}]".to_string(),
+                msgpack: map(vec![(
+                    "foo",
+                    MsgPack::Array(vec![
+                        MsgPack::Array(vec![text("a")]),
+                        MsgPack::Map(vec![(MsgPack::Nil, map(vec![]))]),
+                    ]),
+                )]),
+            },
+            Test {
+                fuji: "foo={a=b},\"\"{a=b}".to_string(),
+                msgpack: map(vec![(
+                    "foo",
+                    MsgPack::Array(vec![
+                        MsgPack::Map(vec![(MsgPack::Nil, map(vec![("a", text("b"))]))]),
+                        map(vec![("", map(vec![("a", text("b"))]))]),
+                    ]),
+                )]),
+            },
+        ];
+
+        for t in tests.iter() {
+            let binding = parse_binding_complete(&t.fuji).unwrap();
+            assert_eq!(Ok(t.msgpack.clone()), to_msgpack(&binding), "{}", t.fuji);
+            assert_eq!(Ok(binding), from_msgpack(&t.msgpack), "{}", t.fuji);
+        }
+    }
+
+    #[test]
+    fn test_document_msgpack() {
+        let document = parse_document_complete("a=b\nc=d{e=f}\na=g").unwrap();
+        let msgpack = map(vec![
+            ("a", text("b")),
+            ("c", map(vec![("d", map(vec![("e", text("f"))]))])),
+            ("a", text("g")),
+        ]);
+        assert_eq!(Ok(msgpack.clone()), document_to_msgpack(&document));
+        assert_eq!(Ok(document), document_from_msgpack(&msgpack));
+
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &msgpack).unwrap();
+        let decoded = rmpv::decode::read_value(&mut &bytes[..]).unwrap();
+        assert_eq!(msgpack, decoded);
+    }
+
+    #[test]
+    fn test_msgpack_references() {
+        let mut document = parse_document_complete("a=&x v b={c=*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "b.c",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            document_to_msgpack(&Document::from_bindings(document.bindings[1..].to_vec()))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "unexpanded anchor `&x`; expand references first"
+            )),
+            document_to_msgpack(&document)
+        );
+
+        document.expand_references().unwrap();
+        let msgpack = map(vec![
+            ("a", text("v")),
+            (
+                "b",
+                MsgPack::Map(vec![(MsgPack::Nil, map(vec![("c", text("v"))]))]),
+            ),
+        ]);
+        assert_eq!(Ok(msgpack.clone()), document_to_msgpack(&document));
+        assert_eq!(Ok(document), document_from_msgpack(&msgpack));
+    }
+
+    #[test]
+    fn test_from_msgpack_errors() {
+        let number = map(vec![(
+            "a",
+            MsgPack::Array(vec![MsgPack::from(1), MsgPack::F64(1.5)]),
+        )]);
+        assert_eq!(
+            Ok(parse_binding_complete("a=1,1.5").unwrap()),
+            from_msgpack(&number)
+        );
+        assert_eq!(
+            Err(ConvertError::new("a[1]", "nil has no fuji representation")),
+            from_msgpack(&map(vec![(
+                "a",
+                MsgPack::Array(vec![text("b"), MsgPack::Nil])
+            )]))
+        );
+        // Maps and empty scalars add no segment to the paths of their children.
+        assert_eq!(
+            Err(ConvertError::new("a.b.c", "nil has no fuji representation")),
+            from_msgpack(&map(vec![(
+                "a",
+                MsgPack::Map(vec![(
+                    MsgPack::Nil,
+                    map(vec![("b", map(vec![("", map(vec![("c", MsgPack::Nil)]))]))])
+                )])
+            )]))
+        );
+        assert_eq!(
+            Err(ConvertError::new("a.b", "expected a map of bindings")),
+            from_msgpack(&map(vec![("a", map(vec![("b", text("c"))]))]))
+        );
+        assert_eq!(
+            Err(ConvertError::new(".", "expected a map with a single entry")),
+            from_msgpack(&map(vec![("a", text("b")), ("c", text("d"))]))
+        );
+        assert_eq!(
+            Err(ConvertError::new("a", "expected a string key")),
+            from_msgpack(&map(vec![(
+                "a",
+                MsgPack::Map(vec![(MsgPack::from(1), map(vec![]))])
+            )]))
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Conversion between documents and protobuf text format.
+//!
+//! The mapping is:
+//!
+//! - each field occurrence becomes a binding; `ports: [80, 443]` becomes a single binding with two
+//!   values, while `ports: 80 ports: 443` becomes two bindings;
+//! - a scalar field becomes a value without children; quoted strings are unescaped;
+//! - a message field (`server { host: "x" }`) becomes a value with children, named after the field.
+//!
+//! When printing, a binding with several values is emitted as one field per value. Values that
+//! look like numbers or booleans are printed bare, everything else as a quoted string. Values with
+//! children can only be printed if they are maps (`server={host=x}`) or equal to the name of their
+//! binding, since messages carry no tag. Anchors and references must be expanded with
+//! `Document::expand_references` first.
+
+use super::{check_expanded, ConvertError};
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag, take_while1},
+    character::complete::{multispace1, one_of},
+    combinator::{all_consuming, map, opt, recognize},
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// Skips whitespace and `#` comments.
+fn ws(input: &str) -> IResult<&str, ()> {
+    map(
+        many0(alt((
+            multispace1,
+            recognize(pair(tag("#"), opt(is_not("\n")))),
+        ))),
+        |_| (),
+    )(input)
+}
+
+fn ident(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_')(input)
+}
+
+fn quoted(input: &str) -> IResult<&str, String> {
+    let (input, quote) = one_of("\"'")(input)?;
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((&input[i + 1..], value)),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::Char,
+    )))
+}
+
+fn bare(input: &str) -> IResult<&str, String> {
+    map(
+        take_while1(|c: char| c.is_ascii_alphanumeric() || "_.+-".contains(c)),
+        |s: &str| s.to_string(),
+    )(input)
+}
+
+/// A message nested in `depth` others, failing if that would nest more than `MAX_DEPTH` of them,
+/// so that adversarial input cannot overflow the stack.
+fn message(depth: usize) -> impl Fn(&str) -> IResult<&str, Vec<Binding>> {
+    move |input| {
+        let (_, _) = one_of("{<")(input)?;
+        if depth >= MAX_DEPTH {
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::TooLarge,
+            )));
+        }
+        alt((
+            delimited(pair(tag("{"), ws), fields(depth + 1), tag("}")),
+            delimited(pair(tag("<"), ws), fields(depth + 1), tag(">")),
+        ))(input)
+    }
+}
+
+fn value<'a>(name: &'a str, depth: usize) -> impl Fn(&'a str) -> IResult<&'a str, Value> {
+    move |input| {
+        alt((
+            map(message(depth), |children| Value {
+                value: name.to_string(),
+                kind: ValueKind::Scalar,
+                children,
+            }),
+            map(alt((quoted, bare)), |value| Value {
+                value,
+                kind: ValueKind::Scalar,
+                children: vec![],
+            }),
+        ))(input)
+    }
+}
+
+fn field(depth: usize) -> impl Fn(&str) -> IResult<&str, Binding> {
+    move |input| {
+        let (input, name) = terminated(ident, ws)(input)?;
+        let (input, values) = alt((
+            map(message(depth), |children| {
+                vec![Value {
+                    value: name.to_string(),
+                    kind: ValueKind::Scalar,
+                    children,
+                }]
+            }),
+            preceded(
+                pair(tag(":"), ws),
+                alt((
+                    delimited(
+                        pair(tag("["), ws),
+                        separated_list0(tuple((ws, tag(","), ws)), value(name, depth)),
+                        pair(ws, tag("]")),
+                    ),
+                    map(value(name, depth), |v| vec![v]),
+                )),
+            ),
+        ))(input)?;
+        Ok((
+            input,
+            Binding {
+                name: name.to_string(),
+                values: values.into(),
+            },
+        ))
+    }
+}
+
+/// The fields of a message nested in `depth` others.
+fn fields(depth: usize) -> impl Fn(&str) -> IResult<&str, Vec<Binding>> {
+    move |input| many0(terminated(field(depth), tuple((ws, opt(one_of(",;")), ws))))(input)
+}
+
+pub fn from_textproto(input: &str) -> Result<Document, ConvertError> {
+    match all_consuming(preceded(ws, fields(0)))(input) {
+        Ok((_, bindings)) => Ok(Document::from_bindings(bindings)),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let offset = input.len() - e.input.len();
+            let line = input[..offset].matches('\n').count() + 1;
+            let column = offset - input[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+            let message = match e.code {
+                nom::error::ErrorKind::TooLarge => {
+                    format!("messages nested more than {} deep", MAX_DEPTH)
+                }
+                _ => "invalid text format".to_string(),
+            };
+            Err(ConvertError::new(&format!("{}:{}", line, column), message))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(ConvertError::new(".", "incomplete input")),
+    }
+}
+
+pub fn to_textproto(document: &Document) -> Result<String, ConvertError> {
+    let mut out = String::new();
+    print_fields(&document.bindings, "", 0, &mut out)?;
+    Ok(out)
+}
+
+fn is_bare(value: &str) -> bool {
+    value == "true" || value == "false" || value.parse::<f64>().is_ok()
+}
+
+fn print_fields(
+    bindings: &[Binding],
+    parent: &str,
+    indent: usize,
+    out: &mut String,
+) -> Result<(), ConvertError> {
+    let prefix = "  ".repeat(indent);
+    for binding in bindings {
+        let path = if parent.is_empty() {
+            binding.name.clone()
+        } else {
+            format!("{}.{}", parent, binding.name)
+        };
+        for value in binding.values.iter() {
+            check_expanded(value, &path)?;
+            if value.items().is_some() {
+                return Err(ConvertError::new(
+                    &path,
+                    "nested lists cannot be represented as fields",
+                ));
+            } else if value.children.is_empty() && !value.is_map() {
+                let value = if is_bare(&value.value) {
+                    value.value.clone()
+                } else {
+                    format!(
+                        "\"{}\"",
+                        value
+                            .value
+                            .replace('\\', "\\\\")
+                            .replace('"', "\\\"")
+                            .replace('\n', "\\n")
+                    )
+                };
+                out.push_str(&format!("{}{}: {}\n", prefix, binding.name, value));
+            } else if value.is_map() || value.value == binding.name {
+                out.push_str(&format!("{}{} {{\n", prefix, binding.name));
+                print_fields(&value.children, &path, indent + 1, out)?;
+                out.push_str(&format!("{}}}\n", prefix));
+            } else {
+                return Err(ConvertError::new(
+                    &path,
+                    format!(
+                        "value `{}` with children cannot be represented as a message",
+                        value.value
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document, print_document};
+
+    #[test]
+    fn test_textproto() {
+        struct Test {
+            fuji: String,
+            textproto: String,
+        }
+
+        let tests = [
+            Test {
+                fuji: "name=foo\nport=80\ndebug=true".to_string(),
+                textproto: "name: \"foo\"\nport: 80\ndebug: true\n".to_string(),
+            },
+            Test {
+                fuji: "server=server{host=localhost port=80}".to_string(),
+                textproto: "server {\n  host: \"localhost\"\n  port: 80\n}\n".to_string(),
+            },
+        ];
+
+        for t in tests.iter() {
+            let (_, document) = parse_document(&t.fuji).unwrap();
+            assert_eq!(Ok(t.textproto.clone()), to_textproto(&document));
+            assert_eq!(
+                Ok(t.fuji.clone()),
+                from_textproto(&t.textproto).map(|d| print_document(&d))
+            );
+        }
+
+        let (_, document) = parse_document("server={port=80}").unwrap();
+        assert_eq!(
+            Ok("server {\n  port: 80\n}\n".to_string()),
+            to_textproto(&document)
+        );
+    }
+
+    #[test]
+    fn test_from_textproto() {
+        let input = r#"
+            # A comment.
+            ports: [80, 443]
+            server: < host: 'a' >, server { host: "b" }
+            mode: TCP;
+        "#;
+        assert_eq!(
+            Ok("ports=80,443\nserver=server{host=a}\nserver=server{host=b}\nmode=TCP".to_string()),
+            from_textproto(input).map(|d| print_document(&d))
+        );
+        assert_eq!(
+            Err(ConvertError::new("2:1", "invalid text format")),
+            from_textproto("a: 1\nb { c: }")
+        );
+        let nested = |depth: usize| "a { ".repeat(depth) + &"}".repeat(depth);
+        assert!(from_textproto(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            Err(ConvertError::new(
+                "1:259",
+                "messages nested more than 64 deep"
+            )),
+            from_textproto(&nested(MAX_DEPTH + 1))
+        );
+        assert_eq!(
+            Err(ConvertError::new(
+                "1:259",
+                "messages nested more than 64 deep"
+            )),
+            from_textproto(&nested(200_000))
+        );
+        let (_, document) = parse_document("a=[[b]]").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "nested lists cannot be represented as fields"
+            )),
+            to_textproto(&document)
+        );
+        let (_, document) = parse_document("a=b,*x").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            to_textproto(&document)
+        );
+    }
+}
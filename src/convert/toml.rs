@@ -0,0 +1,219 @@
+//! Conversion between documents and TOML.
+//!
+//! The mapping is:
+//!
+//! - a document becomes the top-level table;
+//! - a binding with a single value maps to that value, unless it is a list, otherwise to an array
+//!   of its values;
+//! - a nested list becomes an array;
+//! - a value without children becomes a scalar; integers and `true`/`false` are emitted as TOML
+//!   integers and booleans, everything else as a string;
+//! - a value with children becomes a table of its child bindings.
+//!
+//! TOML tables carry no tag, so a value with children can only be converted if it is a map
+//! (`server={...}`) or equal to the name of its binding (`server=server{...}`); conversely a table
+//! is imported with the name of its key as the value. Other tagged values are reported as errors,
+//! as are anchors and references, which must be expanded with `Document::expand_references`.
+
+use super::{check_expanded, ConvertError};
+use crate::{Binding, Document, Value, ValueKind};
+use ::toml::{Table, Value as Toml};
+
+pub fn to_toml(document: &Document) -> Result<Table, ConvertError> {
+    bindings_to_toml(&document.bindings, "")
+}
+
+fn bindings_to_toml(bindings: &[Binding], parent: &str) -> Result<Table, ConvertError> {
+    let mut table = Table::new();
+    for binding in bindings {
+        let path = if parent.is_empty() {
+            binding.name.clone()
+        } else {
+            format!("{}.{}", parent, binding.name)
+        };
+        let mut values = binding
+            .values
+            .iter()
+            .map(|v| value_to_toml(&binding.name, v, &path))
+            .collect::<Result<Vec<_>, _>>()?;
+        match table.get_mut(&binding.name) {
+            Some(Toml::Array(array)) => array.extend(values),
+            Some(existing) => {
+                let mut array = vec![existing.clone()];
+                array.extend(values);
+                *existing = Toml::Array(array);
+            }
+            None => {
+                let value = if values.len() == 1 && binding.values[0].items().is_none() {
+                    values.remove(0)
+                } else {
+                    Toml::Array(values)
+                };
+                table.insert(binding.name.clone(), value);
+            }
+        }
+    }
+    Ok(table)
+}
+
+fn value_to_toml(name: &str, value: &Value, path: &str) -> Result<Toml, ConvertError> {
+    check_expanded(value, path)?;
+    if let Some(items) = value.items() {
+        items
+            .iter()
+            .map(|v| value_to_toml(name, v, path))
+            .collect::<Result<_, _>>()
+            .map(Toml::Array)
+    } else if value.children.is_empty() && !value.is_map() {
+        Ok(if value.value == "true" || value.value == "false" {
+            Toml::Boolean(value.value == "true")
+        } else if let Ok(n) = value.value.parse::<i64>() {
+            Toml::Integer(n)
+        } else {
+            Toml::String(value.value.clone())
+        })
+    } else if value.is_map() || value.value == name {
+        Ok(Toml::Table(bindings_to_toml(&value.children, path)?))
+    } else {
+        Err(ConvertError::new(
+            path,
+            format!(
+                "value `{}` with children cannot be represented as a table",
+                value.value
+            ),
+        ))
+    }
+}
+
+pub fn from_toml(table: &Table) -> Result<Document, ConvertError> {
+    Ok(Document::from_bindings(bindings_from_toml(table, "")?))
+}
+
+fn bindings_from_toml(table: &Table, parent: &str) -> Result<Vec<Binding>, ConvertError> {
+    table
+        .iter()
+        .map(|(name, value)| {
+            let path = if parent.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", parent, name)
+            };
+            let values = match value {
+                Toml::Array(array) => array
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| value_from_toml(name, v, &format!("{}[{}]", path, i)))
+                    .collect::<Result<_, _>>()?,
+                value => vec![value_from_toml(name, value, &path)?],
+            };
+            Ok(Binding {
+                name: name.clone(),
+                values: values.into(),
+            })
+        })
+        .collect()
+}
+
+fn value_from_toml(name: &str, toml: &Toml, path: &str) -> Result<Value, ConvertError> {
+    let scalar = |value: String| {
+        Ok(Value {
+            value,
+            kind: ValueKind::Scalar,
+            children: vec![],
+        })
+    };
+    match toml {
+        Toml::String(s) => scalar(s.clone()),
+        Toml::Integer(n) => scalar(n.to_string()),
+        Toml::Float(f) => scalar(f.to_string()),
+        Toml::Boolean(b) => scalar(b.to_string()),
+        Toml::Datetime(d) => scalar(d.to_string()),
+        Toml::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_toml(name, v, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::list),
+        Toml::Table(table) => Ok(Value {
+            value: name.to_string(),
+            kind: ValueKind::Scalar,
+            children: bindings_from_toml(table, path)?,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document, print_document};
+
+    #[test]
+    fn test_toml() {
+        struct Test {
+            fuji: String,
+            toml: String,
+        }
+
+        let tests = [
+            Test {
+                fuji: "foo=bar\nport=80\ndebug=true".to_string(),
+                toml: "foo = \"bar\"\nport = 80\ndebug = true\n".to_string(),
+            },
+            Test {
+                fuji: "foo=a,b".to_string(),
+                toml: "foo = [\"a\", \"b\"]\n".to_string(),
+            },
+            Test {
+                fuji: "server=server{host=localhost ports=80,443}".to_string(),
+                toml: "[server]\nhost = \"localhost\"\nports = [80, 443]\n".to_string(),
+            },
+            Test {
+                fuji: "servers=servers{host=a},servers{host=b}".to_string(),
+                toml: "[[servers]]\nhost = \"a\"\n\n[[servers]]\nhost = \"b\"\n".to_string(),
+            },
+            Test {
+                fuji: "matrix=[[1,2],[3]]\nrow=[[a]]".to_string(),
+                toml: "matrix = [[1, 2], [3]]\nrow = [[\"a\"]]\n".to_string(),
+            },
+        ];
+
+        for t in tests.iter() {
+            let (_, document) = parse_document(&t.fuji).unwrap();
+            assert_eq!(
+                Ok(t.toml.clone()),
+                to_toml(&document).map(|table| ::toml::to_string(&table).unwrap())
+            );
+            let table: Table = t.toml.parse().unwrap();
+            assert_eq!(
+                Ok(t.fuji.clone()),
+                from_toml(&table).map(|d| print_document(&d))
+            );
+        }
+
+        let (_, document) = parse_document("server={port=80}").unwrap();
+        assert_eq!(
+            Ok("[server]\nport = 80\n".to_string()),
+            to_toml(&document).map(|table| ::toml::to_string(&table).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let (_, document) = parse_document("a=a{mode=tcp{port=80}}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.mode",
+                "value `tcp` with children cannot be represented as a table"
+            )),
+            to_toml(&document)
+        );
+        let (_, document) = parse_document("a=&x v b=*x").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a",
+                "unexpanded anchor `&x`; expand references first"
+            )),
+            to_toml(&document)
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! Conversion between documents and XML.
+//!
+//! The mapping is:
+//!
+//! - a document becomes a root element, `<fuji>` by default, containing its top-level bindings;
+//! - each value of a binding becomes an element named after the binding, so `a=b,c` becomes
+//!   `<a>b</a><a>c</a>`;
+//! - the scalar of a value becomes the text of its element, followed by an element for each value
+//!   of its child bindings, e.g. `<server>main<port>80</port></server>`;
+//! - with [`XmlOptions::attributes`], a child binding with a single value that has no children
+//!   becomes an attribute of the parent element instead, unless a sibling binding shares its
+//!   name, e.g. `<server port="80">main</server>`.
+//!
+//! Binding names must be valid XML names, and nested lists have no XML representation. Anchors and
+//! references must be expanded with `Document::expand_references` first.
+//!
+//! When converting from XML, attributes become bindings before the child elements, adjacent
+//! elements with the same name become a single binding with several values, and the text of an
+//! element with children is trimmed. An element with children but no text becomes a map. Comments,
+//! processing instructions and the XML declaration are ignored. Elements nested more than
+//! `MAX_DEPTH` deep in the root element are rejected.
+
+use super::{check_expanded, ConvertError};
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use crate::{Binding, Document, Value, ValueKind};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Options for [`to_xml`].
+#[derive(Debug, Clone)]
+pub struct XmlOptions {
+    root: String,
+    attributes: bool,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions {
+            root: "fuji".to_string(),
+            attributes: false,
+        }
+    }
+}
+
+impl XmlOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of the root element.
+    pub fn root(mut self, name: impl Into<String>) -> Self {
+        self.root = name.into();
+        self
+    }
+
+    /// Writes child bindings with a single value and no grandchildren as attributes rather than
+    /// elements.
+    pub fn attributes(mut self, attributes: bool) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// Whether `name` is a valid XML name, excluding names with a namespace prefix.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || "-._".contains(c))
+}
+
+fn check_name(name: &str, path: &str) -> Result<(), ConvertError> {
+    if is_name(name) {
+        Ok(())
+    } else {
+        Err(ConvertError::new(path, "name is not a valid XML name"))
+    }
+}
+
+pub fn to_xml(document: &Document, options: &XmlOptions) -> Result<String, ConvertError> {
+    check_name(&options.root, ".")?;
+    let mut out = String::new();
+    element(&mut out, &options.root, "", &document.bindings, "", options)?;
+    Ok(out)
+}
+
+/// Whether `binding` is written as an attribute among `siblings`.
+fn is_attribute(binding: &Binding, siblings: &[Binding], options: &XmlOptions) -> bool {
+    options.attributes
+        && matches!(binding.values.as_slice(), [value] if value.is_scalar() && value.children.is_empty())
+        && siblings.iter().filter(|b| b.name == binding.name).count() == 1
+}
+
+fn element(
+    out: &mut String,
+    name: &str,
+    text: &str,
+    children: &[Binding],
+    path: &str,
+    options: &XmlOptions,
+) -> Result<(), ConvertError> {
+    out.push('<');
+    out.push_str(name);
+    let (attributes, elements): (Vec<_>, Vec<_>) = children
+        .iter()
+        .partition(|b| is_attribute(b, children, options));
+    for attribute in attributes {
+        check_name(&attribute.name, &child_path(path, &attribute.name))?;
+        out.push_str(&format!(
+            " {}=\"{}\"",
+            attribute.name,
+            escape(attribute.values[0].value.as_str())
+        ));
+    }
+    if text.is_empty() && elements.is_empty() {
+        out.push_str("/>");
+        return Ok(());
+    }
+    out.push('>');
+    out.push_str(&escape(text));
+    for binding in elements {
+        let path = child_path(path, &binding.name);
+        check_name(&binding.name, &path)?;
+        for (i, value) in binding.values.iter().enumerate() {
+            let path = if binding.values.len() > 1 {
+                format!("{}[{}]", path, i)
+            } else {
+                path.clone()
+            };
+            check_expanded(value, &path)?;
+            if value.items().is_some() {
+                return Err(ConvertError::new(
+                    &path,
+                    "nested lists have no XML representation",
+                ));
+            }
+            element(
+                out,
+                &binding.name,
+                &value.value,
+                &value.children,
+                &path,
+                options,
+            )?;
+        }
+    }
+    out.push_str(&format!("</{}>", name));
+    Ok(())
+}
+
+fn child_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", parent, name)
+    }
+}
+
+/// An element being read, with the bindings of its attributes and child elements so far.
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Binding>,
+}
+
+fn error(reader: &Reader<&[u8]>, error: impl core::fmt::Display) -> ConvertError {
+    ConvertError::new(
+        &format!("offset {}", reader.buffer_position()),
+        error.to_string(),
+    )
+}
+
+/// Starts reading an element nested in the elements of `stack`, failing if that would nest it more
+/// than `MAX_DEPTH` deep in the root element.
+fn start(
+    reader: &Reader<&[u8]>,
+    start: &BytesStart,
+    stack: &[Element],
+) -> Result<Element, ConvertError> {
+    // The stack holds the document and the root element below the elements nested in it.
+    if stack.len() > MAX_DEPTH + 1 {
+        return Err(error(
+            reader,
+            format!("elements nested more than {} deep", MAX_DEPTH),
+        ));
+    }
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut children = vec![];
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| error(reader, e))?;
+        let value = attribute.unescape_value().map_err(|e| error(reader, e))?;
+        children.push(Binding {
+            name: String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+            values: smallvec![Value::from(value.into_owned())],
+        });
+    }
+    Ok(Element {
+        name,
+        text: String::new(),
+        children,
+    })
+}
+
+/// Adds the value read from `element` to `parent`, as another value of the last binding if it
+/// has the same name.
+fn end(element: Element, parent: &mut Element) {
+    let text = if element.children.is_empty() {
+        element.text
+    } else {
+        element.text.trim().to_string()
+    };
+    let value = Value {
+        kind: if text.is_empty() && !element.children.is_empty() {
+            ValueKind::Map
+        } else {
+            ValueKind::Scalar
+        },
+        value: text,
+        children: element.children,
+    };
+    match parent.children.last_mut() {
+        Some(last) if last.name == element.name => last.values.push(value),
+        _ => parent.children.push(Binding {
+            name: element.name,
+            values: smallvec![value],
+        }),
+    }
+}
+
+pub fn from_xml(input: &str) -> Result<Document, ConvertError> {
+    let mut reader = Reader::from_str(input);
+    // The document, holding the root element once it has been read.
+    let mut stack = vec![Element {
+        name: String::new(),
+        text: String::new(),
+        children: vec![],
+    }];
+    loop {
+        let event = reader.read_event().map_err(|e| error(&reader, e))?;
+        match event {
+            Event::Start(e) => {
+                let element = start(&reader, &e, &stack)?;
+                stack.push(element);
+            }
+            Event::Empty(e) => {
+                let element = start(&reader, &e, &stack)?;
+                end(element, stack.last_mut().unwrap());
+            }
+            Event::End(_) => {
+                let element = stack.pop().unwrap();
+                end(element, stack.last_mut().unwrap());
+            }
+            Event::Text(e) => {
+                let text = e.unescape().map_err(|e| error(&reader, e))?;
+                if stack.len() == 1 && !text.trim().is_empty() {
+                    return Err(error(&reader, "text outside of the root element"));
+                }
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::CData(e) => {
+                let text = String::from_utf8(e.into_inner().into_owned())
+                    .map_err(|e| error(&reader, e))?;
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::Eof if stack.len() > 1 => {
+                let element = &stack.last().unwrap().name;
+                return Err(error(&reader, format!("unclosed element `{}`", element)));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let document = stack.pop().unwrap();
+    match document.children.as_slice() {
+        [root] if root.values.len() == 1 => {
+            let root = &root.values[0];
+            if !root.value.trim().is_empty() {
+                return Err(ConvertError::new(
+                    ".",
+                    "unexpected text in the root element",
+                ));
+            }
+            Ok(Document::from_bindings(root.children.clone()))
+        }
+        _ => Err(ConvertError::new(".", "expected a single root element")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_xml() {
+        struct Test {
+            fuji: String,
+            xml: String,
+            attributes: bool,
+        }
+
+        let tests = [
+            Test {
+                fuji: "a=b".to_string(),
+                xml: "<fuji><a>b</a></fuji>".to_string(),
+                attributes: false,
+            },
+            Test {
+                fuji: "a=b,c\nd=\"<&>\"".to_string(),
+                xml: "<fuji><a>b</a><a>c</a><d>&lt;&amp;&gt;</d></fuji>".to_string(),
+                attributes: false,
+            },
+            Test {
+                fuji: "server=main{host=h port=80 tag=x,y}".to_string(),
+                xml: "<fuji><server>main<host>h</host><port>80</port><tag>x</tag><tag>y</tag></server></fuji>"
+                    .to_string(),
+                attributes: false,
+            },
+            Test {
+                fuji: "server=main{host=h port=80 tag=x,y tls={cert=c}}".to_string(),
+                xml: "<fuji><server host=\"h\" port=\"80\">main<tag>x</tag><tag>y</tag><tls cert=\"c\"/></server></fuji>"
+                    .to_string(),
+                attributes: true,
+            },
+            Test {
+                fuji: "a={b=c{d=e}}".to_string(),
+                xml: "<fuji><a><b>c<d>e</d></b></a></fuji>".to_string(),
+                attributes: false,
+            },
+        ];
+
+        for t in tests.iter() {
+            let document = parse_document_complete(&t.fuji).unwrap();
+            let options = XmlOptions::new().attributes(t.attributes);
+            assert_eq!(Ok(t.xml.clone()), to_xml(&document, &options), "{}", t.fuji);
+            assert_eq!(Ok(document), from_xml(&t.xml), "{}", t.xml);
+        }
+    }
+
+    #[test]
+    fn test_from_xml() {
+        let xml = "<?xml version=\"1.0\"?>\n\
+                   <config version=\"2\">\n  \
+                     <!-- servers -->\n  \
+                     <server>\n    main\n    <port>80</port>\n  </server>\n  \
+                     <name><![CDATA[a<b]]></name>\n  \
+                     <empty/>\n\
+                   </config>";
+        let expected =
+            parse_document_complete("version=2 server=main{port=80} name=\"a<b\" empty=\"\"")
+                .unwrap();
+        assert_eq!(Ok(expected), from_xml(xml));
+    }
+
+    #[test]
+    fn test_xml_errors() {
+        let document = parse_document_complete("a=b{\"c d\"=e}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new("a.c d", "name is not a valid XML name")),
+            to_xml(&document, &XmlOptions::new())
+        );
+        let document = parse_document_complete("a=[b,[c]]").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a[1]",
+                "nested lists have no XML representation"
+            )),
+            to_xml(&document, &XmlOptions::new())
+        );
+        let document = parse_document_complete("a=b{c=*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.c",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            to_xml(&document, &XmlOptions::new())
+        );
+        assert_eq!(
+            Err(ConvertError::new(".", "name is not a valid XML name")),
+            to_xml(&document, &XmlOptions::new().root("1"))
+        );
+        assert_eq!(
+            Err(ConvertError::new(".", "expected a single root element")),
+            from_xml("<a/><b/>")
+        );
+        assert!(from_xml("<a><b></a>").is_err());
+        assert_eq!(
+            Err(ConvertError::new("offset 21", "unclosed element `fuji`")),
+            from_xml("<fuji><x><a>b</a></x>")
+        );
+        assert_eq!(
+            Err(ConvertError::new("offset 9", "unclosed element `x`")),
+            from_xml("<fuji><x>")
+        );
+
+        let nested = |depth: usize, element: &str| {
+            format!(
+                "<fuji>{}{}{}</fuji>",
+                "<a>".repeat(depth - 1),
+                element,
+                "</a>".repeat(depth - 1)
+            )
+        };
+        assert!(from_xml(&nested(MAX_DEPTH, "<a/>")).is_ok());
+        // The offset is the end of the tag of the element nested too deep.
+        for (element, offset) in [("<a></a>", 201), ("<a/>", 202)].iter() {
+            assert_eq!(
+                Err(ConvertError::new(
+                    &format!("offset {}", offset),
+                    "elements nested more than 64 deep"
+                )),
+                from_xml(&nested(MAX_DEPTH + 1, element))
+            );
+        }
+        assert_eq!(
+            Err(ConvertError::new(
+                "offset 201",
+                "elements nested more than 64 deep"
+            )),
+            from_xml(&nested(200_000, "<a/>"))
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Conversion between documents and YAML.
+//!
+//! The mapping is:
+//!
+//! - a document becomes a mapping from binding names to their values;
+//! - a binding with a single value maps to that value, unless it is a list, otherwise to a
+//!   sequence of its values;
+//! - a nested list becomes a sequence;
+//! - a value without children becomes a scalar; integers and `true`/`false` are emitted as plain
+//!   YAML scalars, everything else as a string;
+//! - a value with children becomes a mapping of its child bindings, tagged with the value itself
+//!   (`!bar {zoo: qat}`). The tag is omitted for maps and when it equals the name of the enclosing
+//!   binding, and conversely an untagged YAML mapping takes the name of its binding as the value.
+//!
+//! Child bindings sharing a name are grouped into a single sequence under that key, so their
+//! relative order with respect to other children is not preserved. Anchors and references must be
+//! expanded with `Document::expand_references` before converting to YAML.
+
+use super::{check_expanded, ConvertError};
+use crate::{Binding, Document, Value, ValueKind};
+use serde_yaml::{value::Tag, value::TaggedValue, Mapping, Value as Yaml};
+
+pub fn to_yaml(document: &Document) -> Result<Yaml, ConvertError> {
+    bindings_to_yaml(&document.bindings, "").map(Yaml::Mapping)
+}
+
+fn bindings_to_yaml(bindings: &[Binding], parent: &str) -> Result<Mapping, ConvertError> {
+    let mut map = Mapping::new();
+    for binding in bindings {
+        let key = Yaml::String(binding.name.clone());
+        let path = if parent.is_empty() {
+            binding.name.clone()
+        } else {
+            format!("{}.{}", parent, binding.name)
+        };
+        let mut values = binding
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let path = if binding.values.len() > 1 {
+                    format!("{}[{}]", path, i)
+                } else {
+                    path.clone()
+                };
+                value_to_yaml(&binding.name, v, &path)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match map.get_mut(&key) {
+            Some(Yaml::Sequence(seq)) => seq.extend(values),
+            Some(existing) => {
+                let mut seq = vec![existing.clone()];
+                seq.extend(values);
+                *existing = Yaml::Sequence(seq);
+            }
+            None => {
+                let value = if values.len() == 1 && binding.values[0].items().is_none() {
+                    values.remove(0)
+                } else {
+                    Yaml::Sequence(values)
+                };
+                map.insert(key, value);
+            }
+        }
+    }
+    Ok(map)
+}
+
+fn value_to_yaml(name: &str, value: &Value, path: &str) -> Result<Yaml, ConvertError> {
+    check_expanded(value, path)?;
+    Ok(if let Some(items) = value.items() {
+        Yaml::Sequence(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| value_to_yaml(name, v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        )
+    } else if value.children.is_empty() && !value.is_map() {
+        if value.value == "true" || value.value == "false" {
+            Yaml::Bool(value.value == "true")
+        } else if let Ok(n) = value.value.parse::<i64>() {
+            Yaml::Number(n.into())
+        } else {
+            Yaml::String(value.value.clone())
+        }
+    } else {
+        let children = Yaml::Mapping(bindings_to_yaml(&value.children, path)?);
+        if value.is_map() || value.value == name {
+            children
+        } else {
+            Yaml::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(value.value.clone()),
+                value: children,
+            }))
+        }
+    })
+}
+
+pub fn from_yaml(yaml: &Yaml) -> Result<Document, ConvertError> {
+    match yaml {
+        Yaml::Mapping(map) => Ok(Document::from_bindings(bindings_from_yaml(map, "")?)),
+        _ => Err(ConvertError::new(
+            ".",
+            "expected a mapping at the top level",
+        )),
+    }
+}
+
+fn bindings_from_yaml(map: &Mapping, parent: &str) -> Result<Vec<Binding>, ConvertError> {
+    map.iter()
+        .map(|(key, value)| {
+            let name = match key {
+                Yaml::String(name) => name.clone(),
+                _ => {
+                    return Err(ConvertError::new(
+                        if parent.is_empty() { "." } else { parent },
+                        "mapping keys must be strings",
+                    ))
+                }
+            };
+            let path = if parent.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", parent, name)
+            };
+            let values = match value {
+                Yaml::Sequence(seq) => seq
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| value_from_yaml(&name, v, &format!("{}[{}]", path, i)))
+                    .collect::<Result<_, _>>()?,
+                value => vec![value_from_yaml(&name, value, &path)?],
+            };
+            Ok(Binding {
+                name,
+                values: values.into(),
+            })
+        })
+        .collect()
+}
+
+fn value_from_yaml(name: &str, yaml: &Yaml, path: &str) -> Result<Value, ConvertError> {
+    let scalar = |value: String| {
+        Ok(Value {
+            value,
+            kind: ValueKind::Scalar,
+            children: vec![],
+        })
+    };
+    match yaml {
+        Yaml::String(s) => scalar(s.clone()),
+        Yaml::Number(n) => scalar(n.to_string()),
+        Yaml::Bool(b) => scalar(b.to_string()),
+        Yaml::Null => Err(ConvertError::new(path, "null has no fuji representation")),
+        Yaml::Sequence(seq) => seq
+            .iter()
+            .enumerate()
+            .map(|(i, v)| value_from_yaml(name, v, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::list),
+        Yaml::Mapping(map) => Ok(Value {
+            value: name.to_string(),
+            kind: ValueKind::Scalar,
+            children: bindings_from_yaml(map, path)?,
+        }),
+        Yaml::Tagged(tagged) => match &tagged.value {
+            Yaml::Mapping(map) => Ok(Value {
+                value: tagged.tag.to_string()[1..].to_string(),
+                kind: ValueKind::Scalar,
+                children: bindings_from_yaml(map, path)?,
+            }),
+            _ => Err(ConvertError::new(path, "only mappings may be tagged")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document, print_document};
+
+    #[test]
+    fn test_yaml() {
+        struct Test {
+            fuji: String,
+            yaml: String,
+        }
+
+        let tests = [
+            Test {
+                fuji: "foo=bar\nport=80\ndebug=true".to_string(),
+                yaml: "foo: bar\nport: 80\ndebug: true\n".to_string(),
+            },
+            Test {
+                fuji: "foo=a,b".to_string(),
+                yaml: "foo:\n- a\n- b\n".to_string(),
+            },
+            Test {
+                fuji: "server=server{host=localhost ports=80,443}".to_string(),
+                yaml: "server:\n  host: localhost\n  ports:\n  - 80\n  - 443\n".to_string(),
+            },
+            Test {
+                fuji: "mode=tcp{port=80}".to_string(),
+                yaml: "mode: !tcp\n  port: 80\n".to_string(),
+            },
+            Test {
+                fuji: "matrix=[[1,2],[3]]\nrow=[[a]]".to_string(),
+                yaml: "matrix:\n- - 1\n  - 2\n- - 3\nrow:\n- - a\n".to_string(),
+            },
+        ];
+
+        for t in tests.iter() {
+            let (_, document) = parse_document(&t.fuji).unwrap();
+            assert_eq!(
+                t.yaml,
+                serde_yaml::to_string(&to_yaml(&document).unwrap()).unwrap()
+            );
+            let yaml: Yaml = serde_yaml::from_str(&t.yaml).unwrap();
+            assert_eq!(
+                Ok(t.fuji.clone()),
+                from_yaml(&yaml).map(|d| print_document(&d))
+            );
+        }
+
+        let (_, document) = parse_document("server={port=80}").unwrap();
+        assert_eq!(
+            "server:\n  port: 80\n",
+            serde_yaml::to_string(&to_yaml(&document).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_yaml_errors() {
+        let (_, document) = parse_document("a=b{c=d,*x}").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.c[1]",
+                "unexpanded reference `*x`; expand references first"
+            )),
+            to_yaml(&document)
+        );
+    }
+
+    #[test]
+    fn test_from_yaml_errors() {
+        let yaml: Yaml = serde_yaml::from_str("a:\n  b: [1, ~]\n").unwrap();
+        assert_eq!(
+            Err(ConvertError::new(
+                "a.b[1]",
+                "null has no fuji representation"
+            )),
+            from_yaml(&yaml)
+        );
+    }
+}
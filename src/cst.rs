@@ -0,0 +1,876 @@
+//! A lossless concrete syntax tree, which keeps comments and the original spacing.
+//!
+//! `print` reproduces the parsed input byte for byte, and `to_ast` projects the tree onto the
+//! regular AST. All fields are public so that tools can edit the tree in place without disturbing
+//! the formatting of the parts they do not touch.
+
+use crate::error::complete;
+use crate::parser::{anchored, key, list_end, open, reference, ws0, ws1, Error};
+use crate::prelude::*;
+use crate::{Address, ParseError, Path, ValueKind};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::{cut, map, not, opt, recognize},
+    error::context,
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// Whitespace and comments, kept verbatim.
+pub type Trivia = String;
+
+/// A sequence of bindings, either at the top level of a document or within braces.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Bindings {
+    /// Each binding, preceded by its leading trivia.
+    pub items: Vec<(Trivia, Binding)>,
+    /// Trivia after the last binding.
+    pub trailing: Trivia,
+}
+
+pub type Document = Bindings;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Binding {
+    /// The name as written, possibly quoted.
+    pub name: String,
+    pub values: Vec<ValueItem>,
+    /// The trivia before a trailing comma after the last value, if there is one.
+    pub trailing_comma: Option<Trivia>,
+    /// The trivia after the opening and before the closing bracket, if the values are bracketed.
+    pub brackets: Option<(Trivia, Trivia)>,
+}
+
+/// A bracketed list nested in another one.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct List {
+    pub values: Vec<ValueItem>,
+    /// The trivia before a trailing comma after the last value, if there is one.
+    pub trailing_comma: Option<Trivia>,
+    /// The trivia after the opening and before the closing bracket.
+    pub brackets: (Trivia, Trivia),
+}
+
+/// A value within a comma-separated list.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ValueItem {
+    /// Trivia before the comma; always empty for the first value.
+    pub before_comma: Trivia,
+    /// Trivia after the comma; always empty for the first value.
+    pub after_comma: Trivia,
+    pub value: Value,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Value {
+    /// The value as written, possibly quoted.
+    pub value: String,
+    /// The children block, if any, with the trivia before its opening brace. A block with an empty
+    /// `value` is a map.
+    pub block: Option<(Trivia, Bindings)>,
+    /// A nested list, in which case `value` is empty and there is no block.
+    pub list: Option<List>,
+}
+
+/// Bindings nested in `depth` blocks and lists, with their trivia.
+fn bindings<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Bindings, E> {
+    let binding = |input| binding(input, depth);
+    let (input, first) = opt(pair(ws0, binding))(input)?;
+    let (input, mut rest) = match first {
+        Some(_) => many0(pair(ws1, binding))(input)?,
+        None => (input, vec![]),
+    };
+    let (input, trailing) = ws0(input)?;
+    if let Some(first) = first {
+        rest.insert(0, first);
+    }
+    Ok((
+        input,
+        Bindings {
+            items: rest
+                .into_iter()
+                .map(|(trivia, binding)| (trivia.to_string(), binding))
+                .collect(),
+            trailing: trailing.to_string(),
+        },
+    ))
+}
+
+/// Values and the trivia before a trailing comma.
+type Values = (Vec<ValueItem>, Option<Trivia>);
+
+fn binding<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Binding, E> {
+    map(
+        pair(
+            terminated(recognize(key), cut(context("'='", tag("=")))),
+            opt(alt((
+                map(
+                    |input| list(input, depth),
+                    |list| (list.values, list.trailing_comma, Some(list.brackets)),
+                ),
+                map(
+                    |input| bare_values(input, depth),
+                    |(values, trailing_comma)| (values, trailing_comma, None),
+                ),
+            ))),
+        ),
+        |(name, values)| {
+            let (values, trailing_comma, brackets) = values.unwrap_or_default();
+            Binding {
+                name: name.to_string(),
+                values,
+                trailing_comma,
+                brackets,
+            }
+        },
+    )(input)
+}
+
+fn value_items(
+    first: Value,
+    rest: Vec<(&str, &str, Value)>,
+    trailing_comma: Option<&str>,
+) -> Values {
+    let mut values = vec![ValueItem {
+        before_comma: String::new(),
+        after_comma: String::new(),
+        value: first,
+    }];
+    values.extend(
+        rest.into_iter()
+            .map(|(before_comma, after_comma, value)| ValueItem {
+                before_comma: before_comma.to_string(),
+                after_comma: after_comma.to_string(),
+                value,
+            }),
+    );
+    (values, trailing_comma.map(str::to_string))
+}
+
+fn bare_values<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Values, E> {
+    let value = |input| value(input, depth);
+    map(
+        tuple((
+            value,
+            many0(tuple((
+                terminated(ws0, tag(",")),
+                terminated(ws0, not(list_end)),
+                cut(value),
+            ))),
+            opt(terminated(ws0, tag(","))),
+        )),
+        |(first, rest, trailing_comma)| value_items(first, rest, trailing_comma),
+    )(input)
+}
+
+fn list<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, List, E> {
+    let element = |input| element(input, depth + 1);
+    map(
+        preceded(
+            open("[", depth),
+            cut(tuple((
+                ws0,
+                opt(tuple((
+                    element,
+                    many0(tuple((terminated(ws0, tag(",")), ws0, element))),
+                    opt(terminated(ws0, tag(","))),
+                ))),
+                terminated(ws0, context("']'", tag("]"))),
+            ))),
+        ),
+        |(open, values, close)| {
+            let (values, trailing_comma) = values
+                .map_or((vec![], None), |(first, rest, trailing)| {
+                    value_items(first, rest, trailing)
+                });
+            List {
+                values,
+                trailing_comma,
+                brackets: (open.to_string(), close.to_string()),
+            }
+        },
+    )(input)
+}
+
+fn element<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Value, E> {
+    alt((
+        map(
+            |input| list(input, depth),
+            |list| Value {
+                value: String::new(),
+                block: None,
+                list: Some(list),
+            },
+        ),
+        |input| value(input, depth),
+    ))(input)
+}
+
+fn value<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Value, E> {
+    let bindings = |input| bindings(input, depth + 1);
+    alt((
+        map(
+            preceded(
+                open("{", depth),
+                cut(terminated(bindings, context("'}'", tag("}")))),
+            ),
+            |bindings| Value {
+                value: String::new(),
+                block: Some((String::new(), bindings)),
+                list: None,
+            },
+        ),
+        map(reference, |value| Value {
+            value: value.to_string(),
+            block: None,
+            list: None,
+        }),
+        map(
+            pair(
+                recognize(anchored),
+                opt(pair(
+                    terminated(ws0, open("{", depth)),
+                    cut(terminated(bindings, context("'}'", tag("}")))),
+                )),
+            ),
+            |(value, block)| Value {
+                value: value.to_string(),
+                block: block.map(|(trivia, bindings)| (trivia.to_string(), bindings)),
+                list: None,
+            },
+        ),
+    ))(input)
+}
+
+pub fn parse_document(input: &str) -> Result<Document, ParseError> {
+    complete(input, |input| bindings(input, 0))
+}
+
+pub fn parse_binding(input: &str) -> Result<Binding, ParseError> {
+    complete(input, |input| binding(input, 0))
+}
+
+impl Bindings {
+    /// Prints the bindings with their trivia. A single space is inserted between bindings whose
+    /// leading trivia has been emptied, so that the output still parses.
+    pub fn print(&self) -> String {
+        let mut out = String::new();
+        for (i, (trivia, binding)) in self.items.iter().enumerate() {
+            if i > 0 && trivia.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(trivia);
+            out.push_str(&binding.print());
+        }
+        out.push_str(&self.trailing);
+        out
+    }
+
+    pub fn to_ast(&self) -> crate::Document {
+        crate::Document::from_bindings(self.items.iter().map(|(_, b)| b.to_ast()).collect())
+    }
+}
+
+impl Binding {
+    pub fn print(&self) -> String {
+        let values = print_values(&self.values, &self.trailing_comma);
+        match &self.brackets {
+            Some((open, close)) => format!("{}=[{}{}{}]", self.name, open, values, close),
+            None => format!("{}={}", self.name, values),
+        }
+    }
+
+    pub fn to_ast(&self) -> crate::Binding {
+        crate::Binding {
+            name: contents(&self.name, key),
+            values: self.values.iter().map(|v| v.value.to_ast()).collect(),
+        }
+    }
+}
+
+fn print_values(values: &[ValueItem], trailing_comma: &Option<Trivia>) -> String {
+    let mut out = String::new();
+    for (i, item) in values.iter().enumerate() {
+        if i > 0 {
+            out.push_str(&item.before_comma);
+            out.push(',');
+            out.push_str(&item.after_comma);
+        }
+        out.push_str(&item.value.print());
+    }
+    if let (Some(trivia), false) = (trailing_comma, values.is_empty()) {
+        out.push_str(trivia);
+        out.push(',');
+    }
+    out
+}
+
+impl List {
+    pub fn print(&self) -> String {
+        let (open, close) = &self.brackets;
+        let values = print_values(&self.values, &self.trailing_comma);
+        format!("[{}{}{}]", open, values, close)
+    }
+}
+
+impl Value {
+    pub fn print(&self) -> String {
+        if let Some(list) = &self.list {
+            return list.print();
+        }
+        match &self.block {
+            Some((trivia, bindings)) => {
+                format!("{}{}{{{}}}", self.value, trivia, bindings.print())
+            }
+            None => self.value.clone(),
+        }
+    }
+
+    pub fn to_ast(&self) -> crate::Value {
+        if let Some(list) = &self.list {
+            return crate::Value::list(list.values.iter().map(|v| v.value.to_ast()));
+        }
+        let (value, kind) = scalar(&self.value);
+        crate::Value {
+            value,
+            kind: if self.value.is_empty() && self.block.is_some() {
+                ValueKind::Map
+            } else {
+                kind
+            },
+            children: self
+                .block
+                .as_ref()
+                .map(|(_, bindings)| bindings.to_ast().bindings)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns the scalar of a value token and its kind: a reference, an anchored scalar or a plain
+/// scalar, which is the token itself if it does not parse, e.g. after an invalid edit.
+pub(crate) fn scalar(token: &str) -> (String, ValueKind) {
+    if let Ok(reference) = complete(token, reference) {
+        return (reference[1..].to_string(), ValueKind::Reference);
+    }
+    match complete(token, anchored) {
+        Ok((Some(anchor), scalar)) => (scalar.into_owned(), ValueKind::Anchored(anchor.into())),
+        Ok((None, scalar)) => (scalar.into_owned(), ValueKind::Scalar),
+        Err(_) => (token.to_string(), ValueKind::Scalar),
+    }
+}
+
+/// Returns the contents of a token as parsed by `parser`, or the token itself if it does not parse,
+/// e.g. after an invalid edit.
+pub(crate) fn contents<'a>(
+    token: &'a str,
+    parser: fn(&'a str) -> IResult<&'a str, Cow<'a, str>, crate::error::Expected<'a>>,
+) -> String {
+    complete(token, parser).map_or_else(|_| token.to_string(), Cow::into_owned)
+}
+
+impl Bindings {
+    /// Returns the byte offset, within the printed bindings, of the node at `address`.
+    pub fn offset(&self, address: &Address) -> Option<usize> {
+        self.offset_of(&address.0)
+    }
+
+    fn offset_of(&self, indices: &[usize]) -> Option<usize> {
+        let (&index, rest) = indices.split_first()?;
+        let mut offset = 0;
+        for (i, (trivia, binding)) in self.items.iter().enumerate() {
+            if i > 0 && trivia.is_empty() {
+                offset += 1;
+            }
+            offset += trivia.len();
+            if i == index {
+                return Some(offset + binding.offset_of(rest)?);
+            }
+            offset += binding.print().len();
+        }
+        None
+    }
+}
+
+impl Binding {
+    fn offset_of(&self, indices: &[usize]) -> Option<usize> {
+        let (&index, rest) = match indices.split_first() {
+            Some(split) => split,
+            None => return Some(0),
+        };
+        let mut offset = self.name.len() + 1;
+        if let Some((open, _)) = &self.brackets {
+            offset += 1 + open.len();
+        }
+        for (i, item) in self.values.iter().enumerate() {
+            if i > 0 {
+                offset += item.before_comma.len() + 1 + item.after_comma.len();
+            }
+            if i == index {
+                return Some(offset + item.value.offset_of(rest)?);
+            }
+            offset += item.value.print().len();
+        }
+        None
+    }
+}
+
+impl Value {
+    fn offset_of(&self, indices: &[usize]) -> Option<usize> {
+        if indices.is_empty() {
+            return Some(0);
+        }
+        let (trivia, bindings) = self.block.as_ref()?;
+        Some(self.value.len() + trivia.len() + 1 + bindings.offset_of(indices)?)
+    }
+}
+
+impl Bindings {
+    /// Replaces the bytes in `range` of the printed document with `new_text`, and returns the
+    /// paths of the values that were added, removed or changed, ignoring their children.
+    ///
+    /// Only the top-level bindings touching `range` are parsed again; the others are kept as they
+    /// are. Edits whose effect may reach beyond those bindings, e.g. opening a comment at the end
+    /// of one, fall back to parsing the whole document. On error the document is left unchanged.
+    ///
+    /// Panics if `range` is out of bounds or does not lie on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) -> Result<Vec<Path>, ParseError> {
+        let spans = self.spans();
+        let len = spans.last().map_or(0, |&(_, end)| end) + self.trailing.len();
+        assert!(range.start <= range.end && range.end <= len);
+        let last = match spans.iter().rposition(|&(start, _)| start <= range.end) {
+            Some(last) => last,
+            None => return self.reparse(range, new_text),
+        };
+        let first = spans
+            .iter()
+            .position(|&(_, end)| end >= range.start)
+            .unwrap_or(last);
+        let at_end = last + 1 == self.items.len();
+
+        let start = spans[first].0;
+        let mut text = String::new();
+        for (i, (trivia, binding)) in self.items[first..=last].iter().enumerate() {
+            if first + i > 0 && trivia.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(trivia);
+            text.push_str(&binding.print());
+        }
+        if at_end {
+            text.push_str(&self.trailing);
+        }
+        text.replace_range(range.start - start..range.end - start, new_text);
+
+        let window: Bindings = match complete(&text, |input| bindings(input, 0)) {
+            Ok(window) => window,
+            Err(_) => return self.reparse(range, new_text),
+        };
+        // The window must still be separated from the bindings around it.
+        let detached = first > 0 && window.items.first().is_some_and(|(t, _)| t.is_empty());
+        if detached || (!at_end && (window.items.is_empty() || !window.trailing.is_empty())) {
+            return self.reparse(range, new_text);
+        }
+
+        let mut names = vec![];
+        for (_, binding) in self.items[first..=last].iter().chain(window.items.iter()) {
+            let name = contents(&binding.name, key);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let old = self.named(&names);
+        self.items.splice(first..=last, window.items);
+        if at_end {
+            self.trailing = window.trailing;
+        }
+        Ok(changed(&old, &self.named(&names)))
+    }
+
+    /// Returns the start of the leading trivia and the end of each binding, in the printed
+    /// bindings.
+    fn spans(&self) -> Vec<(usize, usize)> {
+        let mut offset = 0;
+        let mut spans = vec![];
+        for (i, (trivia, binding)) in self.items.iter().enumerate() {
+            let start = offset;
+            if i > 0 && trivia.is_empty() {
+                offset += 1;
+            }
+            offset += trivia.len() + binding.print().len();
+            spans.push((start, offset));
+        }
+        spans
+    }
+
+    /// Parses the whole document again after an edit.
+    fn reparse(&mut self, range: Range<usize>, new_text: &str) -> Result<Vec<Path>, ParseError> {
+        let mut text = self.print();
+        text.replace_range(range, new_text);
+        let document = parse_document(&text)?;
+        let paths = changed(&self.to_ast(), &document.to_ast());
+        *self = document;
+        Ok(paths)
+    }
+
+    /// Returns the top-level bindings with any of the given names, so that the paths of their
+    /// values are the same as in the whole document.
+    fn named(&self, names: &[String]) -> crate::Document {
+        let matches = |name: &str| {
+            if name.starts_with('"') {
+                names.contains(&contents(name, key))
+            } else {
+                names.iter().any(|n| n == name)
+            }
+        };
+        crate::Document::from_bindings(
+            self.items
+                .iter()
+                .filter(|(_, b)| matches(&b.name))
+                .map(|(_, b)| b.to_ast())
+                .collect(),
+        )
+    }
+}
+
+/// Returns the paths of the values that differ between `old` and `new`, not counting their
+/// children: first those in `new`, in document order, then those only in `old`.
+fn changed(old: &crate::Document, new: &crate::Document) -> Vec<Path> {
+    let shallow = |value: &crate::Value| crate::Value {
+        value: value.value.clone(),
+        kind: value.kind.clone(),
+        children: vec![],
+    };
+    let mut old = old
+        .iter()
+        .map(|(path, value)| (path.to_string(), (path, shallow(value))))
+        .collect::<BTreeMap<_, _>>();
+    let mut paths = vec![];
+    for (path, value) in new.iter() {
+        match old.remove(&path.to_string()) {
+            Some((_, previous)) if previous == shallow(value) => {}
+            _ => paths.push(path),
+        }
+    }
+    paths.extend(old.into_iter().map(|(_, (path, _))| path));
+    paths
+}
+
+/// Rewrites `trivia` in canonical form. Whitespace-only trivia becomes `default`, preserving a
+/// single blank line if `blank_lines` is set. Comments are kept, each on its own line except for
+/// comments trailing a token, which stay on the same line.
+fn normalize_trivia(
+    trivia: &str,
+    default: &str,
+    mut line_start: bool,
+    blank_lines: bool,
+) -> String {
+    let mut out = String::new();
+    let mut newlines = 0;
+    let mut after_comment = false;
+    let mut rest = trivia;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            newlines += 1;
+        }
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+        let blank = newlines >= if after_comment { 1 } else { 2 };
+        if !line_start {
+            out.push(if newlines == 0 { ' ' } else { '\n' });
+        }
+        if blank_lines && blank && !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(rest[..end].trim_end());
+        out.push('\n');
+        rest = &rest[end..];
+        line_start = true;
+        after_comment = true;
+        newlines = 0;
+    }
+    if after_comment {
+        if blank_lines && newlines >= 1 {
+            out.push('\n');
+        }
+        out
+    } else if blank_lines && !line_start && newlines >= 2 {
+        format!("{}\n", default)
+    } else {
+        default.to_string()
+    }
+}
+
+impl Bindings {
+    /// Normalizes all trivia in a document: one top-level binding per line, canonical spacing
+    /// within bindings, and comments preserved. Single blank lines between top-level bindings are
+    /// kept.
+    pub fn normalize(&mut self) {
+        for (i, (trivia, binding)) in self.items.iter_mut().enumerate() {
+            *trivia = if i == 0 {
+                normalize_trivia(trivia, "", true, true)
+            } else {
+                normalize_trivia(trivia, "\n", false, true)
+            };
+            binding.normalize();
+        }
+        self.trailing = if self.items.is_empty() {
+            normalize_trivia(&self.trailing, "", true, false)
+        } else {
+            normalize_trivia(&self.trailing, "\n", false, false)
+        };
+    }
+
+    /// Adds a trailing comma after the last value of every binding with several values if
+    /// `trailing` is set, and removes all other trailing commas.
+    pub fn set_trailing_commas(&mut self, trailing: bool) {
+        for (_, binding) in self.items.iter_mut() {
+            set_trailing_comma(&mut binding.values, &mut binding.trailing_comma, trailing);
+        }
+    }
+
+    fn normalize_block(&mut self) {
+        for (i, (trivia, binding)) in self.items.iter_mut().enumerate() {
+            *trivia = normalize_trivia(trivia, if i == 0 { "" } else { " " }, false, false);
+            binding.normalize();
+        }
+        self.trailing = normalize_trivia(&self.trailing, "", false, false);
+    }
+}
+
+impl Binding {
+    fn normalize(&mut self) {
+        normalize_values(&mut self.values, &mut self.trailing_comma);
+        if let Some(brackets) = &mut self.brackets {
+            normalize_brackets(brackets);
+        }
+    }
+}
+
+fn normalize_values(values: &mut [ValueItem], trailing_comma: &mut Option<Trivia>) {
+    if let Some(trivia) = trailing_comma {
+        *trivia = normalize_trivia(trivia, "", false, false);
+    }
+    for item in values.iter_mut() {
+        item.before_comma = normalize_trivia(&item.before_comma, "", false, false);
+        item.after_comma = normalize_trivia(&item.after_comma, "", false, false);
+        if let Some((trivia, bindings)) = &mut item.value.block {
+            *trivia = normalize_trivia(trivia, "", false, false);
+            bindings.normalize_block();
+        }
+        if let Some(list) = &mut item.value.list {
+            normalize_values(&mut list.values, &mut list.trailing_comma);
+            normalize_brackets(&mut list.brackets);
+        }
+    }
+}
+
+fn normalize_brackets((open, close): &mut (Trivia, Trivia)) {
+    *open = normalize_trivia(open, "", false, false);
+    *close = normalize_trivia(close, "", false, false);
+}
+
+/// Sets a trailing comma after several values if `trailing` is set, or removes it otherwise.
+fn set_trailing_comma(
+    values: &mut [ValueItem],
+    trailing_comma: &mut Option<Trivia>,
+    trailing: bool,
+) {
+    *trailing_comma = if trailing && values.len() > 1 {
+        Some(trailing_comma.take().unwrap_or_default())
+    } else {
+        None
+    };
+    for item in values.iter_mut() {
+        if let Some((_, bindings)) = &mut item.value.block {
+            bindings.set_trailing_commas(trailing);
+        }
+        if let Some(list) = &mut item.value.list {
+            set_trailing_comma(&mut list.values, &mut list.trailing_comma, trailing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless() {
+        let tests = [
+            "foo=bar",
+            "  foo=bar  ",
+            "foo=a , b,c",
+            "# header\nfoo=bar { # open\n  zoo=qat  // trailing\n\n  x=y,z{}\n}\n\nbaz=q\n",
+            "a=b{c=d{e=f}},k {l=m{n=o}}",
+            "a=&x  b {c=d} e=*x,${F:-g}",
+            "a= b=x{c=}\nd= ",
+            "a=b,c , d=e # x\n, f=g{h=i,j,}",
+            "a=[ b , [c,[ ]],d{e=[]} ,]\nf=[\n  # g\n  h,\n]",
+            "a={ b=c } ,{},d {e={f=g}}\nh=[{i=j}, { }]",
+            "a=\"\"\"\n  # b\n\n  \"\"\" c=&d \"\"\"\ne\n\"\"\"{f=g}",
+            "\"a b\"=\"c\\\"d\" {log-level=&x \"\"} e.f=_g",
+            "",
+        ];
+
+        for t in tests.iter() {
+            let cst = parse_document(t).unwrap();
+            assert_eq!(*t, cst.print());
+            assert_eq!(crate::parse_document_complete(t), Ok(cst.to_ast()));
+        }
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut cst = parse_document("# servers\na=b  # keep\nc=d{e=f}\n").unwrap();
+        cst.items[1].1.values[0].value.value = "x".to_string();
+        cst.items
+            .push((String::new(), parse_binding("g=h").unwrap()));
+        assert_eq!("# servers\na=b  # keep\nc=x{e=f} g=h\n", cst.print());
+        assert_eq!(
+            Err(ParseError::new("a=b{", 4, "'}'")),
+            parse_document("a=b{")
+        );
+    }
+
+    #[test]
+    fn test_incremental() {
+        struct Test {
+            range: Range<usize>,
+            new_text: &'static str,
+            text: &'static str,
+            changed: Vec<&'static str>,
+        }
+
+        let tests = [
+            Test {
+                range: 16..17,
+                new_text: "8080",
+                text: "# servers\na=b{c=8080 d=e}\nf=g h=i\n",
+                changed: vec!["a.c"],
+            },
+            Test {
+                range: 28..29,
+                new_text: "j,k",
+                text: "# servers\na=b{c=8080 d=e}\nf=j,k h=i\n",
+                changed: vec!["f[0]", "f[1]", "f"],
+            },
+            Test {
+                range: 28..30,
+                new_text: "",
+                text: "# servers\na=b{c=8080 d=e}\nf=k h=i\n",
+                changed: vec!["f", "f[0]", "f[1]"],
+            },
+            Test {
+                range: 34..34,
+                new_text: "l=m",
+                text: "# servers\na=b{c=8080 d=e}\nf=k h=i\nl=m",
+                changed: vec!["l"],
+            },
+            Test {
+                range: 0..10,
+                new_text: "",
+                text: "a=b{c=8080 d=e}\nf=k h=i\nl=m",
+                changed: vec![],
+            },
+            // A comment opened in one binding hides the next one.
+            Test {
+                range: 2..2,
+                new_text: "x #",
+                text: "a=x #b{c=8080 d=e}\nf=k h=i\nl=m",
+                changed: vec!["a", "a.c", "a.d"],
+            },
+            Test {
+                range: 0..30,
+                new_text: "",
+                text: "",
+                changed: vec!["a", "f", "h", "l"],
+            },
+        ];
+
+        let mut cst = parse_document("# servers\na=b{c=1 d=e}\nf=g h=i\n").unwrap();
+        for t in tests.iter() {
+            let changed = cst.edit(t.range.clone(), t.new_text).unwrap();
+            assert_eq!(t.text, cst.print());
+            assert_eq!(parse_document(t.text), Ok(cst.clone()), "{}", t.text);
+            assert_eq!(
+                t.changed,
+                changed.iter().map(Path::to_string).collect::<Vec<_>>(),
+                "{}",
+                t.text
+            );
+        }
+
+        let mut cst = parse_document("a=b c=d").unwrap();
+        assert_eq!(
+            Err(ParseError::new("a=b{ c=d", 8, "'}'")),
+            cst.edit(3..3, "{")
+        );
+        assert_eq!("a=b c=d", cst.print());
+    }
+
+    #[test]
+    fn test_offset() {
+        let input = "# x
+a=b , c{ d=e f=g,h }
+i=j";
+        let cst = parse_document(input).unwrap();
+        let tests = [
+            (vec![0], "a=b"),
+            (vec![0, 1], "c{"),
+            (vec![0, 1, 1], "f=g"),
+            (vec![0, 1, 1, 1], "h }"),
+            (vec![1, 0], "j"),
+        ];
+
+        for (indices, rest) in tests.iter() {
+            let offset = cst.offset(&Address(indices.clone())).unwrap();
+            assert!(input[offset..].starts_with(rest), "{:?}", indices);
+        }
+        assert_eq!(None, cst.offset(&Address(vec![0, 2])));
+    }
+
+    #[test]
+    fn test_normalize() {
+        struct Test {
+            input: String,
+            normalized: String,
+        }
+
+        let tests = [
+            Test {
+                input: "a=b   c=d{ e=f   g=h , i }".to_string(),
+                normalized: "a=b\nc=d{e=f g=h,i}\n".to_string(),
+            },
+            Test {
+                input: "# header\n\n\na=b # trailing\n\n\n# own\nc=d , e\n\n".to_string(),
+                normalized: "# header\n\na=b # trailing\n\n# own\nc=d,e\n".to_string(),
+            },
+            Test {
+                input: "a=b{ # open\n   c=d   # close\n  }".to_string(),
+                normalized: "a=b{ # open\nc=d # close\n}\n".to_string(),
+            },
+            Test {
+                input: "  # only a comment  ".to_string(),
+                normalized: "# only a comment\n".to_string(),
+            },
+        ];
+
+        for t in tests.iter() {
+            let mut cst = parse_document(&t.input).unwrap();
+            cst.normalize();
+            assert_eq!(t.normalized, cst.print());
+            let mut again = parse_document(&t.normalized).unwrap();
+            again.normalize();
+            assert_eq!(t.normalized, again.print());
+        }
+    }
+}
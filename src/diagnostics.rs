@@ -0,0 +1,256 @@
+//! Rendering of parse and validation errors for terminals, showing the source line they point at
+//! with the offending text underlined, optionally in color.
+//!
+//! ```
+//! use flax::diagnostics::{Diagnostic, Renderer};
+//!
+//! let source = "a=b\nc=d{e}";
+//! let error = flax::parse_document_complete(source).unwrap_err();
+//! let output = Renderer::new().render("config.fuji", source, &Diagnostic::from(&error));
+//! assert_eq!(
+//!     "error: expected '=', found `}`\n \
+//!      --> config.fuji:2:6\n  \
+//!      |\n\
+//!      2 | c=d{e}\n  \
+//!      |      ^ expected '='\n",
+//!     output
+//! );
+//! ```
+
+use crate::prelude::*;
+use crate::span::{self, Span};
+use crate::{line_column, ParseError, Severity, ValidationError};
+use core::fmt::Write;
+
+/// An error or warning, located in a source if possible.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The source text that the diagnostic points at.
+    pub span: Option<Span>,
+    /// A note printed next to the underlined span.
+    pub label: Option<String>,
+}
+
+impl From<&ParseError> for Diagnostic {
+    /// Points at the first character at which `error` occurred.
+    fn from(error: &ParseError) -> Self {
+        let found = match error.snippet.chars().next() {
+            Some(_) => format!("`{}`", error.snippet),
+            None => "end of input".to_string(),
+        };
+        let len = error.snippet.chars().next().map_or(0, char::len_utf8);
+        Diagnostic {
+            severity: Severity::Error,
+            message: format!("expected {}, found {}", error.expected, found),
+            span: Some(Span {
+                start: error.offset,
+                end: error.offset + len,
+            }),
+            label: Some(format!("expected {}", error.expected)),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Points `error`, found in the document parsed from `source`, at its offending node.
+    pub fn from_validation_error(error: &ValidationError, source: &str) -> Self {
+        let span = span::parse_document(source)
+            .ok()
+            .and_then(|document| document.span(&error.address));
+        Diagnostic {
+            severity: error.severity,
+            message: error.to_string(),
+            span,
+            label: None,
+        }
+    }
+}
+
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+/// Renders diagnostics in the style of compilers, e.g.:
+///
+/// ```text
+/// error: server.port: expected a single value, found 2
+///  --> config.fuji:3:3
+///   |
+/// 3 |   port=80,81
+///   |   ^^^^^^^^^^
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    /// Creates a renderer producing plain text.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Colors the output with ANSI escape sequences if `color` is true, e.g. when writing to a
+    /// terminal.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Renders `diagnostic`, found in `source`, which is called `name` in the output, e.g. the
+    /// path of a file. A span extending over several lines is underlined up to the end of its
+    /// first line.
+    pub fn render(&self, name: &str, source: &str, diagnostic: &Diagnostic) -> String {
+        let (severity, style) = match diagnostic.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        let mut out = format!(
+            "{}{}\n",
+            self.paint(style, severity),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+        let span = match diagnostic.span {
+            Some(span) if span.start <= source.len() => span,
+            _ => {
+                let _ = writeln!(out, "{} {}", self.paint(BLUE, "-->"), name);
+                return out;
+            }
+        };
+
+        let (line, column) = line_column(source, span.start);
+        let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let text = source[start..].lines().next().unwrap_or("");
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Tabs are kept in the indentation of the underline, so that it lines up with the text.
+        let indent = source[start..span.start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let end = span.end.clamp(span.start, start + text.len());
+        let carets = "^".repeat(source[span.start..end].chars().count().max(1));
+        let mut underline = format!("{}{}", indent, self.paint(style, &carets));
+        if let Some(label) = &diagnostic.label {
+            underline.push(' ');
+            underline.push_str(&self.paint(style, label));
+        }
+
+        let bar = self.paint(BLUE, "|");
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            gutter,
+            self.paint(BLUE, "-->"),
+            name,
+            line,
+            column
+        );
+        let _ = writeln!(out, "{} {}", gutter, bar);
+        let _ = writeln!(out, "{} {} {}", self.paint(BLUE, &number), bar, text);
+        let _ = writeln!(out, "{} {} {}", gutter, bar, underline);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document_complete, Schema};
+
+    #[test]
+    fn test_render_parse_error() {
+        let tests = [
+            (
+                "a=b{",
+                "error: expected '}', found end of input\n \
+                 --> f:1:5\n  |\n1 | a=b{\n  |     ^ expected '}'\n",
+            ),
+            (
+                "a=b\n\tc=é é,\r\nf=g",
+                "error: expected '=', found `,\r`\n \
+                 --> f:2:7\n  |\n2 | \tc=é é,\n  | \t     ^ expected '='\n",
+            ),
+        ];
+        for (source, expected) in tests.iter() {
+            let error = parse_document_complete(source).unwrap_err();
+            let diagnostic = Diagnostic::from(&error);
+            assert_eq!(*expected, Renderer::new().render("f", source, &diagnostic));
+        }
+
+        let source = &"a=b\n".repeat(10);
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            message: "m".to_string(),
+            span: Some(Span { start: 36, end: 37 }),
+            label: None,
+        };
+        assert_eq!(
+            "warning: m\n  --> f:10:1\n   |\n10 | a=b\n   | ^\n",
+            Renderer::new().render("f", source, &diagnostic)
+        );
+    }
+
+    #[test]
+    fn test_render_validation_error() {
+        let schema = Schema::from_document(
+            &parse_document_complete("field=server{type=struct{field=port{type=string}}}").unwrap(),
+        )
+        .unwrap();
+        let source = "server={\n  port=80,\n  81 host=h\n}";
+        let errors = schema.validate(&parse_document_complete(source).unwrap());
+        let output = errors
+            .iter()
+            .map(|e| {
+                Renderer::new().render("f", source, &Diagnostic::from_validation_error(e, source))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "error: server.port: expected a single value, found 2\n \
+                 --> f:2:3\n  |\n2 |   port=80,\n  |   ^^^^^^^^\n",
+                "warning: server.host: unknown field\n \
+                 --> f:3:6\n  |\n3 |   81 host=h\n  |      ^^^^^^\n",
+            ],
+            output
+        );
+
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "m".to_string(),
+            span: None,
+            label: None,
+        };
+        assert_eq!(
+            "error: m\n--> f\n",
+            Renderer::new().render("f", "", &diagnostic)
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let error = parse_document_complete("a").unwrap_err();
+        let output = Renderer::new()
+            .color(true)
+            .render("f", "a", &Diagnostic::from(&error));
+        assert_eq!(
+            "\x1b[1;31merror\x1b[0m\x1b[1m: expected '=', found end of input\x1b[0m\n \
+             \x1b[1;34m-->\x1b[0m f:1:2\n  \x1b[1;34m|\x1b[0m\n\
+             \x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m a\n  \x1b[1;34m|\x1b[0m  \
+             \x1b[1;31m^\x1b[0m \x1b[1;31mexpected '='\x1b[0m\n",
+            output
+        );
+    }
+}
@@ -0,0 +1,693 @@
+//! Structural differences between bindings, and their application as patches.
+
+use crate::prelude::*;
+use crate::{print_binding, print_value, Binding, Document, Path, Segment, Value};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// A single difference between two trees of bindings.
+///
+/// Paths follow [`Document::query`]: the index of a segment counts the values of all bindings
+/// with that name. Indices are omitted where neither side has more than one value.
+///
+/// Values are only compared for names bound exactly once on both sides; other bindings, including
+/// ones without values such as `a=`, are added and removed as a whole.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DiffEntry {
+    /// A value present only in the new tree.
+    Added { path: Path, value: Value },
+    /// A value present only in the old tree.
+    Removed { path: Path, value: Value },
+    /// A value replaced by a different one, including one of a different kind such as a list or
+    /// a reference; values of the same kind that only differ in their children are compared
+    /// recursively instead.
+    Changed { path: Path, old: Value, new: Value },
+    /// A binding present only in the new tree, at `position` among its siblings there.
+    BindingAdded {
+        path: Path,
+        position: usize,
+        binding: Binding,
+    },
+    /// A binding present only in the old tree, at `position` among its siblings there.
+    BindingRemoved {
+        path: Path,
+        position: usize,
+        binding: Binding,
+    },
+}
+
+impl DiffEntry {
+    /// Returns the path of the value, or of the binding, that differs.
+    pub fn path(&self) -> &Path {
+        match self {
+            DiffEntry::Added { path, .. }
+            | DiffEntry::Removed { path, .. }
+            | DiffEntry::Changed { path, .. }
+            | DiffEntry::BindingAdded { path, .. }
+            | DiffEntry::BindingRemoved { path, .. } => path,
+        }
+    }
+}
+
+/// Writes a binding prefixed with the path of its parent, e.g. `a.b=c` for `b=c` at `a.b`.
+fn write_binding(f: &mut fmt::Formatter, path: &Path, binding: &Binding) -> fmt::Result {
+    for segment in path.segments.split_last().map_or(&[][..], |(_, init)| init) {
+        write!(f, "{}.", segment)?;
+    }
+    write!(f, "{}", print_binding(binding))
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffEntry::Added { path, value } => write!(f, "+ {}: {}", path, print_value(value)),
+            DiffEntry::Removed { path, value } => {
+                write!(f, "- {}: {}", path, print_value(value))
+            }
+            DiffEntry::Changed { path, old, new } => write!(
+                f,
+                "~ {}: {} -> {}",
+                path,
+                print_value(old),
+                print_value(new)
+            ),
+            DiffEntry::BindingAdded { path, binding, .. } => {
+                write!(f, "+ ")?;
+                write_binding(f, path, binding)
+            }
+            DiffEntry::BindingRemoved { path, binding, .. } => {
+                write!(f, "- ")?;
+                write_binding(f, path, binding)
+            }
+        }
+    }
+}
+
+fn child(path: &Path, name: &str, index: Option<usize>) -> Path {
+    let mut path = path.clone();
+    path.segments.push(Segment {
+        name: name.to_string(),
+        index,
+    });
+    path
+}
+
+fn values<'a>(bindings: &'a [Binding], name: &str) -> Vec<&'a Value> {
+    bindings
+        .iter()
+        .filter(|b| b.name == name)
+        .flat_map(|b| b.values.iter())
+        .collect()
+}
+
+/// Returns the pairs of indices of a longest common subsequence of `old` and `new`, as matched by
+/// `matches`, in increasing order.
+fn common(
+    old: &[Binding],
+    new: &[Binding],
+    matches: impl Fn(&Binding, &Binding) -> bool,
+) -> Vec<(usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(o, n)| matches(o, n))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| matches(o, n))
+        .count();
+    let (o, n) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    // lengths[i][j] is the length of a longest common subsequence of o[i..] and n[j..].
+    let mut lengths = vec![vec![0; n.len() + 1]; o.len() + 1];
+    for i in (0..o.len()).rev() {
+        for j in (0..n.len()).rev() {
+            lengths[i][j] = if matches(&o[i], &n[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < o.len() && j < n.len() {
+        if matches(&o[i], &n[j]) {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    pairs
+}
+
+/// Pairs up the bindings of `old` and `new` in order. Bindings of names bound exactly once on both
+/// sides pair up even if they differ, and are compared value by value; any other binding only
+/// pairs up with an identical one, and is otherwise removed and added as a whole.
+fn diff_bindings(path: &Path, old: &[Binding], new: &[Binding], entries: &mut Vec<DiffEntry>) {
+    let counts = |bindings: &[Binding]| {
+        let mut counts = BTreeMap::new();
+        for binding in bindings {
+            *counts.entry(binding.name.clone()).or_insert(0) += 1;
+        }
+        counts
+    };
+    let (old_counts, new_counts) = (counts(old), counts(new));
+    let pairs = common(old, new, |o, n| {
+        o.name == n.name && (o == n || (old_counts[&o.name] == 1 && new_counts[&n.name] == 1))
+    });
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in pairs
+        .into_iter()
+        .chain(core::iter::once((old.len(), new.len())))
+    {
+        for (position, binding) in old.iter().enumerate().take(next_i).skip(i) {
+            entries.push(DiffEntry::BindingRemoved {
+                path: child(path, &binding.name, None),
+                position,
+                binding: binding.clone(),
+            });
+        }
+        for (position, binding) in new.iter().enumerate().take(next_j).skip(j) {
+            entries.push(DiffEntry::BindingAdded {
+                path: child(path, &binding.name, None),
+                position,
+                binding: binding.clone(),
+            });
+        }
+        if let (Some(o), Some(n)) = (old.get(next_i), new.get(next_j)) {
+            if o != n {
+                diff_values(path, &o.name, &o.values, &n.values, entries);
+            }
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+}
+
+fn diff_values(
+    path: &Path,
+    name: &str,
+    old: &[Value],
+    new: &[Value],
+    entries: &mut Vec<DiffEntry>,
+) {
+    let indexed = old.len() > 1 || new.len() > 1;
+    for i in 0..old.len().max(new.len()) {
+        let path = child(path, name, if indexed { Some(i) } else { None });
+        match (old.get(i), new.get(i)) {
+            (Some(old), Some(new)) => diff_value(&path, old, new, entries),
+            (Some(old), None) => entries.push(DiffEntry::Removed {
+                path,
+                value: old.clone(),
+            }),
+            (None, Some(new)) => entries.push(DiffEntry::Added {
+                path,
+                value: new.clone(),
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_value(path: &Path, old: &Value, new: &Value, entries: &mut Vec<DiffEntry>) {
+    if old.value != new.value || old.kind != new.kind {
+        entries.push(DiffEntry::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        });
+    } else {
+        diff_bindings(path, &old.children, &new.children, entries);
+    }
+}
+
+/// Returns the differences between two bindings, in document order.
+pub fn diff(old: &Binding, new: &Binding) -> Vec<DiffEntry> {
+    diff_documents(
+        &Document::from_bindings(vec![old.clone()]),
+        &Document::from_bindings(vec![new.clone()]),
+    )
+}
+
+/// Returns the differences between the top-level bindings of two documents, in document order.
+pub fn diff_documents(old: &Document, new: &Document) -> Vec<DiffEntry> {
+    let mut entries = vec![];
+    diff_bindings(
+        &Path { segments: vec![] },
+        &old.bindings,
+        &new.bindings,
+        &mut entries,
+    );
+    entries
+}
+
+/// A patch entry that does not apply to the target.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum PatchError {
+    /// The path of the entry, or of its parent for additions, does not exist.
+    NotFound(Path),
+    /// The value at the path differs from the old value recorded in the entry.
+    Mismatch {
+        path: Path,
+        expected: Box<Value>,
+        found: Box<Value>,
+    },
+    /// The binding at the position of a removal differs from the one recorded in the entry.
+    BindingMismatch {
+        path: Path,
+        expected: Box<Binding>,
+        found: Box<Binding>,
+    },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::NotFound(path) => write!(f, "path `{}` not found", path),
+            PatchError::Mismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected `{}` at `{}`, found `{}`",
+                print_value(expected),
+                path,
+                print_value(found)
+            ),
+            PatchError::BindingMismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected `{}` at `{}`, found `{}`",
+                print_binding(expected),
+                path,
+                print_binding(found)
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// Returns the position of the `index`-th value among the bindings named `name`, as a binding
+/// index and a value index within that binding.
+fn position(bindings: &[Binding], name: &str, index: usize) -> Option<(usize, usize)> {
+    let mut remaining = index;
+    for (i, binding) in bindings.iter().enumerate() {
+        if binding.name != name {
+            continue;
+        }
+        if remaining < binding.values.len() {
+            return Some((i, remaining));
+        }
+        remaining -= binding.values.len();
+    }
+    None
+}
+
+fn children_mut<'a>(
+    mut bindings: &'a mut Vec<Binding>,
+    segments: &[Segment],
+) -> Option<&'a mut Vec<Binding>> {
+    for segment in segments {
+        let (i, j) = position(bindings, &segment.name, segment.index.unwrap_or(0))?;
+        bindings = &mut bindings[i].values[j].children;
+    }
+    Some(bindings)
+}
+
+fn check(path: &Path, expected: &Value, found: &Value) -> Result<(), PatchError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(PatchError::Mismatch {
+            path: path.clone(),
+            expected: Box::new(expected.clone()),
+            found: Box::new(found.clone()),
+        })
+    }
+}
+
+fn apply(bindings: &mut Vec<Binding>, entry: &DiffEntry) -> Result<(), PatchError> {
+    let path = entry.path();
+    let not_found = || PatchError::NotFound(path.clone());
+    let (last, init) = path.segments.split_last().ok_or_else(not_found)?;
+    let parent = children_mut(bindings, init).ok_or_else(not_found)?;
+    let index = last.index.unwrap_or(0);
+    match entry {
+        DiffEntry::Added { value, .. } => match position(parent, &last.name, index) {
+            Some((i, j)) => parent[i].values.insert(j, value.clone()),
+            None if index == values(parent, &last.name).len() => {
+                match parent.iter().rposition(|b| b.name == last.name) {
+                    Some(i) => parent[i].values.push(value.clone()),
+                    None => parent.push(Binding {
+                        name: last.name.clone(),
+                        values: smallvec![value.clone()],
+                    }),
+                }
+            }
+            None => return Err(not_found()),
+        },
+        DiffEntry::Removed { value, .. } => {
+            let (i, j) = position(parent, &last.name, index).ok_or_else(not_found)?;
+            check(path, value, &parent[i].values[j])?;
+            parent[i].values.remove(j);
+        }
+        DiffEntry::Changed { old, new, .. } => {
+            let (i, j) = position(parent, &last.name, index).ok_or_else(not_found)?;
+            check(path, old, &parent[i].values[j])?;
+            parent[i].values[j] = new.clone();
+        }
+        DiffEntry::BindingAdded {
+            position, binding, ..
+        } => {
+            if *position > parent.len() {
+                return Err(not_found());
+            }
+            parent.insert(*position, binding.clone());
+        }
+        DiffEntry::BindingRemoved {
+            position, binding, ..
+        } => {
+            let found = parent.get(*position).ok_or_else(not_found)?;
+            if found != binding {
+                return Err(PatchError::BindingMismatch {
+                    path: path.clone(),
+                    expected: Box::new(binding.clone()),
+                    found: Box::new(found.clone()),
+                });
+            }
+            parent.remove(*position);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the order in which an entry is applied: see [`apply_patch`].
+fn stage(entry: &DiffEntry) -> usize {
+    match entry {
+        DiffEntry::Added { .. } | DiffEntry::Changed { .. } => 0,
+        DiffEntry::Removed { .. } => 1,
+        DiffEntry::BindingRemoved { .. } => 2,
+        DiffEntry::BindingAdded { .. } => 3,
+    }
+}
+
+fn apply_all(bindings: &mut Vec<Binding>, patch: &[DiffEntry]) -> Result<(), PatchError> {
+    let entries = |s| patch.iter().filter(move |e| stage(e) == s);
+    for entry in entries(0)
+        .chain(entries(1).rev())
+        .chain(entries(2).rev())
+        .chain(entries(3))
+    {
+        apply(bindings, entry)?;
+    }
+    Ok(())
+}
+
+/// Applies a patch produced by [`diff`] to `binding`, leaving it unchanged on error.
+///
+/// Values are added and changed first, then removed in reverse order, so that the indices recorded
+/// by `diff` stay valid while the patch is applied. Whole bindings are then removed in reverse
+/// order, and finally added in order, at the positions recorded for them.
+pub fn apply_patch(binding: &mut Binding, patch: &[DiffEntry]) -> Result<(), PatchError> {
+    let mut bindings = vec![binding.clone()];
+    apply_all(&mut bindings, patch)?;
+    match bindings.as_slice() {
+        [patched] => {
+            *binding = patched.clone();
+            Ok(())
+        }
+        _ => Err(PatchError::NotFound(Path {
+            segments: vec![Segment {
+                name: binding.name.clone(),
+                index: None,
+            }],
+        })),
+    }
+}
+
+/// Applies a patch produced by [`diff_documents`] to `document`, leaving it unchanged on error.
+pub fn apply_patch_document(
+    document: &mut Document,
+    patch: &[DiffEntry],
+) -> Result<(), PatchError> {
+    let mut bindings = document.bindings.clone();
+    apply_all(&mut bindings, patch)?;
+    *document.bindings_mut() = bindings;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    #[test]
+    fn test_diff() {
+        struct Test {
+            old: String,
+            new: String,
+            diff: Vec<String>,
+        }
+
+        let tests = [
+            Test {
+                old: "a=b{c=d e=f}".to_string(),
+                new: "a=b{c=d e=f}".to_string(),
+                diff: vec![],
+            },
+            Test {
+                old: "a=b{c=d e=f}".to_string(),
+                new: "a=b{c=x g=h}".to_string(),
+                diff: vec![
+                    "~ a.c: d -> x".to_string(),
+                    "- a.e=f".to_string(),
+                    "+ a.g=h".to_string(),
+                ],
+            },
+            Test {
+                old: "a=b{p=1,2}".to_string(),
+                new: "a=b{p=1,3,4}".to_string(),
+                diff: vec!["~ a.p[1]: 2 -> 3".to_string(), "+ a.p[2]: 4".to_string()],
+            },
+            Test {
+                old: "a=tcp{port=1}".to_string(),
+                new: "a=udp{port=1}".to_string(),
+                diff: vec!["~ a: tcp{port=1} -> udp{port=1}".to_string()],
+            },
+            Test {
+                old: "a=b{p=1}".to_string(),
+                new: "a=b{p=}".to_string(),
+                diff: vec!["- a.p: 1".to_string()],
+            },
+            Test {
+                old: "a=b{c= d=e}".to_string(),
+                new: "a=b{d=e f=}".to_string(),
+                diff: vec!["- a.c=".to_string(), "+ a.f=".to_string()],
+            },
+            Test {
+                old: "a=[x,[y]]".to_string(),
+                new: "a=[x,[z]]".to_string(),
+                diff: vec!["~ a[1]: [y] -> [z]".to_string()],
+            },
+            Test {
+                old: "a={b=c}".to_string(),
+                new: "a={b=d}".to_string(),
+                diff: vec!["~ a.b: c -> d".to_string()],
+            },
+            Test {
+                old: "a={}".to_string(),
+                new: "a=\"\"".to_string(),
+                diff: vec!["~ a: {} -> \"\"".to_string()],
+            },
+            Test {
+                old: "a=&x v{b=c}".to_string(),
+                new: "a=&y v{b=c}".to_string(),
+                diff: vec!["~ a: &x v{b=c} -> &y v{b=c}".to_string()],
+            },
+            Test {
+                old: "a=*x".to_string(),
+                new: "a=x".to_string(),
+                diff: vec!["~ a: *x -> x".to_string()],
+            },
+        ];
+
+        for t in tests.iter() {
+            let old = parse_binding_complete(&t.old).unwrap();
+            let new = parse_binding_complete(&t.new).unwrap();
+            let diff = diff(&old, &new)
+                .iter()
+                .map(DiffEntry::to_string)
+                .collect::<Vec<_>>();
+            assert_eq!(t.diff, diff);
+        }
+
+        let old = parse_document_complete("a=b c=d").unwrap();
+        let new = parse_document_complete("c=d e=f").unwrap();
+        assert_eq!(
+            vec![
+                DiffEntry::BindingRemoved {
+                    path: "a".parse().unwrap(),
+                    position: 0,
+                    binding: parse_binding_complete("a=b").unwrap(),
+                },
+                DiffEntry::BindingAdded {
+                    path: "e".parse().unwrap(),
+                    position: 1,
+                    binding: parse_binding_complete("e=f").unwrap(),
+                },
+            ],
+            diff_documents(&old, &new)
+        );
+
+        let diff_documents = |old, new| {
+            diff_documents(
+                &parse_document_complete(old).unwrap(),
+                &parse_document_complete(new).unwrap(),
+            )
+            .iter()
+            .map(DiffEntry::to_string)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["- a=", "+ b=1"], diff_documents("a=", "b=1"));
+        assert_eq!(vec!["+ à="], diff_documents("", "à="));
+        assert_eq!(vec!["- α="], diff_documents("α=", ""));
+        assert_eq!(
+            vec!["- a=1,2", "- a=3", "+ a=1", "+ a=3,4"],
+            diff_documents("a=1,2 a=3", "a=1 a=3,4")
+        );
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let tests = [
+            ("a=b{c=d e=f}", "a=b{c=x g=h}"),
+            ("a=b{p=1,2,3,4 q=r}", "a=b{p=1,5}"),
+            ("a=b{p=1}", "a=b{p=1,2,3}"),
+            ("a=tcp{port=1}", "a=udp{port=1}"),
+            ("a=b{c=d}", "x=y"),
+            ("a=b{p=1}", "a=b{p=}"),
+            ("a=b{c= d=e}", "a=b{d=e f=}"),
+            ("a=b{c=1 c=2 d=3}", "a=b{d=3 c=2 c=1,4}"),
+            ("a=[[x]]", "a=[[y]]"),
+            ("a=[x,[y]]", "a=[x,[z]]"),
+            ("a={}", "a=\"\""),
+            ("a=&x v", "a=v"),
+            ("a=*x", "a=x"),
+            ("a={b=c}", "a={b=d e=}"),
+            ("a=*x,&x v", "a=&x v,*x"),
+        ];
+
+        for (old, new) in tests.iter() {
+            let mut binding = parse_binding_complete(old).unwrap();
+            let new = parse_binding_complete(new).unwrap();
+            let patch = diff(&binding, &new);
+            assert_eq!(Ok(()), apply_patch(&mut binding, &patch), "{}", old);
+            assert_eq!(new, binding);
+        }
+
+        let mut binding = parse_binding_complete("a=b{c=z}").unwrap();
+        let patch = diff(
+            &parse_binding_complete("a=b{c=d}").unwrap(),
+            &parse_binding_complete("a=b{c=e}").unwrap(),
+        );
+        let error = apply_patch(&mut binding, &patch).unwrap_err();
+        assert_eq!("expected `d` at `a.c`, found `z`", error.to_string());
+        assert_eq!(parse_binding_complete("a=b{c=z}").unwrap(), binding);
+
+        let documents = [
+            ("a=", "b=1"),
+            ("", "à="),
+            ("α=", ""),
+            ("a=1,2 a=3", "a=1 a=3,4"),
+            ("a=1 b=2", "b=2 a=1"),
+            ("a=[x,[y]] b=", "a=[[y],x] b=[]"),
+        ];
+        for (old, new) in documents.iter() {
+            let mut document = parse_document_complete(old).unwrap();
+            let new = parse_document_complete(new).unwrap();
+            let patch = diff_documents(&document, &new);
+            assert_eq!(
+                Ok(()),
+                apply_patch_document(&mut document, &patch),
+                "{}",
+                old
+            );
+            assert_eq!(new, document);
+        }
+
+        let mut document = parse_document_complete("a=1 b=2").unwrap();
+        let patch = diff_documents(
+            &parse_document_complete("a=1 b=3").unwrap(),
+            &parse_document_complete("a=1").unwrap(),
+        );
+        assert_eq!(
+            "expected `b=3` at `b`, found `b=2`",
+            apply_patch_document(&mut document, &patch)
+                .unwrap_err()
+                .to_string()
+        );
+
+        let mut document = parse_document_complete("a=b").unwrap();
+        let patch = [DiffEntry::Added {
+            path: "x.y".parse().unwrap(),
+            value: Value::from("z"),
+        }];
+        assert_eq!(
+            Err(PatchError::NotFound("x.y".parse().unwrap())),
+            apply_patch_document(&mut document, &patch)
+        );
+    }
+
+    /// Gives `new` the names of the bindings of `old` and, where their kinds match, the scalars of
+    /// its values, so that diffing them compares values and children rather than whole bindings.
+    #[cfg(feature = "arbitrary")]
+    fn resemble(new: &mut [Binding], old: &[Binding]) {
+        for (new, old) in new.iter_mut().zip(old) {
+            new.name = old.name.clone();
+            for (new, old) in new.values.iter_mut().zip(&old.values) {
+                if new.kind == old.kind {
+                    new.value = old.value.clone();
+                }
+                resemble(&mut new.children, &old.children);
+            }
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_patch_round_trip(old in any::<Document>(), new in any::<Document>()) {
+            let mut patched = old.clone();
+            prop_assert_eq!(Ok(()), apply_patch_document(&mut patched, &diff_documents(&old, &new)));
+            prop_assert_eq!(new, patched);
+        }
+
+        #[test]
+        fn test_similar_patch_round_trip(old in any::<Document>(), new in any::<Document>()) {
+            let mut bindings = new.bindings().to_vec();
+            resemble(&mut bindings, old.bindings());
+            let new = Document::from_bindings(bindings);
+            let mut patched = old.clone();
+            prop_assert_eq!(Ok(()), apply_patch_document(&mut patched, &diff_documents(&old, &new)));
+            prop_assert_eq!(new, patched);
+        }
+    }
+}
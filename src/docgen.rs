@@ -0,0 +1,443 @@
+//! Generation of reference documentation for the fields of a schema, as Markdown or HTML, e.g.:
+//!
+//! ```
+//! use flax::{cst, docgen, Schema};
+//!
+//! let schema = "## The address to listen on.\nfield=port{type=string default=80}";
+//! let schema = Schema::from_cst(&cst::parse_document(schema).unwrap()).unwrap();
+//! assert_eq!(
+//!     "# Server\n\n## `port`\n\nThe address to listen on.\n\n- Type: `string`\n- Default: `80`\n",
+//!     docgen::markdown(&schema, "Server")
+//! );
+//! ```
+//!
+//! Every field gets a section, headed by its dotted path, in the order of the schema with nested
+//! fields after their parent. Fields of enum variants follow the enum field, and apply only when
+//! it has that variant. Fields of recursive types refer to the section of the enclosing field of
+//! the same type, instead of repeating its fields. Backticks in documentation and in the generated text delimit code, in
+//! HTML as much as in Markdown.
+
+use crate::prelude::*;
+use crate::{print_value, Deprecation, Field, Schema};
+use core::fmt::Write;
+
+/// The documentation of one field.
+struct Section<'a> {
+    path: String,
+    doc: Option<&'a str>,
+    /// Facts about the field, such as its type, in Markdown.
+    facts: Vec<String>,
+    /// The variants of an enum field, with their documentation, in Markdown.
+    variants: Vec<String>,
+}
+
+fn deprecated(deprecation: &Deprecation) -> String {
+    let mut text = "deprecated".to_string();
+    if let Some(replacement) = &deprecation.replacement {
+        write!(text, ", use `{}` instead", replacement).unwrap();
+    }
+    if let Some(message) = &deprecation.message {
+        write!(text, ": {}", message).unwrap();
+    }
+    text
+}
+
+fn type_text(schema: &Schema) -> String {
+    match schema {
+        Schema::String => "`string`".to_string(),
+        Schema::Bool => "`bool`".to_string(),
+        Schema::Struct { .. } => "`struct`".to_string(),
+        Schema::Enum { variants } => {
+            let names = variants
+                .iter()
+                .map(|v| format!("`{}`", v.name()))
+                .collect::<Vec<_>>();
+            format!("`enum`, one of {}", names.join(", "))
+        }
+        Schema::Recursive { schema, .. } => type_text(schema),
+        Schema::Ref(name) => format!("`{}`", name),
+    }
+}
+
+/// Describes `field`, whose values have the schema `schema`.
+fn describe<'a>(
+    field: &'a Field,
+    schema: &Schema,
+    path: String,
+    condition: Option<&str>,
+) -> Section<'a> {
+    let schema = match schema {
+        Schema::Recursive { schema, .. } => schema,
+        schema => schema,
+    };
+    let mut ty = type_text(schema);
+    if field.is_repeated() {
+        ty.push_str(", repeated");
+        match (field.min, field.max) {
+            (Some(min), Some(max)) if min == max => write!(ty, ", exactly {} values", min),
+            (Some(min), Some(max)) => write!(ty, ", {} to {} values", min, max),
+            (Some(min), None) => write!(ty, ", at least {} values", min),
+            (None, Some(max)) => write!(ty, ", at most {} values", max),
+            (None, None) => Ok(()),
+        }
+        .unwrap();
+    }
+    let mut facts = vec![format!("Type: {}", ty)];
+    facts.push(match &field.default {
+        Some(values) => {
+            let values = values
+                .iter()
+                .map(|v| format!("`{}`", print_value(v)))
+                .collect::<Vec<_>>();
+            format!("Default: {}", values.join(", "))
+        }
+        None if field.is_optional() => "Optional".to_string(),
+        None => "Required".to_string(),
+    });
+    if let Some(condition) = condition {
+        facts.push(format!("Only with `{}`", condition));
+    }
+    if field.is_secret() {
+        facts.push("Secret".to_string());
+    }
+    if let Some(deprecation) = field.deprecation() {
+        let mut text = deprecated(deprecation);
+        text[..1].make_ascii_uppercase();
+        facts.push(text);
+    }
+
+    let mut variants = vec![];
+    if let Schema::Enum { variants: all } = schema {
+        for variant in all.iter() {
+            let mut text = format!("`{}`", variant.name());
+            if let Some(deprecation) = variant.deprecation() {
+                write!(text, " ({})", deprecated(deprecation)).unwrap();
+            }
+            if let Some(doc) = variant.doc() {
+                write!(text, ": {}", doc).unwrap();
+            }
+            variants.push(text);
+        }
+    }
+
+    Section {
+        path,
+        doc: field.doc(),
+        facts,
+        variants,
+    }
+}
+
+/// The recursive types enclosing a field, innermost last, with the paths of the fields that have
+/// them and their schemas.
+type Recursive<'a> = Vec<(&'a str, String, &'a Schema)>;
+
+/// Appends the sections of `fields` and their descendants, nested in the field at `prefix`, which
+/// apply only with `condition`, if any.
+fn sections<'a>(
+    fields: &'a [Field],
+    prefix: &str,
+    condition: Option<&str>,
+    recursive: &mut Recursive<'a>,
+    out: &mut Vec<Section<'a>>,
+) {
+    for field in fields {
+        let path = if prefix.is_empty() {
+            field.name().to_string()
+        } else {
+            format!("{}.{}", prefix, field.name())
+        };
+        if let Schema::Ref(name) = field.schema() {
+            if let Some((_, target, schema)) = recursive.iter().rev().find(|(n, ..)| n == name) {
+                let mut section = describe(field, schema, path, condition);
+                section.facts.push(format!("Same as `{}`", target));
+                out.push(section);
+            }
+            continue;
+        }
+        out.push(describe(field, field.schema(), path.clone(), condition));
+        nested(field.schema(), &path, condition, recursive, out);
+    }
+}
+
+/// Appends the sections of the fields within values of `schema`, nested in the field at `prefix`.
+fn nested<'a>(
+    schema: &'a Schema,
+    prefix: &str,
+    condition: Option<&str>,
+    recursive: &mut Recursive<'a>,
+    out: &mut Vec<Section<'a>>,
+) {
+    match schema {
+        Schema::Struct { fields } => sections(fields, prefix, condition, recursive, out),
+        Schema::Enum { variants } => {
+            for variant in variants.iter() {
+                let condition = format!("{}={}", prefix, variant.name());
+                nested(variant.schema(), prefix, Some(&condition), recursive, out);
+            }
+        }
+        Schema::Recursive { name, schema } => {
+            recursive.push((name, prefix.to_string(), schema));
+            nested(schema, prefix, condition, recursive, out);
+            recursive.pop();
+        }
+        Schema::String | Schema::Bool | Schema::Ref(_) => {}
+    }
+}
+
+fn fields(schema: &Schema) -> Vec<Section<'_>> {
+    let mut out = vec![];
+    if let Schema::Struct { fields } = schema {
+        sections(fields, "", None, &mut vec![], &mut out);
+    }
+    out
+}
+
+/// Returns the documentation of the fields of `schema`, a struct, as a Markdown document titled
+/// `title`.
+pub fn markdown(schema: &Schema, title: &str) -> String {
+    let mut out = format!("# {}\n", title);
+    for section in fields(schema) {
+        write!(out, "\n## `{}`\n\n", section.path).unwrap();
+        if let Some(doc) = section.doc {
+            write!(out, "{}\n\n", doc).unwrap();
+        }
+        for fact in section.facts.iter() {
+            writeln!(out, "- {}", fact).unwrap();
+        }
+        if !section.variants.is_empty() {
+            out.push_str("\nVariants:\n\n");
+            for variant in section.variants.iter() {
+                writeln!(out, "- {}", variant.replace('\n', "\n  ")).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// Escapes `text` for HTML, turning code delimited by backticks into `code` elements.
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in text.split('`').enumerate() {
+        if i % 2 == 1 {
+            out.push_str("<code>");
+        }
+        for c in part.chars() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                c => out.push(c),
+            }
+        }
+        if i % 2 == 1 {
+            out.push_str("</code>");
+        }
+    }
+    out
+}
+
+/// Returns the documentation of the fields of `schema`, a struct, as an HTML page titled `title`.
+/// Each section has the path of its field as its id, so that it can be linked to. Blank lines
+/// separate the paragraphs of documentation.
+pub fn html(schema: &Schema, title: &str) -> String {
+    let title = inline_html(title);
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>").unwrap();
+    writeln!(out, "<meta charset=\"utf-8\">\n<title>{}</title>", title).unwrap();
+    writeln!(out, "</head>\n<body>\n<h1>{}</h1>", title).unwrap();
+    for section in fields(schema) {
+        let path = inline_html(&section.path);
+        writeln!(out, "<h2 id=\"{}\"><code>{}</code></h2>", path, path).unwrap();
+        if let Some(doc) = section.doc {
+            for paragraph in doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                writeln!(out, "<p>{}</p>", inline_html(paragraph.trim())).unwrap();
+            }
+        }
+        out.push_str("<ul>\n");
+        for fact in section.facts.iter() {
+            writeln!(out, "<li>{}</li>", inline_html(fact)).unwrap();
+        }
+        out.push_str("</ul>\n");
+        if !section.variants.is_empty() {
+            out.push_str("<p>Variants:</p>\n<ul>\n");
+            for variant in section.variants.iter() {
+                writeln!(out, "<li>{}</li>", inline_html(variant)).unwrap();
+            }
+            out.push_str("</ul>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst;
+
+    const SCHEMA: &str = "
+        # Not documentation.
+
+        ## The name of the service,
+        ## as shown in `status` pages.
+        field=name{type=string}
+        ## Listening sockets.
+        field=listen{doc=\"Ignored\" repeated=true min=1 max=4 type=struct{
+          ## The port, <1024 needs root.
+          field=port{type=string default=80}
+          field=key{type=string optional=true secret=true}
+        }} ## Not documentation either.
+        field=mode{type=enum{
+          ## Reliable.
+          variant=tcp{type=struct{field=nodelay{type=bool default=false}}}
+          variant=udp{deprecated={replacement=tcp}}
+        }}
+        field=old{type=string optional=true doc=\"Unused.\" deprecated={message=\"gone in v2\"}}
+    ";
+
+    fn schema() -> Schema {
+        Schema::from_cst(&cst::parse_document(SCHEMA).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let schema = schema();
+        let fields = match &schema {
+            Schema::Struct { fields } => fields,
+            _ => panic!("expected a struct"),
+        };
+        let docs = fields.iter().map(Field::doc).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Some("The name of the service,\nas shown in `status` pages."),
+                Some("Ignored"),
+                None,
+                Some("Unused."),
+            ],
+            docs
+        );
+        match (fields[1].schema(), fields[2].schema()) {
+            (Schema::Struct { fields }, Schema::Enum { variants }) => {
+                assert_eq!(Some("The port, <1024 needs root."), fields[0].doc());
+                assert_eq!(None, fields[1].doc());
+                assert_eq!(Some("Reliable."), variants[0].doc());
+                assert_eq!(None, variants[1].doc());
+            }
+            _ => panic!("expected a struct and an enum"),
+        }
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let expected = "# Config
+
+## `name`
+
+The name of the service,
+as shown in `status` pages.
+
+- Type: `string`
+- Required
+
+## `listen`
+
+Ignored
+
+- Type: `struct`, repeated, 1 to 4 values
+- Required
+
+## `listen.port`
+
+The port, <1024 needs root.
+
+- Type: `string`
+- Default: `80`
+
+## `listen.key`
+
+- Type: `string`
+- Optional
+- Secret
+
+## `mode`
+
+- Type: `enum`, one of `tcp`, `udp`
+- Required
+
+Variants:
+
+- `tcp`: Reliable.
+- `udp` (deprecated, use `tcp` instead)
+
+## `mode.nodelay`
+
+- Type: `bool`
+- Default: `false`
+- Only with `mode=tcp`
+
+## `old`
+
+Unused.
+
+- Type: `string`
+- Optional
+- Deprecated: gone in v2
+";
+        assert_eq!(expected, markdown(&schema(), "Config"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = html(&schema(), "Config & co");
+        assert!(html.starts_with(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Config &amp; co</title>\n</head>\n<body>\n<h1>Config &amp; co</h1>\n\
+             <h2 id=\"name\"><code>name</code></h2>\n\
+             <p>The name of the service,\nas shown in <code>status</code> pages.</p>\n\
+             <ul>\n<li>Type: <code>string</code></li>\n<li>Required</li>\n</ul>\n"
+        ));
+        assert!(html.contains(
+            "<h2 id=\"listen.port\"><code>listen.port</code></h2>\n\
+             <p>The port, &lt;1024 needs root.</p>\n"
+        ));
+        assert!(html.contains(
+            "<p>Variants:</p>\n<ul>\n<li><code>tcp</code>: Reliable.</li>\n\
+             <li><code>udp</code> (deprecated, use <code>tcp</code> instead)</li>\n</ul>\n"
+        ));
+        assert!(html.ends_with("</ul>\n</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn test_recursive() {
+        let schema = "
+            struct=Tree{
+              ## The children of the node.
+              field=children{type=Tree repeated=true optional=true}
+            }
+            field=root{type=Tree}
+        ";
+        let schema = Schema::from_cst(&cst::parse_document(schema).unwrap()).unwrap();
+        let expected = "# Tree
+
+## `root`
+
+- Type: `struct`
+- Required
+
+## `root.children`
+
+The children of the node.
+
+- Type: `struct`, repeated
+- Optional
+- Same as `root`
+";
+        assert_eq!(expected, markdown(&schema, "Tree"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Handling of sibling bindings that share a name, e.g. `a=b a=c`.
+//!
+//! The grammar allows them, and most of the API treats them as a single binding whose values are
+//! concatenated. A `DuplicatePolicy` rejects or collapses them instead.
+
+use crate::error::offset_in;
+use crate::prelude::*;
+use crate::{borrowed, line_column, Address, Binding, Document, MergeStrategy, ParseError, Value};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// What to do with sibling bindings that share a name.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum DuplicatePolicy {
+    /// Report the first duplicate as an error.
+    Error,
+    /// Keep the first binding with each name, in place.
+    FirstWins,
+    /// Keep the last binding with each name, in place.
+    LastWins,
+    /// Merge each binding into the first one with the same name, as `Binding::merge` does with
+    /// `MergeStrategy::Append`.
+    Merge,
+    /// Merge each binding into the first one with the same name, as layered overrides do: the
+    /// later scalar wins and children are merged by name, recursively. Lists and bindings with
+    /// several values are replaced by the later ones.
+    DeepMerge,
+}
+
+/// Two sibling bindings share a name.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DuplicateError {
+    pub name: String,
+    pub first: Address,
+    pub second: Address,
+}
+
+impl fmt::Display for DuplicateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "duplicate binding `{}`", self.name)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DuplicateError {}
+
+fn check(bindings: &[Binding], address: &Address) -> Result<(), DuplicateError> {
+    let mut seen = BTreeMap::new();
+    for (i, binding) in bindings.iter().enumerate() {
+        if let Some(first) = seen.insert(binding.name.as_str(), i) {
+            return Err(DuplicateError {
+                name: binding.name.clone(),
+                first: address.child(first),
+                second: address.child(i),
+            });
+        }
+    }
+    for (i, binding) in bindings.iter().enumerate() {
+        for (j, value) in binding.values.iter().enumerate() {
+            for value in value.scalars() {
+                check(&value.children, &address.child(i).child(j))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Overlays `later` on `first`, leaving the children of a single value concatenated, for
+/// `collapse` to merge by name.
+fn overlay(first: &mut Binding, later: Binding) {
+    let mut values = later.values;
+    if let ([value], [overlay]) = (first.values.as_mut_slice(), values.as_mut_slice()) {
+        if value.items().is_none() && overlay.items().is_none() {
+            value.value = core::mem::take(&mut overlay.value);
+            value.kind = overlay.kind.clone();
+            value.children.append(&mut overlay.children);
+            return;
+        }
+    }
+    first.values = values;
+}
+
+/// Applies a policy other than `DuplicatePolicy::Error`, which leaves `bindings` unchanged.
+pub(crate) fn collapse(bindings: &mut Vec<Binding>, policy: DuplicatePolicy) {
+    match policy {
+        DuplicatePolicy::Error => {}
+        DuplicatePolicy::FirstWins => {
+            let mut seen = Vec::new();
+            bindings.retain(|b| {
+                let first = !seen.contains(&b.name);
+                seen.push(b.name.clone());
+                first
+            });
+        }
+        DuplicatePolicy::LastWins => {
+            bindings.reverse();
+            collapse(bindings, DuplicatePolicy::FirstWins);
+            bindings.reverse();
+        }
+        DuplicatePolicy::Merge | DuplicatePolicy::DeepMerge => {
+            let mut merged: Vec<Binding> = Vec::with_capacity(bindings.len());
+            for binding in bindings.drain(..) {
+                match merged.iter_mut().find(|b| b.name == binding.name) {
+                    Some(first) if policy == DuplicatePolicy::Merge => {
+                        *first = first.merge(&binding, MergeStrategy::Append)
+                    }
+                    Some(first) => overlay(first, binding),
+                    None => merged.push(binding),
+                }
+            }
+            *bindings = merged;
+        }
+    }
+    for binding in bindings.iter_mut() {
+        for value in binding.values.iter_mut().flat_map(Value::scalars_mut) {
+            collapse(&mut value.children, policy);
+        }
+    }
+}
+
+/// A name bound by two sibling bindings freshly parsed, with the offsets of both.
+pub(crate) struct Parsed<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) first: usize,
+    pub(crate) second: usize,
+}
+
+impl Parsed<'_> {
+    /// Describes the duplicate, located at the second binding and naming the location of the
+    /// first.
+    pub(crate) fn error(&self, input: &str) -> ParseError {
+        let (line, column) = line_column(input, self.first);
+        ParseError::new(
+            input,
+            self.second,
+            &format!(
+                "a unique name, `{}` is already bound at {}:{}",
+                self.name, line, column
+            ),
+        )
+    }
+}
+
+/// Returns every duplicate in bindings freshly parsed from `input`, with each binding after the
+/// first with its name.
+pub(crate) fn find_parsed<'a>(input: &str, bindings: &'a [borrowed::Binding]) -> Vec<Parsed<'a>> {
+    let mut duplicates = vec![];
+    find_parsed_at(input, bindings, 0, &mut duplicates);
+    duplicates
+}
+
+/// Like `find_parsed`, falling back to `parent` for names unescaped from a quoted string, which
+/// cannot be located in `input`.
+fn find_parsed_at<'a>(
+    input: &str,
+    bindings: &'a [borrowed::Binding],
+    parent: usize,
+    duplicates: &mut Vec<Parsed<'a>>,
+) {
+    let offset = |name: &str| offset_in(input, name).unwrap_or(parent);
+    let mut seen = BTreeMap::new();
+    for binding in bindings {
+        let second = offset(&binding.name);
+        match seen.get(&*binding.name) {
+            Some(first) => duplicates.push(Parsed {
+                name: &binding.name,
+                first: *first,
+                second,
+            }),
+            None => {
+                seen.insert(&*binding.name, second);
+            }
+        }
+    }
+    for binding in bindings {
+        for value in binding.values.iter().flat_map(borrowed::Value::scalars) {
+            let at = offset_in(input, &value.value).unwrap_or_else(|| offset(&binding.name));
+            find_parsed_at(input, &value.children, at, duplicates);
+        }
+    }
+}
+
+impl Binding {
+    /// Applies `policy` to the children of each value, recursively. Addresses in errors treat the
+    /// binding as the only one in a document.
+    pub fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), DuplicateError> {
+        if policy == DuplicatePolicy::Error {
+            return check(core::slice::from_ref(self), &Address::default());
+        }
+        for value in self.values.iter_mut().flat_map(Value::scalars_mut) {
+            collapse(&mut value.children, policy);
+        }
+        Ok(())
+    }
+
+    /// Merges the sibling bindings that share a name in the children of each value, recursively,
+    /// as `DuplicatePolicy::DeepMerge` does.
+    pub fn deep_merge(&mut self) {
+        for value in self.values.iter_mut().flat_map(Value::scalars_mut) {
+            collapse(&mut value.children, DuplicatePolicy::DeepMerge);
+        }
+    }
+}
+
+impl Document {
+    /// Applies `policy` to the top-level bindings and, recursively, to the children of each
+    /// value. Only `DuplicatePolicy::Error` can fail, in which case the document is unchanged.
+    pub fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), DuplicateError> {
+        if policy == DuplicatePolicy::Error {
+            return check(&self.bindings, &Address::default());
+        }
+        collapse(self.bindings_mut(), policy);
+        Ok(())
+    }
+
+    /// Merges the sibling bindings that share a name, at the top level and recursively, as
+    /// `DuplicatePolicy::DeepMerge` does, e.g. `server={port=80 tls={cert=a}}` followed by
+    /// `server={tls={key=b}}` becomes `server={port=80 tls={cert=a key=b}}`.
+    pub fn deep_merge(&mut self) {
+        collapse(self.bindings_mut(), DuplicatePolicy::DeepMerge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document_complete, parse_document_with, ParseOptions};
+
+    #[test]
+    fn test_resolve_duplicates() {
+        let input = "a=1 b=x{c=2 d=3 c=4} a=5{e=6} b=x{d=7,8 f=9}";
+        let tests = [
+            (DuplicatePolicy::FirstWins, "a=1\nb=x{c=2 d=3}".to_string()),
+            (
+                DuplicatePolicy::LastWins,
+                "a=5{e=6}\nb=x{d=7,8 f=9}".to_string(),
+            ),
+            (
+                DuplicatePolicy::Merge,
+                "a=5{e=6}\nb=x{c=4 d=3,7,8 f=9}".to_string(),
+            ),
+            (
+                DuplicatePolicy::DeepMerge,
+                "a=5{e=6}\nb=x{c=4 d=7,8 f=9}".to_string(),
+            ),
+        ];
+
+        for (policy, resolved) in tests.iter() {
+            let mut document = parse_document_complete(input).unwrap();
+            document.resolve_duplicates(*policy).unwrap();
+            assert_eq!(*resolved, document.to_string(), "{:?}", policy);
+        }
+
+        let mut document = parse_document_complete(input).unwrap();
+        assert_eq!(
+            Err(DuplicateError {
+                name: "a".to_string(),
+                first: Address(vec![0]),
+                second: Address(vec![2]),
+            }),
+            document.resolve_duplicates(DuplicatePolicy::Error)
+        );
+        let mut binding: Binding = "b=x{c=2 d=3},y{e=4 e=5}".parse().unwrap();
+        assert_eq!(
+            Err(DuplicateError {
+                name: "e".to_string(),
+                first: Address(vec![0, 1, 0]),
+                second: Address(vec![0, 1, 1]),
+            }),
+            binding.resolve_duplicates(DuplicatePolicy::Error)
+        );
+        binding
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+        assert_eq!("b=x{c=2 d=3},y{e=5}", binding.to_string());
+    }
+
+    #[test]
+    fn test_deep_merge() {
+        let mut document = parse_document_complete(
+            "server={port=80 tls={cert=a key=b}} log=info{file=x} tags=a,b \
+             server={tls={key=c} port=8080} log=debug tags=c server={tls={ca=d}}",
+        )
+        .unwrap();
+        document.deep_merge();
+        assert_eq!(
+            "server={port=8080 tls={cert=a key=c ca=d}}\nlog=debug{file=x}\ntags=c",
+            document.to_string()
+        );
+
+        let mut binding: Binding = "a=x{b={c=1} b={c=2 d=3}},y{e=[f] e=g}".parse().unwrap();
+        binding.deep_merge();
+        assert_eq!("a=x{b={c=2 d=3}},y{e=g}", binding.to_string());
+    }
+
+    #[test]
+    fn test_parse_duplicates() {
+        let options = ParseOptions::new().duplicates(DuplicatePolicy::Error);
+        let error = parse_document_with("a=b{\n  c=d\n  c=e\n}", &options).unwrap_err();
+        assert_eq!(
+            "3:3: expected a unique name, `c` is already bound at 2:3, found `c=e`",
+            error.to_string()
+        );
+        assert!(parse_document_with("a=b{c=d} a=c", &options).is_err());
+        let error = parse_document_with("a=b{\n  \"c\\t\"=d \"c\\t\"=e}", &options).unwrap_err();
+        assert_eq!((1, 3), (error.line, error.column));
+        assert!(parse_document_with("a=b{c=d},e{c=f}", &options).is_ok());
+
+        let options = ParseOptions::new().duplicates(DuplicatePolicy::Merge);
+        let document = parse_document_with("a=b{c=d} a=b{e=f}", &options).unwrap();
+        assert_eq!("a=b{c=d e=f}", document.to_string());
+    }
+}
@@ -0,0 +1,168 @@
+//! Parsing of documents from bytes, detecting their encoding, e.g. for files saved by editors
+//! that write a byte order mark or UTF-16.
+
+use crate::prelude::*;
+use crate::{parse_document_complete, Document, ParseError};
+use alloc::borrow::Cow;
+use core::fmt;
+
+/// An encoding of the text of a document.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+        }
+    }
+}
+
+/// A document that could not be parsed from bytes.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum BytesError {
+    /// The bytes are not valid in the detected encoding, from the given byte offset.
+    Encoding { encoding: Encoding, offset: usize },
+    /// A syntax error, located in the decoded text.
+    Parse(ParseError),
+}
+
+impl fmt::Display for BytesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BytesError::Encoding { encoding, offset } => {
+                write!(f, "invalid {} at byte {}", encoding, offset)
+            }
+            BytesError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BytesError {}
+
+/// Returns the encoding of `bytes` and the length of its byte order mark, if any.
+///
+/// Without a byte order mark, the encoding is detected from the first character, which is ASCII
+/// in any valid document: a zero byte after it or before it reveals UTF-16LE or UTF-16BE.
+fn detect(bytes: &[u8]) -> (Encoding, usize) {
+    match bytes {
+        [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+        [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+        [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+        [c, 0, ..] if c.is_ascii() => (Encoding::Utf16Le, 0),
+        [0, c, ..] if c.is_ascii() => (Encoding::Utf16Be, 0),
+        _ => (Encoding::Utf8, 0),
+    }
+}
+
+/// Decodes `bytes`, borrowing them if they are UTF-8.
+fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, BytesError> {
+    let (encoding, start) = detect(bytes);
+    let invalid = |offset| BytesError::Encoding { encoding, offset };
+    let body = &bytes[start..];
+    let unit: fn([u8; 2]) -> u16 = match encoding {
+        Encoding::Utf8 => {
+            return core::str::from_utf8(body)
+                .map(Cow::Borrowed)
+                .map_err(|e| invalid(start + e.valid_up_to()));
+        }
+        Encoding::Utf16Le => u16::from_le_bytes,
+        Encoding::Utf16Be => u16::from_be_bytes,
+    };
+    let units = body.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    let mut text = String::with_capacity(body.len() / 2);
+    let mut offset = start;
+    for c in char::decode_utf16(units) {
+        let c = c.map_err(|_| invalid(offset))?;
+        text.push(c);
+        offset += 2 * c.len_utf16();
+    }
+    if body.len() % 2 == 1 {
+        return Err(invalid(bytes.len() - 1));
+    }
+    Ok(Cow::Owned(text))
+}
+
+/// Parses a document that must span the entire input, from bytes in UTF-8 or UTF-16, with or
+/// without a byte order mark. Without one, the encoding is detected from the first character.
+///
+/// ```
+/// let utf16 = "\u{feff}a=b"
+///     .encode_utf16()
+///     .flat_map(u16::to_le_bytes)
+///     .collect::<Vec<_>>();
+/// let document = flax::parse_document_bytes(&utf16).unwrap();
+/// assert_eq!("a=b", document.to_string());
+/// ```
+pub fn parse_document_bytes(bytes: &[u8]) -> Result<Document, BytesError> {
+    parse_document_complete(&decode(bytes)?).map_err(BytesError::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, encode: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        text.encode_utf16().flat_map(encode).collect()
+    }
+
+    #[test]
+    fn test_parse_document_bytes() {
+        let text = "# π\na=b{c=\"😀\"}";
+        let inputs = [
+            text.as_bytes().to_vec(),
+            format!("\u{feff}{}", text).into_bytes(),
+            utf16(text, u16::to_le_bytes),
+            utf16(text, u16::to_be_bytes),
+            utf16(&format!("\u{feff}{}", text), u16::to_le_bytes),
+            utf16(&format!("\u{feff}{}", text), u16::to_be_bytes),
+        ];
+        let expected = parse_document_complete(text).unwrap();
+        for input in inputs.iter() {
+            assert_eq!(
+                Ok(&expected),
+                parse_document_bytes(input).as_ref(),
+                "{:?}",
+                input
+            );
+        }
+        assert_eq!(
+            Ok(Document::from_bindings(vec![])),
+            parse_document_bytes(b"")
+        );
+        assert_eq!(
+            Ok(Document::from_bindings(vec![])),
+            parse_document_bytes(b"\xff\xfe")
+        );
+    }
+
+    #[test]
+    fn test_parse_document_bytes_errors() {
+        let mut unpaired = utf16("a=b", u16::to_le_bytes);
+        unpaired.extend_from_slice(&0xd800u16.to_le_bytes());
+        let mut odd = utf16("\u{feff}a=b", u16::to_be_bytes);
+        odd.push(b'c');
+        let tests: [(&[u8], &str); 6] = [
+            (b"a=\xff", "invalid UTF-8 at byte 2"),
+            (b"\xef\xbb\xbfa=\xc3", "invalid UTF-8 at byte 5"),
+            (&unpaired, "invalid UTF-16LE at byte 6"),
+            (&odd, "invalid UTF-16BE at byte 8"),
+            (b"a=b c", "1:6: expected '=', found end of input"),
+            (
+                &utf16("\u{feff}a=b\nc", u16::to_le_bytes),
+                "2:2: expected '=', found end of input",
+            ),
+        ];
+        for (input, expected) in tests.iter() {
+            let error = parse_document_bytes(input).unwrap_err();
+            assert_eq!(*expected, error.to_string(), "{:?}", input);
+        }
+    }
+}
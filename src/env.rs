@@ -0,0 +1,262 @@
+//! Interpolation of environment variables into values.
+//!
+//! Values may contain `${VAR}` placeholders, replaced by the value of `VAR`, and `${VAR:-default}`
+//! placeholders, which fall back to `default` if `VAR` is unset or empty, as in the shell. The
+//! parser keeps placeholders verbatim; they are only substituted by an explicit call to
+//! `resolve_env`, or when parsing with `ParseOptions::resolve_env`.
+
+use crate::error::offset_in;
+use crate::prelude::*;
+use crate::{borrowed, Binding, Document, ParseError, Value};
+use alloc::borrow::Cow;
+use core::fmt;
+
+/// A placeholder without a default refers to a variable that is not set.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct EnvError {
+    pub name: String,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "environment variable `{}` is not set", self.name)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EnvError {}
+
+/// An unresolved placeholder, with its byte offset within the value.
+struct Unset<'a> {
+    offset: usize,
+    name: &'a str,
+}
+
+/// Substitutes the placeholders in `value`, returning `None` if there are none.
+fn interpolate<'a, F>(value: &'a str, lookup: &F) -> Result<Option<String>, Unset<'a>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if !value.contains("${") {
+        return Ok(None);
+    }
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(len) => start + len,
+            None => break,
+        };
+        out.push_str(&rest[..start]);
+        let placeholder = &rest[start + 2..end];
+        let (name, default) = match placeholder.find(":-") {
+            Some(i) => (&placeholder[..i], Some(&placeholder[i + 2..])),
+            None => (placeholder, None),
+        };
+        match (lookup(name), default) {
+            (Some(var), Some(default)) if var.is_empty() => out.push_str(default),
+            (Some(var), _) => out.push_str(&var),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => {
+                return Err(Unset {
+                    offset: value.len() - rest.len() + start,
+                    name,
+                })
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Some(out))
+}
+
+/// Substitutes the placeholders in `binding` and its descendants, looking variables up with
+/// `lookup`. On error, `binding` may be partially resolved.
+pub fn resolve_vars<F>(binding: &mut Binding, lookup: F) -> Result<(), EnvError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    resolve_binding(binding, &lookup)
+}
+
+pub fn resolve_vars_document<F>(document: &mut Document, lookup: F) -> Result<(), EnvError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    document
+        .bindings_mut()
+        .iter_mut()
+        .try_for_each(|binding| resolve_binding(binding, &lookup))
+}
+
+/// Substitutes the placeholders in `binding` and its descendants with environment variables.
+#[cfg(feature = "std")]
+pub fn resolve_env(binding: &mut Binding) -> Result<(), EnvError> {
+    resolve_vars(binding, var)
+}
+
+#[cfg(feature = "std")]
+pub fn resolve_env_document(document: &mut Document) -> Result<(), EnvError> {
+    resolve_vars_document(document, var)
+}
+
+/// Looks up an environment variable, treating values that are not valid Unicode as unset.
+#[cfg(feature = "std")]
+pub(crate) fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn resolve_binding<F>(binding: &mut Binding, lookup: &F) -> Result<(), EnvError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for value in binding.values.iter_mut().flat_map(Value::scalars_mut) {
+        match interpolate(&value.value, lookup) {
+            Ok(Some(resolved)) => value.value = resolved,
+            Ok(None) => {}
+            Err(unset) => {
+                return Err(EnvError {
+                    name: unset.name.to_string(),
+                })
+            }
+        }
+        for child in value.children.iter_mut() {
+            resolve_binding(child, lookup)?;
+        }
+    }
+    Ok(())
+}
+
+/// Substitutes the placeholders in a binding freshly parsed from `input`, reporting unset
+/// variables at the location of their placeholder.
+pub(crate) fn resolve_parsed<F>(
+    input: &str,
+    binding: &mut borrowed::Binding,
+    lookup: &F,
+) -> Result<(), ParseError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    resolve_parsed_at(input, binding, lookup, 0)
+}
+
+/// Like `resolve_parsed`, falling back to the offset of the binding, or else to `parent`, for
+/// values unescaped from a quoted string, which cannot be located in `input`.
+fn resolve_parsed_at<F>(
+    input: &str,
+    binding: &mut borrowed::Binding,
+    lookup: &F,
+    parent: usize,
+) -> Result<(), ParseError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let at = offset_in(input, &binding.name).unwrap_or(parent);
+    for value in binding
+        .values
+        .iter_mut()
+        .flat_map(borrowed::Value::scalars_mut)
+    {
+        let resolved = match interpolate(&value.value, lookup) {
+            Ok(resolved) => resolved,
+            Err(unset) => {
+                return Err(ParseError::new(
+                    input,
+                    offset_in(input, &value.value).map_or(at, |offset| offset + unset.offset),
+                    &format!("environment variable `{}` to be set", unset.name),
+                ))
+            }
+        };
+        let at = offset_in(input, &value.value).unwrap_or(at);
+        if let Some(resolved) = resolved {
+            value.value = Cow::Owned(resolved);
+        }
+        for child in value.children.iter_mut() {
+            resolve_parsed_at(input, child, lookup, at)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_with, ParseOptions};
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOST" => Some("example".to_string()),
+            "PORT" => Some("8080".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_vars() {
+        struct Test {
+            input: String,
+            resolved: Result<String, EnvError>,
+        }
+
+        let tests = [
+            Test {
+                input: "a=b".to_string(),
+                resolved: Ok("a=b".to_string()),
+            },
+            Test {
+                input: "a=${HOST},x${PORT}y{c=${PORT}}".to_string(),
+                resolved: Ok("a=example,x8080y{c=8080}".to_string()),
+            },
+            Test {
+                input: "a=[${HOST},[x${PORT}{c=${PORT}}]]".to_string(),
+                resolved: Ok("a=[example,[x8080{c=8080}]]".to_string()),
+            },
+            Test {
+                input: "a=${MISSING:-b}${EMPTY:-c}${HOST:-d}".to_string(),
+                resolved: Ok("a=bcexample".to_string()),
+            },
+            Test {
+                input: "a=${EMPTY}x${MISSING:-}".to_string(),
+                resolved: Ok("a=x".to_string()),
+            },
+            Test {
+                input: "a=b{c=${MISSING}}".to_string(),
+                resolved: Err(EnvError {
+                    name: "MISSING".to_string(),
+                }),
+            },
+        ];
+
+        for t in tests.iter() {
+            let mut binding = parse_binding_complete(&t.input).unwrap();
+            let resolved = resolve_vars(&mut binding, lookup).map(|_| binding.to_string());
+            assert_eq!(t.resolved, resolved, "{}", t.input);
+        }
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let input = "a=${HOST}\nb=c{d=x${MISSING}}";
+        let document = parse_document_with(input, &ParseOptions::new()).unwrap();
+        assert_eq!(input, document.to_string());
+
+        let options = ParseOptions::new().resolve_vars(lookup);
+        let error = parse_document_with(input, &options).unwrap_err();
+        assert_eq!(
+            "2:8: expected environment variable `MISSING` to be set, found `${MISSING}}`",
+            error.to_string()
+        );
+        let error = parse_document_with("a=b{c=\"\\t${MISSING}\"}", &options).unwrap_err();
+        assert_eq!((1, 5), (error.line, error.column));
+        let document = parse_document_with("a=${HOST}{b=${PORT}}", &options).unwrap();
+        assert_eq!("a=example{b=8080}", document.to_string());
+
+        assert_eq!(
+            "1:6: expected '}', found `-x}`",
+            crate::parse_document_complete("a=${X-x}")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use core::fmt;
+use nom::{
+    combinator::all_consuming,
+    error::{ContextError, ErrorKind, ParseError as NomParseError},
+    IResult,
+};
+
+/// Maximum number of characters of the offending input kept in `ParseError::snippet`.
+const SNIPPET_LEN: usize = 20;
+
+/// A syntax error, located in the original input.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ParseError {
+    /// Byte offset of the error from the start of the input.
+    pub offset: usize,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, in characters.
+    pub column: usize,
+    /// Description of the token that was expected at this position.
+    pub expected: String,
+    /// The input starting at the error position, up to the end of the line.
+    pub snippet: String,
+    /// The limit of `ParseOptions` that the input exceeds at this position, if that is the error
+    /// rather than invalid syntax.
+    pub limit: Option<Limit>,
+}
+
+/// A limit on the size of the input, set with `ParseOptions`.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Limit {
+    /// The number of braces and brackets open at once, e.g. 2 in `a={b=[c,d]}`.
+    Depth,
+    /// The number of bindings and values, counting each nested list as a value besides its items,
+    /// e.g. 4 in `a=[b,[c]]`.
+    Nodes,
+    /// The length of the input, in bytes.
+    InputLength,
+}
+
+/// Returns the 1-based line and column (in characters) of the byte `offset` in `input`.
+pub fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Returns the byte offset of `s` in `input` if it is a slice of it, rather than e.g. a string
+/// unescaped from it.
+pub(crate) fn offset_in(input: &str, s: &str) -> Option<usize> {
+    let start = input.as_ptr() as usize;
+    let ptr = s.as_ptr() as usize;
+    if ptr >= start && ptr + s.len() <= start + input.len() {
+        Some(ptr - start)
+    } else {
+        None
+    }
+}
+
+impl ParseError {
+    pub(crate) fn new(input: &str, offset: usize, expected: &str) -> Self {
+        let (line, column) = line_column(input, offset);
+        ParseError {
+            offset,
+            line,
+            column,
+            expected: expected.to_string(),
+            snippet: input[offset..]
+                .chars()
+                .take_while(|c| *c != '\n')
+                .take(SNIPPET_LEN)
+                .collect(),
+            limit: None,
+        }
+    }
+
+    /// An error at `offset` in `input`, where it exceeds `limit`, set to `max`.
+    pub(crate) fn exceeds(input: &str, offset: usize, limit: Limit, max: usize) -> Self {
+        let expected = match limit {
+            Limit::Depth => format!("at most {} levels of nesting", max),
+            Limit::Nodes => format!("at most {} bindings and values", max),
+            Limit::InputLength => format!("at most {} bytes of input", max),
+        };
+        ParseError {
+            limit: Some(limit),
+            ..ParseError::new(input, offset, &expected)
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: expected {}, found ",
+            self.line, self.column, self.expected
+        )?;
+        if self.snippet.is_empty() {
+            write!(f, "end of input")
+        } else {
+            write!(f, "`{}`", self.snippet)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// The nom error type used internally to collect the innermost expected token.
+#[derive(Debug)]
+pub(crate) struct Expected<'a> {
+    input: &'a str,
+    kind: ErrorKind,
+    expected: Option<&'static str>,
+}
+
+impl<'a> NomParseError<&'a str> for Expected<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        Expected {
+            input,
+            kind,
+            expected: None,
+        }
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a> ContextError<&'a str> for Expected<'a> {
+    fn add_context(_input: &'a str, ctx: &'static str, mut other: Self) -> Self {
+        other.expected.get_or_insert(ctx);
+        other
+    }
+}
+
+pub(crate) fn convert(input: &str, error: nom::Err<Expected>) -> ParseError {
+    match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) if e.kind == ErrorKind::TooLarge => {
+            ParseError::exceeds(input, input.len() - e.input.len(), Limit::Depth, MAX_DEPTH)
+        }
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            let expected = match (e.expected, e.kind) {
+                (Some(expected), _) => expected,
+                (None, ErrorKind::Eof) => "end of input",
+                (None, _) => "valid syntax",
+            };
+            ParseError::new(input, input.len() - e.input.len(), expected)
+        }
+        nom::Err::Incomplete(_) => ParseError::new(input, input.len(), "more input"),
+    }
+}
+
+/// Runs `parser` on `input`, requiring it to consume the entire input.
+pub(crate) fn complete<'a, O, F>(input: &'a str, parser: F) -> Result<O, ParseError>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O, Expected<'a>>,
+{
+    all_consuming(parser)(input)
+        .map(|(_, output)| output)
+        .map_err(|e| convert(input, e))
+}
+
+/// Runs `parser` on a prefix of `input`, returning the remaining input.
+#[cfg(feature = "std")]
+pub(crate) fn partial<'a, O, F>(input: &'a str, parser: F) -> Result<(&'a str, O), ParseError>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O, Expected<'a>>,
+{
+    parser(input).map_err(|e| convert(input, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_binding_complete, parse_document_complete, ParseError};
+
+    #[test]
+    fn test_parse_error() {
+        struct Test {
+            string: String,
+            error: ParseError,
+        }
+
+        let tests = [
+            Test {
+                string: "foo".to_string(),
+                error: ParseError::new("foo", 3, "'='"),
+            },
+            Test {
+                string: "=bar".to_string(),
+                error: ParseError::new("=bar", 0, "identifier"),
+            },
+            Test {
+                string: "foo=bar{a=b c}".to_string(),
+                error: ParseError::new("foo=bar{a=b c}", 13, "'='"),
+            },
+            Test {
+                string: "foo=bar{a=b".to_string(),
+                error: ParseError::new("foo=bar{a=b", 11, "'}'"),
+            },
+            Test {
+                string: "foo=a,\n  =".to_string(),
+                error: ParseError::new("foo=a,\n  =", 9, "identifier"),
+            },
+            Test {
+                string: "foo=a,\n  {".to_string(),
+                error: ParseError::new("foo=a,\n  {", 10, "'}'"),
+            },
+            Test {
+                string: "foo=bar baz=qux".to_string(),
+                error: ParseError::new("foo=bar baz=qux", 8, "end of input"),
+            },
+        ];
+
+        for t in tests.iter() {
+            assert_eq!(Err(t.error.clone()), parse_binding_complete(&t.string));
+        }
+
+        let error = parse_document_complete("a=b\nc=d{e}").unwrap_err();
+        assert_eq!((2, 6, "'='"), (error.line, error.column, &*error.expected));
+        assert_eq!("2:6: expected '=', found `}`", error.to_string());
+    }
+}
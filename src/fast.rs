@@ -0,0 +1,485 @@
+//! A hand-written, byte-oriented parser for the common subset of the grammar.
+//!
+//! It covers bindings with ASCII names, blocks, maps, numbers, bare values and quoted strings
+//! without escape sequences, and produces the same nodes as the grammar in `parser`, which stays
+//! the reference. Anything else, including every syntax error and nesting deeper than
+//! `MAX_DEPTH`, makes it give up, and the caller
+//! parses the whole input again with the grammar, which also reports the error.
+//!
+//! The nodes are allocated by a [`Builder`], on the heap as `borrowed` nodes or, with the `arena`
+//! feature, directly in an arena.
+
+use crate::borrowed::{Binding, Document, Value, ValueKind};
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use alloc::borrow::Cow;
+
+/// The input is outside the supported subset, or invalid.
+struct Unsupported;
+
+type Result<T> = core::result::Result<T, Unsupported>;
+
+/// Allocates the nodes built by the parser.
+pub(crate) trait Builder<'a> {
+    type Binding;
+    type Value;
+    type Bindings;
+    type Values;
+
+    fn bindings(&self) -> Self::Bindings;
+    fn push_binding(bindings: &mut Self::Bindings, binding: Self::Binding);
+    fn values(&self) -> Self::Values;
+    fn push_value(values: &mut Self::Values, value: Self::Value);
+    fn binding(&self, name: &'a str, values: Self::Values) -> Self::Binding;
+    /// A scalar followed by its children, or a map if `scalar` is `None`.
+    fn value(&self, scalar: Option<&'a str>, children: Self::Bindings) -> Self::Value;
+}
+
+/// Builds `borrowed` nodes on the heap.
+struct Heap;
+
+impl<'a> Builder<'a> for Heap {
+    type Binding = Binding<'a>;
+    type Value = Value<'a>;
+    type Bindings = Vec<Binding<'a>>;
+    type Values = Vec<Value<'a>>;
+
+    fn bindings(&self) -> Self::Bindings {
+        vec![]
+    }
+
+    fn push_binding(bindings: &mut Self::Bindings, binding: Self::Binding) {
+        bindings.push(binding);
+    }
+
+    fn values(&self) -> Self::Values {
+        vec![]
+    }
+
+    fn push_value(values: &mut Self::Values, value: Self::Value) {
+        values.push(value);
+    }
+
+    fn binding(&self, name: &'a str, values: Self::Values) -> Self::Binding {
+        Binding {
+            name: Cow::Borrowed(name),
+            values,
+        }
+    }
+
+    fn value(&self, scalar: Option<&'a str>, children: Self::Bindings) -> Self::Value {
+        Value {
+            value: Cow::Borrowed(scalar.unwrap_or("")),
+            kind: match scalar {
+                Some(_) => ValueKind::Scalar,
+                None => ValueKind::Map,
+            },
+            children,
+        }
+    }
+}
+
+struct Parser<'a, B> {
+    input: &'a str,
+    pos: usize,
+    /// The number of blocks enclosing the current position.
+    depth: usize,
+    builder: B,
+}
+
+/// Whether `b` may continue a bare name or value. Non-ASCII bytes are not supported, as they may
+/// or may not be part of an alphanumeric character.
+fn is_bare(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'.'
+}
+
+impl<'a, B: Builder<'a>> Parser<'a, B> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.input.as_bytes().get(self.pos + offset).copied()
+    }
+
+    /// Skips whitespace and comments, returning how many bytes were skipped.
+    fn ws(&mut self) -> Result<usize> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.pos += 1,
+                Some(b'#') => self.comment()?,
+                Some(b'/') if self.peek_at(1) == Some(b'/') => self.comment()?,
+                _ => return Ok(self.pos - start),
+            }
+        }
+    }
+
+    /// Skips a comment up to the end of its line.
+    fn comment(&mut self) -> Result<()> {
+        let rest = &self.input.as_bytes()[self.pos..];
+        match rest.iter().position(|&b| b == b'\n' || b == b'\r') {
+            // A carriage return only ends a line before a line feed.
+            Some(i) if rest[i] == b'\r' && rest.get(i + 1) != Some(&b'\n') => Err(Unsupported),
+            Some(i) => {
+                self.pos += i;
+                Ok(())
+            }
+            None => {
+                self.pos = self.input.len();
+                Ok(())
+            }
+        }
+    }
+
+    /// Consumes a run of bytes satisfying `is_bare`, failing before a non-ASCII byte.
+    fn bare_run(&mut self) -> Result<()> {
+        while let Some(b) = self.peek() {
+            if is_bare(b) {
+                self.pos += 1;
+            } else if b >= 0x80 {
+                return Err(Unsupported);
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// A quoted string without escape sequences.
+    fn quoted(&mut self) -> Result<&'a str> {
+        let start = self.pos + 1;
+        let bytes = self.input.as_bytes();
+        for (i, &b) in bytes[start..].iter().enumerate() {
+            match b {
+                b'"' => {
+                    self.pos = start + i + 1;
+                    return Ok(&self.input[start..start + i]);
+                }
+                b'\\' | b'\n' => return Err(Unsupported),
+                _ => {}
+            }
+        }
+        Err(Unsupported)
+    }
+
+    fn key(&mut self) -> Result<&'a str> {
+        match self.peek() {
+            Some(b'"') => self.quoted(),
+            Some(b) if b.is_ascii_alphanumeric() || b == b'_' => {
+                let start = self.pos;
+                self.bare_run()?;
+                Ok(&self.input[start..self.pos])
+            }
+            _ => Err(Unsupported),
+        }
+    }
+
+    fn binding(&mut self) -> Result<B::Binding> {
+        let name = self.key()?;
+        if self.peek() != Some(b'=') {
+            return Err(Unsupported);
+        }
+        self.pos += 1;
+        let values = match self.peek() {
+            None | Some(b' ' | b'\t' | b'\n' | b'\r' | b'}' | b'#' | b'/') => self.builder.values(),
+            Some(_) => self.values()?,
+        };
+        Ok(self.builder.binding(name, values))
+    }
+
+    /// Whether a comma before the current position is trailing.
+    fn list_end(&mut self) -> Result<bool> {
+        match self.peek() {
+            None | Some(b'}') => Ok(true),
+            Some(b) if b == b'"' || b.is_ascii_alphanumeric() || b == b'_' => {
+                let start = self.pos;
+                self.key()?;
+                let end = self.peek() == Some(b'=');
+                self.pos = start;
+                Ok(end)
+            }
+            Some(b) if b >= 0x80 => Err(Unsupported),
+            Some(_) => Ok(false),
+        }
+    }
+
+    fn values(&mut self) -> Result<B::Values> {
+        let mut values = self.builder.values();
+        B::push_value(&mut values, self.value()?);
+        loop {
+            let before = self.pos;
+            self.ws()?;
+            if self.peek() != Some(b',') {
+                self.pos = before;
+                return Ok(values);
+            }
+            self.pos += 1;
+            let after = self.pos;
+            self.ws()?;
+            if self.list_end()? {
+                self.pos = after;
+                return Ok(values);
+            }
+            B::push_value(&mut values, self.value()?);
+        }
+    }
+
+    fn value(&mut self) -> Result<B::Value> {
+        let scalar = match self.peek() {
+            Some(b'{') => {
+                let children = self.block()?;
+                return Ok(self.builder.value(None, children));
+            }
+            Some(b'"') if self.input[self.pos..].starts_with("\"\"\"") => return Err(Unsupported),
+            Some(b'"') => self.quoted()?,
+            Some(b) if b.is_ascii_digit() || b == b'+' || b == b'-' => self.number()?,
+            Some(b) if b.is_ascii_alphabetic() || b == b'_' => {
+                let start = self.pos;
+                self.bare_run()?;
+                if self.peek() == Some(b'$') {
+                    return Err(Unsupported);
+                }
+                &self.input[start..self.pos]
+            }
+            _ => return Err(Unsupported),
+        };
+        let before = self.pos;
+        self.ws()?;
+        let children = if self.peek() == Some(b'{') {
+            self.block()?
+        } else {
+            self.pos = before;
+            self.builder.bindings()
+        };
+        Ok(self.builder.value(Some(scalar), children))
+    }
+
+    fn number(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_alphanumeric() || b"_.+-".contains(&b) {
+                self.pos += 1;
+            } else if b >= 0x80 {
+                return Err(Unsupported);
+            } else {
+                break;
+            }
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn block(&mut self) -> Result<B::Bindings> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Unsupported);
+        }
+        self.pos += 1;
+        self.ws()?;
+        self.depth += 1;
+        let mut bindings = self.builder.bindings();
+        let mut empty = true;
+        loop {
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok(bindings);
+            }
+            if !empty && self.ws()? == 0 && self.peek() != Some(b'}') {
+                return Err(Unsupported);
+            }
+            if self.peek() == Some(b'}') {
+                continue;
+            }
+            B::push_binding(&mut bindings, self.binding()?);
+            empty = false;
+        }
+    }
+
+    /// Bindings separated by whitespace, up to the end of the input.
+    fn bindings(&mut self) -> Result<B::Bindings> {
+        self.ws()?;
+        let mut bindings = self.builder.bindings();
+        while self.pos < self.input.len() {
+            B::push_binding(&mut bindings, self.binding()?);
+            if self.ws()? == 0 && self.pos < self.input.len() {
+                return Err(Unsupported);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+/// Parses the bindings of a document spanning the entire input, if it is within the supported
+/// subset and valid, allocating them with `builder`.
+pub(crate) fn bindings_with<'a, B: Builder<'a>>(builder: B, input: &'a str) -> Option<B::Bindings> {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+        builder,
+    };
+    parser.bindings().ok()
+}
+
+/// Parses a single binding surrounded by optional whitespace and spanning the entire input, if it
+/// is within the supported subset and valid, allocating it with `builder`.
+pub(crate) fn binding_with<'a, B: Builder<'a>>(builder: B, input: &'a str) -> Option<B::Binding> {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+        builder,
+    };
+    parser.ws().ok()?;
+    let binding = parser.binding().ok()?;
+    parser.ws().ok()?;
+    if parser.pos == input.len() {
+        Some(binding)
+    } else {
+        None
+    }
+}
+
+/// Parses a document spanning the entire input, if it is within the supported subset and valid.
+pub(crate) fn document(input: &str) -> Option<Document<'_>> {
+    bindings_with(Heap, input).map(|bindings| Document { bindings })
+}
+
+/// Parses a single binding surrounded by optional whitespace and spanning the entire input, if it
+/// is within the supported subset and valid.
+pub(crate) fn binding(input: &str) -> Option<Binding<'_>> {
+    binding_with(Heap, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "arbitrary")]
+    use crate::arbitrary::document_with_text;
+    use crate::error::complete;
+    use crate::parser;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prop_assert_eq, proptest};
+
+    #[test]
+    fn test_fast() {
+        struct Test {
+            input: &'static str,
+            supported: bool,
+        }
+
+        let tests = [
+            Test {
+                input: "",
+                supported: true,
+            },
+            Test {
+                input: " # comment\n a=b // another\r\n c=d{e=f,g h=\"i j\"} k= l={m=1_000}\n",
+                supported: true,
+            },
+            Test {
+                input: "a=b,c, d=e,\nf=g{h=i}, j=-2.5e3,0xFF,+1 k=x.y-z_",
+                supported: true,
+            },
+            Test {
+                input: "a=b\n{c=d} g=\"\" h={ } i={}",
+                supported: true,
+            },
+            Test {
+                input: "\"a b\"=c,\"d\" \"e\"=f{\"g\"=h,}",
+                supported: true,
+            },
+            Test {
+                input: "a=[b, c]",
+                supported: false,
+            },
+            Test {
+                input: "città=Roma",
+                supported: false,
+            },
+            Test {
+                input: "a=\"b\\nc\"",
+                supported: false,
+            },
+            Test {
+                input: "a=${HOME} b=&x c c=*x",
+                supported: false,
+            },
+            Test {
+                input: "a=\"\"\"\n  b\n  \"\"\"",
+                supported: false,
+            },
+            Test {
+                input: "a=b{c=d",
+                supported: false,
+            },
+            Test {
+                input: "a=b c",
+                supported: false,
+            },
+            Test {
+                input: "a=bc=d",
+                supported: false,
+            },
+            Test {
+                input: "a=b{c=dd=e}",
+                supported: false,
+            },
+            Test {
+                input: "a=12ab",
+                supported: true,
+            },
+            Test {
+                input: "a=b,",
+                supported: true,
+            },
+            Test {
+                input: "a=b, ,",
+                supported: false,
+            },
+            Test {
+                input: "# a\rb",
+                supported: false,
+            },
+        ];
+
+        for t in tests.iter() {
+            let expected = complete(t.input, parser::document);
+            match document(t.input) {
+                Some(document) => {
+                    assert!(t.supported, "{}", t.input);
+                    assert_eq!(expected, Ok(document), "{}", t.input);
+                }
+                None => assert!(!t.supported, "{}", t.input),
+            }
+            assert_eq!(
+                expected,
+                crate::borrowed::parse_document_complete(t.input),
+                "{}",
+                t.input
+            );
+        }
+
+        for input in [" a=b{c=d} ", "a=b c=d", "a=", "a=b,"].iter() {
+            assert_eq!(
+                complete(input, parser::complete_binding).ok(),
+                binding(input),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_fast_parser(document in document_with_text()) {
+            let printed = crate::print_document(&document);
+            if let Some(fast) = super::document(&printed) {
+                let nom = complete(&printed, parser::document);
+                prop_assert_eq!(nom, Ok(fast), "{}", printed);
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Conversion between trees of bindings and flat lists of dotted paths and values, e.g. to store
+//! a document in environment variables, etcd or a Java properties file.
+
+use crate::prelude::*;
+use crate::{print_value, Binding, Document, Path, PathError, Segment, Value, ValueKind};
+
+fn push_pairs(pairs: &mut Vec<(String, String)>, (path, value): (Path, &Value)) {
+    if value.is_map() && !value.children.is_empty() {
+        return;
+    }
+    let value = match value.items() {
+        Some(_) => print_value(value),
+        None => value.value.clone(),
+    };
+    pairs.push((path.to_string(), value));
+}
+
+/// Returns the dotted path and scalar of each value of `binding` and of its descendants, in
+/// document order, as [`Binding::iter`] yields them.
+///
+/// Maps with children are omitted, as their children have pairs of their own. Nested lists are
+/// printed as fuji, and come back from [`unflatten`] as scalars.
+pub fn flatten(binding: &Binding) -> Vec<(String, String)> {
+    let mut pairs = vec![];
+    for entry in binding.iter() {
+        push_pairs(&mut pairs, entry);
+    }
+    pairs
+}
+
+/// Returns the pairs of each top-level binding of `document`, as [`flatten`] does.
+pub fn flatten_document(document: &Document) -> Vec<(String, String)> {
+    let mut pairs = vec![];
+    for entry in document.iter() {
+        push_pairs(&mut pairs, entry);
+    }
+    pairs
+}
+
+/// Sets the scalar at `path` in `bindings`, as `insert_segments` does.
+fn insert(bindings: &mut Vec<Binding>, path: &str, scalar: &str) -> Result<(), PathError> {
+    let parsed: Path = path.parse()?;
+    insert_segments(bindings, &parsed.segments, scalar)
+        .ok_or_else(|| PathError::NotFound(path.to_string()))
+}
+
+/// Sets the scalar at the path of `segments` in `bindings`, creating the bindings and values
+/// along it. Values created along the path are maps until their own scalar is set. Returns `None`
+/// if an index is more than one past the last value of its binding.
+pub(crate) fn insert_segments(
+    bindings: &mut Vec<Binding>,
+    segments: &[Segment],
+    scalar: &str,
+) -> Option<()> {
+    let mut bindings = bindings;
+    let last = segments.len() - 1;
+    for (i, segment) in segments.iter().enumerate() {
+        let index = match bindings.iter().position(|b| b.name == segment.name) {
+            Some(index) => index,
+            None => {
+                bindings.push(Binding::new(segment.name.clone()));
+                bindings.len() - 1
+            }
+        };
+        let values = &mut bindings[index].values;
+        let value_index = segment.index.unwrap_or(0);
+        if value_index == values.len() {
+            values.push(Value {
+                value: String::new(),
+                kind: ValueKind::Map,
+                children: vec![],
+            });
+        } else if value_index > values.len() {
+            return None;
+        }
+        let value = &mut values[value_index];
+        if i == last {
+            value.value = scalar.to_string();
+            value.kind = ValueKind::Scalar;
+        }
+        bindings = &mut value.children;
+    }
+    Some(())
+}
+
+/// Builds a document from pairs of dotted paths and scalars, such as those returned by
+/// [`flatten_document`]. Later pairs override earlier ones with the same path.
+///
+/// Each path segment adds to the first binding with its name, so bindings that shared a name
+/// come back as a single binding with several values. The values of a binding must be set in
+/// order, with an index at most one past the last one; otherwise the error names the path.
+pub fn unflatten_document<I, K, V>(pairs: I) -> Result<Document, PathError>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut bindings = vec![];
+    for (path, scalar) in pairs {
+        insert(&mut bindings, path.as_ref(), scalar.as_ref())?;
+    }
+    Ok(Document::from_bindings(bindings))
+}
+
+/// Builds a binding from pairs of dotted paths and scalars, such as those returned by
+/// [`flatten`], as [`unflatten_document`] does. All paths must start with the same name: the
+/// first path that does not is reported as invalid, and no pairs at all as an empty path not
+/// found.
+pub fn unflatten<I, K, V>(pairs: I) -> Result<Binding, PathError>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut bindings = vec![];
+    for (path, scalar) in pairs {
+        let path = path.as_ref();
+        insert(&mut bindings, path, scalar.as_ref())?;
+        if bindings.len() > 1 {
+            return Err(PathError::Invalid(path.to_string()));
+        }
+    }
+    bindings
+        .pop()
+        .ok_or_else(|| PathError::NotFound(String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete, print_document};
+
+    #[test]
+    fn test_flatten() {
+        let binding =
+            parse_binding_complete("server=main{host=h ports=80,443 tls={cert=c} \"a b\"={}}")
+                .unwrap();
+        let pairs = flatten(&binding);
+        assert_eq!(
+            vec![
+                ("server", "main"),
+                ("server.host", "h"),
+                ("server.ports[0]", "80"),
+                ("server.ports[1]", "443"),
+                ("server.tls.cert", "c"),
+                ("server.\"a b\"", ""),
+            ],
+            pairs
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Ok(parse_binding_complete(
+                "server=main{host=h ports=80,443 tls={cert=c} \"a b\"=\"\"}"
+            )
+            .unwrap()),
+            unflatten(pairs)
+        );
+    }
+
+    #[test]
+    fn test_unflatten_document() {
+        let document = parse_document_complete("a=x{b=y} c=[d,[e]] f=g f=h").unwrap();
+        let pairs = flatten_document(&document);
+        assert_eq!(
+            "a=x{b=y}\nc=d,\"[e]\"\nf=g,h",
+            print_document(&unflatten_document(pairs).unwrap())
+        );
+
+        let pairs = [("a.b", "1"), ("a", "x"), ("a.b", "2"), ("c[0].d", "3")];
+        assert_eq!(
+            "a=x{b=2}\nc={d=3}",
+            print_document(&unflatten_document(pairs.iter().copied()).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_unflatten_errors() {
+        assert_eq!(
+            Err(PathError::NotFound("a[2]".to_string())),
+            unflatten_document(vec![("a[0]", "x"), ("a[2]", "y")])
+        );
+        assert_eq!(
+            Err(PathError::Invalid("a..b".to_string())),
+            unflatten_document(vec![("a..b", "x")])
+        );
+        assert_eq!(
+            Err(PathError::Invalid("b".to_string())),
+            unflatten(vec![("a", "x"), ("b", "y")])
+        );
+    }
+}
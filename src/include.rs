@@ -0,0 +1,296 @@
+//! Composition of documents split across several sources.
+//!
+//! At the top level of a document loaded with `load`, an `include "path"` directive is replaced
+//! by the bindings of the named document, which may itself include others. Paths are interpreted
+//! by a `Resolver`, e.g. `FileResolver` for files relative to the including file. The regular
+//! parsers do not accept include directives.
+
+use crate::error::complete;
+use crate::parser::{self, Item};
+use crate::prelude::*;
+use crate::{line_column, Binding, Document, ParseError};
+use core::fmt;
+use nom::Offset;
+
+/// A loaded document.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Source {
+    /// Canonical name of the document, used in error messages and to detect include cycles.
+    pub name: String,
+    pub contents: String,
+}
+
+/// Locates and loads the documents named by include directives.
+pub trait Resolver {
+    /// Loads the document at `path`, as written in an include directive of the document named
+    /// `from`, or as passed to `load` if `from` is `None`. Errors are reported as messages.
+    fn resolve(&self, path: &str, from: Option<&str>) -> Result<Source, String>;
+}
+
+/// Resolves paths on the file system, relative to the directory of the including file.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileResolver;
+
+#[cfg(feature = "std")]
+impl Resolver for FileResolver {
+    fn resolve(&self, path: &str, from: Option<&str>) -> Result<Source, String> {
+        use std::path::{Component, Path, PathBuf};
+
+        let joined = match from.and_then(|from| Path::new(from).parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        // Normalized lexically, so that different spellings of a path are detected as a cycle.
+        let mut normalized = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir
+                    if matches!(
+                        normalized.components().next_back(),
+                        Some(Component::Normal(_))
+                    ) =>
+                {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+        let contents = std::fs::read_to_string(&normalized).map_err(|e| e.to_string())?;
+        Ok(Source {
+            name: normalized.to_string_lossy().into_owned(),
+            contents,
+        })
+    }
+}
+
+/// The location of an include directive.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct IncludeSite {
+    /// Name of the including document.
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum LoadErrorKind {
+    /// The resolver failed to load the document.
+    Resolve(String),
+    Parse(ParseError),
+    /// The document includes itself, directly or indirectly.
+    Cycle,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct LoadError {
+    /// Name of the document in which the error occurred, or the path it was included as if it
+    /// could not be resolved.
+    pub name: String,
+    pub kind: LoadErrorKind,
+    /// The chain of include directives leading to the document, innermost first.
+    pub included_from: Vec<IncludeSite>,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            LoadErrorKind::Resolve(message) => write!(f, "{}: {}", self.name, message)?,
+            LoadErrorKind::Parse(e) => write!(f, "{}:{}", self.name, e)?,
+            LoadErrorKind::Cycle => write!(f, "{}: include cycle", self.name)?,
+        }
+        for site in self.included_from.iter() {
+            write!(
+                f,
+                "\n  included from {}:{}:{}",
+                site.name, site.line, site.column
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+/// Loads the document at `path` with `resolver`, expanding include directives.
+pub fn load<R: Resolver>(path: &str, resolver: &R) -> Result<Document, LoadError> {
+    let source = resolver.resolve(path, None).map_err(|message| LoadError {
+        name: path.to_string(),
+        kind: LoadErrorKind::Resolve(message),
+        included_from: vec![],
+    })?;
+    let mut loader = Loader {
+        resolver,
+        sites: vec![],
+        open: vec![],
+        bindings: vec![],
+    };
+    loader.load(&source)?;
+    Ok(Document::from_bindings(loader.bindings))
+}
+
+struct Loader<'r, R> {
+    resolver: &'r R,
+    /// The include directives being expanded, outermost first.
+    sites: Vec<IncludeSite>,
+    /// Names of the documents being loaded, outermost first.
+    open: Vec<String>,
+    bindings: Vec<Binding>,
+}
+
+impl<R: Resolver> Loader<'_, R> {
+    fn error(&self, name: &str, kind: LoadErrorKind) -> LoadError {
+        LoadError {
+            name: name.to_string(),
+            kind,
+            included_from: self.sites.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn load(&mut self, source: &Source) -> Result<(), LoadError> {
+        let items = complete(&source.contents, parser::items)
+            .map_err(|e| self.error(&source.name, LoadErrorKind::Parse(e)))?;
+        self.open.push(source.name.clone());
+        for item in items {
+            let (directive, path) = match item {
+                Item::Binding(binding) => {
+                    self.bindings.push(binding.into_owned());
+                    continue;
+                }
+                Item::Include { directive, path } => (directive, path),
+            };
+            let (line, column) = line_column(&source.contents, source.contents.offset(directive));
+            self.sites.push(IncludeSite {
+                name: source.name.clone(),
+                line,
+                column,
+            });
+            let included = self
+                .resolver
+                .resolve(&path, Some(&source.name))
+                .map_err(|message| self.error(&path, LoadErrorKind::Resolve(message)))?;
+            if self.open.contains(&included.name) {
+                return Err(self.error(&included.name, LoadErrorKind::Cycle));
+            }
+            self.load(&included)?;
+            self.sites.pop();
+        }
+        self.open.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    struct Memory(BTreeMap<&'static str, &'static str>);
+
+    impl Resolver for Memory {
+        fn resolve(&self, path: &str, _from: Option<&str>) -> Result<Source, String> {
+            match self.0.get(path) {
+                Some(contents) => Ok(Source {
+                    name: path.to_string(),
+                    contents: contents.to_string(),
+                }),
+                None => Err("not found".to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let resolver = Memory(
+            [
+                ("main", "a=b\ninclude \"common\" # shared\nc=d"),
+                ("common", "x=y{z=w}  include \"leaf\""),
+                ("leaf", "// nothing but a comment"),
+                (
+                    "twice",
+                    "include \"leaf\" include \"common\" include \"common\"",
+                ),
+                ("cycle", "a=b\ninclude \"loop\""),
+                ("loop", "\n  include \"cycle\""),
+                ("broken", "include \"common\"\ninclude \"bad\""),
+                ("bad", "a=b{"),
+                ("missing", "include \"nowhere\""),
+                ("binding", "include=b"),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+        );
+
+        struct Test {
+            path: String,
+            result: Result<String, String>,
+        }
+
+        let tests = [
+            Test {
+                path: "main".to_string(),
+                result: Ok("a=b\nx=y{z=w}\nc=d".to_string()),
+            },
+            Test {
+                path: "twice".to_string(),
+                result: Ok("x=y{z=w}\nx=y{z=w}".to_string()),
+            },
+            Test {
+                path: "binding".to_string(),
+                result: Ok("include=b".to_string()),
+            },
+            Test {
+                path: "cycle".to_string(),
+                result: Err("cycle: include cycle\n  included from loop:2:3\n  \
+                             included from cycle:2:1"
+                    .to_string()),
+            },
+            Test {
+                path: "broken".to_string(),
+                result: Err("bad:1:5: expected '}', found end of input\n  \
+                             included from broken:2:1"
+                    .to_string()),
+            },
+            Test {
+                path: "missing".to_string(),
+                result: Err("nowhere: not found\n  included from missing:1:1".to_string()),
+            },
+            Test {
+                path: "other".to_string(),
+                result: Err("other: not found".to_string()),
+            },
+        ];
+
+        for t in tests.iter() {
+            let result = load(&t.path, &resolver)
+                .map(|document| document.to_string())
+                .map_err(|e| e.to_string());
+            assert_eq!(t.result, result, "{}", t.path);
+        }
+
+        assert!(crate::parse_document_complete("include \"common\"").is_err());
+    }
+
+    #[test]
+    fn test_file_resolver() {
+        let dir = std::env::temp_dir().join(format!("fuji-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("main.fuji"), "include \"sub/a.fuji\"").unwrap();
+        std::fs::write(dir.join("sub/a.fuji"), "a=b include \"../sub/./b.fuji\"").unwrap();
+        std::fs::write(dir.join("sub/b.fuji"), "c=d include \"a.fuji\"").unwrap();
+
+        let main = dir.join("main.fuji");
+        let error = load(main.to_str().unwrap(), &FileResolver).unwrap_err();
+        assert_eq!(LoadErrorKind::Cycle, error.kind);
+        assert_eq!(dir.join("sub/a.fuji").to_str().unwrap(), error.name);
+        assert_eq!(3, error.included_from.len());
+
+        std::fs::write(dir.join("sub/b.fuji"), "c=d").unwrap();
+        let document = load(main.to_str().unwrap(), &FileResolver).unwrap();
+        assert_eq!("a=b\nc=d", document.to_string());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
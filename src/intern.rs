@@ -0,0 +1,238 @@
+//! A variant of the AST whose binding names are interned, for large documents that repeat the
+//! same names many times.
+//!
+//! Each distinct name is stored once in an [`Interner`] and bindings refer to it by a [`Symbol`],
+//! so a document with millions of bindings holds one copy of each of its names. The same interner
+//! may be shared across documents. Scalars are not interned.
+//!
+//! ```
+//! use flax::intern::{parse_document_complete, Interner};
+//!
+//! let mut interner = Interner::new();
+//! let document = parse_document_complete("host=a{port=1} host=b{port=2}", &mut interner).unwrap();
+//! assert_eq!(2, interner.len());
+//! let host = interner.get("host").unwrap();
+//! assert_eq!(host, document.bindings()[1].name());
+//! assert_eq!("host=a{port=1}\nhost=b{port=2}", document.resolve(&interner).to_string());
+//! ```
+
+use crate::prelude::*;
+use crate::{borrowed, ParseError};
+use alloc::collections::BTreeMap;
+
+/// A name stored in an [`Interner`]. Symbols compare equal if and only if their names do, as long
+/// as they come from the same interner.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+pub struct Symbol(u32);
+
+/// A table of distinct names.
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    names: Vec<String>,
+    symbols: BTreeMap<String, Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// Returns the symbol of `name`, adding it to the table if needed.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// Returns the symbol of `name`, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Returns the name of `symbol`.
+    ///
+    /// Panics if `symbol` comes from another interner with more names.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    /// Returns the number of distinct names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn binding(&mut self, binding: borrowed::Binding) -> Binding {
+        Binding {
+            name: self.intern(&binding.name),
+            values: binding.values.into_iter().map(|v| self.value(v)).collect(),
+        }
+    }
+
+    fn value(&mut self, value: borrowed::Value) -> Value {
+        Value {
+            value: value.value.into_owned(),
+            kind: match value.kind {
+                borrowed::ValueKind::Scalar => ValueKind::Scalar,
+                borrowed::ValueKind::List(items) => {
+                    ValueKind::List(items.into_iter().map(|v| self.value(v)).collect())
+                }
+                borrowed::ValueKind::Map => ValueKind::Map,
+                borrowed::ValueKind::Reference => ValueKind::Reference,
+                borrowed::ValueKind::Anchored(anchor) => ValueKind::Anchored(anchor.to_string()),
+            },
+            children: value
+                .children
+                .into_iter()
+                .map(|b| self.binding(b))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Binding {
+    name: Symbol,
+    values: Vec<Value>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Value {
+    value: String,
+    kind: ValueKind,
+    children: Vec<Binding>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ValueKind {
+    Scalar,
+    List(Vec<Value>),
+    Map,
+    Reference,
+    Anchored(String),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Document {
+    bindings: Vec<Binding>,
+}
+
+impl Binding {
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Converts the binding to the usual AST, looking up its names in `interner`.
+    pub fn resolve(&self, interner: &Interner) -> crate::Binding {
+        crate::Binding {
+            name: interner.resolve(self.name).to_string(),
+            values: self.values.iter().map(|v| v.resolve(interner)).collect(),
+        }
+    }
+}
+
+impl Value {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn kind(&self) -> &ValueKind {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &[Binding] {
+        &self.children
+    }
+
+    /// Converts the value to the usual AST, looking up its names in `interner`.
+    pub fn resolve(&self, interner: &Interner) -> crate::Value {
+        crate::Value {
+            value: self.value.clone(),
+            kind: match &self.kind {
+                ValueKind::Scalar => crate::ValueKind::Scalar,
+                ValueKind::List(items) => {
+                    crate::ValueKind::List(items.iter().map(|v| v.resolve(interner)).collect())
+                }
+                ValueKind::Map => crate::ValueKind::Map,
+                ValueKind::Reference => crate::ValueKind::Reference,
+                ValueKind::Anchored(anchor) => crate::ValueKind::Anchored(anchor.clone()),
+            },
+            children: self.children.iter().map(|b| b.resolve(interner)).collect(),
+        }
+    }
+}
+
+impl Document {
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Converts the document to the usual AST, looking up its names in `interner`.
+    pub fn resolve(&self, interner: &Interner) -> crate::Document {
+        crate::Document::from_bindings(self.bindings.iter().map(|b| b.resolve(interner)).collect())
+    }
+}
+
+/// Parses a document that must span the entire input, interning its names in `interner`.
+pub fn parse_document_complete(
+    input: &str,
+    interner: &mut Interner,
+) -> Result<Document, ParseError> {
+    let document = borrowed::parse_document_complete(input)?;
+    Ok(Document {
+        bindings: document
+            .bindings
+            .into_iter()
+            .map(|b| interner.binding(b))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        assert_eq!(a, interner.intern("a"));
+        assert_ne!(a, interner.intern("b"));
+        assert_eq!("b", interner.resolve(interner.get("b").unwrap()));
+        assert_eq!(None, interner.get("c"));
+
+        let input = "a=x{b=1 c=[2,[3{b=4}]]} b={a=5} c=";
+        let document = parse_document_complete(input, &mut interner).unwrap();
+        assert_eq!(3, interner.len());
+        assert_eq!(
+            crate::parse_document_complete(input).unwrap(),
+            document.resolve(&interner)
+        );
+        let b = interner.get("b").unwrap();
+        let list = &document.bindings()[0].values()[0].children()[1].values()[1];
+        match list.kind() {
+            ValueKind::List(items) => assert_eq!(b, items[0].children()[0].name()),
+            kind => panic!("expected a list, found {:?}", kind),
+        }
+
+        let second = parse_document_complete("a=1 d=2", &mut interner).unwrap();
+        assert_eq!(a, second.bindings()[0].name());
+        assert_eq!(4, interner.len());
+
+        assert_eq!(
+            Err(ParseError::new("a=b{", 4, "'}'")),
+            parse_document_complete("a=b{", &mut interner).map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Depth-first iteration over the values of a tree of bindings.
+
+use crate::prelude::*;
+use crate::{Binding, Document, Path, Segment, Value};
+
+/// Returns the paths of the values bound in `bindings`, below `parent`, in document order.
+///
+/// The index of a segment counts the values of all the sibling bindings with the same name, as
+/// `Document::query` does, and is omitted if there is only one such value.
+pub(crate) fn paths(parent: &Path, bindings: &[Binding]) -> Vec<Path> {
+    let count = |bindings: &[Binding], name: &str| -> usize {
+        bindings
+            .iter()
+            .filter(|b| b.name == name)
+            .map(|b| b.values.len())
+            .sum()
+    };
+    let mut paths = vec![];
+    for (i, binding) in bindings.iter().enumerate() {
+        let previous = count(&bindings[..i], &binding.name);
+        let total = count(bindings, &binding.name);
+        for j in 0..binding.values.len() {
+            let mut path = parent.clone();
+            path.segments.push(Segment {
+                name: binding.name.clone(),
+                index: if total > 1 { Some(previous + j) } else { None },
+            });
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// An iterator over the values of a tree of bindings and their paths, depth first in document
+/// order. Created by [`Binding::iter`] and [`Document::iter`].
+///
+/// Each value is yielded before its children. A nested list is yielded as a single value: paths
+/// cannot address its items.
+pub struct Iter<'a> {
+    /// The values still to yield, the next one last.
+    stack: Vec<(Path, &'a Value)>,
+}
+
+impl<'a> Iter<'a> {
+    pub(crate) fn new(parent: Path, bindings: &'a [Binding]) -> Self {
+        let mut iter = Iter { stack: vec![] };
+        iter.push(&parent, bindings);
+        iter
+    }
+
+    fn push(&mut self, parent: &Path, bindings: &'a [Binding]) {
+        let values = bindings.iter().flat_map(|b| b.values.iter());
+        let entries = paths(parent, bindings)
+            .into_iter()
+            .zip(values)
+            .collect::<Vec<_>>();
+        self.stack.extend(entries.into_iter().rev());
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Path, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, value) = self.stack.pop()?;
+        self.push(&path, &value.children);
+        Some((path, value))
+    }
+}
+
+/// A mutable iterator over the leaf values of a tree of bindings, i.e. those without children,
+/// and their paths, in document order. Created by [`Binding::iter_mut`] and
+/// [`Document::iter_mut`].
+///
+/// Values with children are not yielded, as they cannot be borrowed mutably along with their
+/// descendants. A nested list is yielded as a single value.
+pub struct IterMut<'a> {
+    /// The values still to visit, the next one last.
+    stack: Vec<(Path, &'a mut Value)>,
+}
+
+impl<'a> IterMut<'a> {
+    fn new(parent: Path, bindings: &'a mut [Binding]) -> Self {
+        let mut iter = IterMut { stack: vec![] };
+        iter.push(&parent, bindings);
+        iter
+    }
+
+    fn push(&mut self, parent: &Path, bindings: &'a mut [Binding]) {
+        let paths = paths(parent, bindings);
+        let values = bindings.iter_mut().flat_map(|b| b.values.iter_mut());
+        let entries = paths.into_iter().zip(values).collect::<Vec<_>>();
+        self.stack.extend(entries.into_iter().rev());
+    }
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (Path, &'a mut Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (path, value) = self.stack.pop()?;
+            if value.children.is_empty() {
+                return Some((path, value));
+            }
+            self.push(&path, &mut value.children);
+        }
+    }
+}
+
+impl Binding {
+    /// Returns an iterator over the values of this binding and of its descendants, with their
+    /// paths starting from the name of this binding, e.g. `server.port` or `hosts[1]`.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(Path { segments: vec![] }, core::slice::from_ref(self))
+    }
+
+    /// Returns a mutable iterator over the leaf values of this binding and of its descendants,
+    /// with their paths as for [`Binding::iter`].
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut::new(Path { segments: vec![] }, core::slice::from_mut(self))
+    }
+}
+
+impl Document {
+    /// Returns an iterator over all values of the document, with their paths, e.g.
+    /// `server.port`. Each path resolves to its value with `Document::query`.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(Path { segments: vec![] }, &self.bindings)
+    }
+
+    /// Returns a mutable iterator over the leaf values of the document, with their paths.
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut::new(Path { segments: vec![] }, self.bindings_mut())
+    }
+}
+
+impl<'a> IntoIterator for &'a Document {
+    type Item = &'a Binding;
+    type IntoIter = core::slice::Iter<'a, Binding>;
+
+    /// Iterates over the top-level bindings.
+    fn into_iter(self) -> Self::IntoIter {
+        self.bindings.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete, print_document};
+
+    #[test]
+    fn test_iter() {
+        let document = parse_document_complete("a=b{c=d e=f,g} h=i h=j{k=[l, [m]]}").unwrap();
+        let entries = document
+            .iter()
+            .map(|(path, value)| (path.to_string(), crate::print_value(value)))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("a", "b{c=d e=f,g}"),
+            ("a.c", "d"),
+            ("a.e[0]", "f"),
+            ("a.e[1]", "g"),
+            ("h[0]", "i"),
+            ("h[1]", "j{k=[l,[m]]}"),
+            ("h[1].k[0]", "l"),
+            ("h[1].k[1]", "[m]"),
+        ];
+        assert_eq!(
+            expected
+                .iter()
+                .map(|(p, v)| (p.to_string(), v.to_string()))
+                .collect::<Vec<_>>(),
+            entries
+        );
+        for (path, value) in document.iter() {
+            assert_eq!(
+                Ok(vec![value]),
+                document.query(&path.to_string()),
+                "{}",
+                path
+            );
+        }
+
+        let binding = parse_binding_complete("a=b{c=d}").unwrap();
+        let paths = binding
+            .iter()
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "a.c"], paths);
+
+        let names = (&document)
+            .into_iter()
+            .map(Binding::name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "h", "h"], names);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut document = parse_document_complete("a=b{c=d e=f,g} h=i").unwrap();
+        let mut paths = vec![];
+        for (path, value) in document.iter_mut() {
+            paths.push(path.to_string());
+            *value = Value::from(value.value().to_uppercase());
+        }
+        assert_eq!(vec!["a.c", "a.e[0]", "a.e[1]", "h"], paths);
+        assert_eq!("a=b{c=D e=F,G}\nh=I", print_document(&document));
+    }
+}
@@ -0,0 +1,263 @@
+//! A lossless token stream, for tools such as syntax highlighters that need tokens rather than a
+//! full tree.
+//!
+//! The tokens cover the entire input, so concatenating their text reproduces it. Lexing never
+//! fails: any text that cannot start a token, or a string left open, becomes an `Error` token.
+//!
+//! ```
+//! use flax::lexer::{tokenize, TokenKind};
+//!
+//! let kinds = tokenize("a=b # c").map(|t| t.kind).collect::<Vec<_>>();
+//! assert_eq!(
+//!     vec![
+//!         TokenKind::Ident,
+//!         TokenKind::Equals,
+//!         TokenKind::Ident,
+//!         TokenKind::Whitespace,
+//!         TokenKind::Comment,
+//!     ],
+//!     kinds
+//! );
+//! ```
+
+use crate::span::Span;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TokenKind {
+    /// A bare name or value, including numbers and `${VAR}` placeholders, e.g. `log-level`.
+    Ident,
+    /// A quoted string or a text block, including its quotes.
+    String,
+    /// An anchor naming a value, e.g. `&base`.
+    Anchor,
+    /// A reference to an anchored value, e.g. `*base`.
+    Reference,
+    Equals,
+    Comma,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Whitespace,
+    /// A comment, from `#` or `//` up to the end of the line, excluding the line ending.
+    Comment,
+    /// Text that does not form a token, e.g. a stray character or an unterminated string.
+    Error,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// An iterator over the tokens of an input. Created by [`tokenize`].
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+/// Returns an iterator over the tokens of `input`.
+pub fn tokenize(input: &str) -> Tokens<'_> {
+    Tokens { input, offset: 0 }
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+/// Returns the length of the comment at the start of `input`.
+fn comment(input: &str) -> usize {
+    let end = input.find('\n').unwrap_or(input.len());
+    if input[..end].ends_with('\r') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// Returns the kind and length of the quoted string at the start of `input`. A string ending at a
+/// line break or at the end of the input is an error.
+fn quoted(input: &str) -> (TokenKind, usize) {
+    let mut chars = input.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (TokenKind::String, i + 1),
+            '\n' => return (TokenKind::Error, i),
+            '\\' => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    (TokenKind::Error, input.len())
+}
+
+/// Returns the length of the bare name or value at the start of `input`.
+fn ident(input: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &input[len..];
+        if rest.starts_with("${") {
+            match rest.find('}') {
+                Some(i) => len += i + 1,
+                None => return len,
+            }
+            continue;
+        }
+        match rest.chars().next() {
+            Some(c) if c.is_alphanumeric() || "_-.+".contains(c) => len += c.len_utf8(),
+            _ => return len,
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.offset..];
+        let c = rest.chars().next()?;
+        let (kind, len) = match c {
+            c if is_space(c) => (
+                TokenKind::Whitespace,
+                rest.find(|c| !is_space(c)).unwrap_or(rest.len()),
+            ),
+            '#' => (TokenKind::Comment, comment(rest)),
+            '/' if rest.starts_with("//") => (TokenKind::Comment, comment(rest)),
+            '=' => (TokenKind::Equals, 1),
+            ',' => (TokenKind::Comma, 1),
+            '{' => (TokenKind::OpenBrace, 1),
+            '}' => (TokenKind::CloseBrace, 1),
+            '[' => (TokenKind::OpenBracket, 1),
+            ']' => (TokenKind::CloseBracket, 1),
+            '"' if rest.starts_with("\"\"\"") => match rest[3..].find("\"\"\"") {
+                Some(i) => (TokenKind::String, i + 6),
+                None => (TokenKind::Error, rest.len()),
+            },
+            '"' => quoted(rest),
+            '&' | '*' => {
+                let name = rest[1..].find(|c: char| !c.is_alphanumeric());
+                match name.unwrap_or(rest.len() - 1) {
+                    0 => (TokenKind::Error, 1),
+                    len if c == '&' => (TokenKind::Anchor, len + 1),
+                    len => (TokenKind::Reference, len + 1),
+                }
+            }
+            c => match ident(rest) {
+                0 => (TokenKind::Error, c.len_utf8()),
+                len => (TokenKind::Ident, len),
+            },
+        };
+        let start = self.offset;
+        self.offset += len;
+        Some(Token {
+            kind,
+            text: &rest[..len],
+            span: Span {
+                start,
+                end: self.offset,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "arbitrary")]
+    use crate::arbitrary::document_with_text;
+    use crate::prelude::*;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prop_assert_eq, proptest};
+
+    #[test]
+    fn test_tokenize() {
+        use TokenKind::*;
+
+        let input = "# x\r\n\"a b\"=&c -1.5e3 , d{ e=${F:-g}_h } // i\nj=[*c, \"\"\"\nk\"\"\"]";
+        let tokens = tokenize(input).collect::<Vec<_>>();
+        let expected = [
+            (Comment, "# x"),
+            (Whitespace, "\r\n"),
+            (String, "\"a b\""),
+            (Equals, "="),
+            (Anchor, "&c"),
+            (Whitespace, " "),
+            (Ident, "-1.5e3"),
+            (Whitespace, " "),
+            (Comma, ","),
+            (Whitespace, " "),
+            (Ident, "d"),
+            (OpenBrace, "{"),
+            (Whitespace, " "),
+            (Ident, "e"),
+            (Equals, "="),
+            (Ident, "${F:-g}_h"),
+            (Whitespace, " "),
+            (CloseBrace, "}"),
+            (Whitespace, " "),
+            (Comment, "// i"),
+            (Whitespace, "\n"),
+            (Ident, "j"),
+            (Equals, "="),
+            (OpenBracket, "["),
+            (Reference, "*c"),
+            (Comma, ","),
+            (Whitespace, " "),
+            (String, "\"\"\"\nk\"\"\""),
+            (CloseBracket, "]"),
+        ];
+        assert_eq!(
+            expected.to_vec(),
+            tokens.iter().map(|t| (t.kind, t.text)).collect::<Vec<_>>()
+        );
+        for token in tokens.iter() {
+            assert_eq!(token.text, &input[token.span.range()]);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let tests = [
+            (
+                "a=\"b\nc",
+                vec![(TokenKind::Error, "\"b"), (TokenKind::Whitespace, "\n")],
+            ),
+            ("a=\"\"\"b", vec![(TokenKind::Error, "\"\"\"b")]),
+            (
+                "a=@ *",
+                vec![(TokenKind::Error, "@"), (TokenKind::Whitespace, " ")],
+            ),
+            (
+                "a=$x",
+                vec![(TokenKind::Error, "$"), (TokenKind::Ident, "x")],
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let tokens = tokenize(input)
+                .skip(2)
+                .map(|t| (t.kind, t.text))
+                .collect::<Vec<_>>();
+            assert_eq!(expected[..], tokens[..expected.len()], "{}", input);
+            let text = tokenize(input).map(|t| t.text).collect::<String>();
+            assert_eq!(*input, text);
+        }
+        assert_eq!(Some(TokenKind::Error), tokenize("*").next().map(|t| t.kind));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_tokenize_round_trip(document in document_with_text()) {
+            let printed = crate::print_document(&document);
+            let tokens = tokenize(&printed).collect::<Vec<_>>();
+            prop_assert_eq!(&printed, &tokens.iter().map(|t| t.text).collect::<String>());
+            let errors = tokens.iter().filter(|t| t.kind == TokenKind::Error);
+            prop_assert_eq!(0, errors.count(), "{}", printed);
+        }
+    }
+}
@@ -1,108 +1,640 @@
-use nom::{
-    bytes::complete::tag,
-    character::complete::{alphanumeric1, multispace0, multispace1},
-    combinator::{map, opt},
-    multi::separated_list,
-    sequence::{delimited, terminated, tuple},
-    IResult,
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use core::{fmt, str::FromStr};
+use nom::{combinator::map, IResult};
+use prelude::*;
+use smallvec::SmallVec;
+
+mod prelude {
+    pub use alloc::{
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+    pub use smallvec::smallvec;
+}
+
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "arena")]
+pub mod arena;
+mod args;
+pub mod binary;
+pub mod borrowed;
+mod builder;
+mod canonical;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "clap")]
+pub mod clap;
+pub mod codegen;
+mod compat;
+mod config;
+pub mod convert;
+pub mod cst;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+mod diff;
+pub mod docgen;
+mod duplicates;
+mod encoding;
+mod env;
+mod error;
+mod fast;
+mod flatten;
+pub mod include;
+pub mod intern;
+mod iter;
+pub mod lexer;
+mod limits;
+pub mod lint;
+mod mapping;
+mod merge;
+mod number;
+mod options;
+mod parser;
+mod path;
+pub mod query;
+mod quirks;
+mod quote;
+mod recover;
+mod redact;
+mod reference;
+mod schema;
+mod select;
+mod sha256;
+#[cfg(feature = "sign")]
+pub mod sign;
+pub mod span;
+#[cfg(feature = "std")]
+mod stream;
+mod suggest;
+mod template;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "watch")]
+pub mod watch;
+mod width;
+
+#[cfg(feature = "std")]
+pub use args::from_env;
+pub use args::{from_args, from_vars, ArgError};
+pub use compat::{CompatIssue, CompatIssueKind};
+pub use config::{ConfigStack, Layer, Origin};
+pub use diff::{apply_patch, apply_patch_document, diff, diff_documents, DiffEntry, PatchError};
+pub use duplicates::{DuplicateError, DuplicatePolicy};
+pub use encoding::{parse_document_bytes, BytesError, Encoding};
+#[cfg(feature = "std")]
+pub use env::{resolve_env, resolve_env_document};
+pub use env::{resolve_vars, resolve_vars_document, EnvError};
+pub use error::{line_column, Limit, ParseError};
+pub use flatten::{flatten, flatten_document, unflatten, unflatten_document};
+pub use iter::{Iter, IterMut};
+pub use mapping::__private;
+pub use mapping::{DeserializeError, FujiDeserialize, FujiSerialize, ToSchema};
+pub use merge::MergeStrategy;
+pub use number::Number;
+pub use options::{ParseOptions, Strictness, ValidationOptions};
+pub use path::{Address, Path, PathError, Pattern, Segment};
+pub use recover::parse_document_lossy;
+pub use redact::Redaction;
+pub use reference::ReferenceError;
+pub use schema::{
+    decode_document_with_schema, decode_with_schema, print_redacted, SchemaError, SchemaRegistry,
+    Severity, ValidationError,
 };
+pub use select::Selector;
+#[cfg(feature = "tokio")]
+pub use stream::{parse_document_async, AsyncBindingReader};
+#[cfg(feature = "std")]
+pub use stream::{BindingReader, BindingWriter, ReadError};
+pub use template::{render, render_document, TemplateError};
+pub use width::{print_binding_width, print_document_width};
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-enum Schema {
-    Struct { fields: Vec<Field> },
-    Enum { variants: Vec<Variant> },
+pub enum Schema {
+    Struct {
+        fields: Vec<Field>,
+    },
+    Enum {
+        variants: Vec<Variant>,
+    },
     String,
     Bool,
+    /// A struct or enum that contains itself, through the references to `name` in `schema`.
+    Recursive {
+        name: String,
+        schema: Box<Schema>,
+    },
+    /// A reference to the enclosing recursive type of the given name.
+    Ref(String),
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct Variant {
+pub struct Variant {
     name: String,
     schema: Schema,
+    deprecated: Option<Deprecation>,
+    /// The documentation of the variant, from its `doc` attribute or `##` comment.
+    doc: Option<String>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct Field {
+pub struct Field {
     name: String,
     repeated: bool,
+    optional: bool,
+    /// Whether the values of the field are redacted by [`Schema::redact_secrets`].
+    secret: bool,
+    /// The minimum number of values of a repeated field, across all its bindings.
+    min: Option<usize>,
+    /// The maximum number of values of a repeated field, across all its bindings.
+    max: Option<usize>,
     schema: Schema,
+    /// The values bound to the field by [`Schema::apply_defaults`] when it is absent.
+    default: Option<Vec<Value>>,
+    deprecated: Option<Deprecation>,
+    /// The documentation of the field, from its `doc` attribute or `##` comment.
+    doc: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+/// The deprecation of a field or variant, which validation reports as a warning wherever it is
+/// used, e.g. `field=port{type=string deprecated={replacement=listen message="moved in v2"}}`.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Deprecation {
+    /// The name of the field or variant to use instead.
+    pub replacement: Option<String>,
+    /// Why the field or variant is deprecated, or how to migrate away from it.
+    pub message: Option<String>,
+}
+
+/// A name bound to zero or more values, e.g. `foo=a,b{c=d}`.
+///
+/// Bindings are ordered structurally: by name, then by their values compared in turn, with a
+/// prefix ordered first. `Hash` is consistent with this ordering and with equality, so bindings,
+/// values and documents can be used as map keys and sorted deterministically. Equality and order
+/// take the order of child bindings into account; canonicalize both sides to ignore it.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub struct Binding {
     name: String,
-    values: Vec<Value>,
+    values: Values,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+/// The values of a binding. Most bindings have a single value, which is stored inline to save an
+/// allocation. Child bindings are not stored inline, as they are usually absent and an empty
+/// `Vec` does not allocate.
+type Values = SmallVec<[Value; 1]>;
+
+/// A value: a scalar, a nested list, a map or a reference, with child bindings.
+///
+/// Values are ordered by their scalar, compared byte by byte, then by their kind, with scalars
+/// before nested lists, compared by their items, before maps, references and anchored scalars,
+/// compared by their anchors, and lastly by their child bindings compared in turn.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub struct Value {
     value: String,
+    kind: ValueKind,
     children: Vec<Binding>,
 }
 
+/// The syntactic form of a value.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub enum ValueKind {
+    /// A scalar, optionally followed by a block of children, e.g. `a` or `a{b=c}`.
+    Scalar,
+    /// A bracketed list nested in another one, e.g. `[b, c]` in `foo=[a, [b, c]]`. Its scalar is
+    /// empty and it has no children.
+    List(Vec<Value>),
+    /// A block of children without a leading scalar, e.g. `{a=1 b=2}`. Its scalar is empty.
+    Map,
+    /// A reference to an anchored value, e.g. `*base`, until `expand_references` replaces it. Its
+    /// scalar is the name of the anchor, and it has no children.
+    Reference,
+    /// A scalar named by an anchor, optionally followed by a block of children, e.g.
+    /// `&base server{port=80}`, until `expand_references` removes the anchor. Its scalar excludes
+    /// the anchor.
+    Anchored(String),
+}
+
+/// A sequence of top-level bindings, separated by whitespace, ordered by its bindings compared in
+/// turn.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub struct Document {
+    bindings: Vec<Binding>,
+    /// Where the values came from, for a document merged by a [`ConfigStack`].
+    origins: config::Origins,
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value {
+            value: value.to_string(),
+            kind: ValueKind::Scalar,
+            children: vec![],
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value {
+            value,
+            kind: ValueKind::Scalar,
+            children: vec![],
+        }
+    }
+}
+
+impl Binding {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns whether the name is bound to no value, as in `foo=`, as opposed to being absent.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl Value {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn children(&self) -> &[Binding] {
+        &self.children
+    }
+
+    pub fn kind(&self) -> &ValueKind {
+        &self.kind
+    }
+
+    /// Returns the first child binding named `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Binding> {
+        self.children.iter().find(|b| b.name == name)
+    }
+
+    /// Returns the first child binding named `name` ignoring case, if any, e.g. `LogLevel` for
+    /// `loglevel`.
+    pub fn get_ignore_case(&self, name: &str) -> Option<&Binding> {
+        self.children
+            .iter()
+            .find(|b| path::eq_ignore_case(&b.name, name))
+    }
+
+    /// Returns all child bindings named `name`, in document order, as a name may be bound more
+    /// than once.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Binding> + 'a {
+        self.children.iter().filter(move |b| b.name == name)
+    }
+
+    /// Returns the items of a nested list.
+    pub fn items(&self) -> Option<&[Value]> {
+        match &self.kind {
+            ValueKind::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the value itself, or the values nested in it if it is a list, recursively.
+    pub(crate) fn scalars(&self) -> Vec<&Value> {
+        match &self.kind {
+            ValueKind::List(items) => items.iter().flat_map(Value::scalars).collect(),
+            _ => vec![self],
+        }
+    }
+
+    pub(crate) fn scalars_mut(&mut self) -> Vec<&mut Value> {
+        if !matches!(self.kind, ValueKind::List(_)) {
+            return vec![self];
+        }
+        match &mut self.kind {
+            ValueKind::List(items) => items.iter_mut().flat_map(Value::scalars_mut).collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns whether the value is a map, i.e. children without a leading scalar.
+    pub fn is_map(&self) -> bool {
+        self.kind == ValueKind::Map
+    }
+
+    /// Returns whether the value is a scalar without children.
+    pub fn is_scalar(&self) -> bool {
+        self.kind == ValueKind::Scalar && self.children.is_empty()
+    }
+
+    /// Returns the value itself if it has no children.
+    pub fn as_str(&self) -> Option<&str> {
+        if self.is_scalar() {
+            Some(&self.value)
+        } else {
+            None
+        }
+    }
+}
+
+impl Document {
+    pub(crate) fn from_bindings(bindings: Vec<Binding>) -> Self {
+        Document {
+            bindings,
+            origins: config::Origins::default(),
+        }
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Returns the bindings for modification, dropping the origins recorded by a [`ConfigStack`],
+    /// which refer to bindings by position. All changes to the bindings go through this method.
+    pub(crate) fn bindings_mut(&mut self) -> &mut Vec<Binding> {
+        self.origins.clear();
+        &mut self.bindings
+    }
+}
+
 pub fn parse_binding(input: &str) -> IResult<&str, Binding> {
-    map(
-        tuple((
-            terminated(alphanumeric1, tag("=")),
-            separated_list(terminated(tag(","), multispace0), parse_value),
-        )),
-        |(name, values): (&str, Vec<Value>)| Binding {
-            name: name.to_string(),
-            values,
-        },
-    )(input)
+    map(parser::binding, borrowed::Binding::into_owned)(input)
+}
+
+/// Parses a single binding, surrounded by optional whitespace, that must span the entire input.
+pub fn parse_binding_complete(input: &str) -> Result<Binding, ParseError> {
+    borrowed::parse_binding_complete(input).map(borrowed::Binding::into_owned)
 }
 
+/// Prints a binding, bracketing its values if any of them is a list.
 pub fn print_binding(binding: &Binding) -> String {
-    format!(
-        "{}={}",
-        binding.name,
-        binding
-            .values
-            .iter()
-            .map(print_value)
-            .collect::<Vec<_>>()
-            .join(",")
-    )
+    let mut out = String::new();
+    print(&mut out, Print::Binding(binding));
+    out
+}
+
+/// A part of a tree that remains to be printed.
+enum Print<'a> {
+    Binding(&'a Binding),
+    Value(&'a Value),
+    Text(&'static str),
+}
+
+/// Pushes `items` onto `stack`, separated by `separator`, so that they are printed in order.
+fn push_joined<'a>(
+    stack: &mut Vec<Print<'a>>,
+    items: impl DoubleEndedIterator<Item = Print<'a>> + ExactSizeIterator,
+    separator: &'static str,
+) {
+    for (i, item) in items.enumerate().rev() {
+        stack.push(item);
+        if i > 0 {
+            stack.push(Print::Text(separator));
+        }
+    }
+}
+
+/// Prints `item` to `out` with an explicit stack rather than recursion, so that trees of any depth
+/// built programmatically can be printed without overflowing the stack.
+fn print(out: &mut String, item: Print) {
+    let mut stack = vec![item];
+    while let Some(item) = stack.pop() {
+        match item {
+            Print::Text(text) => out.push_str(text),
+            Print::Binding(binding) => {
+                out.push_str(&quote::key(&binding.name));
+                out.push('=');
+                if binding.values.iter().any(|v| v.items().is_some()) {
+                    out.push('[');
+                    stack.push(Print::Text("]"));
+                }
+                push_joined(&mut stack, binding.values.iter().map(Print::Value), ",");
+            }
+            Print::Value(value) => {
+                if let Some(items) = value.items() {
+                    out.push('[');
+                    stack.push(Print::Text("]"));
+                    push_joined(&mut stack, items.iter().map(Print::Value), ",");
+                    continue;
+                }
+                if !value.is_map() {
+                    out.push_str(&quote::value(value));
+                }
+                if !value.children.is_empty() || value.is_map() {
+                    out.push('{');
+                    stack.push(Print::Text("}"));
+                    push_joined(&mut stack, value.children.iter().map(Print::Binding), " ");
+                }
+            }
+        }
+    }
 }
 
 pub fn parse_value(input: &str) -> IResult<&str, Value> {
-    map(
-        tuple((
-            terminated(alphanumeric1, multispace0),
-            opt(delimited(
-                terminated(tag("{"), multispace0),
-                separated_list(multispace1, parse_binding),
-                terminated(tag("}"), multispace0),
-            )),
-        )),
-        |(value, children): (&str, Option<Vec<Binding>>)| Value {
-            value: value.to_string(),
-            children: children.unwrap_or(vec![]),
-        },
-    )(input)
+    map(parser::value, borrowed::Value::into_owned)(input)
 }
 
 pub fn print_value(value: &Value) -> String {
-    let children = if value.children.is_empty() {
-        "".to_string()
-    } else {
-        format!(
-            "{{{}}}",
-            value
-                .children
-                .iter()
-                .map(print_binding)
-                .collect::<Vec<_>>()
-                .join(" ")
-        )
+    let mut out = String::new();
+    print(&mut out, Print::Value(value));
+    out
+}
+
+pub fn parse_document(input: &str) -> IResult<&str, Document> {
+    map(parser::document, borrowed::Document::into_owned)(input)
+}
+
+/// Parses a document that must span the entire input.
+pub fn parse_document_complete(input: &str) -> Result<Document, ParseError> {
+    borrowed::parse_document_complete(input).map(borrowed::Document::into_owned)
+}
+
+/// Parses a document that must span the entire input, as configured by `options`. Syntax quirks
+/// that `options` warn about are accepted silently; see `parse_document_with_warnings`.
+pub fn parse_document_with(input: &str, options: &ParseOptions) -> Result<Document, ParseError> {
+    parse_document_with_warnings(input, options).map(|(document, _)| document)
+}
+
+/// Parses a document as `parse_document_with` does, also returning the syntax quirks that
+/// `options` warn about, in the order they appear in `input`.
+pub fn parse_document_with_warnings(
+    input: &str,
+    options: &ParseOptions,
+) -> Result<(Document, Vec<ParseError>), ParseError> {
+    if let Some(max) = options.max_input_length {
+        limits::check_length(input, max)?;
+    }
+    if let Some(max) = options.max_depth {
+        limits::check_depth(input, max)?;
+    }
+    let mut warnings = vec![];
+    let quoted =
+        match options.special_characters {
+            Strictness::Reject => None,
+            strictness => quirks::quote_special(input).inspect(|quoted| {
+                if strictness == Strictness::Warn {
+                    warnings.extend(quoted.runs.iter().map(|run| {
+                        ParseError::new(input, *run, "quotes around special characters")
+                    }));
+                }
+            }),
+        };
+    // The input actually parsed, in which errors are located before being relocated in `input`.
+    let source = quoted
+        .as_ref()
+        .map_or(input, |quoted| quoted.input.as_str());
+    let original = |offset| {
+        quoted
+            .as_ref()
+            .map_or(offset, |quoted| quoted.original(offset))
+    };
+    let relocate = |error: ParseError| match &quoted {
+        Some(_) => ParseError {
+            limit: error.limit,
+            ..ParseError::new(input, original(error.offset), &error.expected)
+        },
+        None => error,
     };
-    format!("{}{}", value.value, children)
+
+    let mut document = match options.trailing_input {
+        Strictness::Reject => borrowed::parse_document_complete(source),
+        strictness => match parser::document::<error::Expected>(source) {
+            Ok((rest, document)) => {
+                if !rest.is_empty() && strictness == Strictness::Warn {
+                    let offset = source.len() - rest.len();
+                    warnings.push(relocate(ParseError::new(source, offset, "end of input")));
+                }
+                Ok(document)
+            }
+            Err(e) => Err(error::convert(source, e)),
+        },
+    }
+    .map_err(relocate)?;
+    if let Some(max) = options.max_nodes {
+        limits::check_nodes(source, &document.bindings, max).map_err(relocate)?;
+    }
+
+    let commas = match options.trailing_commas {
+        Strictness::Accept => vec![],
+        _ => quirks::trailing_commas(source),
+    };
+    for offset in commas {
+        let comma = ParseError::new(input, original(offset), "no trailing comma");
+        match options.trailing_commas {
+            Strictness::Reject => return Err(comma),
+            _ => warnings.push(comma),
+        }
+    }
+
+    let duplicate_names = match options.duplicates {
+        Some(DuplicatePolicy::Error) => Strictness::Reject,
+        _ => options.duplicate_names,
+    };
+    if duplicate_names != Strictness::Accept {
+        for mut duplicate in duplicates::find_parsed(source, &document.bindings) {
+            duplicate.first = original(duplicate.first);
+            duplicate.second = original(duplicate.second);
+            match duplicate_names {
+                Strictness::Reject => return Err(duplicate.error(input)),
+                _ => warnings.push(duplicate.error(input)),
+            }
+        }
+    }
+    if let Some(lookup) = options.vars {
+        for binding in document.bindings.iter_mut() {
+            env::resolve_parsed(source, binding, &lookup).map_err(relocate)?;
+        }
+    }
+    let mut document = document.into_owned();
+    if let Some(policy) = options.duplicates {
+        duplicates::collapse(document.bindings_mut(), policy);
+    }
+    warnings.sort_by_key(|warning| warning.offset);
+    Ok((document, warnings))
+}
+
+pub fn print_document(document: &Document) -> String {
+    let mut out = String::new();
+    for (i, binding) in document.bindings.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        print(&mut out, Print::Binding(binding));
+    }
+    out
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print_binding(self))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print_value(self))
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print_document(self))
+    }
+}
+
+impl FromStr for Binding {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_binding_complete(s)
+    }
+}
+
+impl FromStr for Document {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_document_complete(s)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "arbitrary")]
+    use crate::arbitrary::document_with_text;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    #[test]
+    fn test_print_deep() {
+        // Printing does not recurse, so trees built programmatically can be any depth.
+        let depth = 200_000;
+        let mut binding = Binding::new("a").value("b");
+        for _ in 1..depth {
+            binding = Binding::new("a").value("b").child(binding);
+        }
+        let expected = "a=b{".repeat(depth - 1) + "a=b" + &"}".repeat(depth - 1);
+        assert_eq!(expected, print_binding(&binding));
+        let document = Document::from_bindings(vec![binding]);
+        assert_eq!(expected, print_document(&document));
+        limits::drop_deep(document.bindings.into_iter().next().unwrap());
+
+        let mut list = Value::new("c");
+        for _ in 0..depth {
+            list = Value::list(vec![list]);
+        }
+        let binding = Binding::new("l").value(list);
+        let expected = format!("l=[{}c{}]", "[".repeat(depth), "]".repeat(depth));
+        assert_eq!(expected, print_binding(&binding));
+        limits::drop_deep(binding);
+    }
 
     #[test]
     fn test_parse_binding() {
@@ -110,7 +642,7 @@ mod tests {
             string: String,
             canonical: String,
             value: Binding,
-        };
+        }
 
         let tests = vec![
             Test {
@@ -118,8 +650,9 @@ mod tests {
                 canonical: "foo=bar".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![],
                     }],
                 },
@@ -129,8 +662,9 @@ mod tests {
                 canonical: "foo=true".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "true".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![],
                     }],
                 },
@@ -140,13 +674,15 @@ mod tests {
                 canonical: "foo=a,b".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![
+                    values: smallvec![
                         Value {
                             value: "a".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![],
                         },
                         Value {
                             value: "b".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![],
                         },
                     ],
@@ -157,12 +693,14 @@ mod tests {
                 canonical: "foo=bar{zoo=qat}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![Binding {
                             name: "zoo".to_string(),
-                            values: vec![Value {
+                            values: smallvec![Value {
                                 value: "qat".to_string(),
+                                kind: ValueKind::Scalar,
                                 children: vec![],
                             }],
                         }],
@@ -174,23 +712,27 @@ mod tests {
                 canonical: "foo=bar{zoo=qat},xxx{aaa=bbb}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![
+                    values: smallvec![
                         Value {
                             value: "bar".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "zoo".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "qat".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![],
                                 }],
                             }],
                         },
                         Value {
                             value: "xxx".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "aaa".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "bbb".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![],
                                 }],
                             }],
@@ -203,17 +745,20 @@ mod tests {
                 canonical: "a=b{c=d{e=f}},k{l=m{n=o}}".to_string(),
                 value: Binding {
                     name: "a".to_string(),
-                    values: vec![
+                    values: smallvec![
                         Value {
                             value: "b".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "c".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "d".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![Binding {
                                         name: "e".to_string(),
-                                        values: vec![Value {
+                                        values: smallvec![Value {
                                             value: "f".to_string(),
+                                            kind: ValueKind::Scalar,
                                             children: vec![],
                                         }],
                                     }],
@@ -222,14 +767,17 @@ mod tests {
                         },
                         Value {
                             value: "k".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "l".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "m".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![Binding {
                                         name: "n".to_string(),
-                                        values: vec![Value {
+                                        values: smallvec![Value {
                                             value: "o".to_string(),
+                                            kind: ValueKind::Scalar,
                                             children: vec![],
                                         }],
                                     }],
@@ -244,23 +792,27 @@ mod tests {
                 canonical: "foo=bar{zoo=qat},xxx{aaa=bbb}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![
+                    values: smallvec![
                         Value {
                             value: "bar".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "zoo".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "qat".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![],
                                 }],
                             }],
                         },
                         Value {
                             value: "xxx".to_string(),
+                            kind: ValueKind::Scalar,
                             children: vec![Binding {
                                 name: "aaa".to_string(),
-                                values: vec![Value {
+                                values: smallvec![Value {
                                     value: "bbb".to_string(),
+                                    kind: ValueKind::Scalar,
                                     children: vec![],
                                 }],
                             }],
@@ -273,12 +825,14 @@ mod tests {
                 canonical: "foo=bar{zoo=qat}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![Binding {
                             name: "zoo".to_string(),
-                            values: vec![Value {
+                            values: smallvec![Value {
                                 value: "qat".to_string(),
+                                kind: ValueKind::Scalar,
                                 children: vec![],
                             }],
                         }],
@@ -290,12 +844,14 @@ mod tests {
                 canonical: "foo=bar{zoo=qat}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![Binding {
                             name: "zoo".to_string(),
-                            values: vec![Value {
+                            values: smallvec![Value {
                                 value: "qat".to_string(),
+                                kind: ValueKind::Scalar,
                                 children: vec![],
                             }],
                         }],
@@ -307,12 +863,69 @@ mod tests {
                 canonical: "foo=bar111{zoo=qat}".to_string(),
                 value: Binding {
                     name: "foo".to_string(),
-                    values: vec![Value {
+                    values: smallvec![Value {
                         value: "bar111".to_string(),
+                        kind: ValueKind::Scalar,
+                        children: vec![Binding {
+                            name: "zoo".to_string(),
+                            values: smallvec![Value {
+                                value: "qat".to_string(),
+                                kind: ValueKind::Scalar,
+                                children: vec![],
+                            }],
+                        }],
+                    }],
+                },
+            },
+            Test {
+                string: "foo=bar{a=b c=d,e}".to_string(),
+                canonical: "foo=bar{a=b c=d,e}".to_string(),
+                value: Binding {
+                    name: "foo".to_string(),
+                    values: smallvec![Value {
+                        value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
+                        children: vec![
+                            Binding {
+                                name: "a".to_string(),
+                                values: smallvec![Value {
+                                    value: "b".to_string(),
+                                    kind: ValueKind::Scalar,
+                                    children: vec![],
+                                }],
+                            },
+                            Binding {
+                                name: "c".to_string(),
+                                values: smallvec![
+                                    Value {
+                                        value: "d".to_string(),
+                                        kind: ValueKind::Scalar,
+                                        children: vec![],
+                                    },
+                                    Value {
+                                        value: "e".to_string(),
+                                        kind: ValueKind::Scalar,
+                                        children: vec![],
+                                    },
+                                ],
+                            },
+                        ],
+                    }],
+                },
+            },
+            Test {
+                string: "foo=bar{ # comment\n  zoo=qat // another\n}".to_string(),
+                canonical: "foo=bar{zoo=qat}".to_string(),
+                value: Binding {
+                    name: "foo".to_string(),
+                    values: smallvec![Value {
+                        value: "bar".to_string(),
+                        kind: ValueKind::Scalar,
                         children: vec![Binding {
                             name: "zoo".to_string(),
-                            values: vec![Value {
+                            values: smallvec![Value {
                                 value: "qat".to_string(),
+                                kind: ValueKind::Scalar,
                                 children: vec![],
                             }],
                         }],
@@ -327,4 +940,369 @@ mod tests {
             assert_eq!(t.canonical, print_binding(&t.value));
         }
     }
+
+    #[test]
+    fn test_parse_document_comments() {
+        let input = "# leading comment\na=b # trailing\n\n// between\nc=d{e=f}#end";
+        assert_eq!(
+            Ok("a=b\nc=d{e=f}".to_string()),
+            parse_document_complete(input).map(|d| print_document(&d))
+        );
+    }
+
+    #[test]
+    fn test_accessors() {
+        let document = parse_document_complete("a=b{c=d},e").unwrap();
+        let binding = &document.bindings()[0];
+        assert_eq!("a", binding.name());
+        let values = binding.values();
+        assert_eq!(
+            ("b", false, None),
+            (values[0].value(), values[0].is_scalar(), values[0].as_str())
+        );
+        assert_eq!(Some("e"), values[1].as_str());
+        assert_eq!("c", values[0].children()[0].name());
+    }
+
+    #[test]
+    fn test_inline_values() {
+        let document = parse_document_complete("a=b{c=d} e=f,g").unwrap();
+        let [single, repeated] = [&document.bindings[0], &document.bindings[1]];
+        assert!(!single.values.spilled());
+        assert!(!single.values[0].children[0].values.spilled());
+        assert!(repeated.values.spilled());
+    }
+
+    #[test]
+    fn test_ord() {
+        let document =
+            parse_document_complete("b=1 a=2{c=d} a=2 a=[[x]] a={c=d} a=2,3 a= a=2").unwrap();
+        let mut bindings = document.bindings().to_vec();
+        bindings.sort();
+        let sorted = bindings.iter().map(print_binding).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["a=", "a=[[x]]", "a={c=d}", "a=2", "a=2", "a=2,3", "a=2{c=d}", "b=1"],
+            sorted
+        );
+
+        let set = document
+            .bindings()
+            .iter()
+            .collect::<alloc::collections::BTreeSet<_>>();
+        assert_eq!(7, set.len());
+    }
+
+    #[test]
+    fn test_get() {
+        let value = parse_value("a{b=1 c=2 b=3,4}").unwrap().1;
+        assert_eq!(Some("1"), value.get("b").map(|b| b.values()[0].value()));
+        assert_eq!(None, value.get("d"));
+        let values = value
+            .get_all("b")
+            .flat_map(Binding::values)
+            .map(Value::value)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["1", "3", "4"], values);
+        assert_eq!(0, value.get_all("d").count());
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        let binding: Binding = " foo=bar{zoo=qat},baz ".parse().unwrap();
+        assert_eq!("foo=bar{zoo=qat},baz", binding.to_string());
+        assert_eq!("bar{zoo=qat}", binding.values()[0].to_string());
+        assert_eq!(
+            Err(ParseError::new("foo=bar x", 8, "end of input")),
+            "foo=bar x".parse::<Binding>()
+        );
+
+        let document: Document = "a=b\nc=d".parse().unwrap();
+        assert_eq!("a=b\nc=d", format!("{}", document));
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let input = "città=Roma{straße=Ünïcødé,東京 名前=値}";
+        let document = parse_document_complete(input).unwrap();
+        assert_eq!(input, document.to_string());
+        assert_eq!(
+            Some("東京"),
+            document.get("città.straße[1]").and_then(Value::as_str)
+        );
+        assert_eq!(
+            Some("12à"),
+            parse_document_complete("a=12à")
+                .unwrap()
+                .get("a")
+                .and_then(Value::as_str)
+        );
+        assert_eq!(
+            Err(ParseError::new("a=b@c", 3, "end of input")),
+            parse_document_complete("a=b@c")
+        );
+    }
+
+    #[test]
+    fn test_keys_and_quoting() {
+        struct Test {
+            input: &'static str,
+            result: Result<&'static str, ParseError>,
+        }
+
+        let tests = [
+            Test {
+                input: "max_connections=10 log-level=debug service.name=api-v1.2 _a=_b",
+                result: Ok("max_connections=10\nlog-level=debug\nservice.name=api-v1.2\n_a=_b"),
+            },
+            Test {
+                input: r#""a b"="x=y{}" c="" "d"="e""#,
+                result: Ok("\"a b\"=\"x=y{}\"\nc=\"\"\nd=e"),
+            },
+            Test {
+                input: r#"a="line\nbreak \"q\" \\ \t","-x","-1""#,
+                result: Ok(r#"a="line\nbreak \"q\" \\ \t",-x,-1"#),
+            },
+            Test {
+                input: r#"a=&x "b c",*x,${A:-b}"#,
+                result: Ok(r#"a=&x "b c",*x,${A:-b}"#),
+            },
+            Test {
+                input: "a=-x,-,1.2.3",
+                result: Ok("a=-x,-,1.2.3"),
+            },
+            Test {
+                input: "a@=b",
+                result: Err(ParseError::new("a@=b", 1, "'='")),
+            },
+            Test {
+                input: "a=\"b",
+                result: Err(ParseError::new("a=\"b", 4, "'\"'")),
+            },
+            Test {
+                input: r#"a="\q""#,
+                result: Err(ParseError::new(r#"a="\q""#, 3, "escape sequence")),
+            },
+        ];
+
+        for t in tests.iter() {
+            let result = parse_document_complete(t.input).map(|document| document.to_string());
+            assert_eq!(t.result.clone().map(str::to_string), result, "{}", t.input);
+            if let Ok(printed) = result {
+                assert_eq!(
+                    parse_document_complete(t.input),
+                    parse_document_complete(&printed)
+                );
+            }
+        }
+
+        let document = parse_document_complete(r#""a\"b"="c\nd""#).unwrap();
+        assert_eq!("a\"b", document.bindings()[0].name());
+        assert_eq!(Some("c\nd"), document.bindings()[0].values()[0].as_str());
+    }
+
+    #[test]
+    fn test_empty_values() {
+        let input = "a= b=c{d=}\ne=,f";
+        assert_eq!(
+            Err(ParseError::new(input, 13, "end of input")),
+            parse_document_complete(input)
+        );
+
+        let document = parse_document_complete("a= b=c{d=  }\ne=\"\"").unwrap();
+        assert_eq!("a=\nb=c{d=}\ne=\"\"", document.to_string());
+        let bindings = document.bindings();
+        assert!(bindings[0].is_empty());
+        assert!(!bindings[2].is_empty());
+        assert_eq!(Some(""), bindings[2].values()[0].as_str());
+        assert_eq!(
+            Some(&[][..]),
+            document.get_binding("b.d").map(Binding::values)
+        );
+        assert_eq!(None, document.get("b.d"));
+        assert_eq!(None, document.get_binding("b.x"));
+        assert_eq!(Binding::new("a"), parse_binding_complete("a=").unwrap());
+    }
+
+    #[test]
+    fn test_print_quoting() {
+        let tests = [
+            (Binding::new("a").value("b{c}"), r#"a="b{c}""#),
+            (
+                Binding::new("a b").value("x,y").value("="),
+                r#""a b"="x,y","=""#,
+            ),
+            (Binding::new("").value(""), r#"""="""#),
+            (Binding::new("a").value("-").value("1e3"), r#"a=-,1e3"#),
+            (
+                Binding::new("a")
+                    .value("*x")
+                    .value(Value::new("*x").child(Binding::new("b"))),
+                r#"a="*x","*x"{b=}"#,
+            ),
+            (
+                Binding::new("a")
+                    .value(Value::reference("x"))
+                    .value(Value::new("y z").anchor("x")),
+                r#"a=*x,&x "y z""#,
+            ),
+            (Binding::new("a").value("&x y z"), r#"a="&x y z""#),
+            (
+                Binding::new("a").value("say \"hi\"\n"),
+                r#"a="say \"hi\"\n""#,
+            ),
+        ];
+
+        for (binding, printed) in tests.iter() {
+            assert_eq!(*printed, print_binding(binding));
+            assert_eq!(Ok(binding.clone()), parse_binding_complete(printed));
+        }
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let tests = [
+            ("a=b,c,", Ok("a=b,c")),
+            ("a=b, c ,\nd=e,", Ok("a=b,c\nd=e")),
+            ("a=b{c=d,e, # x\n}", Ok("a=b{c=d,e}")),
+            ("a=b,\n\"c d\"=e", Ok("a=b\n\"c d\"=e")),
+            ("a=b,\nc", Ok("a=b,c")),
+            ("a=b,,", Err(ParseError::new("a=b,,", 4, "identifier"))),
+            ("a=,", Err(ParseError::new("a=,", 2, "end of input"))),
+            (
+                "a=b,c=d",
+                Err(ParseError::new("a=b,c=d", 4, "end of input")),
+            ),
+        ];
+
+        for (input, printed) in tests.iter() {
+            let document = parse_document_complete(input).map(|d| d.to_string());
+            assert_eq!(printed.clone().map(str::to_string), document, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_lists() {
+        let tests = [
+            ("a=[b, c{d=e}, ]", Ok("a=b,c{d=e}")),
+            ("a=[] b=[ ]", Ok("a=\nb=")),
+            ("a=[[b,c],[],d]", Ok("a=[[b,c],[],d]")),
+            ("a=[[[b]]]", Ok("a=[[[b]]]")),
+            (
+                "a=[\n  # first\n  b,\n  c{d=[e,[f]]}\n]",
+                Ok("a=b,c{d=[e,[f]]}"),
+            ),
+            ("a=[b,c", Err(ParseError::new("a=[b,c", 6, "']'"))),
+            ("a=[b c]", Err(ParseError::new("a=[b c]", 5, "']'"))),
+            (
+                "a=[b],c",
+                Err(ParseError::new("a=[b],c", 5, "end of input")),
+            ),
+            ("a=b,[c]", Err(ParseError::new("a=b,[c]", 4, "identifier"))),
+        ];
+
+        for (input, printed) in tests.iter() {
+            let document = parse_document_complete(input).map(|d| d.to_string());
+            assert_eq!(printed.clone().map(str::to_string), document, "{}", input);
+        }
+
+        let binding = parse_binding_complete("a=[[b,c],d]").unwrap();
+        assert_eq!(None, binding.values()[0].as_str());
+        let items = binding.values()[0].items().unwrap();
+        assert_eq!(
+            vec![Some("b"), Some("c")],
+            items.iter().map(Value::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Binding::new("a")
+                .value(Value::list(vec![Value::new("b"), Value::new("c")]))
+                .value("d"),
+            binding
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let tests = [
+            ("a={b=1 c=2}", Ok("a={b=1 c=2}")),
+            ("a={ } b=\"\"{c=d}", Ok("a={}\nb=\"\"{c=d}")),
+            ("a=[{b={c=d}},[{e=f},{}]]", Ok("a=[{b={c=d}},[{e=f},{}]]")),
+            ("a={b=c", Err(ParseError::new("a={b=c", 6, "'}'"))),
+        ];
+
+        for (input, printed) in tests.iter() {
+            let document = parse_document_complete(input).map(|d| d.to_string());
+            assert_eq!(printed.clone().map(str::to_string), document, "{}", input);
+        }
+
+        let binding = parse_binding_complete("a={b=c},\"\"{b=c}").unwrap();
+        assert!(binding.values()[0].is_map());
+        assert!(!binding.values()[1].is_map());
+        assert_eq!(
+            Binding::new("a")
+                .value(Value::map(vec![Binding::new("b").value("c")]))
+                .value(Value::new("").child(Binding::new("b").value("c"))),
+            binding
+        );
+    }
+
+    #[test]
+    fn test_text_blocks() {
+        let tests = [
+            ("a=\"\"\"\n  x\n    y\n\n  \"\"\"", Ok("x\n  y\n")),
+            ("a=\"\"\"  \nx \\n \"y\"\n\"\"\"", Ok("x \\n \"y\"")),
+            (
+                "a=\"\"\"\n\t  # not a comment\n\t\"\"\"",
+                Ok("  # not a comment"),
+            ),
+            ("a=\"\"\"\n\"\"\"", Ok("")),
+            (
+                "a=\"\"\"x\n\"\"\"",
+                Err(ParseError::new("a=\"\"\"x\n\"\"\"", 5, "line break")),
+            ),
+            (
+                "a=\"\"\"\nx",
+                Err(ParseError::new("a=\"\"\"\nx", 7, "'\"\"\"'")),
+            ),
+            (
+                "a=\"\"\"\nx \"\"\"",
+                Err(ParseError::new("a=\"\"\"\nx \"\"\"", 6, "line break")),
+            ),
+            (
+                "a=\"\"\"\n x\n  \"\"\"",
+                Err(ParseError::new("a=\"\"\"\n x\n  \"\"\"", 6, "indentation")),
+            ),
+        ];
+
+        for (input, value) in tests.iter() {
+            let binding = parse_binding_complete(input);
+            let value = value.clone().map(|v| Binding::new("a").value(v));
+            assert_eq!(value, binding, "{}", input);
+        }
+
+        let binding = parse_binding_complete("a=&x \"\"\"\n  b\n  c\n  \"\"\"{d=e},f").unwrap();
+        assert_eq!("b\nc", binding.values()[0].value());
+        assert_eq!("a=&x \"b\\nc\"{d=e},f", binding.to_string());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_binding_round_trip(binding in any::<Binding>()) {
+            prop_assert_eq!(Ok(binding.clone()), parse_binding_complete(&print_binding(&binding)));
+        }
+
+        #[test]
+        fn test_document_round_trip(document in any::<Document>()) {
+            prop_assert_eq!(
+                Ok(document.clone()),
+                parse_document_complete(&print_document(&document))
+            );
+        }
+
+        #[test]
+        fn test_text_round_trip(document in document_with_text()) {
+            let printed = print_document(&document);
+            prop_assert_eq!(Ok(document), parse_document_complete(&printed), "{}", printed);
+        }
+    }
 }
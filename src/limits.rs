@@ -0,0 +1,220 @@
+//! Checks of the limits that `ParseOptions` can set on the size of the input. The length and
+//! nesting of the input are checked before parsing it, so that parsing never starts on input that
+//! is too large or too deep.
+//!
+//! Regardless of the options, the parsers reject input nesting more than `MAX_DEPTH` blocks and
+//! lists, as they recurse into each of them.
+
+use crate::borrowed::{Binding, Value, ValueKind};
+use crate::error::offset_in;
+use crate::lexer::{tokenize, TokenKind};
+use crate::{Limit, ParseError};
+
+/// The maximum number of blocks and lists nested in one another that the parsers accept, which
+/// keeps their recursion well within the default stack of a thread, even in debug builds.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+/// Fails if `input` is longer than `max` bytes, at the first character beyond them.
+pub(crate) fn check_length(input: &str, max: usize) -> Result<(), ParseError> {
+    if input.len() <= max {
+        return Ok(());
+    }
+    let offset = (0..=max).rev().find(|i| input.is_char_boundary(*i));
+    Err(ParseError::exceeds(
+        input,
+        offset.unwrap_or(0),
+        Limit::InputLength,
+        max,
+    ))
+}
+
+/// Fails if `input` has more than `max` braces and brackets open at once, at the first one
+/// beyond them. Braces and brackets in strings and comments are ignored.
+pub(crate) fn check_depth(input: &str, max: usize) -> Result<(), ParseError> {
+    let mut depth = 0usize;
+    for token in tokenize(input) {
+        match token.kind {
+            TokenKind::OpenBrace | TokenKind::OpenBracket => {
+                depth += 1;
+                if depth > max {
+                    return Err(ParseError::exceeds(
+                        input,
+                        token.span.start,
+                        Limit::Depth,
+                        max,
+                    ));
+                }
+            }
+            TokenKind::CloseBrace | TokenKind::CloseBracket => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Fails if `bindings`, parsed from `input`, have more than `max` bindings and values, at the
+/// first one beyond them.
+pub(crate) fn check_nodes(input: &str, bindings: &[Binding], max: usize) -> Result<(), ParseError> {
+    let mut remaining = max;
+    count_bindings(input, bindings, 0, &mut remaining)
+        .map_err(|offset| ParseError::exceeds(input, offset, Limit::Nodes, max))
+}
+
+/// Counts `bindings` and their values down from `remaining`, returning the offset of the first
+/// node beyond them. Nodes that were unescaped from the input are located at their parent.
+fn count_bindings(
+    input: &str,
+    bindings: &[Binding],
+    parent: usize,
+    remaining: &mut usize,
+) -> Result<(), usize> {
+    for binding in bindings {
+        let at = offset_in(input, &binding.name).unwrap_or(parent);
+        *remaining = remaining.checked_sub(1).ok_or(at)?;
+        for value in binding.values.iter() {
+            count_value(input, value, at, remaining)?;
+        }
+    }
+    Ok(())
+}
+
+fn count_value(
+    input: &str,
+    value: &Value,
+    parent: usize,
+    remaining: &mut usize,
+) -> Result<(), usize> {
+    let at = offset_in(input, &value.value).unwrap_or(parent);
+    *remaining = remaining.checked_sub(1).ok_or(at)?;
+    if let ValueKind::List(items) = &value.kind {
+        for item in items {
+            count_value(input, item, at, remaining)?;
+        }
+    }
+    count_bindings(input, &value.children, at, remaining)
+}
+
+/// Drops `binding` without recursing into its values, as dropping it normally would, so that tests
+/// can build trees too deep for that.
+#[cfg(test)]
+pub(crate) fn drop_deep(binding: crate::Binding) {
+    let mut values = binding.values.into_vec();
+    while let Some(mut value) = values.pop() {
+        if let crate::ValueKind::List(items) = &mut value.kind {
+            values.append(items);
+        }
+        for child in value.children.drain(..) {
+            values.extend(child.values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_DEPTH;
+    use crate::prelude::*;
+    use crate::{cst, parse_binding_complete, parse_document_complete, parse_document_with};
+    use crate::{Limit, ParseOptions, Strictness};
+
+    #[test]
+    fn test_limits() {
+        let tests = [
+            (ParseOptions::new().max_input_length(3), "a=b", None),
+            (
+                ParseOptions::new().max_input_length(3),
+                "a=bc",
+                Some("1:4: expected at most 3 bytes of input, found `c`"),
+            ),
+            (
+                ParseOptions::new().max_input_length(4),
+                "a=\"é\"",
+                Some("1:4: expected at most 4 bytes of input, found `é\"`"),
+            ),
+            (ParseOptions::new().max_depth(2), "a={b=[c,d]}", None),
+            (
+                ParseOptions::new().max_depth(1),
+                "a={b=[c,d]} e=[f]",
+                Some("1:6: expected at most 1 levels of nesting, found `[c,d]} e=[f]`"),
+            ),
+            (ParseOptions::new().max_depth(0), "a=\"{\" # {", None),
+            (ParseOptions::new().max_nodes(4), "a=[b,[c]]", None),
+            (
+                ParseOptions::new().max_nodes(3),
+                "a=[b,[c]]",
+                Some("1:7: expected at most 3 bindings and values, found `c]]`"),
+            ),
+            (
+                ParseOptions::new().max_nodes(3),
+                "a=b{c=d}",
+                Some("1:7: expected at most 3 bindings and values, found `d}`"),
+            ),
+            (
+                ParseOptions::new().max_nodes(2),
+                "a=b,c",
+                Some("1:5: expected at most 2 bindings and values, found `c`"),
+            ),
+            (
+                ParseOptions::new()
+                    .max_nodes(1)
+                    .special_characters(Strictness::Accept),
+                "url=http://x",
+                Some("1:5: expected at most 1 bindings and values, found `http://x`"),
+            ),
+        ];
+
+        for (options, input, expected) in tests.iter() {
+            let result = parse_document_with(input, options);
+            assert_eq!(
+                *expected,
+                result.as_ref().err().map(|e| e.to_string()).as_deref(),
+                "{}",
+                input
+            );
+        }
+
+        let options = ParseOptions::new().max_depth(100);
+        let error = parse_document_with(&"a={".repeat(1000), &options).unwrap_err();
+        assert_eq!(Some(Limit::Depth), error.limit);
+        assert_eq!(None, parse_document_with("a", &options).unwrap_err().limit);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |(prefix, open, value, close): (&str, &str, &str, &str), depth: usize| {
+            let (open, close) = (open.repeat(depth), close.repeat(depth));
+            format!("{}{}{}{}", prefix, open, value, close)
+        };
+        let tests = [
+            ("", "a={", "a=b", "}"),
+            ("", "a=b{", "a=b", "}"),
+            ("a=", "[", "b", "]"),
+        ];
+        for test in tests.iter().copied() {
+            let (prefix, open, _, _) = test;
+            let input = nested(test, MAX_DEPTH);
+            assert!(parse_document_complete(&input).is_ok(), "{}", open);
+            assert!(cst::parse_document(&input).is_ok(), "{}", open);
+
+            let input = nested(test, MAX_DEPTH + 1);
+            let offset = prefix.len() + open.len() * (MAX_DEPTH + 1) - 1;
+            let error = parse_document_complete(&input).unwrap_err();
+            assert_eq!(Some(Limit::Depth), error.limit);
+            assert_eq!(offset, error.offset, "{}", open);
+            assert_eq!("at most 64 levels of nesting", error.expected);
+            assert_eq!(
+                Some(offset),
+                cst::parse_document(&input).err().map(|e| e.offset)
+            );
+
+            // Far deeper input fails just as well instead of overflowing the stack.
+            let input = nested(test, 100_000);
+            assert_eq!(
+                Some(offset),
+                parse_binding_complete(&input).err().map(|e| e.offset)
+            );
+            let options = ParseOptions::lenient();
+            let error = parse_document_with(&input, &options).unwrap_err();
+            assert_eq!((Some(Limit::Depth), offset), (error.limit, error.offset));
+        }
+    }
+}
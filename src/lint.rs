@@ -0,0 +1,429 @@
+//! Lints: checks for constructs that are valid but likely mistakes, such as duplicate keys.
+//!
+//! Each check is a [`LintRule`], run over the concrete syntax tree of a document so that it can
+//! tell how values were written, e.g. whether they were quoted. A [`Linter`] runs the built-in
+//! rules, minus any that are allowed, plus any custom ones:
+//!
+//! ```
+//! use flax::cst;
+//! use flax::lint::Linter;
+//!
+//! let document = cst::parse_document("port=80 Port=8080 tls={}").unwrap();
+//! let lints = Linter::new().allow("unquoted-literals").lint(&document);
+//! let messages = lints.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+//! assert_eq!(
+//!     vec![
+//!         "key `Port` differs only in case from `port` [case-collisions]",
+//!         "empty block [empty-blocks]",
+//!     ],
+//!     messages
+//! );
+//! ```
+//!
+//! Blocks nested within lists are not linted, as their bindings have no `Address`.
+
+use crate::cst::{self, contents};
+use crate::parser::key;
+use crate::path::eq_ignore_case;
+use crate::prelude::*;
+use crate::{Address, Number, Severity};
+use core::fmt;
+
+/// An issue found by a lint rule.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Lint {
+    /// The name of the rule that found the issue.
+    pub rule: &'static str,
+    /// The offending binding or value.
+    pub address: Address,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// A check of documents for a kind of issue.
+pub trait LintRule {
+    /// The name of the rule, in kebab case, e.g. `duplicate-keys`.
+    fn name(&self) -> &'static str;
+
+    /// Appends the issues found in `document` to `lints`.
+    fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>);
+}
+
+/// Calls `f` with every sequence of sibling bindings in `bindings`, including `bindings` itself,
+/// and the address of the value that contains them, in document order.
+pub fn walk_bindings(
+    bindings: &cst::Bindings,
+    address: &Address,
+    f: &mut dyn FnMut(&cst::Bindings, &Address),
+) {
+    f(bindings, address);
+    for (i, (_, binding)) in bindings.items.iter().enumerate() {
+        let binding_address = address.child(i);
+        for (j, item) in binding.values.iter().enumerate() {
+            if let Some((_, block)) = &item.value.block {
+                walk_bindings(block, &binding_address.child(j), f);
+            }
+        }
+    }
+}
+
+/// Returns the name of `binding`, unquoted.
+fn name(binding: &cst::Binding) -> String {
+    contents(&binding.name, key)
+}
+
+/// Reports sibling bindings that share a name, which most of the API concatenates into one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DuplicateKeys;
+
+impl LintRule for DuplicateKeys {
+    fn name(&self) -> &'static str {
+        "duplicate-keys"
+    }
+
+    fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>) {
+        walk_bindings(document, &Address::default(), &mut |bindings, address| {
+            let names = bindings
+                .items
+                .iter()
+                .map(|(_, b)| name(b))
+                .collect::<Vec<_>>();
+            for (i, name) in names.iter().enumerate() {
+                if names[..i].contains(name) {
+                    lints.push(Lint {
+                        rule: self.name(),
+                        address: address.child(i),
+                        message: format!("duplicate key `{}`", name),
+                        severity: Severity::Error,
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Reports blocks without bindings, e.g. `a={}` or `a=b{}`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmptyBlocks;
+
+impl LintRule for EmptyBlocks {
+    fn name(&self) -> &'static str {
+        "empty-blocks"
+    }
+
+    fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>) {
+        walk_bindings(document, &Address::default(), &mut |bindings, address| {
+            for (i, (_, binding)) in bindings.items.iter().enumerate() {
+                for (j, item) in binding.values.iter().enumerate() {
+                    if let Some((_, block)) = &item.value.block {
+                        if block.items.is_empty() {
+                            lints.push(Lint {
+                                rule: self.name(),
+                                address: address.child(i).child(j),
+                                message: "empty block".to_string(),
+                                severity: Severity::Warning,
+                            });
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Reports sibling bindings whose names differ only in case, e.g. `port` and `Port`, which are
+/// told apart unless validated with `ValidationOptions::ignore_case`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CaseCollisions;
+
+impl LintRule for CaseCollisions {
+    fn name(&self) -> &'static str {
+        "case-collisions"
+    }
+
+    fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>) {
+        walk_bindings(document, &Address::default(), &mut |bindings, address| {
+            let names = bindings
+                .items
+                .iter()
+                .map(|(_, b)| name(b))
+                .collect::<Vec<_>>();
+            for (i, name) in names.iter().enumerate() {
+                let first = names[..i]
+                    .iter()
+                    .find(|other| *other != name && eq_ignore_case(other, name));
+                if let Some(first) = first {
+                    lints.push(Lint {
+                        rule: self.name(),
+                        address: address.child(i),
+                        message: format!("key `{}` differs only in case from `{}`", name, first),
+                        severity: Severity::Warning,
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Reports unquoted scalars that look like numbers or bools, e.g. `80` or `true`: they are
+/// strings like any other, which quoting them makes explicit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UnquotedLiterals;
+
+impl LintRule for UnquotedLiterals {
+    fn name(&self) -> &'static str {
+        "unquoted-literals"
+    }
+
+    fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>) {
+        walk_bindings(document, &Address::default(), &mut |bindings, address| {
+            for (i, (_, binding)) in bindings.items.iter().enumerate() {
+                for (j, item) in binding.values.iter().enumerate() {
+                    let value = item.value.value.as_str();
+                    let kind = if value == "true" || value == "false" {
+                        "bool"
+                    } else if Number::parse(value).is_some() {
+                        "number"
+                    } else {
+                        continue;
+                    };
+                    lints.push(Lint {
+                        rule: self.name(),
+                        address: address.child(i).child(j),
+                        message: format!(
+                            "unquoted `{}` looks like a {} but is a string; quote it as `\"{}\"`",
+                            value, kind, value
+                        ),
+                        severity: Severity::Warning,
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Runs a set of lint rules over documents.
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule>>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self.rules.iter().map(|r| r.name());
+        f.debug_list().entries(names).finish()
+    }
+}
+
+impl Linter {
+    /// Creates a linter running the built-in rules: `duplicate-keys`, `empty-blocks`,
+    /// `case-collisions` and `unquoted-literals`.
+    pub fn new() -> Self {
+        Self::empty()
+            .rule(DuplicateKeys)
+            .rule(EmptyBlocks)
+            .rule(CaseCollisions)
+            .rule(UnquotedLiterals)
+    }
+
+    /// Creates a linter running no rules.
+    pub fn empty() -> Self {
+        Linter { rules: vec![] }
+    }
+
+    /// Adds `rule` to the rules to run.
+    pub fn rule(mut self, rule: impl LintRule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Removes the rule named `name` from the rules to run.
+    pub fn allow(mut self, name: &str) -> Self {
+        self.rules.retain(|r| r.name() != name);
+        self
+    }
+
+    /// Returns the names of the rules to run.
+    pub fn rules(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|r| r.name()).collect()
+    }
+
+    /// Returns the issues found in `document` by all the rules, in document order.
+    pub fn lint(&self, document: &cst::Document) -> Vec<Lint> {
+        let mut lints = vec![];
+        for rule in self.rules.iter() {
+            rule.check(document, &mut lints);
+        }
+        lints.sort_by_key(|l| document.offset(&l.address));
+        lints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(linter: &Linter, input: &str) -> Vec<(String, Vec<usize>, Severity)> {
+        let document = cst::parse_document(input).unwrap();
+        linter
+            .lint(&document)
+            .into_iter()
+            .map(|l| (l.to_string(), l.address.0, l.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let linter = Linter::new();
+        let tests =
+            [
+                ("a=b c=\"1\" d=\"true\" e=[x,y]", vec![]),
+                (
+                    "a=b a=c \"a\"=d",
+                    vec![
+                        (
+                            "duplicate key `a` [duplicate-keys]",
+                            vec![1],
+                            Severity::Error,
+                        ),
+                        (
+                            "duplicate key `a` [duplicate-keys]",
+                            vec![2],
+                            Severity::Error,
+                        ),
+                    ],
+                ),
+                (
+                    "s={} t=x{u=y{} v={w=1}}",
+                    vec![
+                    ("empty block [empty-blocks]", vec![0, 0], Severity::Warning),
+                    ("empty block [empty-blocks]", vec![1, 0, 0, 0], Severity::Warning),
+                    (
+                        "unquoted `1` looks like a number but is a string; quote it as `\"1\"` \
+                         [unquoted-literals]",
+                        vec![1, 0, 1, 0, 0, 0],
+                        Severity::Warning,
+                    ),
+                ],
+                ),
+                (
+                    "host=h HOST=i Host=j",
+                    vec![
+                        (
+                            "key `HOST` differs only in case from `host` [case-collisions]",
+                            vec![1],
+                            Severity::Warning,
+                        ),
+                        (
+                            "key `Host` differs only in case from `host` [case-collisions]",
+                            vec![2],
+                            Severity::Warning,
+                        ),
+                    ],
+                ),
+                (
+                    "a=false,-1.5e3,0x1f,1_000{b=on}",
+                    vec![
+                        (
+                            "unquoted `false` looks like a bool but is a string; quote it as \
+                         `\"false\"` [unquoted-literals]",
+                            vec![0, 0],
+                            Severity::Warning,
+                        ),
+                        (
+                            "unquoted `-1.5e3` looks like a number but is a string; quote it as \
+                         `\"-1.5e3\"` [unquoted-literals]",
+                            vec![0, 1],
+                            Severity::Warning,
+                        ),
+                        (
+                            "unquoted `0x1f` looks like a number but is a string; quote it as \
+                         `\"0x1f\"` [unquoted-literals]",
+                            vec![0, 2],
+                            Severity::Warning,
+                        ),
+                        (
+                            "unquoted `1_000` looks like a number but is a string; quote it as \
+                         `\"1_000\"` [unquoted-literals]",
+                            vec![0, 3],
+                            Severity::Warning,
+                        ),
+                    ],
+                ),
+            ];
+
+        for (input, expected) in tests.iter() {
+            let expected = expected
+                .iter()
+                .map(|(message, address, severity)| {
+                    (message.to_string(), address.clone(), *severity)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(expected, lint(&linter, input), "{}", input);
+        }
+    }
+
+    /// Reports bindings named `todo`.
+    struct Todo;
+
+    impl LintRule for Todo {
+        fn name(&self) -> &'static str {
+            "todo"
+        }
+
+        fn check(&self, document: &cst::Document, lints: &mut Vec<Lint>) {
+            walk_bindings(document, &Address::default(), &mut |bindings, address| {
+                for (i, (_, binding)) in bindings.items.iter().enumerate() {
+                    if binding.name == "todo" {
+                        lints.push(Lint {
+                            rule: self.name(),
+                            address: address.child(i),
+                            message: "leftover todo".to_string(),
+                            severity: Severity::Error,
+                        });
+                    }
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_linter() {
+        let linter = Linter::new().allow("unquoted-literals").rule(Todo);
+        assert_eq!(
+            vec!["duplicate-keys", "empty-blocks", "case-collisions", "todo"],
+            linter.rules()
+        );
+        assert_eq!(
+            vec![
+                (
+                    "leftover todo [todo]".to_string(),
+                    vec![0, 0, 0],
+                    Severity::Error
+                ),
+                (
+                    "empty block [empty-blocks]".to_string(),
+                    vec![1, 0],
+                    Severity::Warning
+                ),
+                ("leftover todo [todo]".to_string(), vec![2], Severity::Error),
+            ],
+            lint(&linter, "a=b{todo=1} c={} todo=2 d=[x,[e{f={}}]]")
+        );
+        assert_eq!(
+            Vec::<(String, Vec<usize>, Severity)>::new(),
+            lint(&Linter::empty(), "a=1 a={}")
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use crate::{Binding, Field, Schema, Value, ValueKind, Variant};
+use core::fmt;
+
+/// Converts a Rust value into a fuji `Value`.
+///
+/// Usually implemented via `#[derive(FujiSerialize)]` from the `flax-derive` crate.
+pub trait FujiSerialize {
+    fn to_value(&self) -> Value;
+
+    fn to_binding(&self, name: &str) -> Binding {
+        Binding {
+            name: name.to_string(),
+            values: smallvec![self.to_value()],
+        }
+    }
+}
+
+/// Converts a fuji `Value` back into a Rust value.
+///
+/// Usually implemented via `#[derive(FujiDeserialize)]` from the `flax-derive` crate.
+pub trait FujiDeserialize: Sized {
+    fn from_value(value: &Value) -> Result<Self, DeserializeError>;
+
+    fn from_binding(binding: &Binding) -> Result<Self, DeserializeError> {
+        match binding.values.as_slice() {
+            [value] => Self::from_value(value),
+            values => Err(DeserializeError::ValueCount {
+                name: binding.name.clone(),
+                found: values.len(),
+            }),
+        }
+    }
+}
+
+/// Describes the values a Rust type accepts as a `Schema`, e.g. for a service to publish the schema
+/// of its configuration via `Schema::to_document`.
+///
+/// Usually implemented via `#[derive(ToSchema)]` from the `flax-derive` crate, which maps `Vec`
+/// fields to repeated fields and `Option` fields to optional ones. Recursive types are not
+/// supported, as their schema would be infinite.
+pub trait ToSchema {
+    fn schema() -> Schema;
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DeserializeError {
+    /// A required binding was not present among the children of a value.
+    MissingField(String),
+    /// A singular binding had zero or more than one values.
+    ValueCount { name: String, found: usize },
+    /// A scalar value could not be converted to the target type.
+    InvalidScalar { value: String, expected: String },
+    /// An enum value did not match any of the variant names.
+    UnknownVariant(String),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeserializeError::MissingField(name) => write!(f, "missing field `{}`", name),
+            DeserializeError::ValueCount { name, found } => {
+                write!(f, "expected a single value for `{}`, found {}", name, found)
+            }
+            DeserializeError::InvalidScalar { value, expected } => {
+                write!(f, "invalid value `{}`, expected {}", value, expected)
+            }
+            DeserializeError::UnknownVariant(name) => write!(f, "unknown variant `{}`", name),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializeError {}
+
+fn scalar(value: &str) -> Value {
+    Value {
+        value: value.to_string(),
+        kind: ValueKind::Scalar,
+        children: vec![],
+    }
+}
+
+impl FujiSerialize for String {
+    fn to_value(&self) -> Value {
+        scalar(self)
+    }
+}
+
+impl FujiDeserialize for String {
+    fn from_value(value: &Value) -> Result<Self, DeserializeError> {
+        Ok(value.value.clone())
+    }
+}
+
+impl ToSchema for String {
+    fn schema() -> Schema {
+        Schema::String
+    }
+}
+
+impl FujiSerialize for bool {
+    fn to_value(&self) -> Value {
+        scalar(if *self { "true" } else { "false" })
+    }
+}
+
+impl FujiDeserialize for bool {
+    fn from_value(value: &Value) -> Result<Self, DeserializeError> {
+        match value.value.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            v => Err(DeserializeError::InvalidScalar {
+                value: v.to_string(),
+                expected: "bool".to_string(),
+            }),
+        }
+    }
+}
+
+impl ToSchema for bool {
+    fn schema() -> Schema {
+        Schema::Bool
+    }
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FujiSerialize for $t {
+                fn to_value(&self) -> Value {
+                    scalar(&self.to_string())
+                }
+            }
+
+            impl FujiDeserialize for $t {
+                fn from_value(value: &Value) -> Result<Self, DeserializeError> {
+                    value.value.parse().map_err(|_| DeserializeError::InvalidScalar {
+                        value: value.value.clone(),
+                        expected: stringify!($t).to_string(),
+                    })
+                }
+            }
+
+            impl ToSchema for $t {
+                fn schema() -> Schema {
+                    Schema::String
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl<T: FujiSerialize> FujiSerialize for Box<T> {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl<T: FujiDeserialize> FujiDeserialize for Box<T> {
+    fn from_value(value: &Value) -> Result<Self, DeserializeError> {
+        T::from_value(value).map(Box::new)
+    }
+}
+
+/// Helpers used by the code generated by `flax-derive`. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+    pub use alloc::{vec, vec::Vec};
+
+    pub fn value(tag: &str, children: Vec<Binding>) -> Value {
+        Value {
+            value: tag.to_string(),
+            kind: ValueKind::Scalar,
+            children,
+        }
+    }
+
+    pub fn tag(value: &Value) -> &str {
+        &value.value
+    }
+
+    pub fn push(children: &mut Vec<Binding>, name: &str, values: Vec<Value>) {
+        if !values.is_empty() {
+            children.push(Binding {
+                name: name.to_string(),
+                values: values.into(),
+            });
+        }
+    }
+
+    pub fn values<'a>(value: &'a Value, name: &str) -> Vec<&'a Value> {
+        value
+            .children
+            .iter()
+            .filter(|b| b.name == name)
+            .flat_map(|b| b.values.iter())
+            .collect()
+    }
+
+    pub fn required<T: FujiDeserialize>(
+        name: &str,
+        values: &[&Value],
+    ) -> Result<T, DeserializeError> {
+        match values {
+            [] => Err(DeserializeError::MissingField(name.to_string())),
+            [value] => T::from_value(value),
+            _ => Err(DeserializeError::ValueCount {
+                name: name.to_string(),
+                found: values.len(),
+            }),
+        }
+    }
+
+    pub fn optional<T: FujiDeserialize>(
+        name: &str,
+        values: &[&Value],
+    ) -> Result<Option<T>, DeserializeError> {
+        match values {
+            [] => Ok(None),
+            _ => required(name, values).map(Some),
+        }
+    }
+
+    pub fn repeated<T: FujiDeserialize>(values: &[&Value]) -> Result<Vec<T>, DeserializeError> {
+        values.iter().map(|v| T::from_value(v)).collect()
+    }
+
+    pub fn field(name: &str, repeated: bool, optional: bool, schema: Schema) -> Field {
+        Field {
+            name: name.to_string(),
+            repeated,
+            optional,
+            secret: false,
+            min: None,
+            max: None,
+            schema,
+            default: None,
+            deprecated: None,
+            doc: None,
+        }
+    }
+
+    pub fn variant(name: &str, schema: Schema) -> Variant {
+        Variant {
+            name: name.to_string(),
+            schema,
+            deprecated: None,
+            doc: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(Ok(true), bool::from_value(&true.to_value()));
+        assert_eq!(Ok(42u32), u32::from_value(&42u32.to_value()));
+        assert_eq!(
+            Ok("bar".to_string()),
+            String::from_binding(&"bar".to_string().to_binding("foo"))
+        );
+        assert_eq!(
+            Err(DeserializeError::InvalidScalar {
+                value: "bar".to_string(),
+                expected: "u8".to_string(),
+            }),
+            u8::from_value(&scalar("bar"))
+        );
+        assert_eq!(Schema::Bool, bool::schema());
+        assert_eq!(Schema::String, u8::schema());
+    }
+}
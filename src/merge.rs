@@ -0,0 +1,134 @@
+//! Layering of documents, e.g. a base configuration with environment-specific overrides.
+
+use crate::prelude::*;
+use crate::{Binding, Document, Value};
+
+/// How to combine bindings with several values when merging.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum MergeStrategy {
+    /// The overlay values replace the base values.
+    Replace,
+    /// The overlay values are appended to the base values.
+    Append,
+}
+
+fn merge_bindings(base: &[Binding], overlay: &[Binding], strategy: MergeStrategy) -> Vec<Binding> {
+    let mut merged = base.to_vec();
+    for binding in overlay {
+        match merged.iter_mut().find(|b| b.name == binding.name) {
+            Some(existing) => *existing = existing.merge(binding, strategy),
+            None => merged.push(binding.clone()),
+        }
+    }
+    merged
+}
+
+/// Whether merging `overlay` onto `base` merges their children, rather than replacing `base`.
+pub(crate) fn merges_children(base: &Value, overlay: &Value) -> bool {
+    base.value == overlay.value && base.kind == overlay.kind && !overlay.children.is_empty()
+}
+
+fn merge_value(base: &Value, overlay: &Value, strategy: MergeStrategy) -> Value {
+    if merges_children(base, overlay) {
+        Value {
+            value: overlay.value.clone(),
+            kind: overlay.kind.clone(),
+            children: merge_bindings(&base.children, &overlay.children, strategy),
+        }
+    } else {
+        overlay.clone()
+    }
+}
+
+impl Binding {
+    /// Merges `overlay` on top of this binding, keeping the overlay's name.
+    ///
+    /// If both bindings have a single value, the overlay value replaces the base one, unless both
+    /// values are the same and the overlay has children, in which case children are merged
+    /// recursively by name. Otherwise, the binding is considered repeated and its values are
+    /// combined according to `strategy`.
+    pub fn merge(&self, overlay: &Binding, strategy: MergeStrategy) -> Binding {
+        let values = match (self.values.as_slice(), overlay.values.as_slice(), strategy) {
+            ([base], [value], _) => smallvec![merge_value(base, value, strategy)],
+            (_, _, MergeStrategy::Replace) => overlay.values.clone(),
+            (_, _, MergeStrategy::Append) => {
+                let mut values = self.values.clone();
+                values.extend(overlay.values.iter().cloned());
+                values
+            }
+        };
+        Binding {
+            name: overlay.name.clone(),
+            values,
+        }
+    }
+}
+
+impl Document {
+    /// Merges the top-level bindings of `overlay` on top of this document. Bindings are matched
+    /// by name and merged with [`Binding::merge`]; new bindings are appended.
+    pub fn merge(&self, overlay: &Document, strategy: MergeStrategy) -> Document {
+        Document::from_bindings(merge_bindings(&self.bindings, &overlay.bindings, strategy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document_complete, print_document};
+
+    #[test]
+    fn test_merge() {
+        struct Test {
+            base: String,
+            overlay: String,
+            strategy: MergeStrategy,
+            merged: String,
+        }
+
+        let tests = [
+            Test {
+                base: "a=b c=d".to_string(),
+                overlay: "c=e f=g".to_string(),
+                strategy: MergeStrategy::Replace,
+                merged: "a=b\nc=e\nf=g".to_string(),
+            },
+            Test {
+                base: "s=x{host=h port=80 tags=a,b}".to_string(),
+                overlay: "s=x{port=8080 tags=c,d}".to_string(),
+                strategy: MergeStrategy::Replace,
+                merged: "s=x{host=h port=8080 tags=c,d}".to_string(),
+            },
+            Test {
+                base: "s=x{host=h port=80 tags=a,b}".to_string(),
+                overlay: "s=x{port=8080 tags=c,d}".to_string(),
+                strategy: MergeStrategy::Append,
+                merged: "s=x{host=h port=8080 tags=a,b,c,d}".to_string(),
+            },
+            Test {
+                base: "mode=tcp{port=1}".to_string(),
+                overlay: "mode=udp{size=2}".to_string(),
+                strategy: MergeStrategy::Append,
+                merged: "mode=udp{size=2}".to_string(),
+            },
+            Test {
+                base: "s=x{a=b}".to_string(),
+                overlay: "s=x".to_string(),
+                strategy: MergeStrategy::Replace,
+                merged: "s=x".to_string(),
+            },
+        ];
+
+        for t in tests.iter() {
+            let base = parse_document_complete(&t.base).unwrap();
+            let overlay = parse_document_complete(&t.overlay).unwrap();
+            assert_eq!(
+                t.merged,
+                print_document(&base.merge(&overlay, t.strategy)),
+                "{} + {}",
+                t.base,
+                t.overlay
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Numeric scalars.
+
+use crate::parser::number_syntax;
+use crate::prelude::*;
+use crate::Value;
+use core::fmt;
+use nom::combinator::all_consuming;
+
+/// A numeric scalar, as written in values such as `42`, `-0xFF`, `1_000_000` or `+2.5e-3`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    /// Parses the textual form of a number. Returns `None` if `s` is not a number, or is an
+    /// integer out of the range of `i64`.
+    pub fn parse(s: &str) -> Option<Number> {
+        all_consuming(number_syntax::<nom::error::Error<&str>>)(s).ok()?;
+        let digits = s.replace('_', "");
+        let unsigned = digits.trim_start_matches(['+', '-']);
+        if let Some(hex) = unsigned.strip_prefix("0x") {
+            let sign = &digits[..digits.len() - unsigned.len()];
+            i64::from_str_radix(&format!("{}{}", sign, hex), 16)
+                .ok()
+                .map(Number::Integer)
+        } else if digits.contains(['.', 'e', 'E']) {
+            digits.parse().ok().map(Number::Float)
+        } else {
+            digits.parse().ok().map(Number::Integer)
+        }
+    }
+
+    /// Returns the number as a float, possibly losing precision for large integers.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// Prints the number so that it parses back to the same number. Floats always have a fractional
+/// part or an exponent; non-finite floats print as `NaN`, `inf` and `-inf`, which are not numbers.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "{}", i),
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        Value::from(number.to_string())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::from(Number::Integer(i))
+    }
+}
+
+impl Value {
+    /// Returns the value as a number, if it is a scalar written as one.
+    pub fn as_number(&self) -> Option<Number> {
+        self.as_str().and_then(Number::parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_parse_number() {
+        let tests = [
+            ("0", Some(Number::Integer(0))),
+            ("42", Some(Number::Integer(42))),
+            ("1_000_000", Some(Number::Integer(1_000_000))),
+            ("0xFF", Some(Number::Integer(255))),
+            ("0xdead_beef", Some(Number::Integer(0xdead_beef))),
+            ("2.5", Some(Number::Float(2.5))),
+            ("1_0.2_5e-3", Some(Number::Float(0.01025))),
+            ("3E2", Some(Number::Float(300.0))),
+            ("9223372036854775808", None),
+            ("abc", None),
+            ("1__0", None),
+            ("1.", None),
+            ("0x", None),
+            ("-5", Some(Number::Integer(-5))),
+            ("+5", Some(Number::Integer(5))),
+            ("-0x8000_0000_0000_0000", Some(Number::Integer(i64::MIN))),
+            ("-1.5e+2", Some(Number::Float(-150.0))),
+            ("--5", None),
+            ("-", None),
+        ];
+
+        for (s, number) in tests.iter() {
+            assert_eq!(*number, Number::parse(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_print_number() {
+        let tests = [
+            Number::Integer(-42),
+            Number::Integer(i64::MIN),
+            Number::Float(1.0),
+            Number::Float(-0.0),
+            Number::Float(1e300),
+            Number::Float(-2.5e-7),
+        ];
+
+        for number in tests.iter() {
+            let document = parse_document_complete(&format!("a={}", number)).unwrap();
+            assert_eq!(
+                Some(*number),
+                document.bindings()[0].values()[0].as_number()
+            );
+        }
+        let binding = crate::Binding::new("a").value(-5).value(Number::Float(0.5));
+        assert_eq!("a=-5,0.5", binding.to_string());
+        assert_eq!(Ok(binding.clone()), binding.to_string().parse());
+    }
+
+    #[test]
+    fn test_number_values() {
+        let document = parse_document_complete("a=1_000,0x10,2.5e1,b{c=1} d=7{e=f}").unwrap();
+        let values = document.bindings()[0].values();
+        let numbers = values.iter().map(Value::as_number).collect::<Vec<_>>();
+        assert_eq!(
+            [
+                Some(Number::Integer(1000)),
+                Some(Number::Integer(16)),
+                Some(Number::Float(25.0)),
+                None
+            ],
+            numbers.as_slice()
+        );
+        assert_eq!(None, document.bindings()[1].values()[0].as_number());
+        assert_eq!("a=1_000,0x10,2.5e1,b{c=1}\nd=7{e=f}", document.to_string());
+
+        // Tokens starting with a digit or a sign that are not numbers as a whole are bare values.
+        let tests = [
+            ("host=10.0.0.1", "10.0.0.1"),
+            ("version=1.2.3", "1.2.3"),
+            ("date=2024-01-01", "2024-01-01"),
+            ("a=-", "-"),
+            ("a=-x", "-x"),
+            ("a=12ab", "12ab"),
+            ("a=1__0", "1__0"),
+            ("a=0xZ", "0xZ"),
+            ("a=1e+", "1e+"),
+            ("a=+", "+"),
+        ];
+        for (input, value) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            let values = document.bindings()[0].values();
+            assert_eq!(Some(*value), values[0].as_str(), "{}", input);
+            assert_eq!(None, values[0].as_number(), "{}", input);
+            assert_eq!(*input, document.to_string());
+        }
+    }
+}
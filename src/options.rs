@@ -0,0 +1,194 @@
+//! Options controlling how documents are parsed and validated.
+
+use crate::path::eq_ignore_case;
+use crate::prelude::*;
+use crate::{DuplicatePolicy, Severity};
+
+/// How parsing treats a syntax quirk.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Strictness {
+    /// Fail to parse.
+    Reject,
+    /// Parse, reporting the quirk in the warnings of `parse_document_with_warnings`.
+    Warn,
+    /// Parse silently.
+    Accept,
+}
+
+/// Options for `parse_document_with`. The defaults parse exactly as `parse_document_complete`.
+///
+/// Besides transformations of the document, the options control how strictly parsing treats
+/// syntax quirks: a formatter may accept anything it can make sense of, while a production loader
+/// rejects anything unusual. By default, trailing commas and duplicate names are accepted, and
+/// unquoted special characters and trailing input rejected, as the grammar does.
+///
+/// The options can also limit the size of the input, for input from untrusted sources: inputs
+/// exceeding a limit are rejected with an error whose `limit` is set. There are no limits by
+/// default.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub(crate) vars: Option<fn(&str) -> Option<String>>,
+    pub(crate) duplicates: Option<DuplicatePolicy>,
+    pub(crate) trailing_commas: Strictness,
+    pub(crate) duplicate_names: Strictness,
+    pub(crate) special_characters: Strictness,
+    pub(crate) trailing_input: Strictness,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) max_input_length: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            vars: None,
+            duplicates: None,
+            trailing_commas: Strictness::Accept,
+            duplicate_names: Strictness::Accept,
+            special_characters: Strictness::Reject,
+            trailing_input: Strictness::Reject,
+            max_depth: None,
+            max_nodes: None,
+            max_input_length: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects every syntax quirk.
+    pub fn strict() -> Self {
+        Self::new().quirks(Strictness::Reject)
+    }
+
+    /// Accepts every syntax quirk.
+    pub fn lenient() -> Self {
+        Self::new().quirks(Strictness::Accept)
+    }
+
+    /// Treats every syntax quirk with `strictness`.
+    pub fn quirks(self, strictness: Strictness) -> Self {
+        self.trailing_commas(strictness)
+            .duplicate_names(strictness)
+            .special_characters(strictness)
+            .trailing_input(strictness)
+    }
+
+    /// Treats commas ending a list of values with `strictness`, e.g. the last one in `a=b,c,`.
+    pub fn trailing_commas(mut self, strictness: Strictness) -> Self {
+        self.trailing_commas = strictness;
+        self
+    }
+
+    /// Treats sibling bindings that share a name with `strictness`, reporting them at the second
+    /// binding. This is independent of how `duplicates` then collapses them, but
+    /// `DuplicatePolicy::Error` rejects them regardless.
+    pub fn duplicate_names(mut self, strictness: Strictness) -> Self {
+        self.duplicate_names = strictness;
+        self
+    }
+
+    /// Treats unquoted characters that cannot appear in a bare value, e.g. `/` and `:` in
+    /// `url=http://example.com/`, with `strictness`. If accepted, the characters are read as if
+    /// the run of characters around them, up to whitespace or punctuation, were quoted.
+    pub fn special_characters(mut self, strictness: Strictness) -> Self {
+        self.special_characters = strictness;
+        self
+    }
+
+    /// Treats input left after the last binding that could be parsed, e.g. `}` in `a=b }`, with
+    /// `strictness`. If accepted, the remaining input is ignored.
+    pub fn trailing_input(mut self, strictness: Strictness) -> Self {
+        self.trailing_input = strictness;
+        self
+    }
+
+    /// Rejects input with more than `max` braces and brackets open at once, e.g. `a={b=[c]}` if
+    /// `max` is 1. Nesting is checked before parsing. Whatever the options, parsing rejects input
+    /// nesting more than 64 blocks and lists, so that it cannot overflow the stack.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Rejects input with more than `max` bindings and values, counting each nested list as a
+    /// value besides its items, e.g. `a=[b,[c]]` if `max` is 3.
+    pub fn max_nodes(mut self, max: usize) -> Self {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// Rejects input longer than `max` bytes. The length is checked before parsing.
+    pub fn max_input_length(mut self, max: usize) -> Self {
+        self.max_input_length = Some(max);
+        self
+    }
+
+    /// Substitutes `${VAR}` placeholders in values with environment variables, failing on unset
+    /// variables without a default.
+    #[cfg(feature = "std")]
+    pub fn resolve_env(self) -> Self {
+        self.resolve_vars(crate::env::var)
+    }
+
+    /// Substitutes `${VAR}` placeholders in values, looking variables up with `lookup`.
+    pub fn resolve_vars(mut self, lookup: fn(&str) -> Option<String>) -> Self {
+        self.vars = Some(lookup);
+        self
+    }
+
+    /// Applies `policy` to sibling bindings that share a name. With `DuplicatePolicy::Error`, the
+    /// error is located at the second binding.
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = Some(policy);
+        self
+    }
+}
+
+/// Options for `Schema::validate_with`. The defaults validate exactly as `Schema::validate`.
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    pub(crate) unknown_fields: Severity,
+    pub(crate) ignore_case: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            unknown_fields: Severity::Warning,
+            ignore_case: false,
+        }
+    }
+}
+
+impl ValidationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports bindings that match no field of the schema with `severity`; they are warnings by
+    /// default.
+    pub fn unknown_fields(mut self, severity: Severity) -> Self {
+        self.unknown_fields = severity;
+        self
+    }
+
+    /// Matches binding names to fields ignoring case, e.g. `LogLevel` to a field named
+    /// `loglevel`. Bindings whose names differ only in case are then duplicates of each other.
+    /// `Schema::canonicalize` renames them to the spelling of their fields.
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Returns whether the binding name `name` matches the field name `field`.
+    pub(crate) fn matches(&self, name: &str, field: &str) -> bool {
+        match self.ignore_case {
+            true => eq_ignore_case(name, field),
+            false => name == field,
+        }
+    }
+}
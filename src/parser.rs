@@ -0,0 +1,442 @@
+//! The fuji grammar. All parsers produce borrowed nodes; the owned API converts them afterwards.
+
+use crate::borrowed::{Binding, Document, Value, ValueKind};
+use crate::limits::MAX_DEPTH;
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use nom::{
+    branch::alt,
+    bytes::complete::{is_a, tag, take_while, take_while1},
+    character::complete::{char, multispace1, not_line_ending, one_of, space0, space1},
+    combinator::{consumed, cut, eof, map, not, opt, peek, recognize},
+    error::{context, ContextError, ErrorKind, ParseError},
+    multi::{many0, many1, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// The nom error bounds required by the grammar.
+pub(crate) trait Error<'a>: ParseError<&'a str> + ContextError<&'a str> {}
+
+impl<'a, E: ParseError<&'a str> + ContextError<&'a str>> Error<'a> for E {}
+
+/// A line comment, starting with `#` or `//`.
+fn comment<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(alt((tag("#"), tag("//"))), not_line_ending))(input)
+}
+
+/// Optional whitespace and comments.
+pub(crate) fn ws0<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(many0(alt((multispace1, comment))))(input)
+}
+
+/// Mandatory whitespace and comments.
+pub(crate) fn ws1<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(many1(alt((multispace1, comment))))(input)
+}
+
+/// A run of Unicode letters and digits, as classified by `char::is_alphanumeric`, e.g. `città`.
+pub(crate) fn word<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    take_while1(char::is_alphanumeric)(input)
+}
+
+pub(crate) fn identifier<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    context("identifier", word)(input)
+}
+
+/// A fatal error at `input`, describing the `expected` token.
+fn failure<'a, E: Error<'a>>(input: &'a str, expected: &'static str) -> nom::Err<E> {
+    nom::Err::Failure(E::add_context(
+        input,
+        expected,
+        E::from_error_kind(input, ErrorKind::Verify),
+    ))
+}
+
+/// Recognizes `delimiter`, opening a block or a list nested in `depth` others, failing at it if
+/// that would nest more than `MAX_DEPTH` of them, so that adversarial input cannot overflow the
+/// stack of the recursive parsers.
+pub(crate) fn open<'a, E: Error<'a>>(
+    delimiter: &'static str,
+    depth: usize,
+) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, E> {
+    move |input| {
+        let (rest, open) = tag(delimiter)(input)?;
+        if depth >= MAX_DEPTH {
+            return Err(nom::Err::Failure(E::from_error_kind(
+                input,
+                ErrorKind::TooLarge,
+            )));
+        }
+        Ok((rest, open))
+    }
+}
+
+/// A double-quoted string, returning its contents. The escape sequences `\\`, `\"`, `\n`, `\r`
+/// and `\t` are recognized; contents without them are borrowed from the input.
+pub(crate) fn quoted<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Cow<'a, str>, E> {
+    let (body, _) = char('"')(input)?;
+    let mut owned: Option<String> = None;
+    let mut start = 0;
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let contents = match owned {
+                    Some(mut contents) => {
+                        contents.push_str(&body[start..i]);
+                        Cow::Owned(contents)
+                    }
+                    None => Cow::Borrowed(&body[..i]),
+                };
+                return Ok((&body[i + 1..], contents));
+            }
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    _ => return Err(failure(&body[i..], "escape sequence")),
+                };
+                let contents = owned.get_or_insert_with(String::new);
+                contents.push_str(&body[start..i]);
+                contents.push(escaped);
+                start = i + 2;
+            }
+            '\n' => return Err(failure(&body[i..], "'\"'")),
+            _ => {}
+        }
+    }
+    Err(failure(&body[body.len()..], "'\"'"))
+}
+
+/// A triple-quoted block of lines, e.g. a script or a certificate:
+///
+/// ```text
+/// cert="""
+///   -----BEGIN CERTIFICATE-----
+///   MIIB...
+///   """
+/// ```
+///
+/// The opening `"""` ends its line and the closing one starts its own, after indentation that is
+/// stripped from every line; whitespace-only lines may be shorter. The contents are the lines in
+/// between, without the last line break, and contain no escape sequences.
+pub(crate) fn text_block<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Cow<'a, str>, E> {
+    let (body, _) = tag("\"\"\"")(input)?;
+    let (body, _) = cut(context("line break", pair(space0, char('\n'))))(body)?;
+    let end = match body.find("\"\"\"") {
+        Some(end) => end,
+        None => return Err(failure(&body[body.len()..], "'\"\"\"'")),
+    };
+    let (contents, start) = match body[..end].rfind('\n') {
+        Some(newline) => (&body[..newline], newline + 1),
+        None => ("", 0),
+    };
+    let indent = &body[start..end];
+    if let Some(i) = indent.find(|c| c != ' ' && c != '\t') {
+        return Err(failure(&body[start + i..], "line break"));
+    }
+    let rest = &body[end + 3..];
+    if indent.is_empty() {
+        return Ok((rest, Cow::Borrowed(contents)));
+    }
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in contents.split('\n') {
+        match line.strip_prefix(indent) {
+            Some(line) => lines.push(line),
+            None if line.trim_matches(|c| c == ' ' || c == '\t').is_empty() => lines.push(""),
+            None => return Err(failure(&body[offset..], "indentation")),
+        }
+        offset += line.len() + 1;
+    }
+    Ok((rest, Cow::Owned(lines.join("\n"))))
+}
+
+/// An unquoted binding name: letters, digits and `_`, followed by any of those, `-` and `.`, e.g.
+/// `max_connections`, `log-level` or `service.name`.
+pub(crate) fn bare_key<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(alt((word, tag("_"))), many0(alt((word, is_a("_-."))))))(input)
+}
+
+/// A binding name, bare or quoted.
+pub(crate) fn key<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Cow<'a, str>, E> {
+    context("identifier", alt((quoted, map(bare_key, Cow::Borrowed))))(input)
+}
+
+/// An environment variable placeholder, `${VAR}` or `${VAR:-default}`, kept verbatim in the value
+/// until it is resolved by `resolve_env`.
+fn placeholder<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(tuple((
+        tag("${"),
+        cut(context(
+            "variable name",
+            take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        )),
+        opt(pair(tag(":-"), take_while(char::is_alphanumeric))),
+        cut(context("'}'", tag("}"))),
+    )))(input)
+}
+
+/// Digits in the given radix, optionally separated by single underscores, e.g. `1_000`.
+fn digits<'a, E: Error<'a>>(radix: u32) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
+    let digit = move |c: char| c.is_digit(radix);
+    recognize(pair(
+        take_while1(digit),
+        many0(pair(char('_'), take_while1(digit))),
+    ))
+}
+
+/// The syntax of a number: a hexadecimal integer such as `0xFF`, or a decimal integer or float
+/// such as `42`, `1_000` or `2.5e-3`, with an optional sign.
+pub(crate) fn number_syntax<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(
+        opt(one_of("+-")),
+        alt((
+            recognize(pair(tag("0x"), digits(16))),
+            recognize(tuple((
+                digits(10),
+                opt(pair(char('.'), digits(10))),
+                opt(tuple((one_of("eE"), opt(one_of("+-")), digits(10)))),
+            ))),
+        )),
+    ))(input)
+}
+
+/// A number, or a bare value starting with a digit or a sign. The token runs over letters, digits,
+/// `_`, `.`, `+` and `-`, and is a number only if it is one as a whole: `10.0.0.1`, `1.2.3`,
+/// `2024-01-01`, `12ab` and `-x` are bare values.
+fn number<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    let unsigned = input.strip_prefix(['+', '-']).unwrap_or(input);
+    if unsigned.len() == input.len() && !input.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::Digit)));
+    }
+    let mut len = input.len() - unsigned.len();
+    for (i, c) in unsigned.char_indices() {
+        if !(c.is_alphanumeric() || "_.+-".contains(c)) {
+            break;
+        }
+        len = input.len() - unsigned.len() + i + c.len_utf8();
+    }
+    Ok((&input[len..], &input[..len]))
+}
+
+/// An unquoted value that is not a number: letters, digits, `_` and placeholders, followed by any
+/// of those, `-` and `.`.
+fn bare<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(
+        alt((word, tag("_"), placeholder)),
+        many0(alt((word, is_a("_-."), placeholder))),
+    ))(input)
+}
+
+/// The scalar part of a value: a number, a bare value, a quoted string or a text block.
+pub(crate) fn scalar<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Cow<'a, str>, E> {
+    context(
+        "identifier",
+        alt((
+            map(number, Cow::Borrowed),
+            text_block,
+            quoted,
+            map(bare, Cow::Borrowed),
+        )),
+    )(input)
+}
+
+pub(crate) fn binding<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Binding<'a>, E> {
+    nested_binding(input, 0)
+}
+
+/// A binding nested in `depth` blocks and lists.
+fn nested_binding<'a, E: Error<'a>>(
+    input: &'a str,
+    depth: usize,
+) -> IResult<&'a str, Binding<'a>, E> {
+    map(
+        tuple((terminated(key, cut(context("'='", tag("=")))), |input| {
+            nested_binding_values(input, depth)
+        })),
+        |(name, values)| Binding { name, values },
+    )(input)
+}
+
+/// The values of a binding, after its `=`.
+pub(crate) fn binding_values<'a, E: Error<'a>>(
+    input: &'a str,
+) -> IResult<&'a str, Vec<Value<'a>>, E> {
+    nested_binding_values(input, 0)
+}
+
+fn nested_binding_values<'a, E: Error<'a>>(
+    input: &'a str,
+    depth: usize,
+) -> IResult<&'a str, Vec<Value<'a>>, E> {
+    // An empty list, as in `foo=` or `foo=[]`, binds the name to no value.
+    map(
+        opt(alt((
+            |input| list(input, depth),
+            |input| values(input, depth),
+        ))),
+        Option::unwrap_or_default,
+    )(input)
+}
+
+/// Whether a comma is trailing, i.e. followed by the end of the enclosing block or document or by
+/// another binding, as in `foo=a,b,` or `{foo=a,b, bar=c}`.
+pub(crate) fn list_end<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, (), E> {
+    map(
+        peek(alt((tag("}"), eof, recognize(pair(key, tag("=")))))),
+        |_| (),
+    )(input)
+}
+
+fn values<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Vec<Value<'a>>, E> {
+    let value = |input| nested_value(input, depth);
+    map(
+        terminated(
+            pair(
+                value,
+                many0(preceded(
+                    tuple((ws0, tag(","), ws0, not(list_end))),
+                    cut(value),
+                )),
+            ),
+            opt(pair(ws0, tag(","))),
+        ),
+        |(first, mut rest)| {
+            rest.insert(0, first);
+            rest
+        },
+    )(input)
+}
+
+/// A bracketed list of values, e.g. `[a, b{c=d}, [e]]`, optionally with a trailing comma.
+fn list<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Vec<Value<'a>>, E> {
+    preceded(
+        pair(open("[", depth), ws0),
+        cut(terminated(
+            separated_list0(tuple((ws0, tag(","), ws0)), |input| {
+                element(input, depth + 1)
+            }),
+            tuple((opt(pair(ws0, tag(","))), ws0, context("']'", tag("]")))),
+        )),
+    )(input)
+}
+
+/// An element of a bracketed list: a value or a nested list.
+fn element<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Value<'a>, E> {
+    alt((
+        map(
+            |input| list(input, depth),
+            |items| Value {
+                value: Cow::Borrowed(""),
+                kind: ValueKind::List(items),
+                children: vec![],
+            },
+        ),
+        |input| nested_value(input, depth),
+    ))(input)
+}
+
+/// A reference to an anchored value, `*name`.
+pub(crate) fn reference<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(tag("*"), cut(identifier)))(input)
+}
+
+/// An anchor naming a value, `&name `, returning the name.
+fn anchor<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    delimited(tag("&"), cut(identifier), cut(context("' '", space1)))(input)
+}
+
+/// A scalar, optionally preceded by an anchor naming its value, e.g. `&name scalar`, returning the
+/// name of the anchor and the scalar.
+pub(crate) fn anchored<'a, E: Error<'a>>(
+    input: &'a str,
+) -> IResult<&'a str, (Option<&'a str>, Cow<'a, str>), E> {
+    pair(opt(anchor), scalar)(input)
+}
+
+pub(crate) fn value<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Value<'a>, E> {
+    nested_value(input, 0)
+}
+
+/// A value nested in `depth` blocks and lists.
+fn nested_value<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Value<'a>, E> {
+    let children = |input| block(input, depth);
+    alt((
+        map(children, |children| Value {
+            value: Cow::Borrowed(""),
+            kind: ValueKind::Map,
+            children,
+        }),
+        map(reference, |reference| Value {
+            value: Cow::Borrowed(&reference[1..]),
+            kind: ValueKind::Reference,
+            children: vec![],
+        }),
+        map(
+            tuple((anchored, opt(preceded(ws0, children)))),
+            |((anchor, value), children)| Value {
+                value,
+                kind: anchor.map_or(ValueKind::Scalar, ValueKind::Anchored),
+                children: children.unwrap_or_default(),
+            },
+        ),
+    ))(input)
+}
+
+/// A block of child bindings, `{a=b c=d}`.
+fn block<'a, E: Error<'a>>(input: &'a str, depth: usize) -> IResult<&'a str, Vec<Binding<'a>>, E> {
+    preceded(
+        open("{", depth),
+        cut(terminated(
+            preceded(
+                ws0,
+                separated_list0(ws1, |input| nested_binding(input, depth + 1)),
+            ),
+            preceded(ws0, context("'}'", tag("}"))),
+        )),
+    )(input)
+}
+
+pub(crate) fn document<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Document<'a>, E> {
+    map(
+        delimited(ws0, separated_list0(ws1, binding), ws0),
+        |bindings| Document { bindings },
+    )(input)
+}
+
+/// A top-level item of a document that may contain include directives.
+pub(crate) enum Item<'a> {
+    Binding(Binding<'a>),
+    /// An `include "path"` directive, with the text of the whole directive.
+    Include {
+        directive: &'a str,
+        path: Cow<'a, str>,
+    },
+}
+
+fn item<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Item<'a>, E> {
+    alt((
+        map(
+            consumed(preceded(pair(tag("include"), ws1), quoted)),
+            |(directive, path)| Item::Include { directive, path },
+        ),
+        map(binding, Item::Binding),
+    ))(input)
+}
+
+/// A document whose top level may contain include directives.
+pub(crate) fn items<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Vec<Item<'a>>, E> {
+    delimited(ws0, separated_list0(ws1, item), ws0)(input)
+}
+
+/// Parses a single binding, surrounded by optional whitespace.
+pub(crate) fn complete_binding<'a, E: Error<'a>>(
+    input: &'a str,
+) -> IResult<&'a str, Binding<'a>, E> {
+    delimited(ws0, binding, ws0)(input)
+}
@@ -0,0 +1,724 @@
+use crate::parser::{quoted, word};
+use crate::prelude::*;
+use crate::quote::quote;
+use crate::{Binding, Document, Value};
+use alloc::borrow::Cow;
+use core::fmt;
+use core::str::FromStr;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::digit1,
+    combinator::{all_consuming, map, map_res, opt, recognize},
+    multi::{many0, separated_list1},
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+/// A dotted path into a tree of bindings, e.g. `servers[1].host`.
+///
+/// Each segment selects the first binding with the given name; the optional index selects one of
+/// its values, defaulting to the first one.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Segment {
+    pub name: String,
+    pub index: Option<usize>,
+}
+
+/// A dotted path pattern with wildcards, e.g. `servers.*.host` or `**.password`.
+///
+/// Besides the segments of a [`Path`], a pattern may contain `*`, which matches any single
+/// segment, optionally with an index as in `*[0]`, and `**`, which matches any number of
+/// segments, including none. A segment without an index matches every value of its binding.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Pattern {
+    segments: Vec<PatternSegment>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum PatternSegment {
+    Segment(Segment),
+    Any(Option<usize>),
+    AnyDepth,
+}
+
+/// The position of a node in a document, which unlike a `Path` stays unambiguous when sibling
+/// bindings share a name.
+///
+/// Indices alternate between bindings and values, starting from the top-level bindings: an odd
+/// number of indices addresses a binding, an even number one of its values.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Address(pub Vec<usize>);
+
+impl Address {
+    /// Returns the address of the `index`-th child of this node.
+    pub fn child(&self, index: usize) -> Address {
+        let mut indices = self.0.clone();
+        indices.push(index);
+        Address(indices)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum PathError {
+    /// The path string is not syntactically valid.
+    Invalid(String),
+    /// No binding or value exists at the given (prefix of the) path.
+    NotFound(String),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::Invalid(path) => write!(f, "invalid path `{}`", path),
+            PathError::NotFound(path) => write!(f, "path `{}` not found", path),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PathError {}
+
+/// A segment name that needs no quoting: like a binding name, but without dots.
+fn bare_name(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        alt((word, tag("_"))),
+        many0(alt((word, tag("_"), tag("-")))),
+    )))(input)
+}
+
+fn parse_segment(input: &str) -> IResult<&str, Segment> {
+    map(
+        tuple((
+            alt((quoted, map(bare_name, Cow::Borrowed))),
+            opt(parse_index),
+        )),
+        |(name, index): (Cow<str>, Option<usize>)| Segment {
+            name: name.into_owned(),
+            index,
+        },
+    )(input)
+}
+
+fn parse_path(input: &str) -> IResult<&str, Path> {
+    map(separated_list1(tag("."), parse_segment), |segments| Path {
+        segments,
+    })(input)
+}
+
+impl FromStr for Path {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        all_consuming(parse_path)(s)
+            .map(|(_, path)| path)
+            .map_err(|_| PathError::Invalid(s.to_string()))
+    }
+}
+
+fn parse_index(input: &str) -> IResult<&str, usize> {
+    delimited(
+        tag("["),
+        map_res(digit1, |d: &str| d.parse::<usize>()),
+        tag("]"),
+    )(input)
+}
+
+fn parse_pattern(input: &str) -> IResult<&str, Pattern> {
+    let segment = alt((
+        map(tag("**"), |_| PatternSegment::AnyDepth),
+        map(preceded(tag("*"), opt(parse_index)), PatternSegment::Any),
+        map(parse_segment, PatternSegment::Segment),
+    ));
+    map(separated_list1(tag("."), segment), |segments| Pattern {
+        segments,
+    })(input)
+}
+
+impl FromStr for Pattern {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        all_consuming(parse_pattern)(s)
+            .map(|(_, pattern)| pattern)
+            .map_err(|_| PathError::Invalid(s.to_string()))
+    }
+}
+
+/// Whether a path segment has the given index, which it omits if its binding has a single value.
+fn index_matches(index: Option<usize>, segment: &Segment) -> bool {
+    index.is_none_or(|index| segment.index.unwrap_or(0) == index)
+}
+
+fn matches(pattern: &[PatternSegment], path: &[Segment]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (None, None) => true,
+        (Some((PatternSegment::AnyDepth, rest)), _) => {
+            (0..=path.len()).any(|skip| matches(rest, &path[skip..]))
+        }
+        (Some((PatternSegment::Any(index), rest)), Some((segment, tail))) => {
+            index_matches(*index, segment) && matches(rest, tail)
+        }
+        (Some((PatternSegment::Segment(expected), rest)), Some((segment, tail))) => {
+            expected.name == segment.name
+                && index_matches(expected.index, segment)
+                && matches(rest, tail)
+        }
+        _ => false,
+    }
+}
+
+impl Pattern {
+    /// Whether `path` matches this pattern, e.g. a path yielded by [`Document::iter`].
+    pub fn matches(&self, path: &Path) -> bool {
+        matches(&self.segments, &path.segments)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                PatternSegment::Segment(segment) => segment.to_string(),
+                PatternSegment::Any(Some(index)) => format!("*[{}]", index),
+                PatternSegment::Any(None) => "*".to_string(),
+                PatternSegment::AnyDepth => "**".to_string(),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", segments.join("."))
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if all_consuming(bare_name)(&self.name).is_ok() {
+            write!(f, "{}", self.name)?;
+        } else {
+            write!(f, "{}", quote(&self.name))?;
+        }
+        match self.index {
+            Some(index) => write!(f, "[{}]", index),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let segments = self
+            .segments
+            .iter()
+            .map(Segment::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", segments.join("."))
+    }
+}
+
+fn not_found(segments: &[Segment]) -> PathError {
+    PathError::NotFound(
+        Path {
+            segments: segments.to_vec(),
+        }
+        .to_string(),
+    )
+}
+
+/// Returns whether two names are equal ignoring case, e.g. `LogLevel` and `loglevel`.
+pub(crate) fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// Returns the binding at `path`, comparing names with `eq`.
+fn find_binding<'a>(
+    bindings: &'a [Binding],
+    path: &Path,
+    eq: fn(&str, &str) -> bool,
+) -> Option<&'a Binding> {
+    let (last, init) = path.segments.split_last()?;
+    let mut bindings = bindings;
+    for segment in init {
+        let binding = bindings.iter().find(|b| eq(&b.name, &segment.name))?;
+        bindings = &binding.values.get(segment.index.unwrap_or(0))?.children;
+    }
+    bindings.iter().find(|b| eq(&b.name, &last.name))
+}
+
+/// Returns the value at `path`, comparing names with `eq`.
+fn find<'a>(bindings: &'a [Binding], path: &Path, eq: fn(&str, &str) -> bool) -> Option<&'a Value> {
+    let last = path.segments.last()?;
+    find_binding(bindings, path, eq)?
+        .values
+        .get(last.index.unwrap_or(0))
+}
+
+pub(crate) fn get_binding<'a>(bindings: &'a [Binding], path: &Path) -> Option<&'a Binding> {
+    find_binding(bindings, path, |a, b| a == b)
+}
+
+pub(crate) fn get<'a>(bindings: &'a [Binding], path: &Path) -> Option<&'a Value> {
+    find(bindings, path, |a, b| a == b)
+}
+
+/// Returns all values matching `path`. Each segment selects the values of every binding with
+/// the given name, or only the value at the given index among them.
+pub(crate) fn query<'a>(bindings: &'a [Binding], path: &Path) -> Vec<&'a Value> {
+    let mut values = vec![];
+    let mut parents = vec![bindings];
+    for segment in &path.segments {
+        values = parents
+            .iter()
+            .flat_map(|bindings| {
+                let matching = bindings
+                    .iter()
+                    .filter(|b| b.name == segment.name)
+                    .flat_map(|b| b.values.iter());
+                match segment.index {
+                    Some(index) => matching.skip(index).take(1).collect::<Vec<_>>(),
+                    None => matching.collect(),
+                }
+            })
+            .collect();
+        parents = values.iter().map(|v| v.children.as_slice()).collect();
+    }
+    values
+}
+
+/// Returns the children of the value at `segments`, the prefix of `path` resolved so far.
+fn children_mut<'a>(
+    bindings: &'a mut Vec<Binding>,
+    segments: &[Segment],
+    path: &Path,
+) -> Result<&'a mut Vec<Binding>, PathError> {
+    let mut bindings = bindings;
+    for (i, segment) in segments.iter().enumerate() {
+        bindings = match bindings.iter_mut().find(|b| b.name == segment.name) {
+            Some(binding) => match binding.values.get_mut(segment.index.unwrap_or(0)) {
+                Some(value) => &mut value.children,
+                None => return Err(not_found(&path.segments[..=i])),
+            },
+            None => return Err(not_found(&path.segments[..=i])),
+        };
+    }
+    Ok(bindings)
+}
+
+fn split_last(path: &Path) -> Result<(&Segment, &[Segment]), PathError> {
+    path.segments
+        .split_last()
+        .ok_or_else(|| PathError::Invalid(path.to_string()))
+}
+
+/// Sets the value at `path`.
+///
+/// Without an index on the last segment, all existing values of the binding are replaced; with an
+/// index, only that value is replaced, or appended if the index is one past the end. The last
+/// binding is created if missing, but intermediate bindings must already exist.
+pub(crate) fn set(bindings: &mut Vec<Binding>, path: &Path, value: Value) -> Result<(), PathError> {
+    let (last, init) = split_last(path)?;
+    let bindings = children_mut(bindings, init, path)?;
+    match bindings.iter_mut().find(|b| b.name == last.name) {
+        Some(binding) => match last.index {
+            None => binding.values = smallvec![value],
+            Some(index) if index < binding.values.len() => binding.values[index] = value,
+            Some(index) if index == binding.values.len() => binding.values.push(value),
+            Some(_) => return Err(not_found(&path.segments)),
+        },
+        None => match last.index {
+            None | Some(0) => bindings.push(Binding {
+                name: last.name.clone(),
+                values: smallvec![value],
+            }),
+            Some(_) => return Err(not_found(&path.segments)),
+        },
+    }
+    Ok(())
+}
+
+/// Removes the binding at `path`, or only its value at the index of the last segment, and returns
+/// what was removed. A binding whose only value is removed stays, bound to no value.
+fn remove(bindings: &mut Vec<Binding>, path: &Path) -> Result<Binding, PathError> {
+    let (last, init) = split_last(path)?;
+    let bindings = children_mut(bindings, init, path)?;
+    let position = bindings
+        .iter()
+        .position(|b| b.name == last.name)
+        .ok_or_else(|| not_found(&path.segments))?;
+    match last.index {
+        None => Ok(bindings.remove(position)),
+        Some(index) if index < bindings[position].values.len() => Ok(Binding {
+            name: last.name.clone(),
+            values: smallvec![bindings[position].values.remove(index)],
+        }),
+        Some(_) => Err(not_found(&path.segments)),
+    }
+}
+
+/// Appends `child` to the children of the value at `path`.
+fn insert(bindings: &mut Vec<Binding>, path: &Path, child: Binding) -> Result<(), PathError> {
+    children_mut(bindings, &path.segments, path)?.push(child);
+    Ok(())
+}
+
+/// Renames the binding at `path`, ignoring the index of the last segment.
+fn rename(bindings: &mut Vec<Binding>, path: &Path, name: String) -> Result<(), PathError> {
+    let (last, init) = split_last(path)?;
+    match children_mut(bindings, init, path)?
+        .iter_mut()
+        .find(|b| b.name == last.name)
+    {
+        Some(binding) => {
+            binding.name = name;
+            Ok(())
+        }
+        None => Err(not_found(&path.segments)),
+    }
+}
+
+/// Applies `f` to `binding` as the only binding of a list, for paths that start with its name.
+fn edit<T>(
+    binding: &mut Binding,
+    path: &str,
+    f: impl FnOnce(&mut Vec<Binding>, &Path) -> Result<T, PathError>,
+) -> Result<T, PathError> {
+    let path: Path = path.parse()?;
+    if path.segments[0].name != binding.name {
+        return Err(not_found(&path.segments[..1]));
+    }
+    let empty = Binding {
+        name: String::new(),
+        values: smallvec![],
+    };
+    let mut bindings = vec![core::mem::replace(binding, empty)];
+    let result = f(&mut bindings, &path);
+    *binding = bindings.remove(0);
+    result
+}
+
+impl Binding {
+    /// Removes the binding at the given dotted path, or only one of its values if the last segment
+    /// has an index, and returns what was removed.
+    ///
+    /// Paths start with the name of this binding, as those yielded by [`Binding::iter`]; the
+    /// binding itself cannot be removed, only its values.
+    pub fn remove_child(&mut self, path: &str) -> Result<Binding, PathError> {
+        edit(self, path, |bindings, path| {
+            match path.segments.as_slice() {
+                [Segment { index: None, .. }] => Err(PathError::Invalid(path.to_string())),
+                _ => remove(bindings, path),
+            }
+        })
+    }
+
+    /// Appends `child` to the children of the value at the given dotted path.
+    pub fn insert_child(&mut self, path: &str, child: Binding) -> Result<(), PathError> {
+        edit(self, path, |bindings, path| insert(bindings, path, child))
+    }
+
+    /// Renames the binding at the given dotted path, which may be this binding itself.
+    pub fn rename(&mut self, path: &str, name: impl Into<String>) -> Result<(), PathError> {
+        edit(self, path, |bindings, path| {
+            rename(bindings, path, name.into())
+        })
+    }
+
+    /// Returns the values of this binding and of its descendants whose paths match the given
+    /// [`Pattern`], with those paths, in document order. Paths start with the name of this
+    /// binding, as those yielded by [`Binding::iter`].
+    pub fn glob(&self, pattern: &str) -> Result<Vec<(Path, &Value)>, PathError> {
+        let pattern: Pattern = pattern.parse()?;
+        Ok(self
+            .iter()
+            .filter(|(path, _)| pattern.matches(path))
+            .collect())
+    }
+
+    /// Sets the value at the given dotted path, as [`Document::set`] does.
+    pub fn set_value(&mut self, path: &str, value: impl Into<Value>) -> Result<(), PathError> {
+        edit(self, path, |bindings, path| {
+            set(bindings, path, value.into())
+        })
+    }
+}
+
+impl Document {
+    /// Returns the value at the given dotted path, if any.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        get(&self.bindings, &path.parse().ok()?)
+    }
+
+    /// Returns the binding at the given dotted path, if any, ignoring the index of the last
+    /// segment.
+    pub fn get_binding(&self, path: &str) -> Option<&Binding> {
+        get_binding(&self.bindings, &path.parse().ok()?)
+    }
+
+    /// Returns the value at the given dotted path, if any, matching names ignoring case, e.g.
+    /// `logLevel` finds a binding named `LogLevel`. The first binding that matches wins.
+    pub fn get_ignore_case(&self, path: &str) -> Option<&Value> {
+        find(&self.bindings, &path.parse().ok()?, eq_ignore_case)
+    }
+
+    /// Returns the binding at the given dotted path, if any, matching names ignoring case, as
+    /// `get_ignore_case` does.
+    pub fn get_binding_ignore_case(&self, path: &str) -> Option<&Binding> {
+        find_binding(&self.bindings, &path.parse().ok()?, eq_ignore_case)
+    }
+
+    /// Returns all values matching the given dotted path, in document order. Unlike `get`, a
+    /// segment without an index matches every value of every binding with that name.
+    pub fn query(&self, path: &str) -> Result<Vec<&Value>, PathError> {
+        Ok(query(&self.bindings, &path.parse()?))
+    }
+
+    /// Returns all values whose paths match the given [`Pattern`], e.g. `servers.*.host` or
+    /// `**.password`, with those paths, in document order.
+    pub fn glob(&self, pattern: &str) -> Result<Vec<(Path, &Value)>, PathError> {
+        let pattern: Pattern = pattern.parse()?;
+        Ok(self
+            .iter()
+            .filter(|(path, _)| pattern.matches(path))
+            .collect())
+    }
+
+    /// Sets the value at the given dotted path. See [`Path`] for the path syntax.
+    pub fn set(&mut self, path: &str, value: impl Into<Value>) -> Result<(), PathError> {
+        set(self.bindings_mut(), &path.parse()?, value.into())
+    }
+
+    /// Removes the binding at the given dotted path, or only one of its values if the last segment
+    /// has an index, and returns what was removed.
+    pub fn remove_child(&mut self, path: &str) -> Result<Binding, PathError> {
+        remove(self.bindings_mut(), &path.parse()?)
+    }
+
+    /// Appends `child` to the children of the value at the given dotted path.
+    pub fn insert_child(&mut self, path: &str, child: Binding) -> Result<(), PathError> {
+        insert(self.bindings_mut(), &path.parse()?, child)
+    }
+
+    /// Renames the binding at the given dotted path.
+    pub fn rename(&mut self, path: &str, name: impl Into<String>) -> Result<(), PathError> {
+        rename(self.bindings_mut(), &path.parse()?, name.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document, print_document};
+
+    #[test]
+    fn test_get_ignore_case() {
+        let (_, doc) = parse_document("Server=a{LogLevel=debug} servers=x,y{Host=h}").unwrap();
+
+        assert_eq!(None, doc.get("server.loglevel"));
+        assert_eq!(
+            Some(&Value::from("debug")),
+            doc.get_ignore_case("server.loglevel")
+        );
+        assert_eq!(
+            Some(&Value::from("h")),
+            doc.get_ignore_case("SERVERS[1].host")
+        );
+        assert_eq!(None, doc.get_ignore_case("servers.host"));
+        assert_eq!(
+            Some("Server"),
+            doc.get_binding_ignore_case("SERVER")
+                .map(|b| b.name.as_str())
+        );
+        let server = doc.get("Server").unwrap();
+        assert_eq!(
+            Some("LogLevel"),
+            server.get_ignore_case("loglevel").map(|b| b.name.as_str())
+        );
+        assert_eq!(None, server.get("loglevel"));
+    }
+
+    #[test]
+    fn test_get_set() {
+        let (_, mut doc) =
+            parse_document("server=a{listen=b{port=80}} servers=x{host=h0},y{host=h1}").unwrap();
+
+        assert_eq!(Some(&Value::from("80")), doc.get("server.listen.port"));
+        assert_eq!(Some(&Value::from("h1")), doc.get("servers[1].host"));
+        assert_eq!(None, doc.get("servers[2].host"));
+        assert_eq!(None, doc.get("server.missing"));
+        assert_eq!(None, doc.get("server..port"));
+
+        assert_eq!(Ok(()), doc.set("server.listen.port", "8080"));
+        assert_eq!(Ok(()), doc.set("servers[1].tls", "true"));
+        assert_eq!(Ok(()), doc.set("servers[1].host[1]", "h2"));
+        assert_eq!(
+            Err(PathError::NotFound("missing".to_string())),
+            doc.set("missing.port", "1")
+        );
+        assert_eq!(
+            Err(PathError::Invalid("a[x]".to_string())),
+            doc.set("a[x]", "1")
+        );
+        assert_eq!(
+            "server=a{listen=b{port=8080}}\nservers=x{host=h0},y{host=h1,h2 tls=true}",
+            print_document(&doc)
+        );
+    }
+
+    #[test]
+    fn test_query() {
+        let (_, doc) = parse_document("a=x{b=1,2} a=y{b=3 c=4} d=5").unwrap();
+
+        let tests = [
+            ("a.b", vec!["1", "2", "3"]),
+            ("a[1].b", vec!["3"]),
+            ("a.b[1]", vec!["2"]),
+            ("a.c", vec!["4"]),
+            ("d", vec!["5"]),
+            ("e.f", vec![]),
+        ];
+
+        fn values<'a>(doc: &'a Document, path: &str) -> Vec<&'a str> {
+            let values = doc.query(path).unwrap();
+            values.iter().map(|v| v.value.as_str()).collect()
+        }
+
+        for (path, expected) in tests.iter() {
+            assert_eq!(*expected, values(&doc, path), "{}", path);
+        }
+        assert_eq!(Err(PathError::Invalid("a.".to_string())), doc.query("a."));
+
+        let (_, doc) = parse_document(r#"log-level=debug "service.name"=api{_port=80}"#).unwrap();
+        assert_eq!(vec!["debug"], values(&doc, "log-level"));
+        assert_eq!(vec!["80"], values(&doc, r#""service.name"._port"#));
+        let path: Path = r#""service.name"[0]._port"#.parse().unwrap();
+        assert_eq!("service.name", path.segments[0].name);
+        assert_eq!(r#""service.name"[0]._port"#, path.to_string());
+    }
+
+    #[test]
+    fn test_glob() {
+        let (_, doc) = parse_document(
+            "servers={web={host=h0 password=p0} db={host=h1,h2 tls={password=p1}}} password=p2",
+        )
+        .unwrap();
+
+        let tests = [
+            (
+                "servers.*.host",
+                vec![
+                    "servers.web.host",
+                    "servers.db.host[0]",
+                    "servers.db.host[1]",
+                ],
+            ),
+            ("servers.*.host[1]", vec!["servers.db.host[1]"]),
+            (
+                "servers.db.*",
+                vec!["servers.db.host[0]", "servers.db.host[1]", "servers.db.tls"],
+            ),
+            (
+                "*.*.*[0]",
+                vec![
+                    "servers.web.host",
+                    "servers.web.password",
+                    "servers.db.host[0]",
+                    "servers.db.tls",
+                ],
+            ),
+            (
+                "**.password",
+                vec![
+                    "servers.web.password",
+                    "servers.db.tls.password",
+                    "password",
+                ],
+            ),
+            (
+                "servers.**.tls.**",
+                vec!["servers.db.tls", "servers.db.tls.password"],
+            ),
+            ("password[0]", vec!["password"]),
+            ("password[1]", vec![]),
+            ("hosts.*", vec![]),
+        ];
+
+        for (pattern, expected) in tests.iter() {
+            let paths = doc
+                .glob(pattern)
+                .unwrap()
+                .iter()
+                .map(|(path, _)| path.to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(*expected, paths, "{}", pattern);
+            let parsed: Pattern = pattern.parse().unwrap();
+            assert_eq!(*pattern, parsed.to_string());
+        }
+        assert_eq!(
+            Err(PathError::Invalid("a.***".to_string())),
+            doc.glob("a.***")
+        );
+
+        let (_, binding) = crate::parse_binding("a=x{b=y{c=1} c=2}").unwrap();
+        let values = binding.glob("a.**.c").unwrap();
+        assert_eq!(
+            vec!["1", "2"],
+            values.iter().map(|(_, v)| v.value()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_edit() {
+        let (_, mut doc) = parse_document("a=x{b=1,2 c=3} d=4").unwrap();
+        assert_eq!(Ok(Binding::new("b").value("2")), doc.remove_child("a.b[1]"));
+        assert_eq!(Ok(Binding::new("d").value("4")), doc.remove_child("d"));
+        assert_eq!(Ok(()), doc.insert_child("a", Binding::new("e").value("5")));
+        assert_eq!(Ok(()), doc.rename("a.c", "f"));
+        assert_eq!("a=x{b=1 f=3 e=5}", print_document(&doc));
+
+        let missing = |path: &str| PathError::NotFound(path.to_string());
+        assert_eq!(Err(missing("a.g")), doc.remove_child("a.g"));
+        assert_eq!(Err(missing("a.b[1]")), doc.remove_child("a.b[1]"));
+        assert_eq!(
+            Err(missing("g")),
+            doc.insert_child("g.h", Binding::new("i"))
+        );
+        assert_eq!(
+            Err(missing("a[1]")),
+            doc.insert_child("a[1]", Binding::new("i"))
+        );
+        assert_eq!(Err(missing("a.g")), doc.rename("a.g", "h"));
+
+        let (_, mut binding) = crate::parse_binding("a=x{b=1 c=y{d=2}},z").unwrap();
+        assert_eq!(Ok(()), binding.set_value("a.c.d", "3"));
+        assert_eq!(
+            Ok(()),
+            binding.insert_child("a.c", Binding::new("e").value("4"))
+        );
+        assert_eq!(
+            Ok(Binding::new("b").value("1")),
+            binding.remove_child("a.b")
+        );
+        assert_eq!(
+            Ok(Binding::new("a").value("z")),
+            binding.remove_child("a[1]")
+        );
+        assert_eq!(Ok(()), binding.rename("a.c", "f"));
+        assert_eq!(Ok(()), binding.rename("a", "g"));
+        assert_eq!("g=x{f=y{d=3 e=4}}", crate::print_binding(&binding));
+
+        assert_eq!(
+            Err(PathError::Invalid("g".to_string())),
+            binding.remove_child("g")
+        );
+        assert_eq!(Err(missing("a")), binding.set_value("a.f", "1"));
+        assert_eq!(Err(missing("g.h")), binding.rename("g.h", "i"));
+        assert_eq!("g=x{f=y{d=3 e=4}}", crate::print_binding(&binding));
+    }
+}
@@ -0,0 +1,413 @@
+//! A small jq-like query language over documents, e.g. `.users[] | select(role=admin) | .name`.
+//!
+//! A query is a pipeline of stages separated by `|`, each applied to every node yielded by the
+//! previous one, starting from the document itself. A stage is either a path of steps starting
+//! with a dot, or a filter:
+//!
+//! - `.` yields its input;
+//! - `.name` yields the values of the child bindings named `name`, and `."a b"` quotes a name;
+//! - `..name` yields the values of the bindings named `name` at any depth below its input;
+//! - `[n]` right after a name keeps only the `n`-th of the values it yields, counting from zero;
+//! - `[]` yields the items of nested lists, and other values unchanged; applied to the document
+//!   itself, e.g. as `.[]`, it yields the top-level values;
+//! - `select(path)`, `select(path=value)` and `select(path!=value)` keep their input if it has a
+//!   value at the dotted `path` below it, if one of those values is `value`, or if none is.
+//!
+//! Steps chain, e.g. `.server.ports[1]` or `..user.name`. The results are values along with their
+//! paths, as [`Document::iter`] yields them; the document itself is never a result, and the items
+//! of a nested list have the path of the list.
+//!
+//! ```
+//! use flax::parse_document_complete;
+//! use flax::query::Query;
+//!
+//! let document =
+//!     parse_document_complete("users=[alice{role=admin}, bob{role=dev}, carol{role=admin}]")
+//!         .unwrap();
+//! let query: Query = ".users | select(role=admin)".parse().unwrap();
+//! let matches = query.eval(&document);
+//! let names = matches.iter().map(|m| m.value.value()).collect::<Vec<_>>();
+//! assert_eq!(vec!["alice", "carol"], names);
+//! assert_eq!("users[2]", matches[1].path.to_string());
+//! ```
+
+use crate::error::complete;
+use crate::iter::{paths, Iter};
+use crate::parser::{quoted, Error};
+use crate::path::query;
+use crate::prelude::*;
+use crate::{Binding, Document, ParseError, Path, Segment, Value};
+use alloc::borrow::Cow;
+use core::str::FromStr;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, digit1, multispace0, multispace1},
+    combinator::{cut, eof, map, map_opt, opt, peek},
+    error::context,
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Step {
+    Child { name: String, index: Option<usize> },
+    Descendants { name: String, index: Option<usize> },
+    Iterate,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Condition {
+    Exists,
+    Equals(String),
+    NotEquals(String),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Stage {
+    Steps(Vec<Step>),
+    Select(Path, Condition),
+}
+
+/// A parsed query. See the [module documentation](self) for the syntax.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Query {
+    stages: Vec<Stage>,
+}
+
+/// A value yielded by a query, along with its path.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Match<'a> {
+    pub path: Path,
+    pub value: &'a Value,
+}
+
+/// A binding name in a query: letters, digits, `_` and `-`, or quoted.
+fn name<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, String, E> {
+    context(
+        "name",
+        alt((
+            map(quoted, Cow::into_owned),
+            map(
+                take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-'),
+                String::from,
+            ),
+        )),
+    )(input)
+}
+
+fn index<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, usize, E> {
+    delimited(
+        char('['),
+        map_opt(digit1, |d: &str| d.parse::<usize>().ok()),
+        char(']'),
+    )(input)
+}
+
+fn step<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Step, E> {
+    alt((
+        map(
+            preceded(tag(".."), cut(pair(name, opt(index)))),
+            |(name, index)| Step::Descendants { name, index },
+        ),
+        map(tag(".[]"), |_| Step::Iterate),
+        map(
+            preceded(char('.'), cut(pair(name, opt(index)))),
+            |(name, index)| Step::Child { name, index },
+        ),
+        map(tag("[]"), |_| Step::Iterate),
+    ))(input)
+}
+
+fn steps<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Vec<Step>, E> {
+    let identity = terminated(char('.'), peek(alt((eof, multispace1, tag("|")))));
+    preceded(
+        peek(char('.')),
+        alt((map(identity, |_| vec![]), many1(step))),
+    )(input)
+}
+
+fn condition<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, (Path, Condition), E> {
+    let path = map(
+        preceded(
+            opt(char('.')),
+            separated_list1(char('.'), pair(name, opt(index))),
+        ),
+        |segments| Path {
+            segments: segments
+                .into_iter()
+                .map(|(name, index)| Segment { name, index })
+                .collect(),
+        },
+    );
+    let value = || {
+        context(
+            "value",
+            alt((
+                map(quoted, Cow::into_owned),
+                map(
+                    take_while1(|c: char| !c.is_whitespace() && c != ')'),
+                    String::from,
+                ),
+            )),
+        )
+    };
+    let condition = alt((
+        map(
+            preceded(tuple((multispace0, tag("!="), multispace0)), value()),
+            Condition::NotEquals,
+        ),
+        map(
+            preceded(tuple((multispace0, char('='), multispace0)), value()),
+            Condition::Equals,
+        ),
+        map(multispace0, |_| Condition::Exists),
+    ));
+    pair(path, condition)(input)
+}
+
+fn stage<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Stage, E> {
+    context(
+        "query stage",
+        alt((
+            map(steps, Stage::Steps),
+            map(
+                preceded(
+                    tag("select("),
+                    cut(terminated(
+                        delimited(multispace0, condition, multispace0),
+                        context("')'", char(')')),
+                    )),
+                ),
+                |(path, condition)| Stage::Select(path, condition),
+            ),
+        )),
+    )(input)
+}
+
+fn pipeline<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, Query, E> {
+    map(
+        delimited(
+            multispace0,
+            pair(
+                stage,
+                many0(preceded(
+                    delimited(multispace0, char('|'), multispace0),
+                    cut(stage),
+                )),
+            ),
+            multispace0,
+        ),
+        |(first, rest)| Query {
+            stages: core::iter::once(first).chain(rest).collect(),
+        },
+    )(input)
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        complete(s, pipeline)
+    }
+}
+
+/// A node a stage applies to: the document, or one of its values.
+#[derive(Clone)]
+enum Node<'a> {
+    Root(&'a [Binding]),
+    Value(Path, &'a Value),
+}
+
+impl<'a> Node<'a> {
+    /// Returns the path of the node, and its child bindings.
+    fn children(&self) -> (Path, &'a [Binding]) {
+        match self {
+            Node::Root(bindings) => (Path { segments: vec![] }, bindings),
+            Node::Value(path, value) => (path.clone(), &value.children),
+        }
+    }
+}
+
+fn named(path: &Path, name: &str) -> bool {
+    path.segments.last().is_some_and(|s| s.name == name)
+}
+
+/// Keeps only the `index`-th of `nodes`, if given.
+fn nth<'a>(nodes: Vec<Node<'a>>, index: Option<usize>) -> Vec<Node<'a>> {
+    match index {
+        Some(index) => nodes.into_iter().skip(index).take(1).collect(),
+        None => nodes,
+    }
+}
+
+fn apply<'a>(step: &Step, node: Node<'a>) -> Vec<Node<'a>> {
+    match step {
+        Step::Child { name, index } => {
+            let (path, bindings) = node.children();
+            let values = bindings.iter().flat_map(|b| b.values.iter());
+            let nodes = paths(&path, bindings)
+                .into_iter()
+                .zip(values)
+                .filter(|(path, _)| named(path, name))
+                .map(|(path, value)| Node::Value(path, value))
+                .collect();
+            nth(nodes, *index)
+        }
+        Step::Descendants { name, index } => {
+            let (path, bindings) = node.children();
+            let nodes = Iter::new(path, bindings)
+                .filter(|(path, _)| named(path, name))
+                .map(|(path, value)| Node::Value(path, value))
+                .collect();
+            nth(nodes, *index)
+        }
+        Step::Iterate => match node {
+            Node::Root(bindings) => {
+                let values = bindings.iter().flat_map(|b| b.values.iter());
+                paths(&Path { segments: vec![] }, bindings)
+                    .into_iter()
+                    .zip(values)
+                    .map(|(path, value)| Node::Value(path, value))
+                    .collect()
+            }
+            Node::Value(path, value) => match value.items() {
+                Some(items) => items
+                    .iter()
+                    .map(|item| Node::Value(path.clone(), item))
+                    .collect(),
+                None => vec![Node::Value(path, value)],
+            },
+        },
+    }
+}
+
+fn select(node: &Node, path: &Path, condition: &Condition) -> bool {
+    let (_, bindings) = node.children();
+    let values = query(bindings, path);
+    match condition {
+        Condition::Exists => !values.is_empty(),
+        Condition::Equals(expected) => values.iter().any(|v| v.value == *expected),
+        Condition::NotEquals(expected) => values.iter().all(|v| v.value != *expected),
+    }
+}
+
+impl Query {
+    /// Runs the query against the top-level bindings of `document`.
+    pub fn eval<'a>(&self, document: &'a Document) -> Vec<Match<'a>> {
+        self.run(Node::Root(&document.bindings))
+    }
+
+    /// Runs the query against a document consisting of `binding` alone.
+    pub fn eval_binding<'a>(&self, binding: &'a Binding) -> Vec<Match<'a>> {
+        self.run(Node::Root(core::slice::from_ref(binding)))
+    }
+
+    fn run<'a>(&self, root: Node<'a>) -> Vec<Match<'a>> {
+        let mut nodes = vec![root];
+        for stage in &self.stages {
+            nodes = match stage {
+                Stage::Steps(steps) => steps.iter().fold(nodes, |nodes, step| {
+                    nodes.into_iter().flat_map(|n| apply(step, n)).collect()
+                }),
+                Stage::Select(path, condition) => nodes
+                    .into_iter()
+                    .filter(|n| select(n, path, condition))
+                    .collect(),
+            };
+        }
+        nodes
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Root(_) => None,
+                Node::Value(path, value) => Some(Match { path, value }),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document_complete, print_value};
+
+    #[test]
+    fn test_query() {
+        let document = parse_document_complete(
+            "server=main{ports=80,443 tls={cert=c}}\n\
+             users=[alice{role=admin name=\"Alice A\"}, bob{role=dev}, carol{role=admin}]\n\
+             list=[a,[b,c]]\n\
+             \"odd name\"=x",
+        )
+        .unwrap();
+
+        let tests = [
+            (
+                ".server.ports",
+                vec!["server.ports[0]: 80", "server.ports[1]: 443"],
+            ),
+            (".server.ports[1]", vec!["server.ports[1]: 443"]),
+            (".server | .tls.cert", vec!["server.tls.cert: c"]),
+            ("..cert", vec!["server.tls.cert: c"]),
+            ("..role[2]", vec!["users[2].role: admin"]),
+            (
+                ".users[] | select(role=admin) | .name",
+                vec!["users[0].name: \"Alice A\""],
+            ),
+            (
+                ".users | select(.role != admin)",
+                vec!["users[1]: bob{role=dev}"],
+            ),
+            (
+                ".users | select(name)",
+                vec!["users[0]: alice{role=admin name=\"Alice A\"}"],
+            ),
+            (".list[]", vec!["list[0]: a", "list[1]: b", "list[1]: c"]),
+            (".list[1]", vec!["list[1]: [b,c]"]),
+            (".\"odd name\"", vec!["\"odd name\": x"]),
+            (
+                ".[] | select(ports)",
+                vec!["server: main{ports=80,443 tls={cert=c}}"],
+            ),
+            (".", vec![]),
+            (".missing", vec![]),
+        ];
+
+        for (query, expected) in tests.iter() {
+            let query: Query = query.parse().unwrap();
+            let actual = query
+                .eval(&document)
+                .iter()
+                .map(|m| format!("{}: {}", m.path, print_value(m.value)))
+                .collect::<Vec<_>>();
+            assert_eq!(*expected, actual, "{:?}", query);
+        }
+
+        let binding = &document.bindings[0];
+        let query: Query = "..ports[0]".parse().unwrap();
+        assert_eq!("80", query.eval_binding(binding)[0].value.value());
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let tests = [
+            ("server", 0, "query stage"),
+            (".server |", 9, "query stage"),
+            (".server.", 8, "name"),
+            ("select(a=b", 10, "')'"),
+            (".a b", 3, "end of input"),
+            ("..", 2, "name"),
+        ];
+
+        for (query, offset, expected) in tests.iter() {
+            let error = query.parse::<Query>().unwrap_err();
+            assert_eq!(
+                (*offset, *expected),
+                (error.offset, error.expected.as_str()),
+                "{}",
+                query
+            );
+        }
+    }
+}
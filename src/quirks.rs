@@ -0,0 +1,217 @@
+//! Detection and repair of the syntax quirks that `ParseOptions` can reject, warn about or
+//! accept: trailing commas, unquoted special characters and trailing input. Duplicate names are
+//! handled by `duplicates`.
+
+use crate::lexer::{tokenize, TokenKind};
+use crate::prelude::*;
+
+/// Returns the offsets of the trailing commas in `input`, i.e. those followed by the end of the
+/// enclosing block, list or document, or by another binding, as in `a=b,` or `{a=b, c=d}`.
+pub(crate) fn trailing_commas(input: &str) -> Vec<usize> {
+    let tokens = tokenize(input)
+        .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Comment))
+        .collect::<Vec<_>>();
+    let mut commas = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Comma {
+            continue;
+        }
+        let trailing = match tokens.get(i + 1) {
+            None => true,
+            Some(next) => match next.kind {
+                TokenKind::CloseBrace | TokenKind::CloseBracket => true,
+                // A key directly followed by `=` starts another binding.
+                TokenKind::Ident | TokenKind::String => tokens
+                    .get(i + 2)
+                    .is_some_and(|t| t.kind == TokenKind::Equals && t.span.start == next.span.end),
+                _ => false,
+            },
+        };
+        if trailing {
+            commas.push(token.span.start);
+        }
+    }
+    commas
+}
+
+/// Whether `c` ends an unquoted run of characters.
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "=,{}[]\"".contains(c)
+}
+
+/// An input with its unquoted special characters quoted.
+pub(crate) struct Quoted {
+    pub(crate) input: String,
+    /// The offsets in the original input of the runs that were quoted.
+    pub(crate) runs: Vec<usize>,
+    /// Pairs of offsets in the quoted and original inputs, from which on offsets correspond.
+    map: Vec<(usize, usize)>,
+}
+
+impl Quoted {
+    /// Appends `c`, which is not in the original input, before its offset `at`.
+    fn insert(&mut self, c: char, at: usize) {
+        self.input.push(c);
+        self.map.push((self.input.len(), at));
+    }
+
+    /// Returns the offset in the original input of `offset` in the quoted one.
+    pub(crate) fn original(&self, offset: usize) -> usize {
+        let i = self.map.partition_point(|(quoted, _)| *quoted <= offset);
+        match i {
+            0 => offset,
+            i => {
+                let (quoted, original) = self.map[i - 1];
+                original + (offset - quoted)
+            }
+        }
+    }
+}
+
+/// Quotes each run of characters up to the nearest delimiters around characters that cannot
+/// appear unquoted, e.g. `/usr/bin` in `path=/usr/bin` or `http://a:80` in `url=http://a:80`.
+/// Returns `None` if there are no such characters.
+///
+/// Such characters are those the lexer does not recognize as the start of any token; since they
+/// make the input invalid, quoting them never changes the meaning of a valid document.
+pub(crate) fn quote_special(input: &str) -> Option<Quoted> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    let mut offset = 0;
+    // Tokenizing starts again after each run, as the lexer would take the `//` of a URL to start
+    // a comment.
+    while let Some(token) =
+        tokenize(&input[offset..]).find(|t| t.kind == TokenKind::Error && !t.text.starts_with('"'))
+    {
+        let at = offset + token.span.start;
+        let start = match input[offset..at]
+            .char_indices()
+            .rfind(|(_, c)| is_delimiter(*c))
+        {
+            Some((i, c)) => offset + i + c.len_utf8(),
+            None => offset,
+        };
+        let end = input[at..]
+            .find(is_delimiter)
+            .map_or(input.len(), |i| at + i);
+        runs.push((start, end));
+        offset = end;
+    }
+    if runs.is_empty() {
+        return None;
+    }
+    let mut quoted = Quoted {
+        input: String::new(),
+        runs: runs.iter().map(|(start, _)| *start).collect(),
+        map: vec![],
+    };
+    let mut copied = 0;
+    for (start, end) in runs {
+        quoted.input.push_str(&input[copied..start]);
+        quoted.insert('"', start);
+        for (i, c) in input[start..end].char_indices() {
+            if c == '\\' {
+                quoted.insert('\\', start + i);
+            }
+            quoted.input.push(c);
+        }
+        quoted.insert('"', end);
+        copied = end;
+    }
+    quoted.input.push_str(&input[copied..]);
+    Some(quoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_document_with, parse_document_with_warnings, ParseOptions, Strictness};
+
+    #[test]
+    fn test_trailing_commas() {
+        let tests = [
+            ("a=b,c", vec![]),
+            ("a=b,", vec![3]),
+            ("a=b, # x\n c=d", vec![3]),
+            ("a=[b, [c,],] d={e=f,}", vec![8, 10, 19]),
+            ("a=b, c d=e", vec![]),
+        ];
+
+        for (input, expected) in tests.iter() {
+            assert_eq!(*expected, trailing_commas(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_quote_special() {
+        let tests = [
+            ("a=b", None),
+            ("a=/usr/bin", Some(("a=\"/usr/bin\"", vec![2]))),
+            (
+                "url=http://x:80/ b=c\\d{e=@f}",
+                Some((
+                    "url=\"http://x:80/\" b=\"c\\\\d\"{e=\"@f\"}",
+                    vec![4, 19, 25],
+                )),
+            ),
+            ("a=\"/\" b=\"x", None),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let quoted = quote_special(input);
+            let actual = quoted.as_ref().map(|q| (q.input.as_str(), q.runs.clone()));
+            assert_eq!(*expected, actual, "{}", input);
+        }
+
+        let quoted = quote_special("a=b/c d=e").unwrap();
+        let offsets = (0..quoted.input.len())
+            .map(|i| quoted.original(i))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0, 1, 2, 2, 3, 4, 5, 5, 6, 7, 8], offsets);
+    }
+
+    #[test]
+    fn test_strictness() {
+        let input = "url=http://x/ a=b,c, a=d\n}";
+        let options = |strictness| ParseOptions::new().quirks(strictness);
+
+        let error = parse_document_with(input, &ParseOptions::new()).unwrap_err();
+        assert_eq!(
+            "1:9: expected end of input, found `://x/ a=b,c, a=d`",
+            error.to_string()
+        );
+        let error = parse_document_with("a=b,", &ParseOptions::strict()).unwrap_err();
+        assert_eq!(
+            "1:4: expected no trailing comma, found `,`",
+            error.to_string()
+        );
+        let error = parse_document_with("a=b a=c", &ParseOptions::strict()).unwrap_err();
+        assert_eq!((1, 5), (error.line, error.column));
+
+        let (document, warnings) =
+            parse_document_with_warnings(input, &options(Strictness::Warn)).unwrap();
+        assert_eq!("url=\"http://x/\"\na=b,c\na=d", document.to_string());
+        assert_eq!(
+            vec![
+                "1:5: expected quotes around special characters, found `http://x/ a=b,c, a=d`",
+                "1:20: expected no trailing comma, found `, a=d`",
+                "1:22: expected a unique name, `a` is already bound at 1:15, found `a=d`",
+                "2:1: expected end of input, found `}`",
+            ],
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>()
+        );
+
+        let (document, warnings) =
+            parse_document_with_warnings(input, &ParseOptions::lenient()).unwrap();
+        assert_eq!("url=\"http://x/\"\na=b,c\na=d", document.to_string());
+        assert!(warnings.is_empty());
+
+        // Without quoting, the special characters start the trailing input.
+        let options = ParseOptions::lenient().special_characters(Strictness::Reject);
+        assert_eq!(
+            "url=http",
+            parse_document_with(input, &options).unwrap().to_string()
+        );
+        let error = parse_document_with("a=x/y b={c=d", &ParseOptions::lenient()).unwrap_err();
+        assert_eq!("1:13: expected '}', found end of input", error.to_string());
+    }
+}
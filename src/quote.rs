@@ -0,0 +1,78 @@
+//! Quoting of names and values that cannot be printed bare.
+
+use crate::error::complete;
+use crate::parser::{self, bare_key};
+use crate::prelude::*;
+use crate::{Value, ValueKind};
+use alloc::borrow::Cow;
+
+/// Returns `s` as a quoted string, escaping quotes, backslashes and line breaks.
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Returns a binding name as it must be written.
+pub(crate) fn key(name: &str) -> Cow<'_, str> {
+    if complete(name, bare_key).is_ok() {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(quote(name))
+    }
+}
+
+/// Returns a scalar as it must be written: bare if it parses back to itself, including numbers
+/// and placeholders, or quoted otherwise.
+fn scalar(scalar: &str) -> Cow<'_, str> {
+    match complete(scalar, parser::scalar) {
+        Ok(parsed) if parsed == scalar => Cow::Borrowed(scalar),
+        _ => Cow::Owned(quote(scalar)),
+    }
+}
+
+/// Returns the scalar of `value` as it must be written, preceded by its anchor if it has one, or
+/// the reference itself.
+pub(crate) fn value(value: &Value) -> Cow<'_, str> {
+    match &value.kind {
+        ValueKind::Reference => Cow::Owned(format!("*{}", value.value)),
+        ValueKind::Anchored(anchor) => Cow::Owned(format!("&{} {}", anchor, scalar(&value.value))),
+        _ => scalar(&value.value),
+    }
+}
+
+/// Returns the scalar of `value` as a text block whose lines are indented by `indent` spaces, if it
+/// spans several lines and can be written as one, i.e. it does not contain `"""`.
+#[cfg(feature = "std")]
+pub(crate) fn text_block(value: &Value, indent: usize) -> Option<String> {
+    let scalar = value.value.as_str();
+    let anchor = match &value.kind {
+        ValueKind::Anchored(anchor) => format!("&{} ", anchor),
+        _ => String::new(),
+    };
+    if !scalar.contains('\n') || scalar.contains("\"\"\"") {
+        return None;
+    }
+    let mut out = format!("{}\"\"\"\n", anchor);
+    for line in scalar.split('\n') {
+        if !line.is_empty() {
+            out.push_str(&" ".repeat(indent));
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out.push_str(&" ".repeat(indent));
+    out.push_str("\"\"\"");
+    Some(out)
+}
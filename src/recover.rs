@@ -0,0 +1,119 @@
+//! Parsing that recovers from syntax errors, for tools that need partial results.
+
+use crate::error::{convert, Expected};
+use crate::parser::{self, ws0, ws1};
+use crate::prelude::*;
+use crate::{Document, ParseError};
+
+/// Skips whitespace and comments at the start of `input`.
+fn skip_ws(input: &str) -> &str {
+    ws0::<Expected>(input).map_or(input, |(rest, _)| rest)
+}
+
+/// Returns the input from the first line after `offset` that starts with a valid binding, or the
+/// empty suffix if there is none.
+fn resume(input: &str, offset: usize) -> &str {
+    let mut start = offset;
+    while let Some(i) = input[start..].find('\n') {
+        start += i + 1;
+        let rest = skip_ws(&input[start..]);
+        if parser::binding::<Expected>(rest).is_ok() {
+            return rest;
+        }
+    }
+    &input[input.len()..]
+}
+
+/// Parses a document, skipping past malformed bindings instead of failing.
+///
+/// Each syntax error is reported once, and parsing resumes at the first following line that
+/// starts with a valid binding; the lines in between are dropped. A binding that is malformed
+/// anywhere, including in a nested block, is dropped as a whole. Without errors the document is
+/// the same as that of `parse_document_complete`.
+pub fn parse_document_lossy(input: &str) -> (Document, Vec<ParseError>) {
+    let mut bindings = vec![];
+    let mut errors = vec![];
+    let mut rest = skip_ws(input);
+    while !rest.is_empty() {
+        match parser::binding::<Expected>(rest) {
+            Ok((after, binding)) => {
+                bindings.push(binding.into_owned());
+                rest = match ws1::<Expected>(after) {
+                    Ok((after, _)) => after,
+                    Err(_) if after.is_empty() => after,
+                    Err(_) => {
+                        let error =
+                            ParseError::new(input, input.len() - after.len(), "end of input");
+                        let rest = resume(input, error.offset);
+                        errors.push(error);
+                        rest
+                    }
+                };
+            }
+            Err(e) => {
+                let error = convert(input, e);
+                rest = resume(input, error.offset);
+                errors.push(error);
+            }
+        }
+    }
+    (Document::from_bindings(bindings), errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_lossy() {
+        struct Test {
+            input: &'static str,
+            recovered: &'static str,
+            errors: Vec<(usize, usize, &'static str)>,
+        }
+
+        let tests = [
+            Test {
+                input: "# x\na=b{c=d} e=f,g\n",
+                recovered: "a=b{c=d} e=f,g",
+                errors: vec![],
+            },
+            Test {
+                input: "a=b\nc\nd=e",
+                recovered: "a=b d=e",
+                errors: vec![(2, 2, "'='")],
+            },
+            Test {
+                input: "a=b{\n  c=d e\n}\n\nf=g h=\"i\nj=k",
+                recovered: "f=g j=k",
+                errors: vec![(2, 8, "'='"), (5, 9, "'\"'")],
+            },
+            Test {
+                input: "a=b} c=d\ne=f\n=g\n  h=i",
+                recovered: "a=b e=f h=i",
+                errors: vec![(1, 4, "end of input"), (3, 1, "identifier")],
+            },
+            Test {
+                input: "a=b{c=d",
+                recovered: "",
+                errors: vec![(1, 8, "'}'")],
+            },
+        ];
+
+        for t in tests.iter() {
+            let (document, errors) = parse_document_lossy(t.input);
+            assert_eq!(
+                parse_document_complete(t.recovered),
+                Ok(document),
+                "{}",
+                t.input
+            );
+            let errors = errors
+                .iter()
+                .map(|e| (e.line, e.column, e.expected.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(t.errors, errors, "{}", t.input);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Redaction of values by their paths, e.g. to share a document in a bug report without its
+//! credentials, whatever its schema.
+
+use crate::iter::paths;
+use crate::prelude::*;
+use crate::{Binding, Document, Path, Pattern, Value, Values};
+
+/// What to do with the values matched by [`Document::redact`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Redaction {
+    /// Replace each value, along with its children, with `***`.
+    Mask,
+    /// Remove each value, along with its children, and each binding left without values.
+    Remove,
+}
+
+fn redact(parent: &Path, bindings: &mut Vec<Binding>, patterns: &[Pattern], redaction: Redaction) {
+    let mut paths = paths(parent, bindings).into_iter();
+    bindings.retain_mut(|binding| {
+        let count = binding.values.len();
+        let mut values = Values::new();
+        let value_paths = paths.by_ref().take(count).collect::<Vec<_>>();
+        for (mut value, path) in binding.values.drain(..).zip(value_paths) {
+            if !patterns.iter().any(|p| p.matches(&path)) {
+                redact(&path, &mut value.children, patterns, redaction);
+                values.push(value);
+            } else if redaction == Redaction::Mask {
+                values.push(Value::from("***"));
+            }
+        }
+        binding.values = values;
+        count == 0 || !binding.values.is_empty()
+    });
+}
+
+impl Document {
+    /// Masks or removes every value whose path, as [`Document::iter`] yields it, matches any of
+    /// `patterns`, e.g. `**.password` or `db.*`. Indices are those of the document before
+    /// redaction.
+    ///
+    /// ```
+    /// use flax::{parse_document_complete, Redaction};
+    ///
+    /// let mut document =
+    ///     parse_document_complete("db={user=admin password=hunter2} token=t").unwrap();
+    /// let patterns = ["**.password".parse().unwrap(), "token".parse().unwrap()];
+    /// document.redact(&patterns, Redaction::Remove);
+    /// assert_eq!("db={user=admin}", document.to_string());
+    /// ```
+    pub fn redact(&mut self, patterns: &[Pattern], redaction: Redaction) {
+        redact(
+            &Path { segments: vec![] },
+            self.bindings_mut(),
+            patterns,
+            redaction,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_redact() {
+        let input = "db={user=admin password=hunter2} keys=a,b{id=1},c \
+                     servers={a={token=x host=h} b={token=y}} empty=";
+        let tests = [
+            (
+                vec!["**.password", "servers.*.token"],
+                Redaction::Mask,
+                "db={user=admin password=\"***\"}\nkeys=a,b{id=1},c\n\
+                 servers={a={token=\"***\" host=h} b={token=\"***\"}}\nempty=",
+            ),
+            (
+                vec!["**.password", "servers.*.token"],
+                Redaction::Remove,
+                "db={user=admin}\nkeys=a,b{id=1},c\nservers={a={host=h} b={}}\nempty=",
+            ),
+            (
+                vec!["keys[1]"],
+                Redaction::Mask,
+                "db={user=admin password=hunter2}\nkeys=a,\"***\",c\n\
+                 servers={a={token=x host=h} b={token=y}}\nempty=",
+            ),
+            (
+                vec!["keys[0]", "keys[2]", "db", "empty"],
+                Redaction::Remove,
+                "keys=b{id=1}\nservers={a={token=x host=h} b={token=y}}\nempty=",
+            ),
+            (vec!["**"], Redaction::Remove, "empty="),
+        ];
+
+        for (patterns, redaction, expected) in tests.iter() {
+            let patterns = patterns
+                .iter()
+                .map(|p| p.parse().unwrap())
+                .collect::<Vec<Pattern>>();
+            let mut document = parse_document_complete(input).unwrap();
+            document.redact(&patterns, *redaction);
+            assert_eq!(*expected, document.to_string(), "{:?}", patterns);
+        }
+    }
+}
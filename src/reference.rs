@@ -0,0 +1,165 @@
+//! Expansion of anchors and references.
+//!
+//! A value may be preceded by an anchor, `&name`, and later values may refer to it as `*name`,
+//! e.g. `a=&base server{port=80} b=*base`. The parser keeps both in the AST, as
+//! `ValueKind::Anchored` and `ValueKind::Reference` values; `expand_references` removes the anchors
+//! and replaces each reference with a copy of the anchored value. Quoted scalars such as `"*base"`
+//! are never references. Other formats have no anchors, so expand references before converting a
+//! document to them.
+
+use crate::prelude::*;
+use crate::{Address, Binding, Document, Value, ValueKind};
+use alloc::collections::BTreeMap;
+use core::{fmt, slice};
+
+/// A reference to an anchor that is not defined before it.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ReferenceError {
+    pub name: String,
+    /// Address of the referencing value.
+    pub address: Address,
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "undefined anchor `{}`", self.name)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReferenceError {}
+
+fn expand(
+    bindings: &mut [Binding],
+    address: &Address,
+    anchors: &mut BTreeMap<String, Value>,
+) -> Result<(), ReferenceError> {
+    for (i, binding) in bindings.iter_mut().enumerate() {
+        let address = address.child(i);
+        for (j, value) in binding.values.iter_mut().enumerate() {
+            // Values nested in a list are located at the list.
+            let address = address.child(j);
+            for value in value.scalars_mut() {
+                if value.kind == ValueKind::Reference {
+                    *value = anchors
+                        .get(&value.value)
+                        .cloned()
+                        .ok_or_else(|| ReferenceError {
+                            name: value.value.clone(),
+                            address: address.clone(),
+                        })?;
+                    continue;
+                }
+                expand(&mut value.children, &address, anchors)?;
+                if let ValueKind::Anchored(anchor) = &value.kind {
+                    let anchor = anchor.clone();
+                    value.kind = ValueKind::Scalar;
+                    anchors.insert(anchor, value.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Binding {
+    /// Materializes the references within this binding, as `Document::expand_references` does.
+    /// Addresses in errors treat the binding as the only one in a document.
+    pub fn expand_references(&mut self) -> Result<(), ReferenceError> {
+        expand(
+            slice::from_mut(self),
+            &Address::default(),
+            &mut BTreeMap::new(),
+        )
+    }
+}
+
+impl Document {
+    /// Removes all anchors and replaces each reference with a copy of the value anchored under
+    /// that name. Anchors must precede their references in document order; an anchor may be
+    /// redefined, in which case later references see the latest definition. On error, the
+    /// document may be partially expanded.
+    pub fn expand_references(&mut self) -> Result<(), ReferenceError> {
+        expand(
+            self.bindings_mut(),
+            &Address::default(),
+            &mut BTreeMap::new(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_expand_references() {
+        struct Test {
+            input: String,
+            expanded: Result<String, ReferenceError>,
+        }
+
+        let tests = [
+            Test {
+                input: "a=b".to_string(),
+                expanded: Ok("a=b".to_string()),
+            },
+            Test {
+                input: "a=&base server{port=80} b=*base,c".to_string(),
+                expanded: Ok("a=server{port=80}\nb=server{port=80},c".to_string()),
+            },
+            Test {
+                input: "a=&x  b{c=&y d e=*y} f=*x g=*y".to_string(),
+                expanded: Ok("a=b{c=d e=d}\nf=b{c=d e=d}\ng=d".to_string()),
+            },
+            Test {
+                input: "a=&x b a=&x c d=*x".to_string(),
+                expanded: Ok("a=b\na=c\nd=c".to_string()),
+            },
+            Test {
+                input: "a=&x b{c=*x}".to_string(),
+                expanded: Err(ReferenceError {
+                    name: "x".to_string(),
+                    address: Address(vec![0, 0, 0, 0]),
+                }),
+            },
+            Test {
+                input: "a=*x b=&x c".to_string(),
+                expanded: Err(ReferenceError {
+                    name: "x".to_string(),
+                    address: Address(vec![0, 0]),
+                }),
+            },
+        ];
+
+        for t in tests.iter() {
+            let mut document = parse_document_complete(&t.input).unwrap();
+            let expanded = document.expand_references().map(|_| document.to_string());
+            assert_eq!(t.expanded, expanded, "{}", t.input);
+        }
+
+        let mut document = parse_document_complete("a=&x v b=\"*x\" c=\"&x y\" d=*x").unwrap();
+        assert_eq!("a=&x v\nb=\"*x\"\nc=\"&x y\"\nd=*x", document.to_string());
+        document.expand_references().unwrap();
+        assert_eq!("a=v\nb=\"*x\"\nc=\"&x y\"\nd=v", document.to_string());
+        let document = parse_document_complete("a=&x \"*y\" b=\"&x\"{c=d}").unwrap();
+        assert_eq!(
+            &ValueKind::Anchored("x".to_string()),
+            document.bindings()[0].values()[0].kind()
+        );
+        assert_eq!("*y", document.bindings()[0].values()[0].value());
+        assert_eq!("a=&x \"*y\"\nb=\"&x\"{c=d}", document.to_string());
+        assert_eq!(
+            Ok(document.clone()),
+            parse_document_complete(&document.to_string())
+        );
+
+        let mut binding = "a=&x b{c=d},*x".parse::<Binding>().unwrap();
+        assert_eq!("a=&x b{c=d},*x", binding.to_string());
+        binding.expand_references().unwrap();
+        assert_eq!("a=b{c=d},b{c=d}", binding.to_string());
+        assert!(parse_document_complete("a=&x{b=c}").is_err());
+        assert!(parse_document_complete("a=&x b c=*x{d=e}").is_err());
+    }
+}
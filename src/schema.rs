@@ -0,0 +1,2480 @@
+//! Schemas, written as fuji documents, and validation of documents against them.
+//!
+//! A schema document lists the fields of the top-level struct:
+//!
+//! ```text
+//! field=name{type=string}
+//! field=server{type=struct{
+//!   field=host{type=string}
+//!   field=port{type=string repeated=true}
+//! }}
+//! field=mode{type=enum{variant=tcp{type=struct{field=nodelay{type=bool}}} variant=udp}}
+//! ```
+//!
+//! Types are `string`, `bool`, `struct` (whose children are `field`s) and `enum` (whose children
+//! are `variant`s, each optionally with a struct `type`). A field accepts a single value unless
+//! marked `repeated=true`, and may have `default` values, e.g. `field=port{type=string default=80}`,
+//! which [`Schema::apply_defaults`] binds to it when it is absent. A field without a default must
+//! be present unless marked `optional=true`. A repeated field may bound its number of values with
+//! `min` and `max`, e.g. `field=hosts{type=string repeated=true min=1 max=5}`. A field marked
+//! `secret=true`, such as a password, is masked by [`print_redacted`].
+//!
+//! Fields and variants marked `deprecated=true` are reported with a warning wherever they are
+//! used. The deprecation may name a replacement and explain itself instead, e.g.
+//! `deprecated={replacement=listen message="ports moved to listeners"}`.
+//!
+//! A schema document may also define named struct and enum types, which fields and variants use
+//! as their `type` and which may use each other, in any order:
+//!
+//! ```text
+//! struct=Listener{field=host{type=string} field=port{type=string}}
+//! enum=Mode{variant=tcp{type=Listener} variant=udp}
+//! field=listen{type=Listener repeated=true}
+//! field=mode{type=Mode}
+//! ```
+//!
+//! A named type may contain itself, e.g. `struct=Tree{field=children{type=Tree repeated=true}}`,
+//! in which case it is a [`Schema::Recursive`] type.
+//!
+//! A named type may have type parameters, declared by `param` bindings before its fields or
+//! variants, which stand for the types given as arguments wherever it is used:
+//!
+//! ```text
+//! struct=Pair{param=T field=first{type=T} field=second{type=T}}
+//! struct=Entry{param=K,V field=key{type=K} field=value{type=V}}
+//! field=size{type=Pair{T=string}}
+//! field=limits{type=Entry{K=string V=Pair{T=string}} repeated=true}
+//! ```
+//!
+//! Each use is resolved to a copy of the type with the arguments in place of the parameters. A
+//! recursive instance is named after its arguments, e.g. `List<string>`.
+//!
+//! Fields and variants are documented by a `doc` attribute or, when the schema is read with
+//! [`Schema::from_cst`], by the `##` comment lines right above them:
+//!
+//! ```text
+//! ## The port to listen on.
+//! field=port{type=string default=80}
+//! ```
+
+use crate::path::eq_ignore_case;
+use crate::prelude::*;
+use crate::suggest;
+use crate::{
+    cst, print_document, Address, Binding, Deprecation, DeserializeError, Document, Field,
+    FujiDeserialize, Path, Schema, Segment, ValidationOptions, Value, ValueKind, Variant,
+};
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::fmt;
+
+/// A schema document that does not describe a valid schema.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SchemaError {
+    /// The offending node of the schema document.
+    pub address: Address,
+    pub message: String,
+}
+
+impl SchemaError {
+    fn new(address: &Address, message: impl Into<String>) -> Self {
+        SchemaError {
+            address: address.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SchemaError {}
+
+/// How serious a violation of a schema is.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    /// A violation that does not make the document invalid, such as an unknown field by default.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A violation of a schema by a document.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ValidationError {
+    /// The path of the offending binding or value, e.g. `server.port`.
+    pub path: String,
+    /// The offending node of the validated document. For a missing field, the value or document
+    /// that should contain it.
+    pub address: Address,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// Returns the only value of `binding`, as schema attributes are all singular.
+fn single<'a>(binding: &'a Binding, address: &Address) -> Result<&'a Value, SchemaError> {
+    match binding.values.as_slice() {
+        [value] => Ok(value),
+        values => Err(SchemaError::new(
+            address,
+            format!(
+                "expected a single value for `{}`, found {}",
+                binding.name,
+                values.len()
+            ),
+        )),
+    }
+}
+
+/// A named type defined by a schema document.
+struct Definition<'a> {
+    /// `struct` or `enum`.
+    kind: &'a str,
+    /// The body of the definition, starting with its type parameters.
+    value: &'a Value,
+    address: Address,
+    /// The names of the type parameters, given by the leading `param` bindings of the body.
+    params: Vec<&'a str>,
+    /// The number of `param` bindings.
+    param_bindings: usize,
+}
+
+/// A type being resolved.
+struct Resolving<'a> {
+    name: &'a str,
+    kind: &'a str,
+    args: Vec<Schema>,
+    /// The name of the type with its arguments, e.g. `List<string>`, which the type is referred to
+    /// by if it contains itself.
+    instance: String,
+}
+
+/// The maximum nesting of the types being resolved, beyond which a type is assumed to contain
+/// itself with ever larger type arguments, e.g. `List<T>` containing a `List<Pair<T>>`.
+const MAX_DEPTH: usize = 64;
+
+/// The named types defined by a schema document, resolved on first use.
+#[derive(Default)]
+struct Types<'a> {
+    definitions: BTreeMap<&'a str, Definition<'a>>,
+    /// The types without type parameters resolved so far.
+    resolved: BTreeMap<String, Schema>,
+    /// The types being resolved, innermost last, to detect types that contain themselves.
+    resolving: Vec<Resolving<'a>>,
+    /// The instances being resolved that were found to contain themselves.
+    recursive: BTreeSet<String>,
+    /// The arguments of the type parameters of the types being resolved, innermost last. Only the
+    /// innermost are in scope.
+    scopes: Vec<BTreeMap<&'a str, Schema>>,
+    /// The arguments of each instance named so far, so that distinct instances get distinct names.
+    instances: BTreeMap<String, Vec<Schema>>,
+}
+
+fn built_in(name: &str) -> bool {
+    matches!(name, "struct" | "enum" | "string" | "bool")
+}
+
+/// Returns the name of a type argument in the name of an instance.
+fn type_label(schema: &Schema) -> &str {
+    match schema {
+        Schema::Struct { .. } => "struct",
+        Schema::Enum { .. } => "enum",
+        Schema::String => "string",
+        Schema::Bool => "bool",
+        Schema::Recursive { name, .. } | Schema::Ref(name) => name,
+    }
+}
+
+impl<'a> Types<'a> {
+    /// Registers the type defined by `value`, a `struct` or `enum` as given by `kind`.
+    fn define(
+        &mut self,
+        kind: &'a str,
+        value: &'a Value,
+        address: Address,
+    ) -> Result<(), SchemaError> {
+        let name = value.value.as_str();
+        if built_in(name) {
+            return Err(SchemaError::new(
+                &address,
+                format!("type `{}` is built in", name),
+            ));
+        }
+        if self.definitions.contains_key(name) {
+            return Err(SchemaError::new(
+                &address,
+                format!("duplicate type `{}`", name),
+            ));
+        }
+        let mut params = vec![];
+        let param_bindings = value
+            .children
+            .iter()
+            .take_while(|b| b.name == "param")
+            .count();
+        for (i, binding) in value.children[..param_bindings].iter().enumerate() {
+            for (j, param) in binding.values.iter().enumerate() {
+                let address = address.child(i).child(j);
+                let message = if built_in(&param.value) {
+                    format!("type `{}` is built in", param.value)
+                } else if params.contains(&param.value.as_str()) {
+                    format!("duplicate type parameter `{}`", param.value)
+                } else if !param.children.is_empty() {
+                    format!("type parameter `{}` takes no children", param.value)
+                } else {
+                    params.push(param.value.as_str());
+                    continue;
+                };
+                return Err(SchemaError::new(&address, message));
+            }
+        }
+        let definition = Definition {
+            kind,
+            value,
+            address,
+            params,
+            param_bindings,
+        };
+        self.definitions.insert(name, definition);
+        Ok(())
+    }
+
+    /// Returns the argument of the type parameter `name` of the innermost type being resolved, if
+    /// it has such a parameter.
+    fn argument(&self, name: &str) -> Option<&Schema> {
+        self.scopes.last()?.get(name)
+    }
+
+    /// Returns the type named by `value`, at `address`, whose children are the arguments of the
+    /// type parameters of the type, e.g. `Pair{T=string}`.
+    fn resolve(&mut self, value: &Value, address: &Address) -> Result<Schema, SchemaError> {
+        let name = value.value.as_str();
+        if value.children.is_empty() {
+            if let Some(schema) = self.resolved.get(name) {
+                return Ok(schema.clone());
+            }
+        }
+        let (name, definition) = match self.definitions.get_key_value(name) {
+            Some((name, definition)) => (*name, definition),
+            None => {
+                return Err(SchemaError::new(
+                    address,
+                    format!("unknown type `{}`", name),
+                ))
+            }
+        };
+        if definition.params.is_empty() && !value.children.is_empty() {
+            return Err(SchemaError::new(
+                address,
+                format!("type `{}` takes no children", name),
+            ));
+        }
+        let params = definition.params.clone();
+        let mut bound = BTreeMap::new();
+        for (param, schema, arg_address) in arguments(value, address, self)? {
+            let message = match params.iter().find(|p| **p == param) {
+                Some(param) if !bound.contains_key(param) => {
+                    bound.insert(*param, schema);
+                    continue;
+                }
+                Some(_) => format!("duplicate type argument `{}`", param),
+                None => format!("unknown type parameter `{}` of `{}`", param, name),
+            };
+            return Err(SchemaError::new(&arg_address, message));
+        }
+        if let Some(param) = params.iter().find(|p| !bound.contains_key(*p)) {
+            return Err(SchemaError::new(
+                address,
+                format!("missing type argument `{}` of `{}`", param, name),
+            ));
+        }
+        self.instantiate(name, bound, address)
+    }
+
+    /// Checks the definition of the type `name`, defined at `address`. Types without type
+    /// parameters are resolved, and the others are checked with their parameters standing for
+    /// themselves.
+    fn check(&mut self, name: &str, address: &Address) -> Result<(), SchemaError> {
+        let (name, definition) = match self.definitions.get_key_value(name) {
+            Some((name, definition)) => (*name, definition),
+            None => return Ok(()),
+        };
+        let bound = definition
+            .params
+            .iter()
+            .map(|param| (*param, Schema::Ref(param.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        if bound.is_empty() {
+            self.resolve(&Value::new(name), address)?;
+        } else {
+            self.instantiate(name, bound, address)?;
+        }
+        Ok(())
+    }
+
+    /// Resolves the body of the type `name`, with `bound` as the arguments of its type parameters.
+    fn instantiate(
+        &mut self,
+        name: &'a str,
+        bound: BTreeMap<&'a str, Schema>,
+        address: &Address,
+    ) -> Result<Schema, SchemaError> {
+        let definition = &self.definitions[name];
+        let (kind, value) = (definition.kind, definition.value);
+        let params = definition.param_bindings;
+        let args = definition
+            .params
+            .iter()
+            .map(|param| bound[param].clone())
+            .collect::<Vec<_>>();
+        let definition = definition.address.clone();
+        let same = |r: &&Resolving| r.name == name && r.args == args;
+        if let Some(resolving) = self.resolving.iter().find(same) {
+            let instance = resolving.instance.clone();
+            self.recursive.insert(instance.clone());
+            return Ok(Schema::Ref(instance));
+        }
+        if self.resolving.len() >= MAX_DEPTH {
+            return Err(SchemaError::new(
+                address,
+                format!("type `{}` is nested too deeply", name),
+            ));
+        }
+        let instance = self.instance(name, &args);
+        self.resolving.push(Resolving {
+            name,
+            kind,
+            args,
+            instance,
+        });
+        self.scopes.push(bound);
+        let mut schema = match kind {
+            "struct" => Schema::Struct {
+                fields: fields(&value.children, params, &definition, self)?,
+            },
+            _ => Schema::Enum {
+                variants: variants(&value.children, params, &definition, self)?,
+            },
+        };
+        self.scopes.pop();
+        let Resolving { instance, args, .. } = self.resolving.pop().unwrap();
+        if self.recursive.remove(&instance) {
+            schema = Schema::Recursive {
+                name: instance,
+                schema: Box::new(schema),
+            };
+        }
+        // Types resolved within others may refer to the types enclosing them.
+        if self.resolving.is_empty() && args.is_empty() {
+            self.resolved.insert(name.to_string(), schema.clone());
+        }
+        Ok(schema)
+    }
+
+    /// Returns the name of the instance of the type `name` with the type arguments `args`, e.g.
+    /// `List<string>`, or `name` itself if it has no type parameters.
+    fn instance(&mut self, name: &str, args: &[Schema]) -> String {
+        if args.is_empty() {
+            return name.to_string();
+        }
+        let labels = args.iter().map(type_label).collect::<Vec<_>>();
+        let mut instance = format!("{}<{}>", name, labels.join(", "));
+        // Distinct struct or enum arguments have the same label.
+        loop {
+            match self.instances.get(&instance) {
+                Some(other) if other != args => instance.push('\''),
+                Some(_) => return instance,
+                None => {
+                    self.instances.insert(instance.clone(), args.to_vec());
+                    return instance;
+                }
+            }
+        }
+    }
+
+    /// Returns whether `schema` is a struct, possibly one still being resolved.
+    fn is_struct(&self, schema: &Schema) -> bool {
+        match schema {
+            Schema::Struct { .. } => true,
+            Schema::Recursive { schema, .. } => self.is_struct(schema),
+            Schema::Ref(name) => {
+                self.resolving
+                    .iter()
+                    .any(|r| r.instance == *name && r.kind == "struct")
+                    // A type parameter of a type checked without arguments may be a struct.
+                    || self.argument(name) == Some(schema)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Reads the fields of a struct from `bindings`, skipping the first `params`, which declare the
+/// type parameters of a named type.
+fn fields(
+    bindings: &[Binding],
+    params: usize,
+    address: &Address,
+    types: &mut Types,
+) -> Result<Vec<Field>, SchemaError> {
+    let mut fields = vec![];
+    for (i, binding) in bindings.iter().enumerate().skip(params) {
+        let address = address.child(i);
+        if binding.name != "field" {
+            return Err(SchemaError::new(
+                &address,
+                format!("unexpected `{}`, expected `field`", binding.name),
+            ));
+        }
+        for (j, value) in binding.values.iter().enumerate() {
+            fields.push(field(value, &address.child(j), types)?);
+        }
+    }
+    Ok(fields)
+}
+
+fn field(value: &Value, address: &Address, types: &mut Types) -> Result<Field, SchemaError> {
+    let mut schema = None;
+    let mut repeated = false;
+    let mut optional = false;
+    let mut secret = false;
+    let mut bounds = (None, None);
+    let mut default = None;
+    let mut deprecated = None;
+    let mut doc = None;
+    for (i, binding) in value.children.iter().enumerate() {
+        let address = address.child(i);
+        if binding.name == "default" {
+            default = Some((binding, address));
+            continue;
+        }
+        let attribute = single(binding, &address)?;
+        let attribute_address = address.child(0);
+        match binding.name.as_str() {
+            "type" => schema = Some(schema_type(attribute, &attribute_address, types)?),
+            "repeated" => repeated = flag(attribute, &attribute_address)?,
+            "optional" => optional = flag(attribute, &attribute_address)?,
+            "secret" => secret = flag(attribute, &attribute_address)?,
+            "min" => bounds.0 = Some((count(attribute, &attribute_address)?, address)),
+            "max" => bounds.1 = Some((count(attribute, &attribute_address)?, address)),
+            "deprecated" => deprecated = deprecation(attribute, &attribute_address)?,
+            "doc" => doc = Some(attribute.value.clone()),
+            name => {
+                return Err(SchemaError::new(
+                    &address,
+                    format!("unknown field attribute `{}`", name),
+                ))
+            }
+        }
+    }
+    if let (Some((min, _)), Some((max, address))) = (&bounds.0, &bounds.1) {
+        if min > max {
+            return Err(SchemaError::new(address, "`max` is less than `min`"));
+        }
+    }
+    if let (Some((_, address)), _) | (None, Some((_, address))) = &bounds {
+        if !repeated {
+            return Err(SchemaError::new(
+                address,
+                "`min` and `max` require `repeated=true`",
+            ));
+        }
+    }
+    let field = Field {
+        name: value.value.clone(),
+        repeated,
+        optional,
+        secret,
+        min: bounds.0.map(|(min, _)| min),
+        max: bounds.1.map(|(max, _)| max),
+        schema: schema.ok_or_else(|| {
+            SchemaError::new(address, format!("field `{}` has no type", value.value))
+        })?,
+        default: None,
+        deprecated,
+        doc,
+    };
+    match default {
+        Some((binding, address)) => Ok(Field {
+            default: Some(check_default(&field, binding, &address)?),
+            ..field
+        }),
+        None => Ok(field),
+    }
+}
+
+fn flag(value: &Value, address: &Address) -> Result<bool, SchemaError> {
+    match value.value.as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        v => Err(SchemaError::new(
+            address,
+            format!("invalid value `{}`, expected bool", v),
+        )),
+    }
+}
+
+/// The named types defined by a schema document, with the top-level struct of its fields.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SchemaRegistry {
+    types: BTreeMap<String, Schema>,
+    root: Schema,
+}
+
+impl SchemaRegistry {
+    /// Reads the `struct` and `enum` definitions and the top-level fields of a schema document,
+    /// resolving the types they refer to by name.
+    pub fn from_document(document: &Document) -> Result<SchemaRegistry, SchemaError> {
+        let mut types = Types::default();
+        for (i, binding) in document.bindings.iter().enumerate() {
+            let address = Address::default().child(i);
+            match binding.name.as_str() {
+                kind @ ("struct" | "enum") => {
+                    for (j, value) in binding.values.iter().enumerate() {
+                        types.define(kind, value, address.child(j))?;
+                    }
+                }
+                "field" => {}
+                name => {
+                    return Err(SchemaError::new(
+                        &address,
+                        format!(
+                            "unexpected `{}`, expected `field`, `struct` or `enum`",
+                            name
+                        ),
+                    ))
+                }
+            }
+        }
+        let mut fields = vec![];
+        for (i, binding) in document.bindings.iter().enumerate() {
+            let address = Address::default().child(i);
+            for (j, value) in binding.values.iter().enumerate() {
+                if binding.name == "field" {
+                    fields.push(field(value, &address.child(j), &mut types)?);
+                } else {
+                    types.check(&value.value, &address.child(j))?;
+                }
+            }
+        }
+        Ok(SchemaRegistry {
+            types: types.resolved,
+            root: Schema::Struct { fields },
+        })
+    }
+
+    /// Returns the type named `name`, unless it has type parameters.
+    pub fn get(&self, name: &str) -> Option<&Schema> {
+        self.types.get(name)
+    }
+
+    /// Returns the names of the types, in alphabetical order. Types with type parameters are left
+    /// out, as they are only resolved with arguments.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.types.keys().map(String::as_str)
+    }
+
+    /// Returns the struct of the top-level fields of the schema document.
+    pub fn root(&self) -> &Schema {
+        &self.root
+    }
+}
+
+/// Adds the doc comments above the `field` and `variant` bindings of `bindings` to the values of
+/// the corresponding `ast` bindings as `doc` attributes, recursively. `start` tells whether
+/// `bindings` starts the document, in which case its leading trivia starts on a line of its own.
+fn document_comments(bindings: &cst::Bindings, ast: &mut [Binding], start: bool) {
+    for (i, ((trivia, binding), ast)) in bindings.items.iter().zip(ast.iter_mut()).enumerate() {
+        for (item, value) in binding.values.iter().zip(ast.values.iter_mut()) {
+            if let Some((_, block)) = &item.value.block {
+                document_comments(block, &mut value.children, false);
+            }
+        }
+        let doc = match doc_comment(trivia, start && i == 0) {
+            Some(doc) if ast.name == "field" || ast.name == "variant" => doc,
+            _ => continue,
+        };
+        for value in ast.values.iter_mut() {
+            if value.children.iter().all(|b| b.name != "doc") {
+                value.children.push(Binding::new("doc").value(doc.as_str()));
+            }
+        }
+    }
+}
+
+/// Returns the `##` comment lines at the end of `trivia`, which precedes a binding. Unless
+/// `line_start` is true, the first line of `trivia` follows another token, so it is not part of
+/// the doc comment.
+fn doc_comment(trivia: &str, line_start: bool) -> Option<String> {
+    let mut lines = trivia.split('\n').collect::<Vec<_>>();
+    // The last line is the indentation of the binding.
+    lines.pop();
+    let skip = usize::from(!line_start).min(lines.len());
+    let lines = &lines[skip..];
+    let count = lines
+        .iter()
+        .rev()
+        .take_while(|line| line.trim_start().starts_with("##"))
+        .count();
+    if count == 0 {
+        return None;
+    }
+    let doc = lines[lines.len() - count..]
+        .iter()
+        .map(|line| {
+            let line = line.trim_start().trim_end_matches('\r');
+            let text = &line[2..];
+            text.strip_prefix(' ').unwrap_or(text)
+        })
+        .collect::<Vec<_>>();
+    Some(doc.join("\n"))
+}
+
+/// Reads a `deprecated` attribute: either a bool, or a map of an optional `replacement` and
+/// `message`.
+fn deprecation(value: &Value, address: &Address) -> Result<Option<Deprecation>, SchemaError> {
+    if !value.is_map() {
+        return Ok(flag(value, address)?.then(Deprecation::default));
+    }
+    let mut deprecation = Deprecation::default();
+    for (i, binding) in value.children.iter().enumerate() {
+        let address = address.child(i);
+        let attribute = Some(single(binding, &address)?.value.clone());
+        match binding.name.as_str() {
+            "replacement" => deprecation.replacement = attribute,
+            "message" => deprecation.message = attribute,
+            name => {
+                return Err(SchemaError::new(
+                    &address,
+                    format!("unknown deprecation attribute `{}`", name),
+                ))
+            }
+        }
+    }
+    Ok(Some(deprecation))
+}
+
+/// Describes the use of a deprecated field or variant, e.g. `deprecated field`.
+fn deprecated(what: &str, deprecation: &Deprecation) -> String {
+    let mut message = format!("deprecated {}", what);
+    if let Some(replacement) = &deprecation.replacement {
+        message.push_str(&format!(", use `{}` instead", replacement));
+    }
+    if let Some(reason) = &deprecation.message {
+        message.push_str(&format!(": {}", reason));
+    }
+    message
+}
+
+fn count(value: &Value, address: &Address) -> Result<usize, SchemaError> {
+    value.value.parse().map_err(|_| {
+        SchemaError::new(
+            address,
+            format!(
+                "invalid value `{}`, expected a non-negative integer",
+                value.value
+            ),
+        )
+    })
+}
+
+/// Describes how `count` values violate the bounds of `field`, if they do.
+fn cardinality(field: &Field, count: usize) -> Option<String> {
+    let values = |n: usize| format!("{} value{}", n, if n == 1 { "" } else { "s" });
+    match (field.min, field.max) {
+        (Some(min), Some(max)) if min == max && count != min => {
+            Some(format!("expected exactly {}, found {}", values(min), count))
+        }
+        (Some(min), _) if count < min => Some(format!(
+            "expected at least {}, found {}",
+            values(min),
+            count
+        )),
+        (_, Some(max)) if count > max => {
+            Some(format!("expected at most {}, found {}", values(max), count))
+        }
+        _ => None,
+    }
+}
+
+/// Checks the `default` attribute of `field` against the field itself.
+fn check_default(
+    field: &Field,
+    binding: &Binding,
+    address: &Address,
+) -> Result<Vec<Value>, SchemaError> {
+    if !field.repeated && binding.values.len() > 1 {
+        return Err(SchemaError::new(
+            address,
+            format!(
+                "expected a single default value, found {}",
+                binding.values.len()
+            ),
+        ));
+    }
+    if let Some(message) = cardinality(field, binding.values.len()) {
+        return Err(SchemaError::new(
+            address,
+            format!("invalid default: {}", message),
+        ));
+    }
+    let mut errors = vec![];
+    let node = Node {
+        path: Path { segments: vec![] },
+        address: address.clone(),
+    };
+    let options = ValidationOptions::new();
+    for (i, value) in binding.values.iter().enumerate() {
+        validate_value(
+            &field.schema,
+            value,
+            &node.child(i, None),
+            &options,
+            &mut errors,
+        );
+    }
+    match errors.into_iter().find(|e| e.severity == Severity::Error) {
+        Some(error) => Err(SchemaError::new(
+            &error.address,
+            format!("invalid default: {}", error.message),
+        )),
+        None => Ok(binding.values.to_vec()),
+    }
+}
+
+/// Reads the variants of an enum from `bindings`, skipping the first `params`, as [`fields`] does.
+fn variants(
+    bindings: &[Binding],
+    params: usize,
+    address: &Address,
+    types: &mut Types,
+) -> Result<Vec<Variant>, SchemaError> {
+    let mut variants = vec![];
+    for (i, binding) in bindings.iter().enumerate().skip(params) {
+        let address = address.child(i);
+        if binding.name != "variant" {
+            return Err(SchemaError::new(
+                &address,
+                format!("unexpected `{}`, expected `variant`", binding.name),
+            ));
+        }
+        for (j, value) in binding.values.iter().enumerate() {
+            let address = address.child(j);
+            let mut schema = Schema::Struct { fields: vec![] };
+            let mut deprecated = None;
+            let mut doc = None;
+            for (k, attribute) in value.children.iter().enumerate() {
+                let address = address.child(k);
+                let value = single(attribute, &address)?;
+                match attribute.name.as_str() {
+                    "type" => schema = schema_type(value, &address.child(0), types)?,
+                    "deprecated" => deprecated = deprecation(value, &address.child(0))?,
+                    "doc" => doc = Some(value.value.clone()),
+                    name => {
+                        return Err(SchemaError::new(
+                            &address,
+                            format!("unknown variant attribute `{}`", name),
+                        ))
+                    }
+                }
+                if !types.is_struct(&schema) {
+                    return Err(SchemaError::new(
+                        &address.child(0),
+                        "variant types must be structs",
+                    ));
+                }
+            }
+            variants.push(Variant {
+                name: value.value.clone(),
+                schema,
+                deprecated,
+                doc,
+            });
+        }
+    }
+    Ok(variants)
+}
+
+fn schema_type(value: &Value, address: &Address, types: &mut Types) -> Result<Schema, SchemaError> {
+    let schema = match value.value.as_str() {
+        "struct" => {
+            return Ok(Schema::Struct {
+                fields: fields(&value.children, 0, address, types)?,
+            })
+        }
+        "enum" => {
+            return Ok(Schema::Enum {
+                variants: variants(&value.children, 0, address, types)?,
+            })
+        }
+        "string" => Schema::String,
+        "bool" => Schema::Bool,
+        name => match types.argument(name) {
+            Some(argument) => argument.clone(),
+            None => return types.resolve(value, address),
+        },
+    };
+    if !value.children.is_empty() {
+        return Err(SchemaError::new(
+            address,
+            format!("type `{}` takes no children", value.value),
+        ));
+    }
+    Ok(schema)
+}
+
+/// Reads the type arguments of a named type from the children of `value`, which refers to it,
+/// e.g. `T=string` in `Pair{T=string}`.
+fn arguments<'v>(
+    value: &'v Value,
+    address: &Address,
+    types: &mut Types,
+) -> Result<Vec<(&'v str, Schema, Address)>, SchemaError> {
+    let mut arguments = vec![];
+    for (i, binding) in value.children.iter().enumerate() {
+        let address = address.child(i);
+        let argument = single(binding, &address)?;
+        let schema = schema_type(argument, &address.child(0), types)?;
+        arguments.push((binding.name.as_str(), schema, address));
+    }
+    Ok(arguments)
+}
+
+fn field_to_binding(field: &Field) -> Binding {
+    let mut value = Value::new(field.name.as_str()).child(type_to_binding(&field.schema));
+    if field.repeated {
+        value = value.child(Binding::new("repeated").value("true"));
+    }
+    if field.optional {
+        value = value.child(Binding::new("optional").value("true"));
+    }
+    if field.secret {
+        value = value.child(Binding::new("secret").value("true"));
+    }
+    if let Some(min) = field.min {
+        value = value.child(Binding::new("min").value(min.to_string()));
+    }
+    if let Some(max) = field.max {
+        value = value.child(Binding::new("max").value(max.to_string()));
+    }
+    if let Some(default) = &field.default {
+        value = value.child(Binding {
+            name: "default".to_string(),
+            values: default.iter().cloned().collect(),
+        });
+    }
+    if let Some(deprecation) = &field.deprecated {
+        value = value.child(deprecation_to_binding(deprecation));
+    }
+    if let Some(doc) = &field.doc {
+        value = value.child(Binding::new("doc").value(doc.as_str()));
+    }
+    Binding::new("field").value(value)
+}
+
+fn deprecation_to_binding(deprecation: &Deprecation) -> Binding {
+    let attributes = [
+        ("replacement", &deprecation.replacement),
+        ("message", &deprecation.message),
+    ];
+    let children = attributes
+        .iter()
+        .filter_map(|(name, value)| Some(Binding::new(*name).value(value.as_deref()?)))
+        .collect::<Vec<_>>();
+    if children.is_empty() {
+        Binding::new("deprecated").value("true")
+    } else {
+        Binding::new("deprecated").value(Value::map(children))
+    }
+}
+
+fn type_to_binding(schema: &Schema) -> Binding {
+    let value = match schema {
+        Schema::Struct { fields } => Value {
+            value: "struct".to_string(),
+            kind: ValueKind::Scalar,
+            children: fields.iter().map(field_to_binding).collect(),
+        },
+        Schema::Enum { variants } => Value {
+            value: "enum".to_string(),
+            kind: ValueKind::Scalar,
+            children: variants
+                .iter()
+                .map(|v| {
+                    let mut value = match &v.schema {
+                        Schema::Struct { fields } if fields.is_empty() => {
+                            Value::new(v.name.as_str())
+                        }
+                        schema => Value::new(v.name.as_str()).child(type_to_binding(schema)),
+                    };
+                    if let Some(deprecation) = &v.deprecated {
+                        value = value.child(deprecation_to_binding(deprecation));
+                    }
+                    if let Some(doc) = &v.doc {
+                        value = value.child(Binding::new("doc").value(doc.as_str()));
+                    }
+                    Binding::new("variant").value(value)
+                })
+                .collect(),
+        },
+        Schema::String => Value::new("string"),
+        Schema::Bool => Value::new("bool"),
+        Schema::Recursive { name, .. } | Schema::Ref(name) => Value::new(name.as_str()),
+    };
+    Binding::new("type").value(value)
+}
+
+/// Appends the name and schema of each recursive type in `schema` to `types`, unless a type of
+/// the same name is already there.
+fn recursive_types<'a>(schema: &'a Schema, types: &mut Vec<(&'a str, &'a Schema)>) {
+    match schema {
+        Schema::Recursive { name, schema } => {
+            if types.iter().all(|(other, _)| other != name) {
+                types.push((name, schema));
+                recursive_types(schema, types);
+            }
+        }
+        Schema::Struct { fields } => {
+            for field in fields {
+                recursive_types(&field.schema, types);
+            }
+        }
+        Schema::Enum { variants } => {
+            for variant in variants {
+                recursive_types(&variant.schema, types);
+            }
+        }
+        Schema::String | Schema::Bool | Schema::Ref(_) => {}
+    }
+}
+
+/// Writes the definition of the recursive type `name`, e.g. `struct=Tree{...}`.
+fn definition_to_binding(name: &str, schema: &Schema) -> Binding {
+    let mut value = type_to_binding(schema).values.remove(0);
+    let kind = core::mem::replace(&mut value.value, name.to_string());
+    Binding::new(kind).value(value)
+}
+
+impl Field {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn is_repeated(&self) -> bool {
+        self.repeated
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.secret
+    }
+
+    /// Returns the deprecation of the field, if it is deprecated.
+    pub fn deprecation(&self) -> Option<&Deprecation> {
+        self.deprecated.as_ref()
+    }
+
+    /// Returns the documentation of the field, if any.
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+}
+
+impl Variant {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Returns the deprecation of the variant, if it is deprecated.
+    pub fn deprecation(&self) -> Option<&Deprecation> {
+        self.deprecated.as_ref()
+    }
+
+    /// Returns the documentation of the variant, if any.
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+}
+
+impl Schema {
+    /// Writes the schema in the format read by [`Schema::from_document`]. Only struct schemas
+    /// have fields; any other schema produces an empty document. Recursive types are defined by
+    /// name before the fields, and other types are written inline.
+    pub fn to_document(&self) -> Document {
+        let schema = self.unfold();
+        let fields = match &*schema {
+            Schema::Struct { fields } => fields,
+            _ => return Document::new(),
+        };
+        let mut types = vec![];
+        recursive_types(&schema, &mut types);
+        let definitions = types
+            .iter()
+            .map(|(name, schema)| definition_to_binding(name, schema));
+        Document::from_bindings(
+            definitions
+                .chain(fields.iter().map(field_to_binding))
+                .collect(),
+        )
+    }
+
+    /// Returns the schema of a recursive type with its references to itself replaced by the type,
+    /// which unfolds it by one level, or any other schema as it is.
+    ///
+    /// ```
+    /// use flax::{parse_document_complete, Schema};
+    ///
+    /// let schema = "struct=Tree{field=children{type=Tree repeated=true}} field=root{type=Tree}";
+    /// let schema = Schema::from_document(&parse_document_complete(schema).unwrap()).unwrap();
+    /// let tree = match &schema {
+    ///     Schema::Struct { fields } => fields[0].schema(),
+    ///     _ => unreachable!(),
+    /// };
+    /// assert!(matches!(tree, Schema::Recursive { .. }));
+    /// match &*tree.unfold() {
+    ///     Schema::Struct { fields } => assert_eq!(tree, fields[0].schema()),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn unfold(&self) -> Cow<'_, Schema> {
+        match self {
+            Schema::Recursive { name, schema } => {
+                let mut unfolded = (**schema).clone();
+                substitute(&mut unfolded, name, self);
+                Cow::Owned(unfolded)
+            }
+            schema => Cow::Borrowed(schema),
+        }
+    }
+
+    /// Reads a schema from a document listing the fields of the top-level struct, and the named
+    /// types they use.
+    pub fn from_document(document: &Document) -> Result<Schema, SchemaError> {
+        Ok(SchemaRegistry::from_document(document)?.root)
+    }
+
+    /// Reads a schema from the concrete syntax tree of a schema document, as `from_document` does,
+    /// and documents each field and variant without a `doc` attribute with the `##` comment
+    /// lines right above it, if any, without the `##` and one following space.
+    pub fn from_cst(document: &cst::Document) -> Result<Schema, SchemaError> {
+        let mut ast = document.to_ast();
+        document_comments(document, ast.bindings_mut(), true);
+        Schema::from_document(&ast)
+    }
+
+    /// Binds the default values of every field missing from the values of `binding`, recursively
+    /// through the fields that are present.
+    pub fn apply_defaults(&self, binding: &mut Binding) {
+        for value in binding.values.iter_mut() {
+            apply_defaults_value(self, value);
+        }
+    }
+
+    /// Binds the default values of every field missing from the top-level bindings of `document`,
+    /// recursively through the fields that are present.
+    pub fn apply_defaults_document(&self, document: &mut Document) {
+        apply_defaults_children(self, document.bindings_mut());
+    }
+
+    /// Renames the bindings among the values of `binding` whose names match a field only ignoring
+    /// case to the spelling of that field, recursively, e.g. `LogLevel` to `loglevel`.
+    pub fn canonicalize(&self, binding: &mut Binding) {
+        for value in binding.values.iter_mut() {
+            canonicalize_value(self, value);
+        }
+    }
+
+    /// Renames the top-level bindings of `document` and their descendants to the spelling of
+    /// their fields, as `canonicalize` does.
+    pub fn canonicalize_document(&self, document: &mut Document) {
+        canonicalize_children(self, document.bindings_mut());
+    }
+
+    /// Replaces each value of a secret field among the values of `binding` with `***`,
+    /// recursively through the fields that are present.
+    pub fn redact_secrets(&self, binding: &mut Binding) {
+        for value in binding.values.iter_mut() {
+            redact_value(self, value);
+        }
+    }
+
+    /// Replaces each value of a secret field among the top-level bindings of `document` with
+    /// `***`, recursively through the fields that are present.
+    pub fn redact_secrets_document(&self, document: &mut Document) {
+        redact_children(self, document.bindings_mut());
+    }
+
+    /// Validates the top-level bindings of `document`, returning all violations found. Unknown
+    /// fields are reported as warnings.
+    pub fn validate(&self, document: &Document) -> Vec<ValidationError> {
+        self.validate_with(document, &ValidationOptions::new())
+    }
+
+    /// Validates the top-level bindings of `document` as configured by `options`, returning all
+    /// violations found.
+    pub fn validate_with(
+        &self,
+        document: &Document,
+        options: &ValidationOptions,
+    ) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        let root = Node {
+            path: Path { segments: vec![] },
+            address: Address::default(),
+        };
+        validate_children(self, &document.bindings, &root, options, &mut errors);
+        errors
+    }
+}
+
+/// Replaces the references to `name` in `schema` that are not shadowed by a recursive type of the
+/// same name with `target`.
+fn substitute(schema: &mut Schema, name: &str, target: &Schema) {
+    if matches!(schema, Schema::Ref(other) if other == name) {
+        *schema = target.clone();
+        return;
+    }
+    match schema {
+        Schema::Recursive {
+            name: other,
+            schema,
+        } if other != name => substitute(schema, name, target),
+        Schema::Struct { fields } => {
+            for field in fields.iter_mut() {
+                substitute(&mut field.schema, name, target);
+            }
+        }
+        Schema::Enum { variants } => {
+            for variant in variants.iter_mut() {
+                substitute(&mut variant.schema, name, target);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn apply_defaults_children(schema: &Schema, bindings: &mut Vec<Binding>) {
+    let schema = schema.unfold();
+    let fields = match &*schema {
+        Schema::Struct { fields } => fields,
+        _ => return,
+    };
+    for field in fields {
+        if let Some(default) = &field.default {
+            if !bindings.iter().any(|b| b.name == field.name) {
+                bindings.push(Binding {
+                    name: field.name.clone(),
+                    values: default.iter().cloned().collect(),
+                });
+            }
+        }
+    }
+    for binding in bindings.iter_mut() {
+        if let Some(field) = fields.iter().find(|f| f.name == binding.name) {
+            field.schema.apply_defaults(binding);
+        }
+    }
+}
+
+fn apply_defaults_value(schema: &Schema, value: &mut Value) {
+    let schema = schema.unfold();
+    match &*schema {
+        _ if value.items().is_some() => {}
+        Schema::Struct { .. } => apply_defaults_children(&schema, &mut value.children),
+        Schema::Enum { variants } => {
+            if let Some(variant) = variants.iter().find(|v| v.name == value.value) {
+                apply_defaults_children(&variant.schema, &mut value.children);
+            }
+        }
+        Schema::String | Schema::Bool | Schema::Recursive { .. } | Schema::Ref(_) => {}
+    }
+}
+
+fn redact_children(schema: &Schema, bindings: &mut [Binding]) {
+    let schema = schema.unfold();
+    let fields = match &*schema {
+        Schema::Struct { fields } => fields,
+        _ => return,
+    };
+    for binding in bindings.iter_mut() {
+        match fields.iter().find(|f| f.name == binding.name) {
+            Some(field) if field.secret => {
+                for value in binding.values.iter_mut() {
+                    *value = Value::from("***");
+                }
+            }
+            Some(field) => field.schema.redact_secrets(binding),
+            None => {}
+        }
+    }
+}
+
+fn redact_value(schema: &Schema, value: &mut Value) {
+    let schema = schema.unfold();
+    match &*schema {
+        _ if value.items().is_some() => {}
+        Schema::Struct { .. } => redact_children(&schema, &mut value.children),
+        Schema::Enum { variants } => {
+            if let Some(variant) = variants.iter().find(|v| v.name == value.value) {
+                redact_children(&variant.schema, &mut value.children);
+            }
+        }
+        Schema::String | Schema::Bool | Schema::Recursive { .. } | Schema::Ref(_) => {}
+    }
+}
+
+/// Prints `document` with the values of the secret fields of `schema` replaced with `***`, quoted
+/// as any other value with special characters, e.g. to log a configuration without leaking its
+/// credentials.
+///
+/// ```
+/// use flax::{parse_document_complete, print_redacted, Schema};
+///
+/// let schema = parse_document_complete(
+///     "field=db{type=struct{field=user{type=string} field=password{type=string secret=true}}}",
+/// )
+/// .unwrap();
+/// let schema = Schema::from_document(&schema).unwrap();
+/// let config = parse_document_complete("db={user=admin password=hunter2}").unwrap();
+/// assert_eq!(
+///     "db={user=admin password=\"***\"}",
+///     print_redacted(&config, &schema)
+/// );
+/// ```
+pub fn print_redacted(document: &Document, schema: &Schema) -> String {
+    let mut document = document.clone();
+    schema.redact_secrets_document(&mut document);
+    print_document(&document)
+}
+
+fn canonicalize_children(schema: &Schema, bindings: &mut [Binding]) {
+    let schema = schema.unfold();
+    let fields = match &*schema {
+        Schema::Struct { fields } => fields,
+        _ => return,
+    };
+    for binding in bindings.iter_mut() {
+        let field = fields.iter().find(|f| f.name == binding.name).or_else(|| {
+            fields
+                .iter()
+                .find(|f| eq_ignore_case(&f.name, &binding.name))
+        });
+        if let Some(field) = field {
+            if binding.name != field.name {
+                binding.name = field.name.clone();
+            }
+            field.schema.canonicalize(binding);
+        }
+    }
+}
+
+fn canonicalize_value(schema: &Schema, value: &mut Value) {
+    let schema = schema.unfold();
+    match &*schema {
+        _ if value.items().is_some() => {}
+        Schema::Struct { .. } => canonicalize_children(&schema, &mut value.children),
+        Schema::Enum { variants } => {
+            if let Some(variant) = variants.iter().find(|v| v.name == value.value) {
+                canonicalize_children(&variant.schema, &mut value.children);
+            }
+        }
+        Schema::String | Schema::Bool | Schema::Recursive { .. } | Schema::Ref(_) => {}
+    }
+}
+
+/// The location of the node being validated.
+struct Node {
+    path: Path,
+    address: Address,
+}
+
+impl Node {
+    fn child(&self, index: usize, segment: Option<Segment>) -> Node {
+        let mut path = self.path.clone();
+        path.segments.extend(segment);
+        Node {
+            path,
+            address: self.address.child(index),
+        }
+    }
+
+    /// Returns the location of the `index`-th of the `count` values of the binding at this node.
+    fn value(&self, index: usize, count: usize) -> Node {
+        let mut node = self.child(index, None);
+        if count > 1 {
+            if let Some(segment) = node.path.segments.last_mut() {
+                segment.index = Some(index);
+            }
+        }
+        node
+    }
+
+    /// Returns the path of the field `name` of this node.
+    fn field(&self, name: &str) -> Path {
+        let mut path = self.path.clone();
+        path.segments.push(Segment {
+            name: name.to_string(),
+            index: None,
+        });
+        path
+    }
+
+    fn error(&self, message: impl Into<String>) -> ValidationError {
+        self.issue(Severity::Error, message)
+    }
+
+    fn issue(&self, severity: Severity, message: impl Into<String>) -> ValidationError {
+        ValidationError {
+            path: self.path.to_string(),
+            address: self.address.clone(),
+            message: message.into(),
+            severity,
+        }
+    }
+}
+
+fn validate_children(
+    schema: &Schema,
+    bindings: &[Binding],
+    node: &Node,
+    options: &ValidationOptions,
+    errors: &mut Vec<ValidationError>,
+) {
+    let schema = schema.unfold();
+    let fields = match &*schema {
+        Schema::Struct { fields } => fields.as_slice(),
+        _ => &[],
+    };
+    for field in fields {
+        if !field.optional
+            && field.default.is_none()
+            && bindings
+                .iter()
+                .all(|b| !options.matches(&b.name, &field.name))
+        {
+            let mut missing = node.error("missing required field");
+            missing.path = node.field(&field.name).to_string();
+            errors.push(missing);
+        }
+        if let Some(first) = bindings
+            .iter()
+            .position(|b| options.matches(&b.name, &field.name))
+        {
+            let count = bindings
+                .iter()
+                .filter(|b| options.matches(&b.name, &field.name))
+                .map(|b| b.values.len())
+                .sum();
+            if let Some(message) = cardinality(field, count) {
+                let segment = Segment {
+                    name: field.name.clone(),
+                    index: None,
+                };
+                errors.push(node.child(first, Some(segment)).error(message));
+            }
+        }
+    }
+    for (i, binding) in bindings.iter().enumerate() {
+        let node = node.child(
+            i,
+            Some(Segment {
+                name: binding.name.clone(),
+                index: None,
+            }),
+        );
+        let field = match fields
+            .iter()
+            .find(|f| options.matches(&binding.name, &f.name))
+        {
+            Some(field) => field,
+            None => {
+                let message = unknown_field(&binding.name, fields);
+                errors.push(node.issue(options.unknown_fields, message));
+                continue;
+            }
+        };
+        if let Some(deprecation) = &field.deprecated {
+            errors.push(node.issue(Severity::Warning, deprecated("field", deprecation)));
+        }
+        if !field.repeated {
+            if bindings[..i]
+                .iter()
+                .any(|b| options.matches(&b.name, &field.name))
+            {
+                errors.push(node.error(format!("duplicate field `{}`", binding.name)));
+            } else if binding.values.len() > 1 {
+                errors.push(node.error(format!(
+                    "expected a single value, found {}",
+                    binding.values.len()
+                )));
+            }
+        }
+        for (j, value) in binding.values.iter().enumerate() {
+            let value_node = node.value(j, binding.values.len());
+            validate_value(&field.schema, value, &value_node, options, errors);
+        }
+    }
+}
+
+fn validate_value(
+    schema: &Schema,
+    value: &Value,
+    node: &Node,
+    options: &ValidationOptions,
+    errors: &mut Vec<ValidationError>,
+) {
+    if value.items().is_some() {
+        return errors.push(node.error("unexpected list"));
+    }
+    let schema = schema.unfold();
+    match &*schema {
+        Schema::Struct { .. } => validate_children(&schema, &value.children, node, options, errors),
+        Schema::Enum { variants } => match variants.iter().find(|v| v.name == value.value) {
+            Some(variant) => {
+                if let Some(deprecation) = &variant.deprecated {
+                    let message = deprecated(&format!("variant `{}`", variant.name), deprecation);
+                    errors.push(node.issue(Severity::Warning, message));
+                }
+                validate_children(&variant.schema, &value.children, node, options, errors)
+            }
+            None => errors.push(node.error(unknown_variant(&value.value, variants))),
+        },
+        Schema::String | Schema::Bool if !value.children.is_empty() || value.is_map() => {
+            errors.push(node.error("expected a scalar, found a block"))
+        }
+        Schema::Bool if value.value != "true" && value.value != "false" => {
+            errors.push(node.error(format!("invalid value `{}`, expected bool", value.value)))
+        }
+        Schema::String | Schema::Bool | Schema::Recursive { .. } | Schema::Ref(_) => {}
+    }
+}
+
+/// Spells the booleans accepted by schema-guided decoding as `true` or `false`.
+fn coerce_bool(value: &str) -> Option<&'static str> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some("true"),
+        "false" | "no" | "off" | "0" => Some("false"),
+        _ => None,
+    }
+}
+
+fn coerce_children(schema: &Schema, bindings: &mut [Binding]) {
+    let schema = schema.unfold();
+    let fields = match &*schema {
+        Schema::Struct { fields } => fields,
+        _ => return,
+    };
+    for binding in bindings.iter_mut() {
+        if let Some(field) = fields.iter().find(|f| f.name == binding.name) {
+            for value in binding.values.iter_mut() {
+                coerce_value(&field.schema, value);
+            }
+        }
+    }
+}
+
+fn coerce_value(schema: &Schema, value: &mut Value) {
+    let schema = schema.unfold();
+    match &*schema {
+        _ if value.items().is_some() => {}
+        Schema::Struct { .. } => coerce_children(&schema, &mut value.children),
+        Schema::Enum { variants } => {
+            if let Some(variant) = variants.iter().find(|v| v.name == value.value) {
+                coerce_children(&variant.schema, &mut value.children);
+            }
+        }
+        Schema::Bool if value.children.is_empty() && value.is_scalar() => {
+            if let Some(coerced) = coerce_bool(&value.value) {
+                value.value = coerced.to_string();
+            }
+        }
+        Schema::String | Schema::Bool | Schema::Recursive { .. } | Schema::Ref(_) => {}
+    }
+}
+
+/// Returns the location of the first scalar equal to `scalar` among `bindings` and their
+/// descendants.
+fn locate(bindings: &[Binding], node: &Node, scalar: &str) -> Option<Node> {
+    for (i, binding) in bindings.iter().enumerate() {
+        let node = node.child(
+            i,
+            Some(Segment {
+                name: binding.name.clone(),
+                index: None,
+            }),
+        );
+        for (j, value) in binding.values.iter().enumerate() {
+            let value_node = node.value(j, binding.values.len());
+            if value.is_scalar() && value.value == scalar {
+                return Some(value_node);
+            }
+            if let Some(found) = locate(&value.children, &value_node, scalar) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Validates `bindings` as the fields of `schema` after binding defaults and coercing scalars,
+/// then deserializes them with `deserialize`.
+fn decode<T>(
+    schema: &Schema,
+    mut bindings: Vec<Binding>,
+    deserialize: impl FnOnce(&[Binding]) -> Result<T, DeserializeError>,
+) -> Result<T, Vec<ValidationError>> {
+    apply_defaults_children(schema, &mut bindings);
+    coerce_children(schema, &mut bindings);
+    let root = Node {
+        path: Path { segments: vec![] },
+        address: Address::default(),
+    };
+    let mut errors = vec![];
+    validate_children(
+        schema,
+        &bindings,
+        &root,
+        &ValidationOptions::new(),
+        &mut errors,
+    );
+    errors.retain(|e| e.severity == Severity::Error);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    deserialize(&bindings).map_err(|error| {
+        let node = match &error {
+            DeserializeError::InvalidScalar { value, .. } => locate(&bindings, &root, value),
+            _ => None,
+        };
+        vec![node.unwrap_or(root).error(error.to_string())]
+    })
+}
+
+/// Decodes `binding` as a `T`, guided by `schema`, which describes the values of `binding` as
+/// [`Schema::apply_defaults`] expects.
+///
+/// Unlike [`FujiDeserialize::from_binding`], this binds the defaults of missing fields, accepts
+/// `yes`, `on` and `1` as `true` and `no`, `off` and `0` as `false` for bool fields, in any case,
+/// and reports every violation of the schema with its path, as [`Schema::validate`] does, rather
+/// than the first error of `T`. Unknown fields are ignored. An error of `T` itself, such as an
+/// integer out of range, is reported at the first scalar it names.
+pub fn decode_with_schema<T: FujiDeserialize>(
+    binding: &Binding,
+    schema: &Schema,
+) -> Result<T, Vec<ValidationError>> {
+    let field = Field {
+        name: binding.name.clone(),
+        repeated: false,
+        optional: false,
+        secret: false,
+        min: None,
+        max: None,
+        schema: schema.clone(),
+        default: None,
+        deprecated: None,
+        doc: None,
+    };
+    let wrapper = Schema::Struct {
+        fields: vec![field],
+    };
+    decode(&wrapper, vec![binding.clone()], |bindings| {
+        T::from_binding(&bindings[0])
+    })
+}
+
+/// Decodes the top-level bindings of `document` as the fields of a `T`, guided by `schema`, as
+/// [`decode_with_schema`] does.
+pub fn decode_document_with_schema<T: FujiDeserialize>(
+    document: &Document,
+    schema: &Schema,
+) -> Result<T, Vec<ValidationError>> {
+    decode(schema, document.bindings.clone(), |bindings| {
+        T::from_value(&Value {
+            value: String::new(),
+            kind: ValueKind::Map,
+            children: bindings.to_vec(),
+        })
+    })
+}
+
+/// Describes `name` as an unknown field, with the closest of `fields` if it looks like a typo.
+fn unknown_field(name: &str, fields: &[Field]) -> String {
+    match suggest::closest(name, fields.iter().map(|f| f.name.as_str())) {
+        Some(closest) => format!("unknown field; did you mean `{}`?", closest),
+        None => "unknown field".to_string(),
+    }
+}
+
+/// Describes `name` as an unknown variant, listing the known ones and the closest match.
+fn unknown_variant(name: &str, variants: &[Variant]) -> String {
+    let mut message = format!("unknown variant `{}`", name);
+    if variants.is_empty() {
+        return message;
+    }
+    let names = variants
+        .iter()
+        .map(|v| format!("`{}`", v.name))
+        .collect::<Vec<_>>();
+    message.push_str(&format!(", expected one of {}", names.join(", ")));
+    if let Some(closest) = suggest::closest(name, variants.iter().map(|v| v.name.as_str())) {
+        message.push_str(&format!("; did you mean `{}`?", closest));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    const SCHEMA: &str = "
+        field=name{type=string}
+        field=tags{type=string repeated=true optional=true}
+        field=server{type=struct{field=host{type=string} field=tls{type=bool}}}
+        field=mode{type=enum{variant=tcp{type=struct{field=port{type=string}}} variant=udp}}
+    ";
+
+    #[test]
+    fn test_from_document() {
+        let schema = Schema::from_document(&parse_document_complete(SCHEMA).unwrap()).unwrap();
+        match &schema {
+            Schema::Struct { fields } => {
+                let names = fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+                assert_eq!(vec!["name", "tags", "server", "mode"], names);
+                assert!(fields[1].repeated);
+            }
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+
+        struct Test {
+            schema: String,
+            error: SchemaError,
+        }
+
+        let tests = [
+            Test {
+                schema: "field=a{type=strin}".to_string(),
+                error: SchemaError::new(&Address(vec![0, 0, 0, 0]), "unknown type `strin`"),
+            },
+            Test {
+                schema: "field=a{repeated=true}".to_string(),
+                error: SchemaError::new(&Address(vec![0, 0]), "field `a` has no type"),
+            },
+            Test {
+                schema: "field=a{type=string} fields=b".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![1]),
+                    "unexpected `fields`, expected `field`, `struct` or `enum`",
+                ),
+            },
+            Test {
+                schema: "field=a{type=enum{variant=b{type=bool}}}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 0, 0, 0, 0, 0, 0]),
+                    "variant types must be structs",
+                ),
+            },
+            Test {
+                schema: "field=a{default=x,y type=string}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 0]),
+                    "expected a single default value, found 2",
+                ),
+            },
+            Test {
+                schema: "field=a{type=string min=1}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 1]),
+                    "`min` and `max` require `repeated=true`",
+                ),
+            },
+            Test {
+                schema: "field=a{type=string repeated=true min=2 max=1}".to_string(),
+                error: SchemaError::new(&Address(vec![0, 0, 3]), "`max` is less than `min`"),
+            },
+            Test {
+                schema: "field=a{type=string repeated=true max=-1}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 2, 0]),
+                    "invalid value `-1`, expected a non-negative integer",
+                ),
+            },
+            Test {
+                schema: "field=a{type=string repeated=true min=1 default=}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 3]),
+                    "invalid default: expected at least 1 value, found 0",
+                ),
+            },
+            Test {
+                schema: "field=a{type=bool repeated=true default=true,maybe}".to_string(),
+                error: SchemaError::new(
+                    &Address(vec![0, 0, 2, 1]),
+                    "invalid default: invalid value `maybe`, expected bool",
+                ),
+            },
+        ];
+
+        for t in tests.iter() {
+            let document = parse_document_complete(&t.schema).unwrap();
+            assert_eq!(Err(t.error.clone()), Schema::from_document(&document));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = Schema::from_document(&parse_document_complete(SCHEMA).unwrap()).unwrap();
+
+        struct Test {
+            document: String,
+            errors: Vec<(String, Address)>,
+        }
+
+        let tests = [
+            Test {
+                document: "name=a tags=b,c tags=d server=s{host=h tls=true} mode=tcp{port=1}"
+                    .to_string(),
+                errors: vec![],
+            },
+            Test {
+                document: "name=a,b server=s{tls=yes} mode=sctp".to_string(),
+                errors: vec![
+                    ("name".to_string(), Address(vec![0])),
+                    ("server.host".to_string(), Address(vec![1, 0])),
+                    ("server.tls".to_string(), Address(vec![1, 0, 0, 0])),
+                    ("mode".to_string(), Address(vec![2, 0])),
+                ],
+            },
+            Test {
+                document: "name=a name=b tags=x{y=z}".to_string(),
+                errors: vec![
+                    ("server".to_string(), Address(vec![])),
+                    ("mode".to_string(), Address(vec![])),
+                    ("name".to_string(), Address(vec![1])),
+                    ("tags".to_string(), Address(vec![2, 0])),
+                ],
+            },
+            Test {
+                document: "name={} server={host=h tls=false} mode=udp".to_string(),
+                errors: vec![("name".to_string(), Address(vec![0, 0]))],
+            },
+        ];
+
+        for t in tests.iter() {
+            let errors = schema.validate(&parse_document_complete(&t.document).unwrap());
+            let locations = errors
+                .into_iter()
+                .map(|e| (e.path, e.address))
+                .collect::<Vec<_>>();
+            assert_eq!(t.errors, locations);
+        }
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let schema = Schema::from_document(
+            &parse_document_complete(
+                "
+                field=name{type=string default=\"\"}
+                field=tags{type=string repeated=true default=}
+                field=server{type=struct{
+                    field=host{type=string default=localhost}
+                    field=tls{type=bool default=false}
+                } default=server}
+                field=mode{
+                    type=enum{variant=tcp{type=struct{field=port{type=string default=80}}}}
+                    optional=true
+                }
+                ",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+
+        let tests = [
+            (
+                "",
+                "name=\"\"\ntags=\nserver=server{host=localhost tls=false}",
+            ),
+            (
+                "name=a server=s{tls=true} mode=tcp",
+                "name=a\nserver=s{tls=true host=localhost}\nmode=tcp{port=80}\ntags=",
+            ),
+        ];
+
+        for (input, output) in tests.iter() {
+            let mut document = parse_document_complete(input).unwrap();
+            schema.apply_defaults_document(&mut document);
+            assert_eq!(*output, document.to_string());
+            assert_eq!(Vec::<ValidationError>::new(), schema.validate(&document));
+        }
+
+        let mut binding = Binding::new("server").value("s").value("t");
+        if let Schema::Struct { fields } = &schema {
+            fields[2].schema.apply_defaults(&mut binding);
+        }
+        assert_eq!(
+            "server=s{host=localhost tls=false},t{host=localhost tls=false}",
+            binding.to_string()
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let schema = Schema::from_document(&parse_document_complete(SCHEMA).unwrap()).unwrap();
+        let document =
+            parse_document_complete("name=a x=y server=s{host=h tls=true z=w} mode=udp").unwrap();
+
+        let errors = schema.validate(&document);
+        let issues = errors
+            .iter()
+            .map(|e| (e.path.as_str(), e.message.as_str(), e.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("x", "unknown field", Severity::Warning),
+                ("server.z", "unknown field", Severity::Warning),
+            ],
+            issues
+        );
+        assert_eq!(Address(vec![2, 0, 2]), errors[1].address);
+
+        let options = ValidationOptions::new().unknown_fields(Severity::Error);
+        let errors = schema.validate_with(&document, &options);
+        assert_eq!(2, errors.len());
+
+        let document = parse_document_complete("name=a tgas=t server=s{hots=h} mode=udp").unwrap();
+        let unknown = schema
+            .validate(&document)
+            .into_iter()
+            .filter(|e| e.severity == Severity::Warning)
+            .map(|e| (e.path, e.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "tgas".to_string(),
+                    "unknown field; did you mean `tags`?".to_string()
+                ),
+                (
+                    "server.hots".to_string(),
+                    "unknown field; did you mean `host`?".to_string()
+                ),
+            ],
+            unknown
+        );
+        assert!(errors.iter().all(|e| e.severity == Severity::Error));
+
+        let document = parse_document_complete("tags=a").unwrap();
+        let missing = schema
+            .validate(&document)
+            .into_iter()
+            .map(|e| (e.path, e.address, e.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "name".to_string(),
+                    Address(vec![]),
+                    "missing required field".to_string()
+                ),
+                (
+                    "server".to_string(),
+                    Address(vec![]),
+                    "missing required field".to_string()
+                ),
+                (
+                    "mode".to_string(),
+                    Address(vec![]),
+                    "missing required field".to_string()
+                ),
+            ],
+            missing
+        );
+    }
+
+    #[test]
+    fn test_redact_secrets() {
+        let schema = parse_document_complete(
+            "field=user{type=string}
+             field=tokens{type=string repeated=true secret=true}
+             field=auth{type=enum{
+               variant=basic{type=struct{field=password{type=string secret=true}}}
+               variant=none
+             }}",
+        )
+        .unwrap();
+        let schema = Schema::from_document(&schema).unwrap();
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+
+        let document = parse_document_complete(
+            "user=admin tokens=[a,[b,c]] auth=basic{password=hunter2} extra={password=p}",
+        )
+        .unwrap();
+        assert_eq!(
+            "user=admin\ntokens=\"***\",\"***\"\nauth=basic{password=\"***\"}\nextra={password=p}",
+            print_redacted(&document, &schema)
+        );
+
+        let mut binding: Binding = "auth=basic{password=hunter2}".parse().unwrap();
+        match &schema {
+            Schema::Struct { fields } => fields[2].schema.redact_secrets(&mut binding),
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!("auth=basic{password=\"***\"}", binding.to_string());
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let schema = Schema::from_document(&parse_document_complete(SCHEMA).unwrap()).unwrap();
+        let mut document =
+            parse_document_complete("Name=a SERVER=s{Host=h tls=true} mode=tcp{PORT=1} Tags=x")
+                .unwrap();
+
+        let paths = |errors: Vec<ValidationError>| {
+            errors
+                .into_iter()
+                .map(|e| (e.path, e.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(7, schema.validate(&document).len());
+        let options = ValidationOptions::new().ignore_case(true);
+        assert!(schema.validate_with(&document, &options).is_empty());
+
+        schema.canonicalize_document(&mut document);
+        assert_eq!(
+            "name=a\nserver=s{host=h tls=true}\nmode=tcp{port=1}\ntags=x",
+            document.to_string()
+        );
+        assert!(schema.validate(&document).is_empty());
+
+        let document =
+            parse_document_complete("name=a NAME=b server=s{host=h tls=true} mode=udp").unwrap();
+        assert_eq!(
+            vec![("NAME".to_string(), "duplicate field `NAME`".to_string())],
+            paths(schema.validate_with(&document, &options))
+        );
+    }
+
+    #[test]
+    fn test_unknown_variant() {
+        let schema = Schema::from_document(&parse_document_complete(SCHEMA).unwrap()).unwrap();
+        let tests = [
+            (
+                "mode=tpc",
+                "unknown variant `tpc`, expected one of `tcp`, `udp`; did you mean `tcp`?",
+            ),
+            (
+                "mode=quic",
+                "unknown variant `quic`, expected one of `tcp`, `udp`",
+            ),
+        ];
+
+        for (input, message) in tests.iter() {
+            let document =
+                parse_document_complete(&format!("name=a server=s{{host=h tls=true}} {}", input))
+                    .unwrap();
+            let errors = schema.validate(&document);
+            assert_eq!(
+                vec![message.to_string()],
+                errors.into_iter().map(|e| e.message).collect::<Vec<_>>()
+            );
+        }
+
+        let schema =
+            Schema::from_document(&parse_document_complete("field=a{type=enum}").unwrap()).unwrap();
+        let errors = schema.validate(&parse_document_complete("a=b").unwrap());
+        assert_eq!("unknown variant `b`", errors[0].message);
+    }
+
+    #[test]
+    fn test_deprecation() {
+        let schema = "
+            field=port{type=string optional=true deprecated={replacement=listen message=\"ports moved to listeners\"}}
+            field=listen{type=string optional=true}
+            field=legacy{type=bool optional=true deprecated=true}
+            field=mode{type=enum{variant=tcp variant=udp{deprecated={replacement=quic}} variant=quic}}
+        ";
+        let schema = Schema::from_document(&parse_document_complete(schema).unwrap()).unwrap();
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+        if let Schema::Struct { fields } = &schema {
+            assert_eq!(
+                Some("listen"),
+                fields[0].deprecation().unwrap().replacement.as_deref()
+            );
+            assert_eq!(None, fields[1].deprecation());
+            assert_eq!(Some(&Deprecation::default()), fields[2].deprecation());
+        }
+
+        let document = parse_document_complete("port=80 legacy=true mode=udp").unwrap();
+        let warnings = schema
+            .validate(&document)
+            .into_iter()
+            .map(|e| (e.to_string(), e.address, e.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "port: deprecated field, use `listen` instead: ports moved to listeners"
+                        .to_string(),
+                    Address(vec![0]),
+                    Severity::Warning
+                ),
+                (
+                    "legacy: deprecated field".to_string(),
+                    Address(vec![1]),
+                    Severity::Warning
+                ),
+                (
+                    "mode: deprecated variant `udp`, use `quic` instead".to_string(),
+                    Address(vec![2, 0]),
+                    Severity::Warning
+                ),
+            ],
+            warnings
+        );
+        let document = parse_document_complete("listen=80 mode=quic").unwrap();
+        assert_eq!(Vec::<ValidationError>::new(), schema.validate(&document));
+
+        let tests = [
+            (
+                "field=a{type=string deprecated=maybe}",
+                Address(vec![0, 0, 1, 0]),
+                "invalid value `maybe`, expected bool",
+            ),
+            (
+                "field=a{type=string deprecated={since=2}}",
+                Address(vec![0, 0, 1, 0, 0]),
+                "unknown deprecation attribute `since`",
+            ),
+            (
+                "field=a{type=enum{variant=b{deprecated=yes}}}",
+                Address(vec![0, 0, 0, 0, 0, 0, 0, 0]),
+                "invalid value `yes`, expected bool",
+            ),
+        ];
+        for (input, address, message) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            assert_eq!(
+                Err(SchemaError::new(address, *message)),
+                Schema::from_document(&document)
+            );
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let document = parse_document_complete(
+            "
+            enum=Mode{variant=tcp{type=Listener} variant=udp}
+            field=listen{type=Listener repeated=true}
+            struct=Listener{field=host{type=string} field=port{type=string optional=true}}
+            field=mode{type=Mode}
+            struct=Unused{field=x{type=bool}}
+            ",
+        )
+        .unwrap();
+        let registry = SchemaRegistry::from_document(&document).unwrap();
+        assert_eq!(
+            vec!["Listener", "Mode", "Unused"],
+            registry.names().collect::<Vec<_>>()
+        );
+        let inline = parse_document_complete(
+            "
+            field=listen{type=struct{field=host{type=string} field=port{type=string optional=true}} repeated=true}
+            field=mode{type=enum{
+              variant=tcp{type=struct{field=host{type=string} field=port{type=string optional=true}}}
+              variant=udp
+            }}
+            ",
+        )
+        .unwrap();
+        let inline = Schema::from_document(&inline).unwrap();
+        assert_eq!(&inline, registry.root());
+        assert_eq!(Ok(inline), Schema::from_document(&document));
+        if let Schema::Struct { fields } = registry.root() {
+            assert_eq!(Some(&fields[0].schema), registry.get("Listener"));
+        }
+        assert_eq!(None, registry.get("string"));
+
+        let tests = [
+            (
+                "field=a{type=B}",
+                Address(vec![0, 0, 0, 0]),
+                "unknown type `B`",
+            ),
+            (
+                "struct=B{field=c{type=C}}",
+                Address(vec![0, 0, 0, 0, 0, 0]),
+                "unknown type `C`",
+            ),
+            (
+                "enum=string",
+                Address(vec![0, 0]),
+                "type `string` is built in",
+            ),
+            (
+                "struct=A{} enum=B struct=A{}",
+                Address(vec![2, 0]),
+                "duplicate type `A`",
+            ),
+            (
+                "struct=A{field=b{type=B}} struct=B{field=a{type=A{field=c{type=string}}}}",
+                Address(vec![1, 0, 0, 0, 0, 0]),
+                "type `A` takes no children",
+            ),
+            (
+                "field=a{type=A{field=b{type=string}}} struct=A{}",
+                Address(vec![0, 0, 0, 0]),
+                "type `A` takes no children",
+            ),
+            (
+                "enum=A field=a{type=enum{variant=b{type=A}}}",
+                Address(vec![1, 0, 0, 0, 0, 0, 0, 0]),
+                "variant types must be structs",
+            ),
+        ];
+        for (input, address, message) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            assert_eq!(
+                Err(SchemaError::new(address, *message)),
+                SchemaRegistry::from_document(&document),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_recursive() {
+        let document = parse_document_complete(
+            "
+            struct=Tree{
+              field=name{type=string}
+              field=children{type=Tree repeated=true optional=true}
+            }
+            struct=A{field=b{type=B optional=true}}
+            struct=B{field=a{type=A}}
+            field=root{type=Tree}
+            field=a{type=A optional=true}
+            field=mode{type=enum{variant=tree{type=Tree} variant=none}}
+            ",
+        )
+        .unwrap();
+        let registry = SchemaRegistry::from_document(&document).unwrap();
+        let schema = registry.root();
+        let tree = registry.get("Tree").unwrap();
+        match tree {
+            Schema::Recursive { name, schema } => {
+                assert_eq!("Tree", name);
+                match &**schema {
+                    Schema::Struct { fields } => {
+                        assert_eq!(Schema::Ref("Tree".to_string()), fields[1].schema)
+                    }
+                    _ => panic!("expected a struct"),
+                }
+            }
+            _ => panic!("expected a recursive type"),
+        }
+        match &*tree.unfold() {
+            Schema::Struct { fields } => assert_eq!(tree, &fields[1].schema),
+            _ => panic!("expected a struct"),
+        }
+        // `B` contains itself only through `A`, which is recursive.
+        match registry.get("B") {
+            Some(Schema::Struct { fields }) => {
+                assert_eq!(registry.get("A"), Some(&fields[0].schema))
+            }
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+
+        let input = "
+            root=r{name=r children={name=a children={name=b},{name=c children={nme=d}}}}
+            a={b={a={b={}}}}
+            mode=tree{name=t children={}}
+        ";
+        let mut document = parse_document_complete(input).unwrap();
+        let errors = schema
+            .validate(&document)
+            .iter()
+            .map(ValidationError::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "root.children.children[1].children.name: missing required field",
+                "root.children.children[1].children.nme: unknown field; did you mean `name`?",
+                "a.b.a.b.a: missing required field",
+                "mode.children.name: missing required field",
+            ],
+            errors
+        );
+
+        let before = document.clone();
+        schema.apply_defaults_document(&mut document);
+        schema.canonicalize_document(&mut document);
+        schema.redact_secrets_document(&mut document);
+        assert_eq!(before, document);
+    }
+
+    #[test]
+    fn test_generics() {
+        let document = parse_document_complete(
+            "
+            struct=Pair{param=T field=first{type=T} field=second{type=T}}
+            struct=Entry{param=K,V field=key{type=K} field=value{type=V optional=true}}
+            enum=Option{param=T variant=some{type=T} variant=none}
+            struct=List{param=T field=head{type=T} field=tail{type=List{T=T} optional=true}}
+            struct=Point{field=x{type=string} field=y{type=string}}
+            field=size{type=Pair{T=string}}
+            field=entry{type=Entry{V=Pair{T=bool} K=string}}
+            field=origin{type=Option{T=Point}}
+            field=names{type=List{T=string}}
+            field=tags{type=List{T=Pair{T=string}} optional=true}
+            ",
+        )
+        .unwrap();
+        let registry = SchemaRegistry::from_document(&document).unwrap();
+        assert_eq!(vec!["Point"], registry.names().collect::<Vec<_>>());
+        let inline = parse_document_complete(
+            "
+            field=size{type=struct{field=first{type=string} field=second{type=string}}}
+            field=entry{type=struct{
+              field=key{type=string}
+              field=value{type=struct{field=first{type=bool} field=second{type=bool}} optional=true}
+            }}
+            field=origin{type=enum{
+              variant=some{type=struct{field=x{type=string} field=y{type=string}}}
+              variant=none
+            }}
+            ",
+        )
+        .unwrap();
+        let (schema, inline) = match (registry.root(), Schema::from_document(&inline).unwrap()) {
+            (Schema::Struct { fields }, Schema::Struct { fields: inline }) => (fields, inline),
+            _ => panic!("expected structs"),
+        };
+        assert_eq!(inline.as_slice(), &schema[..3]);
+        match (&schema[3].schema, &schema[4].schema) {
+            (Schema::Recursive { name, .. }, Schema::Recursive { name: pairs, .. }) => {
+                assert_eq!("List<string>", name);
+                assert_eq!("List<struct>", pairs);
+            }
+            _ => panic!("expected recursive types"),
+        }
+        let schema = registry.root();
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+        let input = "
+            size={first=1 second=2} entry={key=k value={first=yes second=true}} origin=none
+            names={head=a tail={head=b tail={tail={head=d}}}}
+        ";
+        let errors = schema
+            .validate(&parse_document_complete(input).unwrap())
+            .iter()
+            .map(ValidationError::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "entry.value.first: invalid value `yes`, expected bool",
+                "names.tail.tail.head: missing required field",
+            ],
+            errors
+        );
+
+        let tests = [
+            (
+                "struct=P{param=T field=a{type=T}} field=a{type=P}",
+                Address(vec![1, 0, 0, 0]),
+                "missing type argument `T` of `P`",
+            ),
+            (
+                "struct=P{param=T field=a{type=T}} field=a{type=P{T=string U=bool}}",
+                Address(vec![1, 0, 0, 0, 1]),
+                "unknown type parameter `U` of `P`",
+            ),
+            (
+                "struct=P{param=T field=a{type=T}} field=a{type=P{T=string T=bool}}",
+                Address(vec![1, 0, 0, 0, 1]),
+                "duplicate type argument `T`",
+            ),
+            (
+                "struct=P{param=T param=T}",
+                Address(vec![0, 0, 1, 0]),
+                "duplicate type parameter `T`",
+            ),
+            (
+                "struct=P{param=string}",
+                Address(vec![0, 0, 0, 0]),
+                "type `string` is built in",
+            ),
+            (
+                "struct=P{param=T field=a{type=T{U=string}}}",
+                Address(vec![0, 0, 1, 0, 0, 0]),
+                "type `T` takes no children",
+            ),
+            (
+                "struct=P{param=T field=a{type=X}}",
+                Address(vec![0, 0, 1, 0, 0, 0]),
+                "unknown type `X`",
+            ),
+            (
+                "struct=P{field=a{type=string} param=T}",
+                Address(vec![0, 0, 1]),
+                "unexpected `param`, expected `field`",
+            ),
+            (
+                "enum=O{param=T variant=some{type=T}} field=o{type=O{T=string}}",
+                Address(vec![0, 0, 1, 0, 0, 0]),
+                "variant types must be structs",
+            ),
+            (
+                "struct=L{param=T field=n{type=L{T=struct{field=a{type=T}}} optional=true}}",
+                Address(vec![0, 0, 1, 0, 0, 0]),
+                "type `L` is nested too deeply",
+            ),
+        ];
+        for (input, address, message) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            assert_eq!(
+                Err(SchemaError::new(address, *message)),
+                SchemaRegistry::from_document(&document),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_with_schema() {
+        let tests = [
+            ("on", Ok(true)),
+            ("No", Ok(false)),
+            ("1", Ok(true)),
+            (
+                "maybe",
+                Err("a: invalid value `maybe`, expected bool".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let binding = Binding::new("a").value(*input);
+            let decoded = decode_with_schema::<bool>(&binding, &Schema::Bool)
+                .map_err(|errors| errors[0].to_string());
+            assert_eq!(*expected, decoded, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_cardinality() {
+        let schema = Schema::from_document(
+            &parse_document_complete(
+                "
+                field=one{type=string repeated=true min=1 max=1}
+                field=some{type=string repeated=true min=1 optional=true}
+                field=few{type=string repeated=true max=2 optional=true}
+                ",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(schema.clone()),
+            Schema::from_document(&schema.to_document())
+        );
+
+        let tests = [
+            ("one=a few=b few=c", vec![]),
+            (
+                "one=a,b",
+                vec![("one", Address(vec![0]), "expected exactly 1 value, found 2")],
+            ),
+            (
+                "one= some= few=a,b few=c",
+                vec![
+                    ("one", Address(vec![0]), "expected exactly 1 value, found 0"),
+                    (
+                        "some",
+                        Address(vec![1]),
+                        "expected at least 1 value, found 0",
+                    ),
+                    (
+                        "few",
+                        Address(vec![2]),
+                        "expected at most 2 values, found 3",
+                    ),
+                ],
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let errors = schema.validate(&parse_document_complete(input).unwrap());
+            let errors = errors
+                .iter()
+                .map(|e| (e.path.as_str(), e.address.clone(), e.message.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(*expected, errors, "{}", input);
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_schema_round_trip(schema in any::<Schema>()) {
+            prop_assert_eq!(Ok(schema.clone()), Schema::from_document(&schema.to_document()));
+        }
+    }
+}
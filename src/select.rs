@@ -0,0 +1,193 @@
+//! Selection of values by their paths and by predicates on their children, e.g. every value
+//! under `services` whose child binding `env` equals `prod`.
+
+use crate::iter::Iter;
+use crate::prelude::*;
+use crate::{Binding, Document, Path, Pattern, Value};
+
+type Predicate<'a> = Box<dyn Fn(&Value) -> bool + 'a>;
+
+/// A builder selecting values of a binding or document, created by [`Binding::select`] or
+/// [`Document::select`].
+///
+/// Without any restriction, a selector yields every value, as [`Document::iter`] does. Each
+/// method keeps only the values that also satisfy its condition.
+///
+/// ```
+/// use flax::parse_document_complete;
+///
+/// let document = parse_document_complete(
+///     "services={api={env=prod} worker={env=dev} web={env=prod}}",
+/// )
+/// .unwrap();
+/// let selected = document
+///     .select()
+///     .matching("services.*".parse().unwrap())
+///     .child_eq("env", "prod")
+///     .find();
+/// let paths = selected.iter().map(|(p, _)| p.to_string()).collect::<Vec<_>>();
+/// assert_eq!(vec!["services.api", "services.web"], paths);
+/// ```
+pub struct Selector<'a> {
+    iter: Iter<'a>,
+    pattern: Option<Pattern>,
+    predicates: Vec<Predicate<'a>>,
+}
+
+/// Returns the scalars of the child bindings of `value` named `name`.
+fn child_values<'a>(value: &'a Value, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    value
+        .get_all(name)
+        .flat_map(|b| b.values.iter())
+        .map(|v| v.value.as_str())
+}
+
+impl<'a> Selector<'a> {
+    fn new(iter: Iter<'a>) -> Self {
+        Selector {
+            iter,
+            pattern: None,
+            predicates: vec![],
+        }
+    }
+
+    /// Keeps the values whose paths match `pattern`, e.g. `services.*`.
+    pub fn matching(mut self, pattern: Pattern) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Keeps the values with a child binding named `name`.
+    pub fn has_child(self, name: &'a str) -> Self {
+        self.filter(move |value| value.get(name).is_some())
+    }
+
+    /// Keeps the values with a child binding named `name` one of whose values is `expected`.
+    pub fn child_eq(self, name: &'a str, expected: &'a str) -> Self {
+        self.filter(move |value| child_values(value, name).any(|v| v == expected))
+    }
+
+    /// Keeps the values without a child binding named `name` one of whose values is `expected`,
+    /// including those without such a child binding at all.
+    pub fn child_ne(self, name: &'a str, expected: &'a str) -> Self {
+        self.filter(move |value| child_values(value, name).all(|v| v != expected))
+    }
+
+    /// Keeps the values satisfying `predicate`.
+    pub fn filter(mut self, predicate: impl Fn(&Value) -> bool + 'a) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Returns an iterator over the selected values with their paths, in document order.
+    pub fn iter(self) -> impl Iterator<Item = (Path, &'a Value)> + 'a {
+        let Selector {
+            iter,
+            pattern,
+            predicates,
+        } = self;
+        iter.filter(move |(path, value)| {
+            pattern.as_ref().is_none_or(|p| p.matches(path))
+                && predicates.iter().all(|predicate| predicate(value))
+        })
+    }
+
+    /// Returns the selected values with their paths, in document order.
+    pub fn find(self) -> Vec<(Path, &'a Value)> {
+        self.iter().collect()
+    }
+
+    /// Returns the first selected value with its path, if any.
+    pub fn first(self) -> Option<(Path, &'a Value)> {
+        self.iter().next()
+    }
+}
+
+impl Binding {
+    /// Returns a selector over the values of this binding and of its descendants, with their
+    /// paths starting from the name of this binding, as [`Binding::iter`] yields them.
+    pub fn select(&self) -> Selector<'_> {
+        Selector::new(self.iter())
+    }
+}
+
+impl Document {
+    /// Returns a selector over all values of the document.
+    pub fn select(&self) -> Selector<'_> {
+        Selector::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    fn paths(selected: Vec<(Path, &Value)>) -> Vec<String> {
+        selected.iter().map(|(path, _)| path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let document = parse_document_complete(
+            "services={api={env=prod replicas=3} worker={env=dev} web={env=staging,prod}}\n\
+             jobs=backup{env=prod}",
+        )
+        .unwrap();
+        let services = || "services.*".parse::<Pattern>().unwrap();
+
+        assert_eq!(
+            vec!["services.api", "services.web", "jobs"],
+            paths(document.select().child_eq("env", "prod").find())
+        );
+        assert_eq!(
+            vec!["services.api", "services.web"],
+            paths(
+                document
+                    .select()
+                    .matching(services())
+                    .child_eq("env", "prod")
+                    .find()
+            )
+        );
+        assert_eq!(
+            vec!["services.worker"],
+            paths(
+                document
+                    .select()
+                    .matching(services())
+                    .child_ne("env", "prod")
+                    .find()
+            )
+        );
+        assert_eq!(
+            vec!["services.api"],
+            paths(document.select().has_child("replicas").find())
+        );
+        assert_eq!(
+            vec!["services.api.replicas"],
+            paths(
+                document
+                    .select()
+                    .filter(|v| v.value().parse::<u32>().is_ok())
+                    .find()
+            )
+        );
+        let (path, value) = document
+            .select()
+            .matching("jobs".parse().unwrap())
+            .first()
+            .unwrap();
+        assert_eq!(
+            ("jobs", "backup"),
+            (path.to_string().as_str(), value.value())
+        );
+        assert!(document.select().has_child("missing").first().is_none());
+
+        let binding = parse_binding_complete("a=x{b={c=1} d={c=2}}").unwrap();
+        assert_eq!(
+            vec!["a.d"],
+            paths(binding.select().child_eq("c", "2").find())
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Ed25519 signatures over the canonical bytes of documents, so that configuration distributed to
+//! other machines can be checked for integrity and origin before it is applied.
+//!
+//! A signature covers [`Document::canonical_bytes`], so it survives reformatting, comments and
+//! reordering of differently named siblings, but not any change to the bindings themselves.
+//! Signatures are either detached, stored and sent apart from the document, or embedded in it:
+//! the embedded signature of a document is a top-level `signature` binding, last by convention,
+//! bound to the 128 lowercase hexadecimal digits of the signature of the rest of the document.
+//!
+//! ```
+//! use flax::parse_document_complete;
+//! use flax::sign::{sign_embedded, verify_embedded, SigningKey};
+//!
+//! let key = SigningKey::from_bytes(&[7; 32]);
+//! let mut document = parse_document_complete("server={port=80}").unwrap();
+//! sign_embedded(&mut document, &key);
+//! let signed = document.to_string();
+//!
+//! let received = parse_document_complete(&signed).unwrap();
+//! let document = verify_embedded(&received, &key.verifying_key()).unwrap();
+//! assert_eq!("server={port=80}", document.to_string());
+//! ```
+
+use crate::prelude::*;
+use crate::{Binding, Document, Value};
+use core::fmt;
+use ed25519_dalek::Signer;
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+
+/// The name of the top-level binding of an embedded signature.
+pub const SIGNATURE: &str = "signature";
+
+/// A signature that could not be verified.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum SignError {
+    /// The document has no embedded signature.
+    Missing,
+    /// The embedded signature is repeated, or not a single scalar of 128 hexadecimal digits.
+    Malformed,
+    /// The signature does not match the document and key.
+    Invalid,
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignError::Missing => write!(f, "missing `{}` binding", SIGNATURE),
+            SignError::Malformed => write!(f, "malformed `{}` binding", SIGNATURE),
+            SignError::Invalid => write!(f, "invalid signature"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignError {}
+
+/// Returns the signature of the canonical bytes of `document`.
+pub fn sign(document: &Document, key: &SigningKey) -> Signature {
+    key.sign(&document.canonical_bytes())
+}
+
+/// Checks that `signature` is the signature of the canonical bytes of `document` by the key of
+/// `key`.
+pub fn verify(
+    document: &Document,
+    signature: &Signature,
+    key: &VerifyingKey,
+) -> Result<(), SignError> {
+    key.verify_strict(&document.canonical_bytes(), signature)
+        .map_err(|_| SignError::Invalid)
+}
+
+/// Signs `document`, replacing any embedded signature with a `signature` binding appended to it.
+pub fn sign_embedded(document: &mut Document, key: &SigningKey) {
+    document.bindings_mut().retain(|b| b.name != SIGNATURE);
+    let signature = sign(document, key);
+    document.bindings_mut().push(Binding {
+        name: SIGNATURE.to_string(),
+        values: smallvec![Value::from(encode_hex(&signature.to_bytes()).as_str())],
+    });
+}
+
+/// Checks the embedded signature of `document` by the key of `key`, returning the document
+/// without its `signature` binding, as it was signed.
+pub fn verify_embedded(document: &Document, key: &VerifyingKey) -> Result<Document, SignError> {
+    let mut signatures = document.bindings.iter().filter(|b| b.name == SIGNATURE);
+    let binding = signatures.next().ok_or(SignError::Missing)?;
+    if signatures.next().is_some() {
+        return Err(SignError::Malformed);
+    }
+    let signature = match binding.values.as_slice() {
+        [value] if value.items().is_none() && value.children.is_empty() => {
+            decode_hex(value.value()).ok_or(SignError::Malformed)?
+        }
+        _ => return Err(SignError::Malformed),
+    };
+    let mut unsigned = document.clone();
+    unsigned.bindings_mut().retain(|b| b.name != SIGNATURE);
+    verify(&unsigned, &Signature::from_bytes(&signature), key)?;
+    Ok(unsigned)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<[u8; 64]> {
+    if hex.len() != 128 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; 64];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = core::str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+
+    #[test]
+    fn test_sign() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let document = parse_document_complete("b=x{d=1 c=2} a=y").unwrap();
+        let signature = sign(&document, &key);
+
+        let reordered = parse_document_complete("# comment\na=y\nb=x{c=2 d=1}").unwrap();
+        assert_eq!(Ok(()), verify(&reordered, &signature, &key.verifying_key()));
+        let changed = parse_document_complete("a=y b=x{c=2 d=3}").unwrap();
+        assert_eq!(
+            Err(SignError::Invalid),
+            verify(&changed, &signature, &key.verifying_key())
+        );
+        let other = SigningKey::from_bytes(&[2; 32]);
+        assert_eq!(
+            Err(SignError::Invalid),
+            verify(&document, &signature, &other.verifying_key())
+        );
+    }
+
+    #[test]
+    fn test_sign_embedded() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut document = parse_document_complete("a=b signature=stale c=d").unwrap();
+        sign_embedded(&mut document, &key);
+        let signed = document.to_string();
+        let hex = signed.strip_prefix("a=b\nc=d\nsignature=").unwrap();
+        assert_eq!(128, hex.len());
+
+        let verified = verify_embedded(&document, &key.verifying_key()).unwrap();
+        assert_eq!("a=b\nc=d", verified.to_string());
+        let moved = parse_document_complete(&format!("signature={} c=d a=b", hex)).unwrap();
+        assert!(verify_embedded(&moved, &key.verifying_key()).is_ok());
+
+        let tests = [
+            ("a=b c=d".to_string(), SignError::Missing),
+            (format!("a=b c=e signature={}", hex), SignError::Invalid),
+            (
+                format!("a=b c=d signature={},{}", hex, hex),
+                SignError::Malformed,
+            ),
+            (
+                format!("a=b c=d signature={} signature=x", hex),
+                SignError::Malformed,
+            ),
+            (
+                format!("a=b c=d signature={}{{x=y}}", hex),
+                SignError::Malformed,
+            ),
+            ("a=b c=d signature=xyz".to_string(), SignError::Malformed),
+            (
+                format!("a=b c=d signature=\"+{}\"", &hex[1..]),
+                SignError::Malformed,
+            ),
+            (
+                format!("a=b c=d signature=\"{}é\"", &hex[2..]),
+                SignError::Malformed,
+            ),
+        ];
+        for (input, expected) in tests.iter() {
+            let document = parse_document_complete(input).unwrap();
+            assert_eq!(
+                Err(expected.clone()),
+                verify_embedded(&document, &key.verifying_key()),
+                "{}",
+                input
+            );
+        }
+    }
+}
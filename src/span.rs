@@ -0,0 +1,285 @@
+//! A variant of the AST whose bindings and values carry the byte range of their source text, so
+//! that diagnostics can point back at the input.
+//!
+//! ```
+//! use flax::span::parse_document;
+//!
+//! let input = "server=a{port=80}\nport=x";
+//! let document = parse_document(input).unwrap();
+//! let port = &document.bindings[0].node.values[0].node.children[0];
+//! assert_eq!("port=80", &input[port.span.range()]);
+//! assert_eq!((1, 10), port.span.line_column(input));
+//! ```
+
+use crate::cst::{self, contents, scalar};
+use crate::parser::key;
+use crate::prelude::*;
+use crate::{line_column, Address, ParseError};
+use core::ops::Range;
+
+/// A range of bytes in the input.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the 1-based line and column (in characters) of the start of the span in `input`.
+    pub fn line_column(&self, input: &str) -> (usize, usize) {
+        line_column(input, self.start)
+    }
+}
+
+/// A node along with the span of its source text.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+/// A binding, spanning from its name to its last value.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Binding {
+    /// The name, spanning its quotes if it is quoted.
+    pub name: Spanned<String>,
+    pub values: Vec<Spanned<Value>>,
+}
+
+/// A value, spanning its children block, or its brackets if it is a nested list.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Value {
+    pub value: String,
+    pub kind: ValueKind,
+    pub children: Vec<Spanned<Binding>>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ValueKind {
+    Scalar,
+    List(Vec<Spanned<Value>>),
+    Map,
+    /// A reference to an anchored value; the value is the name of the anchor.
+    Reference,
+    /// A scalar named by an anchor; the value excludes the anchor.
+    Anchored(String),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Document {
+    pub bindings: Vec<Spanned<Binding>>,
+}
+
+impl Binding {
+    /// Converts the binding to the usual AST, dropping the spans.
+    pub fn to_ast(&self) -> crate::Binding {
+        crate::Binding {
+            name: self.name.node.clone(),
+            values: self.values.iter().map(|v| v.node.to_ast()).collect(),
+        }
+    }
+}
+
+impl Value {
+    /// Converts the value to the usual AST, dropping the spans.
+    pub fn to_ast(&self) -> crate::Value {
+        crate::Value {
+            value: self.value.clone(),
+            kind: match &self.kind {
+                ValueKind::Scalar => crate::ValueKind::Scalar,
+                ValueKind::List(items) => {
+                    crate::ValueKind::List(items.iter().map(|v| v.node.to_ast()).collect())
+                }
+                ValueKind::Map => crate::ValueKind::Map,
+                ValueKind::Reference => crate::ValueKind::Reference,
+                ValueKind::Anchored(anchor) => crate::ValueKind::Anchored(anchor.clone()),
+            },
+            children: self.children.iter().map(|b| b.node.to_ast()).collect(),
+        }
+    }
+}
+
+impl Document {
+    /// Converts the document to the usual AST, dropping the spans.
+    pub fn to_ast(&self) -> crate::Document {
+        crate::Document::from_bindings(self.bindings.iter().map(|b| b.node.to_ast()).collect())
+    }
+
+    /// Returns the span of the binding or value at `address`, e.g. the address of a
+    /// `ValidationError`. The items of nested lists cannot be addressed.
+    pub fn span(&self, address: &Address) -> Option<Span> {
+        let (&index, rest) = address.0.split_first()?;
+        binding_span(self.bindings.get(index)?, rest)
+    }
+}
+
+fn binding_span(binding: &Spanned<Binding>, indices: &[usize]) -> Option<Span> {
+    match indices.split_first() {
+        None => Some(binding.span),
+        Some((&index, rest)) => {
+            let value = binding.node.values.get(index)?;
+            match rest.split_first() {
+                None => Some(value.span),
+                Some((&index, rest)) => binding_span(value.node.children.get(index)?, rest),
+            }
+        }
+    }
+}
+
+fn bindings(bindings: &cst::Bindings, mut offset: usize) -> Vec<Spanned<Binding>> {
+    let mut out = vec![];
+    for (i, (trivia, binding)) in bindings.items.iter().enumerate() {
+        if i > 0 && trivia.is_empty() {
+            offset += 1;
+        }
+        let binding = spanned_binding(binding, offset + trivia.len());
+        offset = binding.span.end;
+        out.push(binding);
+    }
+    out
+}
+
+fn spanned_binding(binding: &cst::Binding, start: usize) -> Spanned<Binding> {
+    let name = Spanned {
+        node: contents(&binding.name, key),
+        span: Span {
+            start,
+            end: start + binding.name.len(),
+        },
+    };
+    let mut offset = name.span.end + 1;
+    if let Some((open, _)) = &binding.brackets {
+        offset += 1 + open.len();
+    }
+    Spanned {
+        node: Binding {
+            name,
+            values: values(&binding.values, offset),
+        },
+        span: Span {
+            start,
+            end: start + binding.print().len(),
+        },
+    }
+}
+
+fn values(values: &[cst::ValueItem], mut offset: usize) -> Vec<Spanned<Value>> {
+    let mut out = vec![];
+    for (i, item) in values.iter().enumerate() {
+        if i > 0 {
+            offset += item.before_comma.len() + 1 + item.after_comma.len();
+        }
+        let value = spanned_value(&item.value, offset);
+        offset = value.span.end;
+        out.push(value);
+    }
+    out
+}
+
+fn spanned_value(value: &cst::Value, start: usize) -> Spanned<Value> {
+    let node = match (&value.list, &value.block) {
+        (Some(list), _) => Value {
+            value: String::new(),
+            kind: ValueKind::List(values(&list.values, start + 1 + list.brackets.0.len())),
+            children: vec![],
+        },
+        (None, block) => {
+            let (scalar, kind) = scalar(&value.value);
+            Value {
+                value: scalar,
+                kind: match kind {
+                    _ if value.value.is_empty() && block.is_some() => ValueKind::Map,
+                    crate::ValueKind::Reference => ValueKind::Reference,
+                    crate::ValueKind::Anchored(anchor) => ValueKind::Anchored(anchor),
+                    _ => ValueKind::Scalar,
+                },
+                children: block.as_ref().map_or_else(Vec::new, |(trivia, children)| {
+                    bindings(children, start + value.value.len() + trivia.len() + 1)
+                }),
+            }
+        }
+    };
+    Spanned {
+        node,
+        span: Span {
+            start,
+            end: start + value.print().len(),
+        },
+    }
+}
+
+/// Parses a document that must span the entire input, recording the span of each node.
+pub fn parse_document(input: &str) -> Result<Document, ParseError> {
+    let document = cst::parse_document(input)?;
+    Ok(Document {
+        bindings: bindings(&document, 0),
+    })
+}
+
+/// Parses a binding that must span the entire input, recording the span of each node.
+pub fn parse_binding(input: &str) -> Result<Spanned<Binding>, ParseError> {
+    let binding = cst::parse_binding(input)?;
+    Ok(spanned_binding(&binding, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Schema;
+
+    #[test]
+    fn test_spans() {
+        let input = "# x\na=b , c{ d=e f=\"g\" }\n\"h i\"=[ j, [k] ,]\nl=";
+        let document = parse_document(input).unwrap();
+        assert_eq!(crate::parse_document_complete(input), Ok(document.to_ast()));
+
+        let text = |span: Span| &input[span.range()];
+        let a = &document.bindings[0];
+        assert_eq!("a=b , c{ d=e f=\"g\" }", text(a.span));
+        assert_eq!("a", text(a.node.name.span));
+        let c = &a.node.values[1];
+        assert_eq!("c{ d=e f=\"g\" }", text(c.span));
+        let f = &c.node.children[1];
+        assert_eq!("f=\"g\"", text(f.span));
+        assert_eq!("\"g\"", text(f.node.values[0].span));
+        assert_eq!("g", f.node.values[0].node.value);
+
+        let h = &document.bindings[1];
+        assert_eq!("\"h i\"", text(h.node.name.span));
+        assert_eq!("h i", h.node.name.node);
+        match &h.node.values[1].node.kind {
+            ValueKind::List(items) => assert_eq!("k", text(items[0].span)),
+            kind => panic!("expected a list, found {:?}", kind),
+        }
+        assert_eq!("[k]", text(h.node.values[1].span));
+        assert_eq!("l=", text(document.bindings[2].span));
+        assert_eq!((4, 1), document.bindings[2].span.line_column(input));
+
+        let binding = parse_binding("a={b=c}").unwrap();
+        assert_eq!(
+            Span { start: 3, end: 6 },
+            binding.node.values[0].node.children[0].span
+        );
+    }
+
+    #[test]
+    fn test_address() {
+        let schema = Schema::from_document(
+            &crate::parse_document_complete("field=tls{type=bool optional=true}").unwrap(),
+        )
+        .unwrap();
+        let input = "# x
+tls=yes";
+        let errors = schema.validate(&crate::parse_document_complete(input).unwrap());
+        let document = parse_document(input).unwrap();
+        let span = document.span(&errors[0].address).unwrap();
+        assert_eq!("yes", &input[span.range()]);
+
+        assert_eq!(None, document.span(&Address(vec![0, 1])));
+        assert_eq!(None, document.span(&Address(vec![])));
+    }
+}
@@ -0,0 +1,645 @@
+//! Incremental reading and writing of top-level bindings, for documents too large to hold in
+//! memory. With the `tokio` feature, bindings can also be read without blocking.
+
+use crate::error::partial;
+use crate::parser::{self, ws0, ws1, Error};
+use crate::quote;
+#[cfg(feature = "tokio")]
+use crate::Document;
+use crate::{borrowed, Binding, ParseError, Value};
+use nom::{sequence::preceded, IResult};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    str,
+};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Number of bytes requested from the underlying reader at a time.
+const CHUNK_LEN: usize = 8 * 1024;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    /// A syntax error, located relative to the start of the stream.
+    Parse(ParseError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+fn next_binding<'a, E: Error<'a>>(input: &'a str) -> IResult<&'a str, borrowed::Binding<'a>, E> {
+    preceded(ws0, parser::binding)(input)
+}
+
+/// The outcome of parsing the buffered input.
+enum Step {
+    /// A binding, followed by the number of bytes it spans.
+    Binding(Binding, usize),
+    Error(ParseError),
+    /// The buffered input may be the prefix of a binding.
+    More,
+    End,
+}
+
+/// What a reader must do next.
+enum Next {
+    Binding(Binding),
+    /// Read more input and pass it to `Buffer::extend`.
+    Fill,
+    End,
+}
+
+/// The buffered input of a stream of bindings, independent of how it is read.
+struct Buffer {
+    buffer: Vec<u8>,
+    eof: bool,
+    /// Offset in the buffer of the last syntax error, which is only reported if it persists after
+    /// reading more input, as it may be caused by a truncated token.
+    error: Option<usize>,
+    /// Length the buffer must reach before it is parsed again: twice its length when it last held
+    /// an incomplete binding, so that a binding spanning many chunks is parsed a logarithmic number
+    /// of times rather than once per chunk, and reading it takes linear time.
+    retry_len: usize,
+    /// Position of the start of the buffer in the stream.
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            buffer: Vec::new(),
+            eof: false,
+            error: None,
+            retry_len: 0,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn step(&self, input: &str) -> Step {
+        match partial(input, next_binding) {
+            Ok((rest, binding)) => {
+                let end = match ws0::<nom::error::Error<&str>>(rest) {
+                    Ok((after, _)) => after.is_empty(),
+                    Err(_) => false,
+                };
+                if end && !self.eof {
+                    Step::More
+                } else if !rest.is_empty() && ws1::<nom::error::Error<&str>>(rest).is_err() {
+                    // As in a whole document, bindings must be separated by whitespace or comments.
+                    let offset = input.len() - rest.len();
+                    Step::Error(ParseError::new(input, offset, "end of input"))
+                } else {
+                    Step::Binding(binding.into_owned(), input.len() - rest.len())
+                }
+            }
+            Err(_) if matches!(ws0::<nom::error::Error<&str>>(input), Ok(("", _))) => {
+                if self.eof {
+                    Step::End
+                } else {
+                    Step::More
+                }
+            }
+            Err(e) => Step::Error(e),
+        }
+    }
+
+    /// Drops the first `len` bytes of the buffer, which must be valid UTF-8.
+    fn consume(&mut self, len: usize) {
+        let consumed = str::from_utf8(&self.buffer[..len]).unwrap();
+        match consumed.rfind('\n') {
+            Some(i) => {
+                self.line += consumed.matches('\n').count();
+                self.column = consumed[i + 1..].chars().count() + 1;
+            }
+            None => self.column += consumed.chars().count(),
+        }
+        self.offset += len;
+        self.buffer.drain(..len);
+        self.error = None;
+        self.retry_len = 0;
+    }
+
+    /// Appends `chunk` read from the stream, which ends if it is empty.
+    fn extend(&mut self, chunk: &[u8]) {
+        if chunk.is_empty() {
+            self.eof = true;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    fn next(&mut self) -> Result<Next, ReadError> {
+        if !self.eof && self.buffer.len() < self.retry_len {
+            return Ok(Next::Fill);
+        }
+        // The buffer may end in the middle of a character, which the input leaves out.
+        let input = match str::from_utf8(&self.buffer) {
+            Ok(input) => input,
+            Err(e) if e.error_len().is_none() && !self.eof => {
+                str::from_utf8(&self.buffer[..e.valid_up_to()]).unwrap()
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+        };
+        match self.step(input) {
+            Step::Binding(binding, len) => {
+                self.consume(len);
+                Ok(Next::Binding(binding))
+            }
+            // The error may be due to the character left out, so read it before reporting any.
+            Step::Error(_) if input.len() < self.buffer.len() => {
+                self.retry_len = self.buffer.len() + 1;
+                Ok(Next::Fill)
+            }
+            Step::Error(e) if !self.eof && self.error != Some(e.offset) => {
+                self.error = Some(e.offset);
+                self.retry_len = 2 * self.buffer.len();
+                Ok(Next::Fill)
+            }
+            Step::Error(mut e) => {
+                if e.line == 1 {
+                    e.column += self.column - 1;
+                }
+                e.line += self.line - 1;
+                e.offset += self.offset;
+                Err(ReadError::Parse(e))
+            }
+            Step::More => {
+                self.retry_len = 2 * self.buffer.len();
+                Ok(Next::Fill)
+            }
+            Step::End => Ok(Next::End),
+        }
+    }
+}
+
+/// An iterator over the top-level bindings of a document read from `R`, buffering only the
+/// binding being parsed.
+///
+/// A binding is yielded once the input following it shows that it cannot continue, i.e. there
+/// is a token after it other than `,` or `{`. Bindings must be separated by whitespace or
+/// comments, so the reader accepts the same documents as `parse_document_complete`. Iteration
+/// stops after the first error.
+pub struct BindingReader<R> {
+    reader: R,
+    buffer: Buffer,
+    failed: bool,
+}
+
+impl<R: Read> BindingReader<R> {
+    pub fn new(reader: R) -> Self {
+        BindingReader {
+            reader,
+            buffer: Buffer::new(),
+            failed: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => self.buffer.extend(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            return Ok(());
+        }
+    }
+
+    fn next_binding(&mut self) -> Result<Option<Binding>, ReadError> {
+        loop {
+            match self.buffer.next()? {
+                Next::Binding(binding) => return Ok(Some(binding)),
+                Next::Fill => self.fill()?,
+                Next::End => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for BindingReader<R> {
+    type Item = Result<Binding, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_binding().transpose();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+/// Reads the top-level bindings of a document from `R` without blocking, as [`BindingReader`]
+/// does, for services reading documents from the network.
+///
+/// ```
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// use flax::AsyncBindingReader;
+///
+/// let mut reader = AsyncBindingReader::new("a=b c=d".as_bytes());
+/// while let Some(binding) = reader.next_binding().await.unwrap() {
+///     println!("{}", binding);
+/// }
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+pub struct AsyncBindingReader<R> {
+    reader: R,
+    buffer: Buffer,
+    failed: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncBindingReader<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncBindingReader {
+            reader,
+            buffer: Buffer::new(),
+            failed: false,
+        }
+    }
+
+    /// Returns the next binding, or `None` at the end of the document or after an error.
+    pub async fn next_binding(&mut self) -> Result<Option<Binding>, ReadError> {
+        if self.failed {
+            return Ok(None);
+        }
+        let mut chunk = vec![0; CHUNK_LEN];
+        loop {
+            let next = self.buffer.next().inspect_err(|_| self.failed = true)?;
+            match next {
+                Next::Binding(binding) => return Ok(Some(binding)),
+                Next::Fill => {
+                    let n = self
+                        .reader
+                        .read(&mut chunk)
+                        .await
+                        .inspect_err(|_| self.failed = true)?;
+                    self.buffer.extend(&chunk[..n]);
+                }
+                Next::End => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Parses a document read from `R` without blocking, buffering only the binding being parsed
+/// rather than the whole input.
+#[cfg(feature = "tokio")]
+pub async fn parse_document_async<R: AsyncRead + Unpin>(reader: R) -> Result<Document, ReadError> {
+    let mut reader = AsyncBindingReader::new(reader);
+    let mut bindings = vec![];
+    while let Some(binding) = reader.next_binding().await? {
+        bindings.push(binding);
+    }
+    Ok(Document::from_bindings(bindings))
+}
+
+/// Writes bindings to `W` as they are produced, one top-level binding per line.
+pub struct BindingWriter<W: Write> {
+    writer: W,
+    pretty: bool,
+}
+
+/// Number of spaces per nesting level in pretty mode.
+const INDENT: usize = 2;
+
+impl<W: Write> BindingWriter<W> {
+    /// Creates a writer printing each binding compactly, as `print_binding` does.
+    pub fn new(writer: W) -> Self {
+        BindingWriter {
+            writer,
+            pretty: false,
+        }
+    }
+
+    /// Creates a writer printing each child binding on its own, indented line, and values that
+    /// span several lines as text blocks.
+    pub fn pretty(writer: W) -> Self {
+        BindingWriter {
+            writer,
+            pretty: true,
+        }
+    }
+
+    pub fn write(&mut self, binding: &Binding) -> io::Result<()> {
+        let indent = if self.pretty { Some(0) } else { None };
+        write_binding(&mut self.writer, binding, indent)?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes `binding`, compactly if `indent` is `None`, or else pretty-printed at the given
+/// nesting level.
+fn write_binding<W: Write>(w: &mut W, binding: &Binding, indent: Option<usize>) -> io::Result<()> {
+    write!(w, "{}=", quote::key(&binding.name))?;
+    if binding.values.iter().any(|v| v.items().is_some()) {
+        write_list(w, &binding.values, indent)
+    } else {
+        write_values(w, &binding.values, indent)
+    }
+}
+
+fn write_values<W: Write>(w: &mut W, values: &[Value], indent: Option<usize>) -> io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write_value(w, value, indent)?;
+    }
+    Ok(())
+}
+
+fn write_list<W: Write>(w: &mut W, values: &[Value], indent: Option<usize>) -> io::Result<()> {
+    w.write_all(b"[")?;
+    write_values(w, values, indent)?;
+    w.write_all(b"]")
+}
+
+fn write_value<W: Write>(w: &mut W, value: &Value, indent: Option<usize>) -> io::Result<()> {
+    if let Some(items) = value.items() {
+        return write_list(w, items, indent);
+    }
+    if !value.is_map() {
+        let block = indent.and_then(|level| quote::text_block(value, (level + 1) * INDENT));
+        match block {
+            Some(block) => w.write_all(block.as_bytes())?,
+            None => w.write_all(quote::value(value).as_bytes())?,
+        }
+    }
+    if value.children.is_empty() {
+        return w.write_all(if value.is_map() { b"{}" } else { b"" });
+    }
+    w.write_all(b"{")?;
+    match indent {
+        None => {
+            for (i, child) in value.children.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b" ")?;
+                }
+                write_binding(w, child, None)?;
+            }
+        }
+        Some(level) => {
+            for child in &value.children {
+                write!(w, "\n{:1$}", "", (level + 1) * INDENT)?;
+                write_binding(w, child, Some(level + 1))?;
+            }
+            write!(w, "\n{:1$}", "", level * INDENT)?;
+        }
+    }
+    w.write_all(b"}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "arbitrary")]
+    use crate::arbitrary::document_with_text;
+    use crate::{parse_binding_complete, parse_document_complete};
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prop_assert_eq, proptest};
+
+    /// A reader returning at most `len` bytes at a time, to exercise buffering.
+    struct Trickle<'a> {
+        input: &'a [u8],
+        len: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.len.min(buf.len()).min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_binding_reader() {
+        let inputs = [
+            "",
+            "  # only a comment",
+            "a=b",
+            "a=b c=d,e\nf=g{h=i j=k} # end\n",
+            "a=b\n  , c\n{d=e}\n\n// x\nf=g",
+            "# π ü\na=b{c=d} // é ö\ne=f",
+            "\"\u{3347a}\"=",
+            "a=\"\u{1f600}\"\n\"\u{10ffff}\u{800}\"=b{\"\u{1f600}\"=c}",
+        ];
+
+        for input in inputs.iter() {
+            let expected = parse_document_complete(input).unwrap().bindings;
+            for len in 1..4 {
+                let reader = BindingReader::new(Trickle {
+                    input: input.as_bytes(),
+                    len,
+                });
+                let bindings = reader.collect::<Result<Vec<_>, _>>().unwrap();
+                assert_eq!(expected, bindings, "{:?} in chunks of {}", input, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_binding_reader_errors() {
+        let mut reader = BindingReader::new("a=b\nc=d e{f=g}".as_bytes());
+        assert_eq!("a=b", reader.next().unwrap().unwrap().to_string());
+        assert_eq!("c=d", reader.next().unwrap().unwrap().to_string());
+        match reader.next() {
+            Some(Err(ReadError::Parse(e))) => {
+                assert_eq!((2, 6, 9), (e.line, e.column, e.offset));
+                assert_eq!("'='", e.expected);
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(reader.next().is_none());
+
+        let mut reader = BindingReader::new("a=b{".as_bytes());
+        assert!(matches!(reader.next(), Some(Err(ReadError::Parse(_)))));
+
+        // Bindings that are not separated are rejected as by the full parser.
+        for input in ["a=b{c=d}e=f", "a={}b=c", "a=\"x\"b=c", "x=y{z=w}bb="].iter() {
+            let expected = parse_document_complete(input).unwrap_err();
+            assert_eq!("end of input", expected.expected, "{}", input);
+            for len in 1..4 {
+                let mut reader = BindingReader::new(Trickle {
+                    input: input.as_bytes(),
+                    len,
+                });
+                match reader.next() {
+                    Some(Err(ReadError::Parse(e))) => assert_eq!(expected, e, "{}", input),
+                    result => panic!("unexpected {:?} for {}", result, input),
+                }
+                assert!(reader.next().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_binding_reader_large_binding() {
+        let mut input = "root={".to_string();
+        for i in 0..100_000 {
+            input.push_str(&format!("k{}=v ", i));
+        }
+        input.push_str(
+            "}
+next=x",
+        );
+        let expected = parse_document_complete(&input).unwrap().bindings;
+        let reader = BindingReader::new(Trickle {
+            input: input.as_bytes(),
+            len: CHUNK_LEN,
+        });
+        let bindings = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(expected, bindings);
+
+        // The buffer is parsed each time its length doubles, not after every chunk.
+        let mut buffer = Buffer::new();
+        let mut parses = 0;
+        for chunk in input.as_bytes().chunks(CHUNK_LEN) {
+            buffer.extend(chunk);
+            parses += usize::from(buffer.buffer.len() >= buffer.retry_len);
+            assert!(matches!(buffer.next(), Ok(Next::Fill)));
+        }
+        assert!(parses < 10, "{} parses", parses);
+    }
+
+    #[cfg(feature = "tokio")]
+    impl tokio::io::AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            let n = self.len.min(buf.remaining()).min(self.input.len());
+            buf.put_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_binding_reader() {
+        let input = "# π ü\na=b{c=d} // é ö\ne=f,\n  g h=[i,[j]]\n\"\u{3347a}\"=";
+        let expected = parse_document_complete(input).unwrap();
+        for len in 1..4 {
+            let trickle = || Trickle {
+                input: input.as_bytes(),
+                len,
+            };
+            let mut reader = AsyncBindingReader::new(trickle());
+            let mut bindings = vec![];
+            while let Some(binding) = reader.next_binding().await.unwrap() {
+                bindings.push(binding);
+            }
+            assert_eq!(expected.bindings, bindings, "in chunks of {}", len);
+            assert_eq!(expected, parse_document_async(trickle()).await.unwrap());
+        }
+
+        let mut reader = AsyncBindingReader::new("a=b\nc=d e{f=g}".as_bytes());
+        assert_eq!(
+            "a=b",
+            reader.next_binding().await.unwrap().unwrap().to_string()
+        );
+        assert_eq!(
+            "c=d",
+            reader.next_binding().await.unwrap().unwrap().to_string()
+        );
+        match reader.next_binding().await {
+            Err(ReadError::Parse(e)) => assert_eq!((2, 6, 9), (e.line, e.column, e.offset)),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(reader.next_binding().await.unwrap().is_none());
+
+        // Bindings that are not separated are rejected as by the full parser.
+        let expected = parse_document_complete("a=b{c=d}e=f").unwrap_err();
+        match parse_document_async("a=b{c=d}e=f".as_bytes()).await {
+            Err(ReadError::Parse(e)) => assert_eq!(expected, e),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let error = parse_document_async(&b"a=\xff"[..]).await.unwrap_err();
+        assert!(matches!(error, ReadError::Io(e) if e.kind() == io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_binding_writer() {
+        let document = parse_document_complete("a=b c=d{e=f g=h{i=j},k}").unwrap();
+
+        let mut writer = BindingWriter::new(Vec::new());
+        for binding in document.bindings() {
+            writer.write(binding).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!("a=b\nc=d{e=f g=h{i=j},k}\n", output);
+
+        let mut writer = BindingWriter::pretty(Vec::new());
+        for binding in document.bindings() {
+            writer.write(binding).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!("a=b\nc=d{\n  e=f\n  g=h{\n    i=j\n  },k\n}\n", output);
+        assert_eq!(Ok(document), parse_document_complete(&output));
+
+        let binding = parse_binding_complete("a={b=c},{}").unwrap();
+        let mut writer = BindingWriter::pretty(Vec::new());
+        writer.write(&binding).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!("a={\n  b=c\n},{}\n", output);
+
+        let binding = parse_binding_complete("a=b{c=\"x\\n\\n  y\"},\"z\\n\"").unwrap();
+        let mut writer = BindingWriter::pretty(Vec::new());
+        writer.write(&binding).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            "a=b{\n  c=\"\"\"\n    x\n\n      y\n    \"\"\"\n},\"\"\"\n  z\n\n  \"\"\"\n",
+            output
+        );
+        assert_eq!(Ok(binding), parse_binding_complete(&output));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_pretty_text_round_trip(document in document_with_text()) {
+            let mut writer = BindingWriter::pretty(Vec::new());
+            for binding in document.bindings() {
+                writer.write(binding).unwrap();
+            }
+            let printed = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+            prop_assert_eq!(Ok(document), parse_document_complete(&printed), "{}", printed);
+        }
+    }
+}
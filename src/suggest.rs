@@ -0,0 +1,79 @@
+//! Suggestions of the closest known name for a misspelt one.
+
+use crate::prelude::*;
+
+/// Returns the edit distance between `a` and `b`, counting insertions, deletions, substitutions
+/// and transpositions of adjacent characters, as typos commonly swap two keys.
+pub(crate) fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = vec![0; b.len() + 1];
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut next = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            next[j] = (row[j - 1] + cost).min(row[j] + 1).min(next[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                next[j] = next[j].min(previous[j - 2] + 1);
+            }
+        }
+        previous = core::mem::replace(&mut row, next);
+    }
+    row[b.len()]
+}
+
+/// Returns the candidate closest to `name`, if it is within a third of the length of `name` or one
+/// edit, so that unrelated names are not suggested. Nor is a candidate that differs from `name` in
+/// every character, such as `a` for `x`. Ties go to the first candidate.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let length = name.chars().count();
+    let limit = (length / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= limit && *distance < length.max(candidate.chars().count())
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        let tests = [
+            ("", "", 0),
+            ("abc", "", 3),
+            ("tcp", "sctp", 2),
+            ("tcp", "tcpp", 1),
+            ("kitten", "sitting", 3),
+            ("héllo", "hello", 1),
+            ("tcp", "tpc", 1),
+            ("ab", "ba", 1),
+        ];
+
+        for (a, b, expected) in tests.iter() {
+            assert_eq!(*expected, distance(a, b), "{} {}", a, b);
+            assert_eq!(*expected, distance(b, a), "{} {}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_closest() {
+        let candidates = ["tcp", "udp", "timeout_ms"];
+        assert_eq!(Some("tcp"), closest("tcpp", candidates));
+        assert_eq!(Some("tcp"), closest("dcp", candidates));
+        assert_eq!(Some("timeout_ms"), closest("timeuot_ms", candidates));
+        assert_eq!(None, closest("sctp", candidates));
+        assert_eq!(None, closest("x", Vec::new()));
+        assert_eq!(None, closest("x", ["a"]));
+        assert_eq!(Some("xy"), closest("x", ["a", "xy"]));
+    }
+}
@@ -0,0 +1,137 @@
+//! Rendering of text templates with values from a document, e.g. to generate systemd units or
+//! nginx snippets from a configuration.
+//!
+//! A template is text with `{{path}}` placeholders, each replaced by the value at the dotted
+//! `path`, as [`Document::get`] finds it. Whitespace around the path is ignored, so `{{ a.b }}`
+//! is the same placeholder as `{{a.b}}`. A value is replaced by its scalar, without its children,
+//! and a nested list by its fuji representation, e.g. `[a,b]`.
+
+use crate::path::get;
+use crate::prelude::*;
+use crate::{print_value, Binding, Document, Path, PathError};
+use core::fmt;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum TemplateError {
+    /// The placeholder starting at the byte offset has no closing `}}`.
+    Unclosed { offset: usize },
+    /// The path of the placeholder starting at the byte offset is invalid or has no value.
+    Path { offset: usize, error: PathError },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Unclosed { offset } => {
+                write!(f, "unclosed placeholder at offset {}", offset)
+            }
+            TemplateError::Path { offset, error } => {
+                write!(f, "{} in placeholder at offset {}", error, offset)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TemplateError {}
+
+fn render_bindings(template: &str, bindings: &[Binding]) -> Result<String, TemplateError> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let offset = template.len() - rest.len() + start;
+        let end = match rest[start..].find("}}") {
+            Some(len) => start + len,
+            None => return Err(TemplateError::Unclosed { offset }),
+        };
+        out.push_str(&rest[..start]);
+        let path = rest[start + 2..end].trim();
+        let error = |error| TemplateError::Path { offset, error };
+        let parsed: Path = path.parse().map_err(error)?;
+        let value =
+            get(bindings, &parsed).ok_or_else(|| error(PathError::NotFound(path.to_string())))?;
+        match value.items() {
+            Some(_) => out.push_str(&print_value(value)),
+            None => out.push_str(&value.value),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Renders `template` with values from `data`. Paths start with the name of `data`, as those
+/// yielded by [`Binding::iter`], e.g. `{{server.port}}` for a binding named `server`.
+pub fn render(template: &str, data: &Binding) -> Result<String, TemplateError> {
+    render_bindings(template, core::slice::from_ref(data))
+}
+
+/// Renders `template` with values from `document`.
+pub fn render_document(template: &str, document: &Document) -> Result<String, TemplateError> {
+    render_bindings(template, &document.bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete};
+
+    #[test]
+    fn test_render() {
+        let document = parse_document_complete(
+            "service=api{port=8080 hosts=a,b user=\"www data\"}\nlist=[x,[y,z]]",
+        )
+        .unwrap();
+
+        let tests = [
+            ("plain", Ok("plain".to_string())),
+            (
+                "ExecStart=/bin/api --port={{service.port}}",
+                Ok("ExecStart=/bin/api --port=8080".to_string()),
+            ),
+            (
+                "{{ service }}:{{service.hosts[1]}} {{service.user}}",
+                Ok("api:b www data".to_string()),
+            ),
+            ("{{list[1]}}", Ok("[y,z]".to_string())),
+            ("a {b} }}", Ok("a {b} }}".to_string())),
+            (
+                "x {{service.missing}}",
+                Err(TemplateError::Path {
+                    offset: 2,
+                    error: PathError::NotFound("service.missing".to_string()),
+                }),
+            ),
+            (
+                "{{a..b}}",
+                Err(TemplateError::Path {
+                    offset: 0,
+                    error: PathError::Invalid("a..b".to_string()),
+                }),
+            ),
+            (
+                "{{service}} {{service",
+                Err(TemplateError::Unclosed { offset: 12 }),
+            ),
+        ];
+
+        for (template, expected) in tests.iter() {
+            assert_eq!(
+                *expected,
+                render_document(template, &document),
+                "{}",
+                template
+            );
+        }
+
+        let binding = parse_binding_complete("server=main{listen=80}").unwrap();
+        assert_eq!(
+            Ok("listen 80; # main".to_string()),
+            render("listen {{server.listen}}; # {{server}}", &binding)
+        );
+        assert_eq!(
+            "path `listen` not found in placeholder at offset 0",
+            render("{{listen}}", &binding).unwrap_err().to_string()
+        );
+    }
+}
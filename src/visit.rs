@@ -0,0 +1,163 @@
+//! Traversal of the tree of bindings and values.
+//!
+//! Implement [`Visit`] (or [`VisitMut`]) and override the hooks for the nodes of interest; the
+//! default hooks call the `walk_*` functions, which visit the children of a node in document
+//! order. An overriding hook calls the corresponding `walk_*` function itself to keep descending,
+//! or returns without doing so to skip the subtree:
+//!
+//! ```
+//! use flax::visit::{self, Visit};
+//! use flax::{parse_document_complete, Binding};
+//!
+//! struct Names(Vec<String>);
+//!
+//! impl Visit for Names {
+//!     fn visit_binding(&mut self, binding: &Binding) {
+//!         self.0.push(binding.name().to_string());
+//!         visit::walk_binding(self, binding);
+//!     }
+//! }
+//!
+//! let document = parse_document_complete("a=b{c=d} e=[f, [g{h=i}]]").unwrap();
+//! let mut names = Names(vec![]);
+//! names.visit_document(&document);
+//! assert_eq!(vec!["a", "c", "e", "h"], names.0);
+//! ```
+
+use crate::{Binding, Document, Value, ValueKind};
+
+/// A read-only visitor over a tree of bindings and values.
+pub trait Visit {
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document);
+    }
+
+    fn visit_binding(&mut self, binding: &Binding) {
+        walk_binding(self, binding);
+    }
+
+    /// Visits a value, including a nested list, whose items are visited as values in turn.
+    fn visit_value(&mut self, value: &Value) {
+        walk_value(self, value);
+    }
+}
+
+/// Visits the top-level bindings of `document`.
+pub fn walk_document<V: Visit + ?Sized>(visitor: &mut V, document: &Document) {
+    for binding in document.bindings.iter() {
+        visitor.visit_binding(binding);
+    }
+}
+
+/// Visits the values of `binding`.
+pub fn walk_binding<V: Visit + ?Sized>(visitor: &mut V, binding: &Binding) {
+    for value in binding.values.iter() {
+        visitor.visit_value(value);
+    }
+}
+
+/// Visits the items of `value` if it is a nested list, and then its child bindings.
+pub fn walk_value<V: Visit + ?Sized>(visitor: &mut V, value: &Value) {
+    if let ValueKind::List(items) = &value.kind {
+        for item in items.iter() {
+            visitor.visit_value(item);
+        }
+    }
+    for binding in value.children.iter() {
+        visitor.visit_binding(binding);
+    }
+}
+
+/// A visitor that may modify the tree of bindings and values in place.
+pub trait VisitMut {
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document);
+    }
+
+    fn visit_binding_mut(&mut self, binding: &mut Binding) {
+        walk_binding_mut(self, binding);
+    }
+
+    /// Visits a value, including a nested list, whose items are visited as values in turn.
+    fn visit_value_mut(&mut self, value: &mut Value) {
+        walk_value_mut(self, value);
+    }
+}
+
+/// Visits the top-level bindings of `document`.
+pub fn walk_document_mut<V: VisitMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    for binding in document.bindings_mut().iter_mut() {
+        visitor.visit_binding_mut(binding);
+    }
+}
+
+/// Visits the values of `binding`.
+pub fn walk_binding_mut<V: VisitMut + ?Sized>(visitor: &mut V, binding: &mut Binding) {
+    for value in binding.values.iter_mut() {
+        visitor.visit_value_mut(value);
+    }
+}
+
+/// Visits the items of `value` if it is a nested list, and then its child bindings.
+pub fn walk_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, value: &mut Value) {
+    if let ValueKind::List(items) = &mut value.kind {
+        for item in items.iter_mut() {
+            visitor.visit_value_mut(item);
+        }
+    }
+    for binding in value.children.iter_mut() {
+        visitor.visit_binding_mut(binding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_binding_complete, parse_document_complete, print_document};
+
+    /// Counts values, without descending into the children of bindings named `skip`.
+    struct Count(usize);
+
+    impl Visit for Count {
+        fn visit_binding(&mut self, binding: &Binding) {
+            if binding.name() != "skip" {
+                walk_binding(self, binding);
+            }
+        }
+
+        fn visit_value(&mut self, value: &Value) {
+            self.0 += 1;
+            walk_value(self, value);
+        }
+    }
+
+    struct Upper;
+
+    impl VisitMut for Upper {
+        fn visit_value_mut(&mut self, value: &mut Value) {
+            value.value = value.value.to_uppercase();
+            walk_value_mut(self, value);
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let document = parse_document_complete("a=b,c{d=e} f=[g, [h]] skip=i{j=k}").unwrap();
+        let mut count = Count(0);
+        count.visit_document(&document);
+        // `b`, `c`, `e`, `g`, the nested list `[h]` and its item `h`.
+        assert_eq!(6, count.0);
+
+        let binding = parse_binding_complete("a={b=c}").unwrap();
+        let mut count = Count(0);
+        count.visit_binding(&binding);
+        assert_eq!(2, count.0);
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let mut document = parse_document_complete("a=b,c{d=e} f=[g, [h]] i={j=k}").unwrap();
+        Upper.visit_document_mut(&mut document);
+        assert_eq!("a=B,C{d=E}\nf=[G,[H]]\ni={j=K}", print_document(&document));
+    }
+}
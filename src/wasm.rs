@@ -0,0 +1,148 @@
+//! JavaScript bindings, built with `wasm-bindgen`.
+//!
+//! Build with
+//! `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and run `wasm-bindgen` on the output. Documents are exchanged as the JSON mapping of
+//! [`crate::convert::json`], with references expanded; errors are thrown as objects with a `message` and, where available,
+//! the `line`, `column` and `offset` of the offending input.
+
+use crate::convert::json::{document_from_json, document_to_json};
+use crate::convert::ConvertError;
+use crate::{cst, line_column, parse_document_complete, print_document, ParseError, Schema};
+use serde_json::{json, Value as Json};
+use wasm_bindgen::prelude::*;
+
+fn parse_error(e: &ParseError) -> Json {
+    json!({
+        "message": e.to_string(),
+        "expected": e.expected,
+        "offset": e.offset,
+        "line": e.line,
+        "column": e.column,
+    })
+}
+
+fn convert_error(e: &ConvertError) -> Json {
+    json!({ "message": e.to_string(), "path": e.path })
+}
+
+fn parse_json(input: &str) -> Result<Json, Json> {
+    let mut document = parse_document_complete(input).map_err(|e| parse_error(&e))?;
+    document
+        .expand_references()
+        .map_err(|e| json!({ "message": e.to_string() }))?;
+    document_to_json(&document).map_err(|e| convert_error(&e))
+}
+
+fn print_json(document: &Json) -> Result<String, Json> {
+    document_from_json(document)
+        .map(|document| print_document(&document))
+        .map_err(|e| convert_error(&e))
+}
+
+/// Returns the violations of `schema` by `input`, each with its location in `input`.
+fn validate_json(schema: &str, input: &str) -> Result<Json, Json> {
+    let schema = parse_document_complete(schema).map_err(|e| parse_error(&e))?;
+    let schema = Schema::from_document(&schema).map_err(|e| json!({ "message": e.message }))?;
+    let document = cst::parse_document(input).map_err(|e| parse_error(&e))?;
+    let errors = schema
+        .validate(&document.to_ast())
+        .iter()
+        .map(|e| {
+            let offset = document.offset(&e.address).unwrap_or(0);
+            let (line, column) = line_column(input, offset);
+            json!({
+                "message": e.message,
+                "severity": e.severity.to_string(),
+                "path": e.path,
+                "offset": offset,
+                "line": line,
+                "column": column,
+            })
+        })
+        .collect();
+    Ok(Json::Array(errors))
+}
+
+fn to_js(json: &Json) -> JsValue {
+    js_sys::JSON::parse(&json.to_string()).unwrap_or(JsValue::NULL)
+}
+
+fn from_js(value: &JsValue) -> Result<Json, JsValue> {
+    let text: String = js_sys::JSON::stringify(value)?.into();
+    serde_json::from_str(&text).map_err(|e| to_js(&json!({ "message": e.to_string() })))
+}
+
+/// Parses a fuji document into its JSON mapping.
+#[wasm_bindgen]
+pub fn parse(input: &str) -> Result<JsValue, JsValue> {
+    parse_json(input)
+        .map(|json| to_js(&json))
+        .map_err(|e| to_js(&e))
+}
+
+/// Prints the JSON mapping of a document as fuji.
+#[wasm_bindgen]
+pub fn print(document: &JsValue) -> Result<String, JsValue> {
+    print_json(&from_js(document)?).map_err(|e| to_js(&e))
+}
+
+/// Validates a fuji document against a fuji schema, returning an array of violations.
+#[wasm_bindgen]
+pub fn validate(schema: &str, input: &str) -> Result<JsValue, JsValue> {
+    validate_json(schema, input)
+        .map(|json| to_js(&json))
+        .map_err(|e| to_js(&e))
+}
+
+/// Converts a fuji document to JSON text.
+#[wasm_bindgen(js_name = toJson)]
+pub fn to_json(input: &str) -> Result<String, JsValue> {
+    parse_json(input)
+        .map(|json| json.to_string())
+        .map_err(|e| to_js(&e))
+}
+
+/// Converts JSON text to a fuji document.
+#[wasm_bindgen(js_name = fromJson)]
+pub fn from_json(json: &str) -> Result<String, JsValue> {
+    let json =
+        serde_json::from_str(json).map_err(|e| to_js(&json!({ "message": e.to_string() })))?;
+    print_json(&json).map_err(|e| to_js(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_helpers() {
+        let json = parse_json("a=b{c=d}").unwrap();
+        assert_eq!(json!([{"a": {"b": [{"c": "d"}]}}]), json);
+        assert_eq!(Ok("a=b{c=d}".to_string()), print_json(&json));
+
+        let error = parse_json("a=b\nc").unwrap_err();
+        assert_eq!(json!(2), error["line"]);
+        assert_eq!(json!(2), error["column"]);
+
+        let errors = validate_json("field=a{type=bool}", "x=y\na=maybe").unwrap();
+        assert_eq!(
+            json!([{
+                "message": "unknown field",
+                "severity": "warning",
+                "path": "x",
+                "offset": 0,
+                "line": 1,
+                "column": 1,
+            }, {
+                "message": "invalid value `maybe`, expected bool",
+                "severity": "error",
+                "path": "a",
+                "offset": 6,
+                "line": 2,
+                "column": 3,
+            }]),
+            errors
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Reloading of configuration files when they change, so that long-running services can pick up
+//! new configuration without restarting.
+//!
+//! A [`Watcher`] loads a document with [`include::load`], then polls the modification times and
+//! sizes of the document and of every file it includes. When any of them changes, it reloads and
+//! validates the document, and delivers it with its [`diff_documents`] from the previous one,
+//! or the error preventing the reload, in which case the previous document stays current.
+//!
+//! ```no_run
+//! use flax::watch::Watcher;
+//! use std::time::Duration;
+//!
+//! let (config, _handle, reloads) = Watcher::new("server.fuji")
+//!     .interval(Duration::from_secs(1))
+//!     .channel()
+//!     .unwrap();
+//! println!("loaded {}", config);
+//! for reload in reloads {
+//!     match reload {
+//!         Ok(reload) => println!("{} changes", reload.diff.len()),
+//!         Err(e) => eprintln!("keeping the previous configuration: {}", e),
+//!     }
+//! }
+//! ```
+
+use crate::include::{self, FileResolver, LoadError, Resolver, Source};
+use crate::{diff_documents, DiffEntry, Document, Schema, Severity, ValidationError};
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+/// A document that could not be reloaded.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum WatchError {
+    Load(LoadError),
+    /// The document violates the schema of the watcher; only errors are reported.
+    Invalid(Vec<ValidationError>),
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchError::Load(e) => write!(f, "{}", e),
+            WatchError::Invalid(errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", errors.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+/// A document reloaded after a change.
+#[derive(Debug, Clone)]
+pub struct Reload {
+    pub document: Document,
+    /// The differences from the previous document.
+    pub diff: Vec<DiffEntry>,
+    /// The warnings of the schema of the watcher, if any.
+    pub warnings: Vec<ValidationError>,
+}
+
+/// The receiving end of the reloads of [`Watcher::channel`].
+pub type Reloads = Receiver<Result<Reload, WatchError>>;
+
+/// A builder for a background thread watching a document and the files it includes.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    path: String,
+    interval: Duration,
+    schema: Option<Schema>,
+}
+
+/// Stops the thread of a [`Watcher`] when dropped.
+#[derive(Debug)]
+pub struct WatchHandle {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Stops watching, waiting for the thread to finish delivering any reload in progress.
+    pub fn stop(self) {}
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Resolves files as [`FileResolver`] does, recording their names.
+#[derive(Default)]
+struct Recorder {
+    files: RefCell<Vec<String>>,
+}
+
+impl Resolver for Recorder {
+    fn resolve(&self, path: &str, from: Option<&str>) -> Result<Source, String> {
+        let source = FileResolver.resolve(path, from)?;
+        self.files.borrow_mut().push(source.name.clone());
+        Ok(source)
+    }
+}
+
+/// The modification times and sizes of the watched files, `None` for those that are missing.
+type Snapshot = Vec<Option<(SystemTime, u64)>>;
+
+fn snapshot(files: &[String]) -> Snapshot {
+    files
+        .iter()
+        .map(|file| {
+            let metadata = std::fs::metadata(file).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// The current document of a watcher, with the files it was loaded from.
+struct State {
+    path: String,
+    schema: Option<Schema>,
+    files: Vec<String>,
+    snapshot: Snapshot,
+}
+
+impl State {
+    /// Loads and validates the document, adding the files it reads to the watched ones, and
+    /// returns it with its warnings.
+    fn load(&mut self) -> Result<(Document, Vec<ValidationError>), WatchError> {
+        let recorder = Recorder::default();
+        let loaded = include::load(&self.path, &recorder);
+        let files = recorder.files.into_inner();
+        // A document that fails to load is still watched through the files read so far, along
+        // with those it was previously loaded from, so that fixing any of them reloads it.
+        match loaded {
+            Ok(_) => self.files = files,
+            Err(_) => {
+                for file in files {
+                    if !self.files.contains(&file) {
+                        self.files.push(file);
+                    }
+                }
+            }
+        }
+        if self.files.is_empty() {
+            self.files.push(self.path.clone());
+        }
+        self.snapshot = snapshot(&self.files);
+
+        let document = loaded.map_err(WatchError::Load)?;
+        let (errors, warnings) = match &self.schema {
+            Some(schema) => schema
+                .validate(&document)
+                .into_iter()
+                .partition(|e| e.severity == Severity::Error),
+            None => (vec![], vec![]),
+        };
+        if !errors.is_empty() {
+            return Err(WatchError::Invalid(errors));
+        }
+        Ok((document, warnings))
+    }
+}
+
+impl Watcher {
+    /// Watches the document at `path`, every second by default.
+    pub fn new(path: impl Into<String>) -> Self {
+        Watcher {
+            path: path.into(),
+            interval: Duration::from_secs(1),
+            schema: None,
+        }
+    }
+
+    /// Sets how often the files are checked for changes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Validates each reloaded document against `schema`, rejecting those with errors.
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Loads the document and starts watching it, calling `callback` from a background thread
+    /// with each reload, or with the error preventing it. Changes that leave the document
+    /// unchanged, such as a new comment, are not reported.
+    ///
+    /// Returns the initial document and the handle of the thread, or the error preventing the
+    /// initial load, in which case nothing is watched.
+    pub fn spawn<F>(self, mut callback: F) -> Result<(Document, WatchHandle), WatchError>
+    where
+        F: FnMut(Result<Reload, WatchError>) + Send + 'static,
+    {
+        self.start(move |reload| {
+            callback(reload);
+            true
+        })
+    }
+
+    /// Loads the document and starts watching it, as [`Watcher::spawn`] does, delivering reloads
+    /// on a channel instead. Watching stops when the receiver is dropped, or the handle.
+    pub fn channel(self) -> Result<(Document, WatchHandle, Reloads), WatchError> {
+        let (sender, receiver) = mpsc::channel();
+        let (document, handle) = self.start(move |reload| sender.send(reload).is_ok())?;
+        Ok((document, handle, receiver))
+    }
+
+    /// Starts watching, delivering reloads with `deliver` until it returns `false`.
+    fn start<F>(self, mut deliver: F) -> Result<(Document, WatchHandle), WatchError>
+    where
+        F: FnMut(Result<Reload, WatchError>) -> bool + Send + 'static,
+    {
+        let mut state = State {
+            path: self.path,
+            schema: self.schema,
+            files: vec![],
+            snapshot: vec![],
+        };
+        let (document, _) = state.load()?;
+        let mut current = document.clone();
+        let interval = self.interval;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if snapshot(&state.files) == state.snapshot {
+                    continue;
+                }
+                let reload = match state.load() {
+                    Ok((document, _)) if document == current => continue,
+                    Ok((document, warnings)) => {
+                        let diff = diff_documents(&current, &document);
+                        current = document.clone();
+                        Ok(Reload {
+                            document,
+                            diff,
+                            warnings,
+                        })
+                    }
+                    Err(e) => Err(e),
+                };
+                if !deliver(reload) {
+                    return;
+                }
+            }
+        });
+        let handle = WatchHandle {
+            stop: Some(stop),
+            thread: Some(thread),
+        };
+        Ok((document, handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+    use std::path::PathBuf;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flax-watch-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir = dir("channel");
+        let main = dir.join("main.fuji");
+        let common = dir.join("common.fuji");
+        std::fs::write(&main, "name=a\ninclude \"common.fuji\"").unwrap();
+        std::fs::write(&common, "port=80").unwrap();
+        let schema = Schema::from_document(
+            &parse_document_complete("field=name{type=string} field=port{type=string}").unwrap(),
+        )
+        .unwrap();
+
+        let (document, handle, reloads) = Watcher::new(main.to_str().unwrap())
+            .interval(Duration::from_millis(10))
+            .schema(schema)
+            .channel()
+            .unwrap();
+        assert_eq!("name=a\nport=80", document.to_string());
+
+        std::fs::write(&common, "port=8080").unwrap();
+        let reload = reloads.recv_timeout(TIMEOUT).unwrap().unwrap();
+        assert_eq!("name=a\nport=8080", reload.document.to_string());
+        let paths = reload
+            .diff
+            .iter()
+            .map(|e| e.path().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["port"], paths);
+
+        std::fs::write(&common, "port=8080\nextra=x").unwrap();
+        let reload = reloads.recv_timeout(TIMEOUT).unwrap().unwrap();
+        assert_eq!(1, reload.warnings.len());
+
+        std::fs::write(&common, "port={").unwrap();
+        match reloads.recv_timeout(TIMEOUT).unwrap() {
+            Err(WatchError::Load(e)) => assert!(e.name.ends_with("common.fuji"), "{}", e),
+            reload => panic!("unexpected reload {:?}", reload),
+        }
+
+        std::fs::write(&common, "port=1,2").unwrap();
+        match reloads.recv_timeout(TIMEOUT).unwrap() {
+            Err(WatchError::Invalid(errors)) => {
+                assert_eq!(
+                    "port: expected a single value, found 2",
+                    errors[0].to_string()
+                )
+            }
+            reload => panic!("unexpected reload {:?}", reload),
+        }
+
+        std::fs::write(&common, "port=443").unwrap();
+        let reload = reloads.recv_timeout(TIMEOUT).unwrap().unwrap();
+        assert_eq!(Some("443"), reload.document.get("port").map(|v| v.value()));
+
+        handle.stop();
+        std::fs::write(&common, "port=1").unwrap();
+        assert!(reloads.recv_timeout(Duration::from_millis(100)).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spawn() {
+        let dir = dir("spawn");
+        let main = dir.join("main.fuji");
+        assert!(matches!(
+            Watcher::new(main.to_str().unwrap()).spawn(|_| {}),
+            Err(WatchError::Load(_))
+        ));
+
+        std::fs::write(&main, "a=b").unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let (_, handle) = Watcher::new(main.to_str().unwrap())
+            .interval(Duration::from_millis(10))
+            .spawn(move |reload| sender.send(reload.unwrap().document).unwrap())
+            .unwrap();
+        std::fs::write(&main, "a=c").unwrap();
+        assert_eq!("a=c", receiver.recv_timeout(TIMEOUT).unwrap().to_string());
+        drop(handle);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+//! Printing that fits a maximum line width, keeping short bindings on one line.
+
+use crate::prelude::*;
+use crate::{print_binding, print_value, quote, Binding, Document, Value};
+
+/// Number of spaces per nesting level.
+const INDENT: usize = 2;
+
+fn indent(out: &mut String, level: usize) {
+    out.push('\n');
+    out.push_str(&" ".repeat(level * INDENT));
+}
+
+/// Whether `text`, starting at the indentation of `level`, fits within `width` columns.
+fn fits(text: &str, level: usize, width: usize) -> bool {
+    level * INDENT + text.chars().count() <= width
+}
+
+fn binding(out: &mut String, binding: &Binding, level: usize, width: usize) {
+    let compact = print_binding(binding);
+    if fits(&compact, level, width) {
+        out.push_str(&compact);
+        return;
+    }
+    out.push_str(&quote::key(&binding.name));
+    out.push('=');
+    match binding.values.as_slice() {
+        [] => {}
+        [single] if single.items().is_none() => value(out, single, level, width),
+        values => list(out, values, level, width),
+    }
+}
+
+/// Prints a bracketed list with each item on its own line.
+fn list(out: &mut String, items: &[Value], level: usize, width: usize) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        indent(out, level + 1);
+        let compact = print_value(item);
+        if fits(&compact, level + 1, width) {
+            out.push_str(&compact);
+        } else {
+            value(out, item, level + 1, width);
+        }
+    }
+    indent(out, level);
+    out.push(']');
+}
+
+/// Prints a value whose binding does not fit on one line, with each child binding on its own line.
+fn value(out: &mut String, value: &Value, level: usize, width: usize) {
+    if let Some(items) = value.items() {
+        return list(out, items, level, width);
+    }
+    if !value.is_map() {
+        out.push_str(&quote::value(value));
+    }
+    if value.children.is_empty() {
+        if value.is_map() {
+            out.push_str("{}");
+        }
+        return;
+    }
+    out.push('{');
+    for child in value.children.iter() {
+        indent(out, level + 1);
+        binding(out, child, level + 1, width);
+    }
+    indent(out, level);
+    out.push('}');
+}
+
+/// Prints a binding on one line if it fits within `width` columns, or else with each child binding
+/// on its own, indented line, deciding again for each of them. Several values are then bracketed,
+/// with each value on its own line. Lines may still exceed `width` if a single scalar does.
+pub fn print_binding_width(b: &Binding, width: usize) -> String {
+    let mut out = String::new();
+    binding(&mut out, b, 0, width);
+    out
+}
+
+/// Prints each top-level binding of a document on its own line, as `print_binding_width` does.
+pub fn print_document_width(document: &Document, width: usize) -> String {
+    document
+        .bindings
+        .iter()
+        .map(|b| print_binding_width(b, width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_document_complete;
+    #[cfg(feature = "arbitrary")]
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+
+    #[test]
+    fn test_width() {
+        let input = "short=a{b=c}\n\
+                     server=main{host=example.com port=8080 tls={cert=cert.pem key=k}}\n\
+                     hosts=[alpha, beta{x=1}, gamma{name=a-rather-long-name}]\n\
+                     a=x{y=z},w{v=u}";
+        let document = parse_document_complete(input).unwrap();
+
+        let expected = "short=a{b=c}\n\
+                        server=main{\n  \
+                          host=example.com\n  \
+                          port=8080\n  \
+                          tls={cert=cert.pem key=k}\n\
+                        }\n\
+                        hosts=[\n  \
+                          alpha,\n  \
+                          beta{x=1},\n  \
+                          gamma{name=a-rather-long-name}\n\
+                        ]\n\
+                        a=x{y=z},w{v=u}";
+        let printed = print_document_width(&document, 32);
+        assert_eq!(expected, printed);
+        assert_eq!(Ok(document.clone()), parse_document_complete(&printed));
+
+        let printed = print_document_width(&document, 16);
+        assert!(printed.contains("tls={\n    cert=cert.pem\n    key=k\n  }"));
+        assert!(printed.contains("  gamma{\n    name=a-rather-long-name\n  }"));
+        assert_eq!(Ok(document.clone()), parse_document_complete(&printed));
+
+        assert_eq!(
+            crate::print_document(&document),
+            print_document_width(&document, 100)
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest! {
+        #[test]
+        fn test_width_round_trip(document in any::<Document>(), width in 0..40usize) {
+            let printed = print_document_width(&document, width);
+            prop_assert_eq!(Ok(document), parse_document_complete(&printed), "{}", printed);
+        }
+    }
+}
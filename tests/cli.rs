@@ -0,0 +1,342 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn fuji(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fuji"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The command may exit without reading its input.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_fmt() {
+    let output = fuji(&["fmt"], "a=b   c=d{ e=f # keep\n}");
+    assert!(output.status.success());
+    assert_eq!(
+        "a=b\nc=d{e=f # keep\n}\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["fmt", "-"], "a=b{");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("-:1:5: expected '}'"));
+
+    let input = "a=b,c , d=e, f=g{h=i,j,}";
+    let output = fuji(&["fmt"], input);
+    assert_eq!(
+        "a=b,c,\nd=e,\nf=g{h=i,j,}\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = fuji(&["fmt", "--trailing-commas"], input);
+    assert_eq!(
+        "a=b,c,\nd=e\nf=g{h=i,j,}\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = fuji(&["fmt", "--no-trailing-commas"], input);
+    assert_eq!(
+        "a=b,c\nd=e\nf=g{h=i,j}\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn test_fmt_write() {
+    let path = std::env::temp_dir().join(format!("fuji-fmt-{}.fuji", std::process::id()));
+    std::fs::write(&path, "a=b ,c").unwrap();
+    let output = fuji(&["fmt", "-w", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!("a=b,c\n", std::fs::read_to_string(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_unknown_option() {
+    for (args, option) in [
+        (&["fmt", "--wirte"][..], "--wirte"),
+        (
+            &["validate", "--schema", "schema.fuji", "--strcit"],
+            "--strcit",
+        ),
+        (&["lint", "-a", "empty-blocks"], "-a"),
+        (&["doc", "--htm", "schema.fuji"], "--htm"),
+        (&["convert", "--too", "json"], "--too"),
+        (&["get", "--jsno", "a"], "--jsno"),
+        (&["query", "--path", ".a"], "--path"),
+    ] {
+        let output = fuji(args, "a=b");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let expected = format!("{}: unknown option `{}`\n\nusage: fuji", args[0], option);
+        assert!(stderr.starts_with(&expected), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_validate() {
+    let schema = std::env::temp_dir().join(format!("fuji-schema-{}.fuji", std::process::id()));
+    std::fs::write(
+        &schema,
+        "field=name{type=string}\nfield=server{type=struct{field=tls{type=bool}}}\n",
+    )
+    .unwrap();
+    let schema = schema.to_str().unwrap();
+
+    let output = fuji(
+        &["validate", "--schema", schema],
+        "name=a server=s{tls=true}",
+    );
+    assert!(output.status.success());
+
+    let output = fuji(
+        &["validate", "--schema", schema, "-"],
+        "name=a,b\nserver=s{\n  tls=maybe\n}\n",
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "-:1:1: name: expected a single value, found 2",
+            "-:3:7: server.tls: invalid value `maybe`, expected bool",
+            "validate: some files are not valid",
+        ],
+        lines
+    );
+
+    let output = fuji(
+        &["validate", "--schema", schema],
+        "name=a\nserver=s{tls=true port=80}",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        "-:2:19: warning: server.port: unknown field\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = fuji(&["validate", "--schema", schema, "--strict"], "x=y");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "-:1:1: name: missing required field",
+            "-:1:1: server: missing required field",
+            "-:1:1: x: unknown field",
+            "validate: some files are not valid",
+        ],
+        lines
+    );
+
+    let output = fuji(
+        &["validate", "--schema", schema, "--strict", "--ignore-case"],
+        "Name=a SERVER=s{TLS=true}",
+    );
+    assert!(output.status.success());
+
+    std::fs::write(schema, "field=name{type=text}").unwrap();
+    let output = fuji(&["validate", "--schema", schema], "name=a");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(":1:17: unknown type `text`\n"));
+    std::fs::remove_file(schema).unwrap();
+}
+
+#[test]
+fn test_lint() {
+    let output = fuji(&["lint"], "port=80\nPort=\"8080\" tls={}");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        vec![
+            "-:1:6: warning: unquoted `80` looks like a number but is a string; quote it as \
+             `\"80\"` [unquoted-literals]",
+            "-:2:1: warning: key `Port` differs only in case from `port` [case-collisions]",
+            "-:2:17: warning: empty block [empty-blocks]",
+        ],
+        stderr.lines().collect::<Vec<_>>()
+    );
+
+    let output = fuji(&["lint", "--strict", "-A", "unquoted-literals"], "port=80");
+    assert!(output.status.success());
+    let output = fuji(&["lint", "--strict"], "port=80");
+    assert!(!output.status.success());
+
+    let output = fuji(&["lint"], "a=b\na=c");
+    assert!(!output.status.success());
+    assert_eq!(
+        "-:2:1: duplicate key `a` [duplicate-keys]\nlint: some files have issues\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = fuji(&["lint", "--allow", "typos"], "a=b");
+    assert!(!output.status.success());
+    assert_eq!(
+        "lint: unknown rule `typos`\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_doc() {
+    let schema = std::env::temp_dir().join(format!("fuji-doc-{}.fuji", std::process::id()));
+    std::fs::write(
+        &schema,
+        "## The port to listen on.\nfield=port{type=string default=80}",
+    )
+    .unwrap();
+    let schema = schema.to_str().unwrap();
+    let output = fuji(&["doc", "--title", "Server", schema], "");
+    assert!(output.status.success());
+    assert_eq!(
+        "# Server\n\n## `port`\n\nThe port to listen on.\n\n- Type: `string`\n- Default: `80`\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["doc", "--html", schema], "");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("<p>The port to listen on.</p>"));
+
+    std::fs::write(schema, "field=port{type=strin}").unwrap();
+    let output = fuji(&["doc", schema], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(":1:17: unknown type `strin`\n"));
+    std::fs::remove_file(schema).unwrap();
+}
+
+#[test]
+fn test_convert() {
+    let output = fuji(
+        &["convert", "--from", "textproto", "--to", "fuji"],
+        "a: 1 b { c: \"d\" }",
+    );
+    assert!(output.status.success());
+    assert_eq!("a=1\nb=b{c=d}\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = fuji(&["convert", "--to", "csv"], "a=b{c=d,e}");
+    assert!(output.status.success());
+    assert_eq!(
+        "path,value\na,b\na.c,d\na.c,e\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["convert", "--from", "tsv"], "path\tvalue\n");
+    assert!(!output.status.success());
+    assert_eq!(
+        "-: tsv can only be converted to\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = fuji(&["convert", "--to", "ini"], "a=b");
+    assert!(!output.status.success());
+    assert_eq!(
+        "convert: unknown format `ini`\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_convert_json() {
+    let output = fuji(&["convert", "--from", "fuji", "--to", "json"], "a=b{c=d}");
+    assert!(output.status.success());
+    let json = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    let output = fuji(&["convert", "--from", "json"], &json);
+    assert!(output.status.success());
+    assert_eq!("a=b{c=d}\n", String::from_utf8_lossy(&output.stdout));
+
+    // References are expanded, as JSON has no anchors.
+    let output = fuji(&["convert", "--to", "json"], "a=&x v b=*x");
+    assert!(output.status.success());
+    let json = String::from_utf8_lossy(&output.stdout).into_owned();
+    let output = fuji(&["convert", "--from", "json"], &json);
+    assert_eq!("a=v\nb=v\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = fuji(&["convert", "--to", "json"], "a=*x");
+    assert!(!output.status.success());
+    assert_eq!(
+        "-: undefined anchor `x`\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn test_convert_xml() {
+    let output = fuji(&["convert", "--to", "xml"], "a=b{c=d}");
+    assert!(output.status.success());
+    assert_eq!(
+        "<fuji><a>b<c>d</c></a></fuji>\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["convert", "--from", "xml"], "<config><a>b</a></config>");
+    assert!(output.status.success());
+    assert_eq!("a=b\n", String::from_utf8_lossy(&output.stdout));
+
+    let input = format!("<fuji>{}{}</fuji>", "<a>".repeat(5000), "</a>".repeat(5000));
+    let output = fuji(&["convert", "--from", "xml"], &input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("elements nested more than 64 deep"));
+}
+
+#[test]
+fn test_get() {
+    let input = "server=s{ports=80,443 host=h{zone=a}}";
+    let output = fuji(&["get", "server.ports"], input);
+    assert!(output.status.success());
+    assert_eq!("80\n443\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = fuji(&["get", "server.host", "-"], input);
+    assert_eq!("h{zone=a}\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = fuji(&["get", "--raw", "server.host"], input);
+    assert_eq!("h\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = fuji(&["get", "server.missing"], input);
+    assert!(!output.status.success());
+    assert_eq!(
+        "get: no values match `server.missing`\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_query() {
+    let input = "users=[alice{role=admin}, bob{role=dev}, carol{role=admin}]";
+    let output = fuji(&["query", ".users | select(role=admin)"], input);
+    assert!(output.status.success());
+    assert_eq!(
+        "alice{role=admin}\ncarol{role=admin}\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["query", "--paths", "..role", "-"], input);
+    assert_eq!(
+        "users[0].role\tadmin\nusers[1].role\tdev\nusers[2].role\tadmin\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = fuji(&["query", ".users |"], input);
+    assert!(!output.status.success());
+    assert_eq!(
+        "query:1:9: expected query stage, found end of input\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_get_json() {
+    let output = fuji(&["get", "--json", "a"], "a=b{c=d},e");
+    assert!(output.status.success());
+    assert_eq!(
+        "{\"b\":[{\"c\":\"d\"}]}\n\"e\"\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
@@ -0,0 +1,78 @@
+use flax::{codegen, parse_document_complete, print_document, FujiSerialize, Schema};
+
+mod config {
+    include!("codegen/config.rs");
+}
+
+use config::{Backend, BackendServer, Config, Mode, Server};
+
+fn schema() -> Schema {
+    Schema::from_document(&parse_document_complete(include_str!("codegen/config.fuji")).unwrap())
+        .unwrap()
+}
+
+#[test]
+fn test_generated_up_to_date() {
+    // Regenerate with `codegen::generate(&schema, "Config")` after changing the generator.
+    assert_eq!(
+        include_str!("codegen/config.rs"),
+        codegen::generate(&schema(), "Config")
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let schema = schema();
+    let input = "name=web server={host=localhost type=a,b} \
+                 mode=unix-socket{path=sock} backend={server={name=x}}";
+    let mut document = parse_document_complete(input).unwrap();
+    assert_eq!(
+        Vec::<flax::ValidationError>::new(),
+        schema.validate(&document)
+    );
+    schema.apply_defaults_document(&mut document);
+
+    let config = Config::from_document(&document).unwrap();
+    assert_eq!(
+        Config {
+            name: "web".to_string(),
+            debug: false,
+            server: Server {
+                host: "localhost".to_string(),
+                port: None,
+                r#type: vec!["a".to_string(), "b".to_string()],
+            },
+            mode: Mode::UnixSocket {
+                path: "sock".to_string(),
+            },
+            backend: vec![Backend {
+                server: BackendServer {
+                    name: "x".to_string(),
+                },
+            }],
+        },
+        config
+    );
+    assert_eq!(
+        "name=web\ndebug=false\nserver={host=localhost type=a,b}\nmode=unix-socket{path=sock}\nbackend={server={name=x}}",
+        print_document(&config.to_document())
+    );
+    assert_eq!(
+        Ok(config.clone()),
+        Config::from_document(&config.to_document())
+    );
+
+    let tcp = Mode::Tcp { nodelay: true };
+    assert_eq!("tcp{nodelay=true}", flax::print_value(&tcp.to_value()));
+    let udp = parse_document_complete("a=udp").unwrap();
+    assert_eq!(
+        Ok(Mode::Udp),
+        flax::FujiDeserialize::from_binding(&udp.bindings()[0])
+    );
+
+    let missing = parse_document_complete("name=web mode=udp").unwrap();
+    assert_eq!(
+        Err(flax::DeserializeError::MissingField("debug".to_string())),
+        Config::from_document(&missing)
+    );
+}
@@ -0,0 +1,160 @@
+// @generated by flax::codegen. Do not edit.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub name: String,
+    pub debug: bool,
+    pub server: Server,
+    pub mode: Mode,
+    pub backend: Vec<Backend>,
+}
+
+impl ::flax::FujiSerialize for Config {
+    fn to_value(&self) -> ::flax::Value {
+        let mut children = ::flax::__private::Vec::new();
+        ::flax::__private::push(&mut children, "name", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.name)]);
+        ::flax::__private::push(&mut children, "debug", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.debug)]);
+        ::flax::__private::push(&mut children, "server", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.server)]);
+        ::flax::__private::push(&mut children, "mode", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.mode)]);
+        ::flax::__private::push(&mut children, "backend", self.backend.iter().map(::flax::FujiSerialize::to_value).collect());
+        ::flax::Value::map(children)
+    }
+}
+
+impl ::flax::FujiDeserialize for Config {
+    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+        Ok(Config {
+            name: ::flax::__private::required("name", &::flax::__private::values(value, "name"))?,
+            debug: ::flax::__private::required("debug", &::flax::__private::values(value, "debug"))?,
+            server: ::flax::__private::required("server", &::flax::__private::values(value, "server"))?,
+            mode: ::flax::__private::required("mode", &::flax::__private::values(value, "mode"))?,
+            backend: ::flax::__private::repeated(&::flax::__private::values(value, "backend"))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Server {
+    pub host: String,
+    pub port: Option<String>,
+    pub r#type: Vec<String>,
+}
+
+impl ::flax::FujiSerialize for Server {
+    fn to_value(&self) -> ::flax::Value {
+        let mut children = ::flax::__private::Vec::new();
+        ::flax::__private::push(&mut children, "host", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.host)]);
+        ::flax::__private::push(&mut children, "port", self.port.iter().map(::flax::FujiSerialize::to_value).collect());
+        ::flax::__private::push(&mut children, "type", self.r#type.iter().map(::flax::FujiSerialize::to_value).collect());
+        ::flax::Value::map(children)
+    }
+}
+
+impl ::flax::FujiDeserialize for Server {
+    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+        Ok(Server {
+            host: ::flax::__private::required("host", &::flax::__private::values(value, "host"))?,
+            port: ::flax::__private::optional("port", &::flax::__private::values(value, "port"))?,
+            r#type: ::flax::__private::repeated(&::flax::__private::values(value, "type"))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Tcp {
+        nodelay: bool,
+    },
+    Udp,
+    UnixSocket {
+        path: String,
+    },
+}
+
+impl ::flax::FujiSerialize for Mode {
+    fn to_value(&self) -> ::flax::Value {
+        match self {
+            Mode::Tcp { nodelay } => {
+                let mut children = ::flax::__private::Vec::new();
+                ::flax::__private::push(&mut children, "nodelay", ::flax::__private::vec![::flax::FujiSerialize::to_value(nodelay)]);
+                ::flax::__private::value("tcp", children)
+            }
+            Mode::Udp => ::flax::__private::value("udp", ::flax::__private::Vec::new()),
+            Mode::UnixSocket { path } => {
+                let mut children = ::flax::__private::Vec::new();
+                ::flax::__private::push(&mut children, "path", ::flax::__private::vec![::flax::FujiSerialize::to_value(path)]);
+                ::flax::__private::value("unix-socket", children)
+            }
+        }
+    }
+}
+
+impl ::flax::FujiDeserialize for Mode {
+    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+        match ::flax::__private::tag(value) {
+            "tcp" => Ok(Mode::Tcp {
+                nodelay: ::flax::__private::required("nodelay", &::flax::__private::values(value, "nodelay"))?,
+            }),
+            "udp" => Ok(Mode::Udp),
+            "unix-socket" => Ok(Mode::UnixSocket {
+                path: ::flax::__private::required("path", &::flax::__private::values(value, "path"))?,
+            }),
+            tag => Err(::flax::DeserializeError::UnknownVariant(tag.into())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backend {
+    pub server: BackendServer,
+}
+
+impl ::flax::FujiSerialize for Backend {
+    fn to_value(&self) -> ::flax::Value {
+        let mut children = ::flax::__private::Vec::new();
+        ::flax::__private::push(&mut children, "server", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.server)]);
+        ::flax::Value::map(children)
+    }
+}
+
+impl ::flax::FujiDeserialize for Backend {
+    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+        Ok(Backend {
+            server: ::flax::__private::required("server", &::flax::__private::values(value, "server"))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendServer {
+    pub name: String,
+}
+
+impl ::flax::FujiSerialize for BackendServer {
+    fn to_value(&self) -> ::flax::Value {
+        let mut children = ::flax::__private::Vec::new();
+        ::flax::__private::push(&mut children, "name", ::flax::__private::vec![::flax::FujiSerialize::to_value(&self.name)]);
+        ::flax::Value::map(children)
+    }
+}
+
+impl ::flax::FujiDeserialize for BackendServer {
+    fn from_value(value: &::flax::Value) -> Result<Self, ::flax::DeserializeError> {
+        Ok(BackendServer {
+            name: ::flax::__private::required("name", &::flax::__private::values(value, "name"))?,
+        })
+    }
+}
+
+impl Config {
+    /// Reads the fields from the top-level bindings of `document`.
+    pub fn from_document(document: &::flax::Document) -> Result<Self, ::flax::DeserializeError> {
+        ::flax::FujiDeserialize::from_value(&::flax::Value::map(document.bindings().to_vec()))
+    }
+
+    /// Writes the fields as top-level bindings.
+    pub fn to_document(&self) -> ::flax::Document {
+        let value = ::flax::FujiSerialize::to_value(self);
+        value.children().iter().cloned().fold(::flax::Document::new(), ::flax::Document::binding)
+    }
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "lsp")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+struct Server {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Server {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fuji-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Server { child, stdout }
+    }
+
+    fn send(&mut self, message: &str) {
+        let stdin = self.child.stdin.as_mut().unwrap();
+        write!(
+            stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .unwrap();
+        stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> String {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            match line.trim_end() {
+                "" => break,
+                header => {
+                    if let Some(value) = header.strip_prefix("Content-Length: ") {
+                        length = value.parse().unwrap();
+                    }
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    }
+
+    /// Sends a request and returns its response, skipping any notifications before it.
+    fn request(&mut self, id: u32, method: &str, params: &str) -> String {
+        self.send(&format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+            id, method, params
+        ));
+        loop {
+            let message = self.receive();
+            if message.contains(&format!(r#""id":{}"#, id)) {
+                return message;
+            }
+        }
+    }
+}
+
+const URI: &str = "file:///config.fuji";
+
+fn position(method: &str, line: u32, character: u32) -> (String, String) {
+    let params = format!(
+        r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#,
+        URI, line, character
+    );
+    (method.to_string(), params)
+}
+
+#[test]
+fn test_lsp() {
+    let schema = std::env::temp_dir().join(format!("fuji-lsp-{}.fuji", std::process::id()));
+    std::fs::write(
+        &schema,
+        "field=server{type=struct{field=host{type=string} field=tls{type=bool}}}
+         field=base{type=string optional=true}
+         field=copy{type=string optional=true}",
+    )
+    .unwrap();
+
+    let mut server = Server::start();
+    let response = server.request(
+        1,
+        "initialize",
+        &format!(
+            r#"{{"capabilities":{{}},"initializationOptions":{{"schema":"{}"}}}}"#,
+            schema.display()
+        ),
+    );
+    assert!(
+        response.contains(r#""definitionProvider":true"#),
+        "{}",
+        response
+    );
+    server.send(r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#);
+
+    let text = r#"server=s{host=h tls=yes}\nbase=&b x\ncopy=*b  \n"#;
+    server.send(&format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"fuji","version":1,"text":"{}"}}}}}}"#,
+        URI, text
+    ));
+    let diagnostics = server.receive();
+    assert!(
+        diagnostics.contains(
+            r#""range":{"end":{"character":23,"line":0},"start":{"character":20,"line":0}}"#
+        ),
+        "{}",
+        diagnostics
+    );
+    assert!(
+        diagnostics.contains("server.tls: invalid value `yes`, expected bool"),
+        "{}",
+        diagnostics
+    );
+
+    let (method, params) = position("textDocument/definition", 2, 6);
+    let response = server.request(2, &method, &params);
+    assert!(
+        response.contains(
+            r#""range":{"end":{"character":9,"line":1},"start":{"character":5,"line":1}}"#
+        ),
+        "{}",
+        response
+    );
+
+    let (method, params) = position("textDocument/completion", 0, 9);
+    let response = server.request(3, &method, &params);
+    assert!(response.contains(r#""label":"host""#), "{}", response);
+    assert!(response.contains(r#""label":"tls""#), "{}", response);
+    assert!(!response.contains(r#""label":"base""#), "{}", response);
+
+    let response = server.request(
+        4,
+        "textDocument/formatting",
+        &format!(
+            r#"{{"textDocument":{{"uri":"{}"}},"options":{{"tabSize":2,"insertSpaces":true}}}}"#,
+            URI
+        ),
+    );
+    assert!(
+        response.contains(r#""newText":"server=s{host=h tls=yes}\nbase=&b x\ncopy=*b\n""#),
+        "{}",
+        response
+    );
+
+    server.send(r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///config.fuji","version":2},"contentChanges":[{"text":"a=b{"}]}}"#);
+    let diagnostics = server.receive();
+    assert!(diagnostics.contains("expected '}'"), "{}", diagnostics);
+
+    server.request(5, "shutdown", "null");
+    server.send(r#"{"jsonrpc":"2.0","method":"exit"}"#);
+    assert!(server.child.wait().unwrap().success());
+    std::fs::remove_file(schema).unwrap();
+}